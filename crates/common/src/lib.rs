@@ -98,11 +98,22 @@ impl StorageAddress {
     }
 
     pub fn from_map_name_and_key(name: &[u8], key: Felt) -> Self {
+        Self::from_var_name_and_keys(name, &[key])
+    }
+
+    /// The address of a Cairo 0 `@storage_var` named `name`, for the given keys.
+    ///
+    /// This is `pedersen(..pedersen(sn_keccak(name), keys[0])..., keys[n])` reduced modulo
+    /// `2**251 - 256`. See:
+    /// <https://github.com/starkware-libs/cairo-lang/blob/v0.12.2/src/starkware/starknet/public/abi.py#L45-L52>
+    pub fn from_var_name_and_keys(name: &[u8], keys: &[Felt]) -> Self {
         use sha3::Digest;
         use std::ops::Rem;
 
         let intermediate = truncated_keccak(<[u8; 32]>::from(sha3::Keccak256::digest(name)));
-        let value = stark_hash::stark_hash(intermediate, key);
+        let value = keys
+            .iter()
+            .fold(intermediate, |acc, key| stark_hash::stark_hash(acc, *key));
 
         let value = primitive_types::U256::from_big_endian(value.as_be_bytes());
         let max_address = primitive_types::U256::from_str_radix(
@@ -528,6 +539,48 @@ mod tests {
         assert_eq!(EntryPoint::CONSTRUCTOR, expected);
     }
 
+    mod storage_address {
+        use crate::macro_prelude::*;
+        use crate::StorageAddress;
+        use stark_hash::Felt;
+
+        #[test]
+        fn without_keys() {
+            assert_eq!(
+                StorageAddress::from_var_name_and_keys(b"my_storage_var", &[]),
+                storage_address!(
+                    "0x1275130f95dda36bcbb6e9d28796c1d7e10b6e9fd5ed083e0ede4b12f613528"
+                )
+            );
+            assert_eq!(
+                StorageAddress::from_var_name_and_keys(b"my_storage_var", &[]),
+                StorageAddress::from_name(b"my_storage_var")
+            );
+        }
+
+        #[test]
+        fn single_key() {
+            let key = Felt::from_u64(0x1234);
+            assert_eq!(
+                StorageAddress::from_var_name_and_keys(b"ERC20_balances", &[key]),
+                storage_address!(
+                    "0x4fc7b23d1ef6e4f099416be09d83699fe35126d2a42325636e93745a36ce3cb"
+                )
+            );
+        }
+
+        #[test]
+        fn multiple_keys() {
+            let keys = [Felt::from_u64(0x1234), Felt::from_u64(0x5678)];
+            assert_eq!(
+                StorageAddress::from_var_name_and_keys(b"ERC20_allowances", &keys),
+                storage_address!(
+                    "0x61b42aa29464f85d526b4c2f6ece78ab1f3eb50199544bb906b795c42f0b8f8"
+                )
+            );
+        }
+    }
+
     mod starknet_version {
         use super::super::StarknetVersion;
 
@@ -579,4 +632,41 @@ mod tests {
             assert_eq!(result, BlockId::Hash(block_hash!("0xdeadbeef")));
         }
     }
+
+    mod field_bound {
+        use crate::{BlockHash, ContractAddress, ContractRoot, ContractStateHash, StorageAddress};
+
+        const PRIME: &str =
+            r#""0x800000000000011000000000000000000000000000000000000000000000001""#;
+        const PRIME_MINUS_ONE: &str =
+            r#""0x800000000000011000000000000000000000000000000000000000000000000""#;
+        const TWO_POW_251: &str =
+            r#""0x800000000000000000000000000000000000000000000000000000000000000""#;
+        const TWO_POW_251_MINUS_ONE: &str =
+            r#""0x7ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff""#;
+
+        #[test]
+        fn felt_newtypes_reject_the_prime() {
+            serde_json::from_str::<ContractRoot>(PRIME).unwrap_err();
+            serde_json::from_str::<ContractStateHash>(PRIME).unwrap_err();
+            serde_json::from_str::<BlockHash>(PRIME).unwrap_err();
+        }
+
+        #[test]
+        fn felt_newtypes_accept_just_below_the_prime() {
+            serde_json::from_str::<ContractRoot>(PRIME_MINUS_ONE).unwrap();
+            serde_json::from_str::<ContractStateHash>(PRIME_MINUS_ONE).unwrap();
+            serde_json::from_str::<BlockHash>(PRIME_MINUS_ONE).unwrap();
+        }
+
+        #[test]
+        fn felt251_newtypes_reject_251_bit_overflow() {
+            serde_json::from_str::<ContractAddress>(PRIME_MINUS_ONE).unwrap_err();
+            serde_json::from_str::<ContractAddress>(TWO_POW_251).unwrap_err();
+            serde_json::from_str::<StorageAddress>(TWO_POW_251).unwrap_err();
+
+            serde_json::from_str::<ContractAddress>(TWO_POW_251_MINUS_ONE).unwrap();
+            serde_json::from_str::<StorageAddress>(TWO_POW_251_MINUS_ONE).unwrap();
+        }
+    }
 }
@@ -1,6 +1,7 @@
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 use fake::Dummy;
+use serde::{Deserialize, Serialize};
 use stark_curve::FieldElement;
 use stark_poseidon::{poseidon_hash_many, PoseidonHasher};
 
@@ -9,7 +10,7 @@ use crate::{
     StateDiffCommitment, StorageAddress, StorageValue,
 };
 
-#[derive(Default, Debug, Clone, PartialEq, Dummy)]
+#[derive(Default, Debug, Clone, PartialEq, Dummy, Serialize, Deserialize)]
 pub struct StateUpdate {
     pub block_hash: BlockHash,
     pub parent_state_commitment: StateCommitment,
@@ -20,7 +21,7 @@ pub struct StateUpdate {
     pub declared_sierra_classes: HashMap<SierraHash, CasmHash>,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Dummy)]
+#[derive(Default, Debug, Clone, PartialEq, Dummy, Serialize, Deserialize)]
 pub struct ContractUpdate {
     pub storage: HashMap<StorageAddress, StorageValue>,
     /// The class associated with this update as the result of either a deploy or class replacement transaction.
@@ -28,12 +29,12 @@ pub struct ContractUpdate {
     pub nonce: Option<ContractNonce>,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Dummy)]
+#[derive(Default, Debug, Clone, PartialEq, Dummy, Serialize, Deserialize)]
 pub struct SystemContractUpdate {
     pub storage: HashMap<StorageAddress, StorageValue>,
 }
 
-#[derive(Debug, Clone, PartialEq, Dummy)]
+#[derive(Debug, Clone, PartialEq, Dummy, Serialize, Deserialize)]
 pub enum ContractClassUpdate {
     Deploy(ClassHash),
     Replace(ClassHash),
@@ -558,4 +559,36 @@ mod tests {
             .contract_class(contract_address_bytes!(b"bogus"))
             .is_none());
     }
+
+    #[test]
+    fn serde_roundtrip() {
+        let state_update = StateUpdate::default()
+            .with_block_hash(block_hash!("0x1"))
+            .with_state_commitment(state_commitment!("0x2"))
+            .with_contract_nonce(contract_address!("0x3"), contract_nonce!("0x4"))
+            .with_declared_cairo_class(class_hash!("0x5"))
+            .with_declared_sierra_class(sierra_hash!("0x6"), casm_hash!("0x7"))
+            .with_deployed_contract(contract_address!("0x3"), class_hash!("0x5"))
+            .with_replaced_class(contract_address!("0x8"), class_hash!("0x9"))
+            .with_storage_update(
+                contract_address!("0x8"),
+                storage_address!("0xa"),
+                storage_value!("0xb"),
+            )
+            .with_system_storage_update(
+                ContractAddress::ONE,
+                storage_address!("0xc"),
+                storage_value!("0xd"),
+            );
+
+        let json = serde_json::to_value(&state_update).unwrap();
+        assert_eq!(json["block_hash"], serde_json::json!("0x1"));
+        assert_eq!(
+            json["contract_updates"]["0x8"]["storage"]["0xa"],
+            serde_json::json!("0xb")
+        );
+
+        let deserialized: StateUpdate = serde_json::from_value(json).unwrap();
+        assert_eq!(deserialized, state_update);
+    }
 }
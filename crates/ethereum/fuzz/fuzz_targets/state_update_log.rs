@@ -0,0 +1,22 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// Logs are untrusted input, so parsing may fail but must never panic.
+//
+// The seed corpus in `corpus/state_update_log` holds logs in both the two and three word data
+// layouts, both as JSON and as raw log data.
+fuzz_target!(|data: &[u8]| {
+    // Arbitrary JSON exercises the log's fields, whereas raw bytes used as the log data
+    // exercise the decoding of its words.
+    let log = serde_json::from_slice(data).unwrap_or_else(|_| {
+        serde_json::json!({
+            "data": format!("0x{}", hex::encode(data)),
+            "blockNumber": "0x1",
+            "blockHash": "0x2",
+            "transactionHash": "0x3",
+            "logIndex": "0x4",
+        })
+    });
+
+    let _ = pathfinder_ethereum::fuzz_parse_state_update_log(&log);
+});
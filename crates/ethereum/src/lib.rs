@@ -1,6 +1,12 @@
-use pathfinder_common::{BlockHash, BlockNumber, EthereumChain, StateCommitment};
+use anyhow::Context;
+use pathfinder_common::{
+    BlockHash, BlockNumber, ContractAddress, EntryPoint, EthereumAddress, EthereumChain, Fee,
+    L1ToL2MessageNonce, L1ToL2MessagePayloadElem, StateCommitment,
+};
 use primitive_types::{H160, H256, U256};
 use stark_hash::Felt;
+use std::num::NonZeroU64;
+use std::time::{Duration, Instant};
 
 pub mod core_addr {
     use const_decoder::Decoder;
@@ -17,21 +23,132 @@ pub struct EthereumStateUpdate {
     pub state_root: StateCommitment,
     pub block_number: BlockNumber,
     pub block_hash: BlockHash,
+    /// The Ethereum block at which this state was read from the core contract, if known.
+    pub ethereum_block_number: Option<u64>,
+}
+
+/// A `LogStateUpdate` event emitted by the Starknet core contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateUpdateLog {
+    pub state_root: StateCommitment,
+    pub block_number: BlockNumber,
+    /// Only present in logs emitted by core contract versions which include the block hash.
+    pub block_hash: Option<BlockHash>,
+    pub origin: EthereumOrigin,
+}
+
+/// Identifies where on Ethereum a log was emitted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EthereumOrigin {
+    pub block_number: u64,
+    pub block_hash: H256,
+    pub transaction_hash: H256,
+    pub log_index: u64,
+}
+
+impl StateUpdateLog {
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "block_number": self.block_number.get(),
+            "state_root": self.state_root,
+            "block_hash": self.block_hash,
+            "origin": {
+                "block_number": self.origin.block_number,
+                "block_hash": format!("{:#x}", self.origin.block_hash),
+                "transaction_hash": format!("{:#x}", self.origin.transaction_hash),
+                "log_index": self.origin.log_index,
+            },
+        })
+    }
+}
+
+/// A `LogMessageToL2` event emitted by the Starknet core contract when a message is sent from
+/// Ethereum to Starknet. Each message is consumed by an L1 handler transaction on Starknet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct L1ToL2Message {
+    pub from_address: EthereumAddress,
+    pub to_address: ContractAddress,
+    pub selector: EntryPoint,
+    pub payload: Vec<L1ToL2MessagePayloadElem>,
+    pub nonce: L1ToL2MessageNonce,
+    /// Only present in logs emitted by core contract versions which charge a fee for messages.
+    pub fee: Option<Fee>,
+    pub origin: EthereumOrigin,
+}
+
+impl L1ToL2Message {
+    /// Parses a raw `LogMessageToL2` log, as returned by `eth_getLogs`.
+    pub fn from_log(log: &serde_json::Value) -> anyhow::Result<Self> {
+        parse_message_to_l2_log(log)
+    }
+
+    /// The hash identifying the message on both Ethereum and Starknet, which is also the message
+    /// hash of the L1 handler transaction consuming it.
+    pub fn message_hash(&self) -> H256 {
+        let mut preimage = Vec::with_capacity(32 * (5 + self.payload.len()));
+        preimage.extend_from_slice(H256::from(self.from_address.0).as_bytes());
+        preimage.extend_from_slice(&self.to_address.0.to_be_bytes());
+        preimage.extend_from_slice(&self.nonce.0.to_be_bytes());
+        preimage.extend_from_slice(&self.selector.0.to_be_bytes());
+        preimage.extend_from_slice(H256::from_low_u64_be(self.payload.len() as u64).as_bytes());
+        for elem in &self.payload {
+            preimage.extend_from_slice(&elem.0.to_be_bytes());
+        }
+
+        let mut output = [0u8; 32];
+        keccak_hash::keccak_256(&preimage, &mut output);
+        H256(output)
+    }
 }
 
 #[async_trait::async_trait]
 pub trait EthereumApi {
     async fn get_starknet_state(&self, address: &H160) -> anyhow::Result<EthereumStateUpdate>;
     async fn get_chain(&self) -> anyhow::Result<EthereumChain>;
+    /// Fetches the messages sent to Starknet through the core contract at `address` in the
+    /// Ethereum block range `from..=to`.
+    async fn get_message_to_l2_logs(
+        &self,
+        address: &H160,
+        from: u64,
+        to: u64,
+    ) -> anyhow::Result<Vec<L1ToL2Message>>;
 }
 
 #[derive(Clone, Debug)]
 pub struct EthereumClient {
     http: reqwest::Client,
     url: reqwest::Url,
+    /// Delay before the first retry of a failed call, doubled for each subsequent retry.
+    retry_delay: Duration,
+}
+
+/// Total number of attempts made for a single Ethereum call which fails with a transient error.
+const MAX_ATTEMPTS: usize = 3;
+
+/// The error of a single Ethereum JSON-RPC call.
+#[derive(Debug, thiserror::Error)]
+enum CallError {
+    #[error(transparent)]
+    Transport(#[from] reqwest::Error),
+    #[error("Ethereum call failed with HTTP status {0}")]
+    Status(reqwest::StatusCode),
+    #[error("Ethereum call failed with JSON-RPC error {code}: {message}")]
+    Rpc { code: i64, message: String },
 }
 
-const HTTP_OK: u16 = 200;
+impl CallError {
+    /// Whether the call is likely to succeed if retried.
+    fn is_transient(&self) -> bool {
+        match self {
+            CallError::Transport(e) => e.is_connect() || e.is_timeout(),
+            CallError::Status(status) => {
+                status.is_server_error() || *status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            }
+            CallError::Rpc { .. } => false,
+        }
+    }
+}
 
 impl EthereumClient {
     pub fn with_password(mut url: reqwest::Url, password: &str) -> anyhow::Result<Self> {
@@ -42,12 +159,16 @@ impl EthereumClient {
 
     pub fn new(url: reqwest::Url) -> anyhow::Result<Self> {
         Ok(Self {
-            http: reqwest::ClientBuilder::new().build()?,
+            http: reqwest::ClientBuilder::new()
+                .timeout(Duration::from_secs(30))
+                .build()?,
             url,
+            retry_delay: Duration::from_secs(1),
         })
     }
 
-    async fn get_finalized_block_hash(&self) -> anyhow::Result<H256> {
+    /// Returns the hash and number of the latest finalized Ethereum block.
+    async fn get_finalized_block(&self) -> anyhow::Result<(H256, u64)> {
         self.call_ethereum(serde_json::json!({
             "jsonrpc": "2.0",
             "method": "eth_getBlockByNumber",
@@ -58,7 +179,64 @@ impl EthereumClient {
             "id": 0
         }))
         .await
-        .and_then(|value| get_h256(&value["hash"]))
+        .and_then(|block| {
+            let hash = get_h256(&block["hash"])?;
+            let number = get_u256(&block["number"]).and_then(get_u64)?;
+            Ok((hash, number))
+        })
+    }
+
+    /// Fetches the `LogStateUpdate` events emitted by the core contract at `address` in the
+    /// Ethereum block range `from..=to`.
+    pub async fn get_state_update_logs(
+        &self,
+        address: &H160,
+        from: u64,
+        to: u64,
+    ) -> anyhow::Result<Vec<StateUpdateLog>> {
+        self.get_logs(address, from, to, &LOG_STATE_UPDATE_SIGNATURES)
+            .await?
+            .iter()
+            .map(parse_state_update_log)
+            .collect()
+    }
+
+    /// Fetches the logs emitted by the contract at `address` in the Ethereum block range
+    /// `from..=to`, whose first topic is the hash of any of the event `signatures`.
+    async fn get_logs(
+        &self,
+        address: &H160,
+        from: u64,
+        to: u64,
+        signatures: &[&str],
+    ) -> anyhow::Result<Vec<serde_json::Value>> {
+        anyhow::ensure!(from <= to, "Invalid block range {from}..={to}");
+
+        let topics = signatures
+            .iter()
+            .map(|signature| format!("0x{}", hex::encode(event_topic(signature))))
+            .collect::<Vec<_>>();
+
+        let logs = self
+            .call_ethereum(serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "eth_getLogs",
+                "params": [
+                    {
+                        "address": format!("0x{}", hex::encode(address.as_bytes())),
+                        "fromBlock": format!("{from:#x}"),
+                        "toBlock": format!("{to:#x}"),
+                        "topics": [topics]
+                    }
+                ],
+                "id": 0
+            }))
+            .await?;
+
+        match logs {
+            serde_json::Value::Array(logs) => Ok(logs),
+            _ => anyhow::bail!("Logs are not an array"),
+        }
     }
 
     async fn call_starknet_contract(
@@ -84,25 +262,52 @@ impl EthereumClient {
         .await
     }
 
+    /// Sends the JSON-RPC request, retrying transient failures with an exponential backoff.
+    ///
+    /// JSON-RPC errors are returned as errors instead of an empty result.
     async fn call_ethereum(&self, value: serde_json::Value) -> anyhow::Result<serde_json::Value> {
-        let res = self.http.post(self.url.clone()).json(&value).send().await?;
+        let mut delay = self.retry_delay;
+        let mut attempt = 1;
+        loop {
+            match self.call_ethereum_once(&value).await {
+                Err(e) if e.is_transient() && attempt < MAX_ATTEMPTS => {
+                    tracing::warn!(reason=%e, ?delay, "Ethereum call failed, retrying");
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                result => return result.map_err(Into::into),
+            }
+        }
+    }
+
+    async fn call_ethereum_once(
+        &self,
+        value: &serde_json::Value,
+    ) -> Result<serde_json::Value, CallError> {
+        let res = self.http.post(self.url.clone()).json(value).send().await?;
 
         let status = res.status();
-        let (code, message) = (status.as_u16(), status.as_str());
-        if code != HTTP_OK {
-            tracing::error!(code, message, "Ethereum call failed");
-            anyhow::bail!(code);
+        if status != reqwest::StatusCode::OK {
+            return Err(CallError::Status(status));
+        }
+
+        let mut response: serde_json::Value = res.json().await?;
+        if let Some(error) = response.get("error") {
+            return Err(CallError::Rpc {
+                code: error["code"].as_i64().unwrap_or_default(),
+                message: error["message"].as_str().unwrap_or_default().to_owned(),
+            });
         }
 
-        let response: serde_json::Value = res.json().await?;
-        Ok(response["result"].clone())
+        Ok(response["result"].take())
     }
 }
 
 #[async_trait::async_trait]
 impl EthereumApi for EthereumClient {
     async fn get_starknet_state(&self, address: &H160) -> anyhow::Result<EthereumStateUpdate> {
-        let hash = self.get_finalized_block_hash().await?;
+        let (hash, ethereum_block_number) = self.get_finalized_block().await?;
         let hash = format!("0x{}", hex::encode(hash.as_bytes()));
         let addr = format!("0x{}", hex::encode(address.as_bytes()));
         Ok(EthereumStateUpdate {
@@ -123,6 +328,7 @@ impl EthereumApi for EthereumClient {
                 .await
                 .and_then(|value| get_u256(&value))
                 .and_then(get_number)?,
+            ethereum_block_number: Some(ethereum_block_number),
         })
     }
 
@@ -142,6 +348,267 @@ impl EthereumApi for EthereumClient {
             x => EthereumChain::Other(x),
         })
     }
+
+    async fn get_message_to_l2_logs(
+        &self,
+        address: &H160,
+        from: u64,
+        to: u64,
+    ) -> anyhow::Result<Vec<L1ToL2Message>> {
+        self.get_logs(address, from, to, &LOG_MESSAGE_TO_L2_SIGNATURES)
+            .await?
+            .iter()
+            .map(parse_message_to_l2_log)
+            .collect()
+    }
+}
+
+/// Fetches `LogStateUpdate` events over a range of Ethereum blocks, one chunk of blocks at a time.
+///
+/// Some providers are slow to answer `eth_getLogs` for wide ranges, so chunks are bounded by time
+/// as well as by block count: whenever fetching a chunk takes longer than the configured duration,
+/// the chunk size is halved for all subsequent fetches.
+pub struct StateUpdateLogFetcher {
+    client: EthereumClient,
+    address: H160,
+    chunk_size: u64,
+    max_fetch_duration: Duration,
+    timeout: Duration,
+}
+
+/// The error returned by [StateUpdateLogFetcher::fetch].
+#[derive(Debug, thiserror::Error)]
+pub enum FetchError {
+    /// The provider did not answer in time. This is worth retrying, as the range has been shrunk.
+    #[error("Fetching logs for blocks {from}..={to} timed out after {timeout:?}")]
+    Timeout {
+        from: u64,
+        to: u64,
+        timeout: Duration,
+    },
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl StateUpdateLogFetcher {
+    /// The default bound on a single `eth_getLogs` call, including the client's own retries.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(120);
+
+    pub fn new(
+        client: EthereumClient,
+        address: H160,
+        chunk_size: NonZeroU64,
+        max_fetch_duration: Duration,
+    ) -> Self {
+        Self {
+            client,
+            address,
+            chunk_size: chunk_size.get(),
+            max_fetch_duration,
+            timeout: Self::DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Sets the time after which a single `eth_getLogs` call is abandoned.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    /// The number of Ethereum blocks covered by a single `eth_getLogs` call.
+    pub fn chunk_size(&self) -> u64 {
+        self.chunk_size
+    }
+
+    /// Fetches the logs in the Ethereum block range `from..=to`.
+    pub async fn fetch(&mut self, from: u64, to: u64) -> Result<Vec<StateUpdateLog>, FetchError> {
+        if from > to {
+            return Err(anyhow::anyhow!("Invalid block range {from}..={to}").into());
+        }
+
+        let mut logs = Vec::new();
+        let mut start = from;
+        loop {
+            let end = to.min(start.saturating_add(self.chunk_size - 1));
+
+            let started = Instant::now();
+            let chunk = tokio::time::timeout(
+                self.timeout,
+                self.client.get_state_update_logs(&self.address, start, end),
+            )
+            .await;
+            let elapsed = started.elapsed();
+
+            if elapsed > self.max_fetch_duration && self.chunk_size > 1 {
+                self.chunk_size /= 2;
+                tracing::debug!(
+                    ?elapsed,
+                    chunk_size=%self.chunk_size,
+                    "Fetching logs was slow, shrinking the block range"
+                );
+            }
+
+            let chunk = chunk
+                .map_err(|_| FetchError::Timeout {
+                    from: start,
+                    to: end,
+                    timeout: self.timeout,
+                })?
+                .with_context(|| format!("Fetching logs for blocks {start}..={end}"))?;
+            logs.extend(chunk);
+
+            if end == to {
+                return Ok(logs);
+            }
+            start = end + 1;
+        }
+    }
+}
+
+/// The `LogStateUpdate` event signatures of the current and of older core contract versions.
+const LOG_STATE_UPDATE_SIGNATURES: [&str; 2] = [
+    "LogStateUpdate(uint256,int256,uint256)",
+    "LogStateUpdate(uint256,int256)",
+];
+
+/// Exposes log parsing to the fuzz targets.
+#[cfg(fuzzing)]
+pub fn fuzz_parse_state_update_log(log: &serde_json::Value) -> anyhow::Result<StateUpdateLog> {
+    parse_state_update_log(log)
+}
+
+fn parse_state_update_log(log: &serde_json::Value) -> anyhow::Result<StateUpdateLog> {
+    let data = log["data"].as_str().context("Log data is missing")?;
+    let data = hex::decode(data.strip_prefix("0x").unwrap_or(data)).context("Decoding log data")?;
+    let words = data
+        .chunks_exact(32)
+        .map(H256::from_slice)
+        .collect::<Vec<_>>();
+    anyhow::ensure!(
+        data.len() % 32 == 0 && (words.len() == 2 || words.len() == 3),
+        "Unexpected log data length {}",
+        data.len()
+    );
+
+    Ok(StateUpdateLog {
+        state_root: StateCommitment(get_felt(words[0])?),
+        block_number: get_number(U256::from_big_endian(words[1].as_bytes()))?,
+        block_hash: words
+            .get(2)
+            .copied()
+            .map(get_felt)
+            .transpose()?
+            .map(BlockHash),
+        origin: EthereumOrigin {
+            block_number: get_u256(&log["blockNumber"]).and_then(get_u64)?,
+            block_hash: get_h256(&log["blockHash"])?,
+            transaction_hash: get_h256(&log["transactionHash"])?,
+            log_index: get_u256(&log["logIndex"]).and_then(get_u64)?,
+        },
+    })
+}
+
+/// The `LogMessageToL2` event signatures of the current and of older core contract versions.
+///
+/// Only the current version's event carries the fee.
+const LOG_MESSAGE_TO_L2_SIGNATURES: [&str; 2] = [
+    "LogMessageToL2(address,uint256,uint256,uint256[],uint256,uint256)",
+    "LogMessageToL2(address,uint256,uint256,uint256[],uint256)",
+];
+
+/// The first topic of the logs of the event with the given `signature`.
+fn event_topic(signature: &str) -> H256 {
+    let mut output: [u8; 32] = Default::default();
+    keccak_hash::keccak_256(signature.as_bytes(), &mut output[..]);
+    H256(output)
+}
+
+fn parse_message_to_l2_log(log: &serde_json::Value) -> anyhow::Result<L1ToL2Message> {
+    let topics = log["topics"].as_array().context("Log topics are missing")?;
+    anyhow::ensure!(
+        topics.len() == 4,
+        "Unexpected number of log topics {}",
+        topics.len()
+    );
+    let signature = get_h256(&topics[0])?;
+    let [with_fee, without_fee] = LOG_MESSAGE_TO_L2_SIGNATURES.map(event_topic);
+    let has_fee = if signature == with_fee {
+        true
+    } else if signature == without_fee {
+        false
+    } else {
+        anyhow::bail!("Not a LogMessageToL2 event");
+    };
+
+    let from_address = get_h256(&topics[1])?;
+    anyhow::ensure!(
+        from_address[..12].iter().all(|b| *b == 0),
+        "Sender is not an Ethereum address"
+    );
+    let from_address = EthereumAddress(H160::from_slice(&from_address[12..]));
+    let to_address = ContractAddress::new(get_felt(get_h256(&topics[2])?)?)
+        .context("Recipient is not a contract address")?;
+    let selector = EntryPoint(get_felt(get_h256(&topics[3])?)?);
+
+    let data = log["data"].as_str().context("Log data is missing")?;
+    let data = hex::decode(data.strip_prefix("0x").unwrap_or(data)).context("Decoding log data")?;
+    anyhow::ensure!(
+        data.len() % 32 == 0,
+        "Unexpected log data length {}",
+        data.len()
+    );
+    let words = data
+        .chunks_exact(32)
+        .map(H256::from_slice)
+        .collect::<Vec<_>>();
+    let word = |index: usize| {
+        words
+            .get(index)
+            .copied()
+            .with_context(|| format!("Log data word {index} is missing"))
+    };
+    let number =
+        |index: usize| word(index).and_then(|w| get_u64(U256::from_big_endian(w.as_bytes())));
+
+    // The payload is dynamically sized, so the first word is its offset. It follows the nonce and,
+    // if the event has one, the fee.
+    let offset = number(0)?;
+    let expected_offset = if has_fee { 0x60 } else { 0x40 };
+    anyhow::ensure!(
+        offset == expected_offset,
+        "Unexpected payload offset {offset}"
+    );
+    let nonce = L1ToL2MessageNonce(get_felt(word(1)?)?);
+    let fee = if has_fee {
+        Some(Fee(get_felt(word(2)?)?))
+    } else {
+        None
+    };
+
+    let length_index = offset as usize / 32;
+    let length = number(length_index)? as usize;
+    anyhow::ensure!(
+        words.len().checked_sub(length_index + 1) == Some(length),
+        "Expected a payload of {length} words"
+    );
+    let payload = words[length_index + 1..]
+        .iter()
+        .map(|word| get_felt(*word).map(L1ToL2MessagePayloadElem))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(L1ToL2Message {
+        from_address,
+        to_address,
+        selector,
+        payload,
+        nonce,
+        fee,
+        origin: EthereumOrigin {
+            block_number: get_u256(&log["blockNumber"]).and_then(get_u64)?,
+            block_hash: get_h256(&log["blockHash"])?,
+            transaction_hash: get_h256(&log["transactionHash"])?,
+            log_index: get_u256(&log["logIndex"]).and_then(get_u64)?,
+        },
+    })
 }
 
 fn encode_ethereum_call_data(signature: &[u8]) -> String {
@@ -174,10 +641,19 @@ fn get_felt(value: H256) -> anyhow::Result<Felt> {
 }
 
 fn get_number(value: U256) -> anyhow::Result<BlockNumber> {
-    let value = value.as_u64();
+    let value = get_u64(value)?;
     BlockNumber::new(value).ok_or(anyhow::anyhow!("Failed to read u64 from U256"))
 }
 
+/// Unlike [U256::as_u64], this fails instead of panicking if `value` does not fit.
+fn get_u64(value: U256) -> anyhow::Result<u64> {
+    anyhow::ensure!(
+        value <= U256::from(u64::MAX),
+        "Failed to read u64 from U256 {value}"
+    );
+    Ok(value.low_u64())
+}
+
 fn lpad64(value: &str) -> String {
     let input = value.strip_prefix("0x").unwrap_or(value);
     let prefix = if value.starts_with("0x") { "0x" } else { "" };
@@ -293,6 +769,7 @@ mod tests {
             state_root: StateCommitment(get_felt(global_root)?),
             block_number: get_number(block_number)?,
             block_hash: BlockHash(get_felt(block_hash)?),
+            ethereum_block_number: Some(0x1048e0e),
         };
 
         let addr = H160::from_slice(&core_addr::MAINNET);
@@ -306,6 +783,368 @@ mod tests {
         Ok(())
     }
 
+    /// Serves one HTTP request per connection with each of `responses` in turn, as status code
+    /// and body.
+    ///
+    /// Unlike a [MockServer], the responses change with each request served, instead of when the
+    /// test gets around to replacing a mock.
+    async fn serve_in_sequence(responses: Vec<(u16, &'static str)>) -> Url {
+        use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+
+        tokio::spawn(async move {
+            for (status, body) in responses {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut stream = BufReader::new(stream);
+
+                // The request must be read in full before replying.
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    stream.read_line(&mut line).await.unwrap();
+                    let line = line.trim_end().to_ascii_lowercase();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some(length) = line.strip_prefix("content-length:") {
+                        content_length = length.trim().parse().unwrap();
+                    }
+                }
+                let mut request = vec![0; content_length];
+                stream.read_exact(&mut request).await.unwrap();
+
+                let response = format!(
+                    "HTTP/1.1 {status} Status\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+                stream.shutdown().await.unwrap();
+            }
+        });
+
+        url
+    }
+
+    #[tokio::test]
+    async fn transient_error_is_retried() -> anyhow::Result<()> {
+        let url = serve_in_sequence(vec![
+            (503, ""),
+            (200, r#"{"jsonrpc":"2.0","id":0,"result":"0x1"}"#),
+        ])
+        .await;
+        let eth = EthereumClient {
+            retry_delay: std::time::Duration::ZERO,
+            ..EthereumClient::new(url)?
+        };
+
+        let chain_id = eth.get_chain().await?;
+
+        assert_eq!(chain_id, EthereumChain::Mainnet);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rpc_error_is_not_retried() -> anyhow::Result<()> {
+        let server = MockServer::start_async().await;
+
+        let mock = server.mock(|when, then| {
+            when.path("/").method(POST);
+            then.status(200)
+                .header("Content-type", "application/json")
+                .body(r#"{"jsonrpc":"2.0","id":0,"error":{"code":-32601,"message":"Method not found"}}"#);
+        });
+
+        let url = Url::parse(&server.url("/"))?;
+        let eth = EthereumClient::new(url)?;
+        let error = eth.get_chain().await.unwrap_err();
+
+        mock.assert_hits(1);
+        assert!(error.to_string().contains("Method not found"), "{error}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn state_update_logs() -> anyhow::Result<()> {
+        let server = MockServer::start_async().await;
+
+        let mock = server.mock(|when, then| {
+            when.path("/")
+                .method(POST)
+                .body_contains(r#""method":"eth_getLogs""#)
+                .body_contains(r#""fromBlock":"0x10","toBlock":"0x20""#);
+            then.status(200)
+                .header("Content-type", "application/json")
+                .body(r#"{"jsonrpc":"2.0","id":0,"result":[
+                    {
+                        "blockNumber":"0x11",
+                        "blockHash":"0x00000000000000000000000000000000000000000000000000000000000000a1",
+                        "transactionHash":"0x00000000000000000000000000000000000000000000000000000000000000b1",
+                        "logIndex":"0x0",
+                        "data":"0x000000000000000000000000000000000000000000000000000000000000000100000000000000000000000000000000000000000000000000000000000000020000000000000000000000000000000000000000000000000000000000000003"
+                    },
+                    {
+                        "blockNumber":"0x1f",
+                        "blockHash":"0x00000000000000000000000000000000000000000000000000000000000000a2",
+                        "transactionHash":"0x00000000000000000000000000000000000000000000000000000000000000b2",
+                        "logIndex":"0x5",
+                        "data":"0x00000000000000000000000000000000000000000000000000000000000000040000000000000000000000000000000000000000000000000000000000000005"
+                    }
+                ]}"#);
+        });
+
+        let url = Url::parse(&server.url("/"))?;
+        let eth = EthereumClient::new(url)?;
+        let addr = H160::from_slice(&core_addr::MAINNET);
+        let logs = eth.get_state_update_logs(&addr, 0x10, 0x20).await?;
+
+        mock.assert();
+        assert_eq!(
+            logs,
+            vec![
+                StateUpdateLog {
+                    state_root: StateCommitment(Felt::from_u64(1)),
+                    block_number: BlockNumber::new_or_panic(2),
+                    block_hash: Some(BlockHash(Felt::from_u64(3))),
+                    origin: EthereumOrigin {
+                        block_number: 0x11,
+                        block_hash: H256::from_low_u64_be(0xa1),
+                        transaction_hash: H256::from_low_u64_be(0xb1),
+                        log_index: 0,
+                    },
+                },
+                StateUpdateLog {
+                    state_root: StateCommitment(Felt::from_u64(4)),
+                    block_number: BlockNumber::new_or_panic(5),
+                    block_hash: None,
+                    origin: EthereumOrigin {
+                        block_number: 0x1f,
+                        block_hash: H256::from_low_u64_be(0xa2),
+                        transaction_hash: H256::from_low_u64_be(0xb2),
+                        log_index: 5,
+                    },
+                },
+            ]
+        );
+
+        assert_eq!(
+            logs[1].to_json(),
+            serde_json::json!({
+                "block_number": 5,
+                "state_root": "0x4",
+                "block_hash": null,
+                "origin": {
+                    "block_number": 0x1f,
+                    "block_hash": "0x00000000000000000000000000000000000000000000000000000000000000a2",
+                    "transaction_hash": "0x00000000000000000000000000000000000000000000000000000000000000b2",
+                    "log_index": 5,
+                },
+            })
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn message_to_l2_logs() -> anyhow::Result<()> {
+        let server = MockServer::start_async().await;
+
+        let mock = server.mock(|when, then| {
+            when.path("/")
+                .method(POST)
+                .body_contains(r#""method":"eth_getLogs""#)
+                .body_contains(r#""fromBlock":"0x10","toBlock":"0x20""#);
+            then.status(200)
+                .header("Content-type", "application/json")
+                .body(r#"{"jsonrpc":"2.0","id":0,"result":[
+                    {
+                        "blockNumber":"0x12",
+                        "blockHash":"0x00000000000000000000000000000000000000000000000000000000000000a1",
+                        "transactionHash":"0x00000000000000000000000000000000000000000000000000000000000000b1",
+                        "logIndex":"0x3",
+                        "topics":[
+                            "0xdb80dd488acf86d17c747445b0eabb5d57c541d3bd7b6b87af987858e5066b2b",
+                            "0x000000000000000000000000ae0ee0a63a2ce6baeeffe56e7714fb4efe48d419",
+                            "0x073314940630fd6dcda0d772d4c972c4e0a9946bef9dabf4ef84eda8ef542b82",
+                            "0x02d757788a8d8d6f21d1cd40bce38a8222d70654214e96ff95d8086e684fbee5"
+                        ],
+                        "data":"0x00000000000000000000000000000000000000000000000000000000000000600000000000000000000000000000000000000000000000000000000000001a2b000000000000000000000000000000000000000000000000000000003b9aca0000000000000000000000000000000000000000000000000000000000000000030000000000000000000000000000000000000000000000000000000000000123000000000000000000000000000000000000000000000000002386f26fc100000000000000000000000000000000000000000000000000000000000000000000"
+                    }
+                ]}"#);
+        });
+
+        let url = Url::parse(&server.url("/"))?;
+        let eth = EthereumClient::new(url)?;
+        let addr = H160::from_slice(&core_addr::MAINNET);
+        let messages = eth.get_message_to_l2_logs(&addr, 0x10, 0x20).await?;
+
+        mock.assert();
+        assert_eq!(
+            messages,
+            vec![L1ToL2Message {
+                from_address: EthereumAddress(H160::from_str(
+                    "0xae0ee0a63a2ce6baeeffe56e7714fb4efe48d419"
+                )?),
+                to_address: ContractAddress::new_or_panic(Felt::from_hex_str(
+                    "0x073314940630fd6dcda0d772d4c972c4e0a9946bef9dabf4ef84eda8ef542b82"
+                )?),
+                selector: EntryPoint(Felt::from_hex_str(
+                    "0x02d757788a8d8d6f21d1cd40bce38a8222d70654214e96ff95d8086e684fbee5"
+                )?),
+                payload: vec![
+                    L1ToL2MessagePayloadElem(Felt::from_u64(0x123)),
+                    L1ToL2MessagePayloadElem(Felt::from_u64(0x2386f26fc10000)),
+                    L1ToL2MessagePayloadElem(Felt::ZERO),
+                ],
+                nonce: L1ToL2MessageNonce(Felt::from_u64(0x1a2b)),
+                fee: Some(Fee(Felt::from_u64(0x3b9aca00))),
+                origin: EthereumOrigin {
+                    block_number: 0x12,
+                    block_hash: H256::from_low_u64_be(0xa1),
+                    transaction_hash: H256::from_low_u64_be(0xb1),
+                    log_index: 3,
+                },
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn message_to_l2_log_with_truncated_payload_is_rejected() {
+        let log = serde_json::json!({
+            "blockNumber":"0x12",
+            "blockHash":"0xa1",
+            "transactionHash":"0xb1",
+            "logIndex":"0x3",
+            "topics":[
+                // The older event, without a fee.
+                "0x7d3450d4f5138e54dcb21a322312d50846ead7856426fb38778f8ef33aeccc01",
+                "0x1",
+                "0x2",
+                "0x3"
+            ],
+            // Declares a payload of 3 words, but only contains one.
+            "data":"0x0000000000000000000000000000000000000000000000000000000000000040000000000000000000000000000000000000000000000000000000000000000100000000000000000000000000000000000000000000000000000000000000030000000000000000000000000000000000000000000000000000000000000123"
+        });
+
+        let error = L1ToL2Message::from_log(&log).unwrap_err();
+        assert_eq!(error.to_string(), "Expected a payload of 3 words");
+    }
+
+    #[test]
+    fn message_to_l2_log_layout_follows_signature() {
+        let log = |signature: &str| {
+            serde_json::json!({
+                "blockNumber":"0x12",
+                "blockHash":"0xa1",
+                "transactionHash":"0xb1",
+                "logIndex":"0x3",
+                "topics":[signature, "0x1", "0x2", "0x3"],
+                // The older layout, without a fee and with a payload of a single word.
+                "data":"0x000000000000000000000000000000000000000000000000000000000000004000000000000000000000000000000000000000000000000000000000000000010000000000000000000000000000000000000000000000000000000000000001000000000000000000000000000000000000000000000000000000000000abcd"
+            })
+        };
+
+        let without_fee = log("0x7d3450d4f5138e54dcb21a322312d50846ead7856426fb38778f8ef33aeccc01");
+        let message = L1ToL2Message::from_log(&without_fee).unwrap();
+        assert_eq!(message.fee, None);
+        assert_eq!(
+            message.payload,
+            vec![L1ToL2MessagePayloadElem(Felt::from_u64(0xabcd))]
+        );
+
+        // The current event has a fee, so its data cannot be in the older layout.
+        let with_fee = log("0xdb80dd488acf86d17c747445b0eabb5d57c541d3bd7b6b87af987858e5066b2b");
+        let error = L1ToL2Message::from_log(&with_fee).unwrap_err();
+        assert_eq!(error.to_string(), "Unexpected payload offset 64");
+
+        let other_event = log("0xd342ddf7a308dec111745b00315c14b7efb2bdae570a6856e088ed0c65a3576c");
+        let error = L1ToL2Message::from_log(&other_event).unwrap_err();
+        assert_eq!(error.to_string(), "Not a LogMessageToL2 event");
+    }
+
+    #[tokio::test]
+    async fn state_update_logs_invalid_range() -> anyhow::Result<()> {
+        let url = Url::parse("http://localhost")?;
+        let eth = EthereumClient::new(url)?;
+        let addr = H160::from_slice(&core_addr::MAINNET);
+
+        let error = eth.get_state_update_logs(&addr, 2, 1).await.unwrap_err();
+        assert_eq!(error.to_string(), "Invalid block range 2..=1");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn slow_log_fetch_shrinks_range() -> anyhow::Result<()> {
+        let server = MockServer::start_async().await;
+
+        let mock = server.mock(|when, then| {
+            when.path("/")
+                .method(POST)
+                .body_contains(r#""method":"eth_getLogs""#);
+            then.status(200)
+                .header("Content-type", "application/json")
+                .delay(std::time::Duration::from_millis(200))
+                .body(r#"{"jsonrpc":"2.0","id":0,"result":[]}"#);
+        });
+
+        let url = Url::parse(&server.url("/"))?;
+        let addr = H160::from_slice(&core_addr::MAINNET);
+        let mut fetcher = StateUpdateLogFetcher::new(
+            EthereumClient::new(url)?,
+            addr,
+            NonZeroU64::new(100).unwrap(),
+            std::time::Duration::from_millis(50),
+        );
+
+        // The first chunk covers the whole range, but is too slow.
+        fetcher.fetch(0, 99).await?;
+        mock.assert_hits(1);
+        assert_eq!(fetcher.chunk_size(), 50);
+
+        // Subsequent calls use the smaller range, which keeps shrinking while the provider is slow.
+        fetcher.fetch(100, 149).await?;
+        mock.assert_hits(2);
+        assert_eq!(fetcher.chunk_size(), 25);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stuck_log_fetch_times_out() -> anyhow::Result<()> {
+        // Accepts connections but never responds.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let url = Url::parse(&format!("http://{}", listener.local_addr()?))?;
+        std::thread::spawn(move || {
+            let _connections = listener.incoming().collect::<Vec<_>>();
+        });
+
+        let addr = H160::from_slice(&core_addr::MAINNET);
+        let mut fetcher = StateUpdateLogFetcher::new(
+            EthereumClient::new(url)?,
+            addr,
+            NonZeroU64::new(100).unwrap(),
+            std::time::Duration::from_secs(10),
+        )
+        .with_timeout(std::time::Duration::from_millis(100));
+
+        let result = tokio::time::timeout(std::time::Duration::from_secs(5), fetcher.fetch(0, 99))
+            .await
+            .expect("Fetch should time out by itself");
+
+        assert!(
+            matches!(
+                result,
+                Err(FetchError::Timeout {
+                    from: 0,
+                    to: 99,
+                    ..
+                })
+            ),
+            "{result:?}"
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_h256() {
         assert!(H256::from_str(
@@ -320,6 +1159,63 @@ mod tests {
         assert_eq!(H256::from_str(&lpad64("0x7eeb")).unwrap(), expected);
     }
 
+    #[test]
+    fn oversized_log_numbers_are_rejected() {
+        let log = |data: String, ethereum_block: &str| {
+            serde_json::json!({
+                "data": data,
+                "blockNumber": ethereum_block,
+                "blockHash": "0x1",
+                "transactionHash": "0x2",
+                "logIndex": "0x0",
+            })
+        };
+        let word = |value: &str| format!("{value:0>64}");
+
+        let valid = log(format!("0x{}{}", word("1"), word("2")), "0x10");
+        let result = parse_state_update_log(&valid).unwrap();
+        assert_eq!(result.block_number, BlockNumber::new_or_panic(2));
+        assert_eq!(result.origin.block_number, 0x10);
+
+        // Starknet block number which exceeds u64.
+        let invalid = log(
+            format!("0x{}{}", word("1"), word("10000000000000000")),
+            "0x10",
+        );
+        parse_state_update_log(&invalid).unwrap_err();
+
+        // Ethereum block number which exceeds u64.
+        let invalid = log(
+            format!("0x{}{}", word("1"), word("2")),
+            "0x10000000000000000",
+        );
+        parse_state_update_log(&invalid).unwrap_err();
+
+        // Truncated data.
+        let invalid = log(format!("0x{}{}", word("1"), "02"), "0x10");
+        parse_state_update_log(&invalid).unwrap_err();
+    }
+
+    #[test]
+    fn fuzz_seed_logs_parse() {
+        // Keeps the fuzzer's seed corpus in both data layouts valid as the parser changes.
+        let two_words: serde_json::Value = serde_json::from_str(include_str!(
+            "../fuzz/corpus/state_update_log/log_two_words.json"
+        ))
+        .unwrap();
+        let result = parse_state_update_log(&two_words).unwrap();
+        assert_eq!(result.block_number, BlockNumber::GENESIS);
+        assert_eq!(result.block_hash, None);
+
+        let three_words: serde_json::Value = serde_json::from_str(include_str!(
+            "../fuzz/corpus/state_update_log/log_three_words.json"
+        ))
+        .unwrap();
+        let result = parse_state_update_log(&three_words).unwrap();
+        assert_eq!(result.block_number, BlockNumber::new_or_panic(0x7eeb));
+        assert!(result.block_hash.is_some());
+    }
+
     #[test]
     fn test_lpad64() {
         for (input, expected) in [
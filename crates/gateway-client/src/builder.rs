@@ -10,13 +10,22 @@
 //!   3. [Params](stage::Params) where you select the retry behavior.
 //!   4. [Final](stage::Final) where you select the REST operation type, which is then executed.
 use crate::metrics::{with_metrics, BlockTag, RequestMetadata};
-use pathfinder_common::{BlockId, ClassHash, TransactionHash};
+use crate::recording::Recording;
+use crate::request_limit::RequestLimit;
+use pathfinder_common::{BlockId, ClassHash, ContractAddress, TransactionHash};
 use starknet_gateway_types::error::SequencerError;
+use std::sync::Arc;
 
 /// A Sequencer Request builder.
 pub struct Request<'a, S: RequestState> {
     state: S,
     url: reqwest::Url,
+    /// Tried in order if the request to `url` fails with a connection or server error.
+    fallbacks: Vec<reqwest::Url>,
+    /// Limits the number of requests in flight, shared between all requests of a client.
+    limit: Option<Arc<RequestLimit>>,
+    /// Records or replays the responses to `GET` requests.
+    recording: Option<Recording>,
     client: &'a reqwest::Client,
 }
 
@@ -38,6 +47,7 @@ pub mod stage {
     /// Specify the request parameters:
     /// - [at_block](super::Request::with_block)
     /// - [with_class_hash](super::Request::with_class_hash)
+    /// - [with_contract_address](super::Request::with_contract_address)
     /// - [with_optional_token](super::Request::with_optional_token)
     /// - [with_transaction_hash](super::Request::with_transaction_hash)
     /// - [add_param](super::Request::add_param) (allows adding custom (name, value) parameter)
@@ -65,8 +75,21 @@ pub mod stage {
 impl<'a> Request<'a, stage::Init> {
     /// Initialize a [Request] builder.
     pub fn builder(client: &'a reqwest::Client, url: reqwest::Url) -> Request<'a, stage::Method> {
+        Self::builder_with_fallbacks(client, url, Vec::new())
+    }
+
+    /// Initialize a [Request] builder which falls back to the given urls, in order, if the
+    /// request to `url` fails with a connection or server error.
+    pub fn builder_with_fallbacks(
+        client: &'a reqwest::Client,
+        url: reqwest::Url,
+        fallbacks: Vec<reqwest::Url>,
+    ) -> Request<'a, stage::Method> {
         Request {
             url,
+            fallbacks,
+            limit: None,
+            recording: None,
             client,
             state: stage::Method,
         }
@@ -121,8 +144,19 @@ mod request_macros {
 }
 
 impl<'a> Request<'a, stage::Method> {
+    /// Each request attempt waits for a permit from `limit` before it is sent.
+    pub(crate) fn with_request_limit(self, limit: Option<Arc<RequestLimit>>) -> Self {
+        Self { limit, ..self }
+    }
+
+    /// `GET` responses are recorded to, or replayed from, disk as specified by `recording`.
+    pub fn with_recording(self, recording: Option<Recording>) -> Self {
+        Self { recording, ..self }
+    }
+
     request_macros::methods!(
         add_transaction,
+        estimate_fee,
         get_block,
         get_class_by_hash,
         get_compiled_class_by_class_hash,
@@ -132,17 +166,22 @@ impl<'a> Request<'a, stage::Method> {
         get_block_traces,
         get_transaction_trace,
         get_signature,
+        get_class_hash_at,
     );
 
     /// Appends the given method to the request url.
     fn with_method(mut self, method: &'static str) -> Request<'a, stage::Params> {
-        self.url
-            .path_segments_mut()
-            .expect("Base URL is valid")
-            .push(method);
+        for url in std::iter::once(&mut self.url).chain(&mut self.fallbacks) {
+            url.path_segments_mut()
+                .expect("Base URL is valid")
+                .push(method);
+        }
 
         Request {
             url: self.url,
+            fallbacks: self.fallbacks,
+            limit: self.limit,
+            recording: self.recording,
             client: self.client,
             state: stage::Params {
                 meta: RequestMetadata::new(method),
@@ -175,6 +214,10 @@ impl<'a> Request<'a, stage::Params> {
         self.add_param("classHash", &class_hash.0.to_hex_str())
     }
 
+    pub fn with_contract_address(self, address: ContractAddress) -> Self {
+        self.add_param("contractAddress", &address.0.to_hex_str())
+    }
+
     pub fn with_optional_token(self, token: Option<&str>) -> Self {
         match token {
             Some(token) => self.add_param("token", token),
@@ -187,7 +230,9 @@ impl<'a> Request<'a, stage::Params> {
     }
 
     pub fn add_param(mut self, name: &str, value: &str) -> Self {
-        self.url.query_pairs_mut().append_pair(name, value);
+        for url in std::iter::once(&mut self.url).chain(&mut self.fallbacks) {
+            url.query_pairs_mut().append_pair(name, value);
+        }
         self
     }
 
@@ -200,6 +245,9 @@ impl<'a> Request<'a, stage::Params> {
     pub fn with_retry(self, retry: bool) -> Request<'a, stage::Final> {
         Request {
             url: self.url,
+            fallbacks: self.fallbacks,
+            limit: self.limit,
+            recording: self.recording,
             client: self.client,
             state: stage::Final {
                 meta: self.state.meta,
@@ -218,28 +266,30 @@ impl<'a> Request<'a, stage::Final> {
         async fn send_request<T: serde::de::DeserializeOwned>(
             url: reqwest::Url,
             client: &reqwest::Client,
+            recording: Option<&Recording>,
             meta: RequestMetadata,
         ) -> Result<T, SequencerError> {
             with_metrics(meta, async move {
                 tracing::trace!(%url, "Fetching data from feeder gateway");
-                let response = client.get(url).send().await?;
+                let response = send_get(client, url, recording).await?;
                 parse::<T>(response).await
             })
             .await
         }
 
+        let send = || {
+            with_fallbacks(
+                &self.url,
+                &self.fallbacks,
+                self.limit.as_deref(),
+                fallback_condition,
+                |url| send_request(url, self.client, self.recording.as_ref(), self.state.meta),
+            )
+        };
+
         match self.state.retry {
-            false => send_request(self.url, self.client, self.state.meta).await,
-            true => {
-                retry0(
-                    || async {
-                        let clone_url = self.url.clone();
-                        send_request(clone_url, self.client, self.state.meta).await
-                    },
-                    retry_condition,
-                )
-                .await
-            }
+            false => send().await,
+            true => retry0(send, retry_condition).await,
         }
     }
 
@@ -248,10 +298,11 @@ impl<'a> Request<'a, stage::Final> {
         async fn get_as_bytes_inner(
             url: reqwest::Url,
             client: &reqwest::Client,
+            recording: Option<&Recording>,
             meta: RequestMetadata,
         ) -> Result<bytes::Bytes, SequencerError> {
             with_metrics(meta, async {
-                let response = client.get(url).send().await?;
+                let response = send_get(client, url, recording).await?;
                 let response = parse_raw(response).await?;
                 let bytes = response.bytes().await?;
                 Ok(bytes)
@@ -259,18 +310,21 @@ impl<'a> Request<'a, stage::Final> {
             .await
         }
 
+        let send = || {
+            with_fallbacks(
+                &self.url,
+                &self.fallbacks,
+                self.limit.as_deref(),
+                fallback_condition,
+                |url| {
+                    get_as_bytes_inner(url, self.client, self.recording.as_ref(), self.state.meta)
+                },
+            )
+        };
+
         match self.state.retry {
-            false => get_as_bytes_inner(self.url, self.client, self.state.meta).await,
-            true => {
-                retry0(
-                    || async {
-                        let clone_url = self.url.clone();
-                        get_as_bytes_inner(clone_url, self.client, self.state.meta).await
-                    },
-                    retry_condition,
-                )
-                .await
-            }
+            false => send().await,
+            true => retry0(send, retry_condition).await,
         }
     }
 
@@ -298,22 +352,35 @@ impl<'a> Request<'a, stage::Final> {
             .await
         }
 
+        let send = || {
+            with_fallbacks(
+                &self.url,
+                &self.fallbacks,
+                self.limit.as_deref(),
+                post_fallback_condition,
+                |url| post_with_json_inner(url, self.client, self.state.meta, json),
+            )
+        };
+
         match self.state.retry {
-            false => post_with_json_inner(self.url, self.client, self.state.meta, json).await,
-            true => {
-                retry0(
-                    || async {
-                        let clone_url = self.url.clone();
-                        post_with_json_inner(clone_url, self.client, self.state.meta, json).await
-                    },
-                    retry_condition,
-                )
-                .await
-            }
+            false => send().await,
+            true => retry0(send, retry_condition).await,
         }
     }
 }
 
+/// Sends a `GET` request, unless a `recording` is being replayed.
+async fn send_get(
+    client: &reqwest::Client,
+    url: reqwest::Url,
+    recording: Option<&Recording>,
+) -> Result<reqwest::Response, SequencerError> {
+    match recording {
+        Some(recording) => recording.get(client, url).await,
+        None => Ok(client.get(url).send().await?),
+    }
+}
+
 async fn parse<T>(response: reqwest::Response) -> Result<T, SequencerError>
 where
     T: ::serde::de::DeserializeOwned,
@@ -347,6 +414,81 @@ async fn parse_raw(response: reqwest::Response) -> Result<reqwest::Response, Seq
 
 pub trait RequestState {}
 
+/// Sends the request to `primary`, and then to each of the `fallbacks` in order for as long as
+/// the request fails with an error which `condition` deems gateway specific.
+///
+/// All responses are parsed identically, regardless of which gateway served them.
+///
+/// If a `limit` is given, each attempt holds one of its permits while in flight, and the attempt's
+/// outcome is used to adapt the limit.
+async fn with_fallbacks<T, Fut, F>(
+    primary: &reqwest::Url,
+    fallbacks: &[reqwest::Url],
+    limit: Option<&RequestLimit>,
+    condition: fn(&SequencerError) -> bool,
+    mut send: F,
+) -> Result<T, SequencerError>
+where
+    Fut: futures::Future<Output = Result<T, SequencerError>>,
+    F: FnMut(reqwest::Url) -> Fut,
+{
+    let mut send = |url| {
+        let request = send(url);
+        async move {
+            match limit {
+                Some(limit) => {
+                    let _permit = limit.acquire().await;
+                    let result = request.await;
+                    limit.record(&result);
+                    result
+                }
+                None => request.await,
+            }
+        }
+    };
+
+    let mut result = send(primary.clone()).await;
+
+    for url in fallbacks {
+        match &result {
+            Err(e) if condition(e) => {
+                tracing::warn!(reason=%e, fallback=%url, "Request failed, trying fallback gateway");
+                result = send(url.clone()).await;
+            }
+            _ => break,
+        }
+    }
+
+    result
+}
+
+/// Determines if a request should be attempted against the next fallback gateway.
+///
+/// Starknet errors are valid replies and are therefore never retried against another gateway.
+fn fallback_condition(e: &SequencerError) -> bool {
+    match e {
+        SequencerError::ReqwestError(e) => {
+            e.is_connect()
+                || e.is_timeout()
+                || e.status().is_some_and(|status| status.is_server_error())
+        }
+        SequencerError::StarknetError(_) => false,
+        SequencerError::InvalidStarknetErrorVariant => true,
+    }
+}
+
+/// Like [fallback_condition], but for `POST` requests such as `add_transaction`.
+///
+/// A timeout or server error does not mean that the request was not processed, so resending it
+/// to another gateway could submit the same transaction twice. Only requests which never reached
+/// the gateway are sent to the next fallback.
+fn post_fallback_condition(e: &SequencerError) -> bool {
+    match e {
+        SequencerError::ReqwestError(e) => e.is_connect(),
+        SequencerError::StarknetError(_) | SequencerError::InvalidStarknetErrorVariant => false,
+    }
+}
+
 /// Wrapper function to allow retrying sequencer queries in an exponential manner.
 async fn retry0<T, Fut, FutureFactory, Ret>(
     future_factory: FutureFactory,
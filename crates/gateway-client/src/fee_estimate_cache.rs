@@ -0,0 +1,135 @@
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+
+use pathfinder_common::{BlockId, StateCommitment};
+use starknet_gateway_types::error::SequencerError;
+use starknet_gateway_types::reply::FeeEstimate;
+use starknet_gateway_types::request::add_transaction::AddTransaction;
+
+use crate::GatewayApi;
+
+/// Caches fee estimates forwarded to the sequencer.
+///
+/// A fee estimate depends only on the transaction and the state it is executed on, so
+/// estimates are keyed by the serialized transaction payload and the block's state commitment.
+/// Identical requests against the same state are served from the cache.
+#[derive(Clone)]
+pub struct FeeEstimateCache(Arc<Mutex<lru::LruCache<(StateCommitment, Vec<u8>), FeeEstimate>>>);
+
+impl FeeEstimateCache {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self(Arc::new(Mutex::new(lru::LruCache::new(capacity))))
+    }
+
+    /// Returns the cached estimate for `transaction` on top of the state identified by
+    /// `state_commitment`, or forwards the request to the `sequencer` and caches its reply.
+    ///
+    /// `block` must be the block whose state commitment is `state_commitment`.
+    pub async fn estimate_fee(
+        &self,
+        sequencer: &impl GatewayApi,
+        transaction: AddTransaction,
+        block: BlockId,
+        state_commitment: StateCommitment,
+    ) -> Result<FeeEstimate, SequencerError> {
+        let payload = match serde_json::to_vec(&transaction) {
+            Ok(payload) => payload,
+            // Not cacheable, but the sequencer might still accept it.
+            Err(_) => return sequencer.estimate_fee(transaction, block).await,
+        };
+        let key = (state_commitment, payload);
+
+        if let Some(cached) = self.0.lock().unwrap().get(&key) {
+            return Ok(cached.clone());
+        }
+
+        let estimate = sequencer.estimate_fee(transaction, block).await?;
+        self.0.lock().unwrap().put(key, estimate.clone());
+
+        Ok(estimate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockGatewayApi;
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::{ContractAddress, Fee, TransactionNonce, TransactionVersion};
+    use starknet_gateway_types::request::add_transaction::InvokeFunction;
+
+    fn transaction() -> AddTransaction {
+        AddTransaction::Invoke(InvokeFunction {
+            version: TransactionVersion::ONE,
+            max_fee: Fee::ZERO,
+            signature: vec![],
+            nonce: Some(TransactionNonce::ZERO),
+            sender_address: ContractAddress::ONE,
+            entry_point_selector: None,
+            calldata: vec![],
+        })
+    }
+
+    fn estimate() -> FeeEstimate {
+        FeeEstimate {
+            overall_fee: 1000,
+            gas_price: 10,
+            gas_usage: 100,
+            unit: "wei".to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn identical_request_is_cached() {
+        let mut sequencer = MockGatewayApi::new();
+        sequencer
+            .expect_estimate_fee()
+            .times(1)
+            .returning(|_, _| Ok(estimate()));
+
+        let cache = FeeEstimateCache::new(NonZeroUsize::new(10).unwrap());
+        let root = state_commitment_bytes!(b"root");
+
+        let first = cache
+            .estimate_fee(&sequencer, transaction(), BlockId::Latest, root)
+            .await
+            .unwrap();
+        let second = cache
+            .estimate_fee(&sequencer, transaction(), BlockId::Latest, root)
+            .await
+            .unwrap();
+
+        assert_eq!(first, estimate());
+        assert_eq!(second, estimate());
+    }
+
+    #[tokio::test]
+    async fn different_state_is_not_cached() {
+        let mut sequencer = MockGatewayApi::new();
+        sequencer
+            .expect_estimate_fee()
+            .times(2)
+            .returning(|_, _| Ok(estimate()));
+
+        let cache = FeeEstimateCache::new(NonZeroUsize::new(10).unwrap());
+
+        cache
+            .estimate_fee(
+                &sequencer,
+                transaction(),
+                BlockId::Latest,
+                state_commitment_bytes!(b"root 0"),
+            )
+            .await
+            .unwrap();
+        cache
+            .estimate_fee(
+                &sequencer,
+                transaction(),
+                BlockId::Latest,
+                state_commitment_bytes!(b"root 1"),
+            )
+            .await
+            .unwrap();
+    }
+}
@@ -16,7 +16,13 @@ use starknet_gateway_types::{
 use std::{fmt::Debug, result::Result, time::Duration};
 
 mod builder;
+mod fee_estimate_cache;
 mod metrics;
+mod recording;
+mod request_limit;
+
+pub use fee_estimate_cache::FeeEstimateCache;
+pub use recording::Recording;
 
 #[allow(unused_variables)]
 #[mockall::automock]
@@ -140,6 +146,22 @@ pub trait GatewayApi: Sync {
     async fn signature(&self, block: BlockId) -> Result<reply::BlockSignature, SequencerError> {
         unimplemented!();
     }
+
+    async fn class_hash_at(
+        &self,
+        contract_address: ContractAddress,
+        block: BlockId,
+    ) -> Result<ClassHash, SequencerError> {
+        unimplemented!();
+    }
+
+    async fn estimate_fee(
+        &self,
+        transaction: AddTransaction,
+        block: BlockId,
+    ) -> Result<reply::FeeEstimate, SequencerError> {
+        unimplemented!();
+    }
 }
 
 /// This is a **temporary** measure to keep the sync logic unchanged
@@ -298,6 +320,22 @@ impl<T: GatewayApi + Sync + Send> GatewayApi for std::sync::Arc<T> {
     async fn signature(&self, block: BlockId) -> Result<reply::BlockSignature, SequencerError> {
         self.as_ref().signature(block).await
     }
+
+    async fn class_hash_at(
+        &self,
+        contract_address: ContractAddress,
+        block: BlockId,
+    ) -> Result<ClassHash, SequencerError> {
+        self.as_ref().class_hash_at(contract_address, block).await
+    }
+
+    async fn estimate_fee(
+        &self,
+        transaction: AddTransaction,
+        block: BlockId,
+    ) -> Result<reply::FeeEstimate, SequencerError> {
+        self.as_ref().estimate_fee(transaction, block).await
+    }
 }
 
 /// Starknet sequencer client using REST API.
@@ -318,6 +356,14 @@ pub struct Client {
     gateway: Url,
     /// Starknet feeder gateway URL.
     feeder_gateway: Url,
+    /// Gateway URLs which are tried in order if a request to `gateway` fails.
+    gateway_fallbacks: Vec<Url>,
+    /// Feeder gateway URLs which are tried in order if a request to `feeder_gateway` fails.
+    feeder_gateway_fallbacks: Vec<Url>,
+    /// Caps the number of requests in flight across all clones of this client.
+    request_limit: Option<std::sync::Arc<request_limit::RequestLimit>>,
+    /// Records or replays feeder gateway responses.
+    recording: Option<Recording>,
     /// Whether __read only__ requests should be retried, defaults to __true__ for production.
     /// Use [disable_retry_for_tests](Client::disable_retry_for_tests) to disable retry logic for all __read only__ requests when testing.
     retry: bool,
@@ -357,16 +403,88 @@ impl Client {
         metrics::register();
 
         Ok(Self {
-            inner: reqwest::Client::builder()
-                .timeout(Duration::from_secs(120))
-                .user_agent(pathfinder_common::consts::USER_AGENT)
-                .build()?,
+            inner: Self::http_client(Default::default())?,
             gateway,
             feeder_gateway,
+            gateway_fallbacks: Vec::new(),
+            feeder_gateway_fallbacks: Vec::new(),
+            request_limit: None,
+            recording: None,
             retry: true,
         })
     }
 
+    /// Sets fallback sequencers which are tried, in order, if a request fails with a connection
+    /// or server error.
+    ///
+    /// Transactions are only resubmitted to a fallback if the connection failed, since the
+    /// previous gateway may have accepted them despite an error.
+    ///
+    /// Like [Client::with_base_url], each base url must serve both the gateway and feeder gateway.
+    pub fn with_fallback_base_urls(self, bases: Vec<Url>) -> anyhow::Result<Self> {
+        let (gateway_fallbacks, feeder_gateway_fallbacks) = bases
+            .iter()
+            .map(|base| Ok((base.join("gateway")?, base.join("feeder_gateway")?)))
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .into_iter()
+            .unzip();
+
+        Ok(Self {
+            gateway_fallbacks,
+            feeder_gateway_fallbacks,
+            ..self
+        })
+    }
+
+    /// Sets headers which are sent with every request made by this client.
+    ///
+    /// This is useful for gateways or caching proxies which require an API key or other
+    /// authentication header.
+    pub fn with_default_headers(self, headers: reqwest::header::HeaderMap) -> anyhow::Result<Self> {
+        Ok(Self {
+            inner: Self::http_client(headers)?,
+            ..self
+        })
+    }
+
+    /// Limits the number of requests in flight to `limit`, shared across all clones of the
+    /// returned client. Requests beyond the limit wait until an earlier request completes.
+    ///
+    /// The limit adapts to the sequencer's rate limiting: it is halved whenever a request is
+    /// rejected with `429 Too Many Requests`, and gradually grows back to `limit` as requests
+    /// succeed.
+    pub fn with_request_limit(self, limit: std::num::NonZeroUsize) -> Self {
+        Self {
+            request_limit: Some(std::sync::Arc::new(request_limit::RequestLimit::new(limit))),
+            ..self
+        }
+    }
+
+    /// The current limit on requests in flight, if any. See [Client::with_request_limit].
+    pub fn request_limit(&self) -> Option<usize> {
+        self.request_limit.as_ref().map(|limit| limit.limit())
+    }
+
+    /// Records feeder gateway responses to, or replays them from, a directory. See [Recording].
+    ///
+    /// Requests to the gateway, i.e. transaction submissions, are never recorded or replayed.
+    pub fn with_recording(self, recording: Recording) -> Self {
+        Self {
+            recording: Some(recording),
+            ..self
+        }
+    }
+
+    fn http_client(headers: reqwest::header::HeaderMap) -> anyhow::Result<reqwest::Client> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(120))
+            .user_agent(pathfinder_common::consts::USER_AGENT)
+            .default_headers(headers)
+            .build()?;
+
+        Ok(client)
+    }
+
     /// Use this method to disable retry logic for all __non write__ requests when testing.
     pub fn disable_retry_for_tests(self) -> Self {
         Self {
@@ -376,11 +494,22 @@ impl Client {
     }
 
     fn gateway_request(&self) -> builder::Request<'_, builder::stage::Method> {
-        builder::Request::builder(&self.inner, self.gateway.clone())
+        builder::Request::builder_with_fallbacks(
+            &self.inner,
+            self.gateway.clone(),
+            self.gateway_fallbacks.clone(),
+        )
+        .with_request_limit(self.request_limit.clone())
     }
 
     fn feeder_gateway_request(&self) -> builder::Request<'_, builder::stage::Method> {
-        builder::Request::builder(&self.inner, self.feeder_gateway.clone())
+        builder::Request::builder_with_fallbacks(
+            &self.inner,
+            self.feeder_gateway.clone(),
+            self.feeder_gateway_fallbacks.clone(),
+        )
+        .with_request_limit(self.request_limit.clone())
+        .with_recording(self.recording.clone())
     }
 
     async fn block_with_retry_behaviour(
@@ -681,6 +810,40 @@ impl GatewayApi for Client {
             .get()
             .await
     }
+
+    #[tracing::instrument(skip(self))]
+    async fn class_hash_at(
+        &self,
+        contract_address: ContractAddress,
+        block: BlockId,
+    ) -> Result<ClassHash, SequencerError> {
+        self.feeder_gateway_request()
+            .get_class_hash_at()
+            .with_contract_address(contract_address)
+            .with_block(block)
+            .with_retry(self.retry)
+            .get()
+            .await
+    }
+
+    /// Estimates the fee of a transaction executed on top of the given block.
+    #[tracing::instrument(skip(self))]
+    async fn estimate_fee(
+        &self,
+        transaction: AddTransaction,
+        block: BlockId,
+    ) -> Result<reply::FeeEstimate, SequencerError> {
+        // Note that we don't do retries here.
+        // This method is used to proxy a fee estimation from the JSON-RPC
+        // API to the sequencer. Retries should be implemented in the JSON-RPC
+        // client instead.
+        self.feeder_gateway_request()
+            .estimate_fee()
+            .with_block(block)
+            .with_retry(false)
+            .post_with_json(&transaction)
+            .await
+    }
 }
 
 #[async_trait::async_trait]
@@ -927,6 +1090,385 @@ mod tests {
         server_handle.await.unwrap();
     }
 
+    #[tokio::test]
+    async fn client_default_headers() {
+        use pathfinder_common::BlockTimestamp;
+        use starknet_gateway_types::reply::{Block, Status};
+        use std::convert::Infallible;
+        use warp::Filter;
+
+        let filter =
+            warp::header::optional("x-api-key").and_then(|api_key: Option<String>| async move {
+                assert_eq!(api_key.as_deref(), Some("secret"));
+
+                Ok::<_, Infallible>(warp::reply::json(&Block {
+                    block_hash: BlockHash(Felt::ZERO),
+                    block_number: BlockNumber::GENESIS,
+                    gas_price: None,
+                    parent_block_hash: BlockHash(Felt::ZERO),
+                    sequencer_address: None,
+                    state_commitment: pathfinder_common::StateCommitment(Felt::ZERO),
+                    status: Status::AcceptedOnL2,
+                    timestamp: BlockTimestamp::new_or_panic(0),
+                    transaction_receipts: vec![],
+                    transactions: vec![],
+                    starknet_version: StarknetVersion::default(),
+                }))
+            });
+
+        let (addr, run_srv) = warp::serve(filter).bind_ephemeral(([127, 0, 0, 1], 0));
+        let server_handle = tokio::spawn(run_srv);
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("x-api-key", "secret".parse().unwrap());
+
+        let url = Url::parse(&format!("http://{addr}")).unwrap();
+        let client = Client::with_base_url(url)
+            .unwrap()
+            .with_default_headers(headers)
+            .unwrap()
+            .disable_retry_for_tests();
+
+        client
+            .block(BlockNumber::GENESIS.into())
+            .await
+            .expect("Header should be sent");
+
+        server_handle.abort();
+    }
+
+    #[tokio::test]
+    async fn fallback_gateway_is_used_on_failure() {
+        use starknet_gateway_types::reply::MaybePendingBlock;
+        use warp::Filter;
+
+        // The primary sequencer is unavailable.
+        let primary = warp::any()
+            .map(|| warp::reply::with_status("", warp::http::StatusCode::SERVICE_UNAVAILABLE));
+        let (primary_addr, run_srv) = warp::serve(primary).bind_ephemeral(([127, 0, 0, 1], 0));
+        let primary_handle = tokio::spawn(run_srv);
+
+        let fallback = warp::path!("feeder_gateway" / "get_block").map(|| v0_9_0::block::GENESIS);
+        let (fallback_addr, run_srv) = warp::serve(fallback).bind_ephemeral(([127, 0, 0, 1], 0));
+        let fallback_handle = tokio::spawn(run_srv);
+
+        let client = Client::with_base_url(Url::parse(&format!("http://{primary_addr}")).unwrap())
+            .unwrap()
+            .with_fallback_base_urls(vec![Url::parse(&format!("http://{fallback_addr}")).unwrap()])
+            .unwrap()
+            .disable_retry_for_tests();
+
+        let block = client.block(BlockNumber::GENESIS.into()).await.unwrap();
+        assert_matches!(block, MaybePendingBlock::Block(block) => assert_eq!(block.block_number, BlockNumber::GENESIS));
+
+        primary_handle.abort();
+        fallback_handle.abort();
+    }
+
+    #[tokio::test]
+    async fn request_limit_is_respected() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use warp::Filter;
+
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+
+        let filter = {
+            let in_flight = in_flight.clone();
+            let max_in_flight = max_in_flight.clone();
+            warp::path!("feeder_gateway" / "get_block").then(move || {
+                let in_flight = in_flight.clone();
+                let max_in_flight = max_in_flight.clone();
+                async move {
+                    let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_flight.fetch_max(current, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    v0_9_0::block::GENESIS
+                }
+            })
+        };
+        let (addr, run_srv) = warp::serve(filter).bind_ephemeral(([127, 0, 0, 1], 0));
+        let server_handle = tokio::spawn(run_srv);
+
+        let client = Client::with_base_url(Url::parse(&format!("http://{addr}")).unwrap())
+            .unwrap()
+            .with_request_limit(std::num::NonZeroUsize::new(2).unwrap())
+            .disable_retry_for_tests();
+
+        let requests = (0..5).map(|_| {
+            let client = client.clone();
+            async move { client.block(BlockNumber::GENESIS.into()).await }
+        });
+        for result in futures::future::join_all(requests).await {
+            result.unwrap();
+        }
+
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+
+        server_handle.abort();
+    }
+
+    #[tokio::test]
+    async fn request_limit_adapts_to_rate_limiting() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use warp::Filter;
+
+        // The first requests are rate limited, all later ones succeed.
+        const RATE_LIMITED: usize = 2;
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let filter = {
+            let requests = requests.clone();
+            warp::path!("feeder_gateway" / "get_block").map(move || {
+                let status = if requests.fetch_add(1, Ordering::SeqCst) < RATE_LIMITED {
+                    warp::http::StatusCode::TOO_MANY_REQUESTS
+                } else {
+                    warp::http::StatusCode::OK
+                };
+                warp::reply::with_status(v0_9_0::block::GENESIS, status)
+            })
+        };
+        let (addr, run_srv) = warp::serve(filter).bind_ephemeral(([127, 0, 0, 1], 0));
+        let server_handle = tokio::spawn(run_srv);
+
+        let client = Client::with_base_url(Url::parse(&format!("http://{addr}")).unwrap())
+            .unwrap()
+            .with_request_limit(std::num::NonZeroUsize::new(4).unwrap())
+            .disable_retry_for_tests();
+
+        for _ in 0..RATE_LIMITED {
+            client.block(BlockNumber::GENESIS.into()).await.unwrap_err();
+        }
+        assert_eq!(client.request_limit(), Some(1));
+
+        // The limit grows by one after as many successes as the current limit.
+        client.block(BlockNumber::GENESIS.into()).await.unwrap();
+        assert_eq!(client.request_limit(), Some(2));
+        for _ in 0..(2 + 3) {
+            client.block(BlockNumber::GENESIS.into()).await.unwrap();
+        }
+        assert_eq!(client.request_limit(), Some(4));
+
+        // The limit never exceeds the configured maximum.
+        for _ in 0..10 {
+            client.block(BlockNumber::GENESIS.into()).await.unwrap();
+        }
+        assert_eq!(client.request_limit(), Some(4));
+
+        server_handle.abort();
+    }
+
+    #[tokio::test]
+    async fn reads_and_writes_use_their_own_endpoint() {
+        use warp::Filter;
+
+        let feeder_gateway =
+            warp::path!("feeder_gateway" / "get_block").map(|| v0_9_0::block::GENESIS);
+        let (feeder_gateway_addr, run_srv) =
+            warp::serve(feeder_gateway).bind_ephemeral(([127, 0, 0, 1], 0));
+        let feeder_gateway_handle = tokio::spawn(run_srv);
+
+        let gateway = warp::path!("gateway" / "add_transaction")
+            .map(|| r#"{"code":"TRANSACTION_RECEIVED","transaction_hash":"0x1"}"#);
+        let (gateway_addr, run_srv) = warp::serve(gateway).bind_ephemeral(([127, 0, 0, 1], 0));
+        let gateway_handle = tokio::spawn(run_srv);
+
+        let client = Client::with_urls(
+            Url::parse(&format!("http://{gateway_addr}/gateway")).unwrap(),
+            Url::parse(&format!("http://{feeder_gateway_addr}/feeder_gateway")).unwrap(),
+        )
+        .unwrap()
+        .disable_retry_for_tests();
+
+        client.block(BlockNumber::GENESIS.into()).await.unwrap();
+        client
+            .add_invoke_transaction(
+                TransactionVersion::ONE,
+                Fee::ZERO,
+                vec![],
+                Some(TransactionNonce::ZERO),
+                ContractAddress::ONE,
+                None,
+                vec![],
+            )
+            .await
+            .unwrap();
+
+        feeder_gateway_handle.abort();
+        gateway_handle.abort();
+    }
+
+    #[tokio::test]
+    async fn starknet_errors_do_not_fall_back() {
+        use warp::Filter;
+
+        let primary = warp::any().map(|| {
+            let (body, status) = response_from(KnownStarknetErrorCode::BlockNotFound);
+            warp::reply::with_status(body, warp::http::StatusCode::from_u16(status).unwrap())
+        });
+        let (primary_addr, run_srv) = warp::serve(primary).bind_ephemeral(([127, 0, 0, 1], 0));
+        let primary_handle = tokio::spawn(run_srv);
+
+        let fallback = warp::any().map(|| -> &'static str {
+            panic!("Fallback should not be queried");
+        });
+        let (fallback_addr, run_srv) = warp::serve(fallback).bind_ephemeral(([127, 0, 0, 1], 0));
+        let fallback_handle = tokio::spawn(run_srv);
+
+        let client = Client::with_base_url(Url::parse(&format!("http://{primary_addr}")).unwrap())
+            .unwrap()
+            .with_fallback_base_urls(vec![Url::parse(&format!("http://{fallback_addr}")).unwrap()])
+            .unwrap()
+            .disable_retry_for_tests();
+
+        let error = client.block(BlockNumber::GENESIS.into()).await.unwrap_err();
+        assert_matches!(
+            error,
+            SequencerError::StarknetError(e) => assert_eq!(e.code, KnownStarknetErrorCode::BlockNotFound.into())
+        );
+
+        primary_handle.abort();
+        fallback_handle.abort();
+    }
+
+    #[tokio::test]
+    async fn add_transaction_does_not_fall_back_on_server_error() {
+        use warp::Filter;
+
+        // The primary may have accepted the transaction before failing.
+        let primary = warp::any()
+            .map(|| warp::reply::with_status("", warp::http::StatusCode::SERVICE_UNAVAILABLE));
+        let (primary_addr, run_srv) = warp::serve(primary).bind_ephemeral(([127, 0, 0, 1], 0));
+        let primary_handle = tokio::spawn(run_srv);
+
+        let fallback = warp::any().map(|| -> &'static str {
+            panic!("Fallback should not be queried");
+        });
+        let (fallback_addr, run_srv) = warp::serve(fallback).bind_ephemeral(([127, 0, 0, 1], 0));
+        let fallback_handle = tokio::spawn(run_srv);
+
+        let client = Client::with_base_url(Url::parse(&format!("http://{primary_addr}")).unwrap())
+            .unwrap()
+            .with_fallback_base_urls(vec![Url::parse(&format!("http://{fallback_addr}")).unwrap()])
+            .unwrap()
+            .disable_retry_for_tests();
+
+        let error = client
+            .add_invoke_transaction(
+                TransactionVersion::ONE,
+                Fee::ZERO,
+                vec![],
+                Some(TransactionNonce::ZERO),
+                ContractAddress::ONE,
+                None,
+                vec![],
+            )
+            .await
+            .unwrap_err();
+        assert_matches!(error, SequencerError::ReqwestError(e) => assert!(e.is_status()));
+
+        primary_handle.abort();
+        fallback_handle.abort();
+    }
+
+    #[tokio::test]
+    async fn add_transaction_falls_back_on_connection_error() {
+        use warp::Filter;
+
+        // Nothing listens on the primary's port.
+        let primary_addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let fallback = warp::path!("gateway" / "add_transaction")
+            .map(|| r#"{"code":"TRANSACTION_RECEIVED","transaction_hash":"0x1"}"#);
+        let (fallback_addr, run_srv) = warp::serve(fallback).bind_ephemeral(([127, 0, 0, 1], 0));
+        let fallback_handle = tokio::spawn(run_srv);
+
+        let client = Client::with_base_url(Url::parse(&format!("http://{primary_addr}")).unwrap())
+            .unwrap()
+            .with_fallback_base_urls(vec![Url::parse(&format!("http://{fallback_addr}")).unwrap()])
+            .unwrap()
+            .disable_retry_for_tests();
+
+        client
+            .add_invoke_transaction(
+                TransactionVersion::ONE,
+                Fee::ZERO,
+                vec![],
+                Some(TransactionNonce::ZERO),
+                ContractAddress::ONE,
+                None,
+                vec![],
+            )
+            .await
+            .unwrap();
+
+        fallback_handle.abort();
+    }
+
+    #[tokio::test]
+    async fn estimate_fee() {
+        use starknet_gateway_types::request::add_transaction::InvokeFunction;
+        use warp::Filter;
+
+        let filter = warp::post()
+            .and(warp::path!("feeder_gateway" / "estimate_fee"))
+            .and(warp::query::<std::collections::HashMap<String, String>>())
+            .and(warp::body::json())
+            .map(
+                |query: std::collections::HashMap<String, String>, body: serde_json::Value| {
+                    assert_eq!(query.get("blockNumber").map(String::as_str), Some("latest"));
+                    assert_eq!(body["type"], "INVOKE_FUNCTION");
+
+                    warp::reply::json(&serde_json::json!({
+                        "overall_fee": 1000,
+                        "gas_price": 10,
+                        "gas_usage": 100,
+                        "unit": "wei"
+                    }))
+                },
+            );
+
+        let (addr, run_srv) = warp::serve(filter).bind_ephemeral(([127, 0, 0, 1], 0));
+        let server_handle = tokio::spawn(run_srv);
+
+        let url = Url::parse(&format!("http://{addr}")).unwrap();
+        let client = Client::with_base_url(url)
+            .unwrap()
+            .disable_retry_for_tests();
+
+        let transaction = AddTransaction::Invoke(InvokeFunction {
+            version: TransactionVersion::ONE,
+            max_fee: Fee::ZERO,
+            signature: vec![],
+            nonce: Some(TransactionNonce::ZERO),
+            sender_address: ContractAddress::ONE,
+            entry_point_selector: None,
+            calldata: vec![],
+        });
+
+        let estimate = client
+            .estimate_fee(transaction, BlockId::Latest)
+            .await
+            .unwrap();
+        assert_eq!(
+            estimate,
+            reply::FeeEstimate {
+                overall_fee: 1000,
+                gas_price: 10,
+                gas_usage: 100,
+                unit: "wei".to_owned(),
+            }
+        );
+
+        server_handle.abort();
+    }
+
     mod block_matches_by_hash_on {
         use super::*;
 
@@ -1805,4 +2347,85 @@ mod tests {
                 .unwrap();
         }
     }
+
+    mod class_hash_at {
+        use super::*;
+
+        #[tokio::test]
+        async fn success() {
+            let (_jh, client) = setup([(
+                "/feeder_gateway/get_class_hash_at?contractAddress=0x123&blockNumber=1",
+                (r#""0x456""#, 200),
+            )]);
+
+            let class_hash = client
+                .class_hash_at(
+                    contract_address!("0x123"),
+                    BlockId::Number(BlockNumber::new_or_panic(1)),
+                )
+                .await
+                .unwrap();
+            assert_eq!(class_hash, class_hash!("0x456"));
+        }
+    }
+
+    mod recording {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[tokio::test]
+        async fn replays_recorded_block() {
+            let directory = tempfile::tempdir().unwrap();
+            let block = BlockNumber::new_or_panic(231579);
+            let next = BlockNumber::new_or_panic(231580);
+
+            let (_jh, client) = setup([
+                (
+                    "/feeder_gateway/get_block?blockNumber=231579",
+                    (v0_9_0::block::NUMBER_231579, 200),
+                ),
+                (
+                    "/feeder_gateway/get_block?blockNumber=231580",
+                    response_from(KnownStarknetErrorCode::BlockNotFound),
+                ),
+            ]);
+            let client = client.with_recording(Recording::Record(directory.path().to_owned()));
+
+            let recorded = client.block(block.into()).await.unwrap();
+            let recorded_error = client.block(next.into()).await.unwrap_err();
+
+            // Nothing listens on this port, so any request which is not replayed fails.
+            let client = Client::with_base_url(Url::parse("http://127.0.0.1:1/").unwrap())
+                .unwrap()
+                .disable_retry_for_tests()
+                .with_recording(Recording::Replay(directory.path().to_owned()));
+
+            let replayed = client.block(block.into()).await.unwrap();
+            assert_eq!(replayed, recorded);
+            assert_matches!(
+                (recorded, replayed),
+                (reply::MaybePendingBlock::Block(recorded), reply::MaybePendingBlock::Block(replayed)) => {
+                    assert_eq!(replayed.state_commitment, recorded.state_commitment)
+                }
+            );
+
+            let replayed_error = client.block(next.into()).await.unwrap_err();
+            assert_matches!(
+                (recorded_error, replayed_error),
+                (SequencerError::StarknetError(recorded), SequencerError::StarknetError(replayed)) => {
+                    assert_eq!(replayed, recorded)
+                }
+            );
+
+            // Blocks which were never requested are reported as not found.
+            let error = client
+                .block(BlockNumber::new_or_panic(231581).into())
+                .await
+                .unwrap_err();
+            assert_matches!(
+                error,
+                SequencerError::StarknetError(e) => assert_eq!(e.code, KnownStarknetErrorCode::BlockNotFound.into())
+            );
+        }
+    }
 }
@@ -0,0 +1,156 @@
+//! Records feeder gateway responses to disk and replays them in place of the network.
+//!
+//! This makes a sync session reproducible offline: a session run with [Recording::Record] can be
+//! repeated with [Recording::Replay] against the same directory, and is then fed exactly the
+//! responses that were originally received.
+//!
+//! Each response is stored in its own file, named after the API method and query parameters e.g.
+//! `get_block_blockNumber_5`. Starknet error replies are stored with their HTTP status code as
+//! file extension, so that they are replayed as errors as well.
+use std::path::{Path, PathBuf};
+
+use starknet_gateway_types::error::{KnownStarknetErrorCode, SequencerError, StarknetError};
+
+/// Determines whether feeder gateway responses are recorded to, or replayed from, a directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Recording {
+    /// Every response received is written to the directory.
+    Record(PathBuf),
+    /// Responses are read from the directory instead of being fetched.
+    Replay(PathBuf),
+}
+
+/// Status codes for which the sequencer replies with a [StarknetError] body.
+const ERROR_STATUSES: [reqwest::StatusCode; 2] = [
+    reqwest::StatusCode::BAD_REQUEST,
+    reqwest::StatusCode::INTERNAL_SERVER_ERROR,
+];
+
+impl Recording {
+    /// Performs a `GET` request for `url`, either recording the response or replaying a
+    /// previously recorded one.
+    pub(crate) async fn get(
+        &self,
+        client: &reqwest::Client,
+        url: reqwest::Url,
+    ) -> Result<reqwest::Response, SequencerError> {
+        match self {
+            Recording::Record(directory) => {
+                let response = client.get(url.clone()).send().await?;
+                let status = response.status();
+                let body = response.bytes().await?;
+
+                if status.is_success() || ERROR_STATUSES.contains(&status) {
+                    if let Err(e) = record(directory, &url, status, &body) {
+                        tracing::warn!(%url, error=%e, "Failed to record feeder gateway response");
+                    }
+                }
+
+                Ok(into_response(status, body))
+            }
+            Recording::Replay(directory) => replay(directory, &url),
+        }
+    }
+}
+
+fn record(
+    directory: &Path,
+    url: &reqwest::Url,
+    status: reqwest::StatusCode,
+    body: &[u8],
+) -> std::io::Result<()> {
+    let key = key(url);
+
+    // Only the latest response is kept, so remove any recording with a different status.
+    for path in std::iter::once(directory.join(&key)).chain(
+        ERROR_STATUSES
+            .iter()
+            .map(|status| directory.join(format!("{key}.{}", status.as_u16()))),
+    ) {
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+
+    let path = match status.is_success() {
+        true => directory.join(key),
+        false => directory.join(format!("{key}.{}", status.as_u16())),
+    };
+
+    std::fs::create_dir_all(directory)?;
+    std::fs::write(path, body)
+}
+
+/// Replays the recorded response for `url`.
+///
+/// Requests which were not recorded are answered with [KnownStarknetErrorCode::BlockNotFound], so
+/// that sync stops at the last recorded block.
+fn replay(directory: &Path, url: &reqwest::Url) -> Result<reqwest::Response, SequencerError> {
+    let key = key(url);
+
+    let recordings = std::iter::once((reqwest::StatusCode::OK, directory.join(&key))).chain(
+        ERROR_STATUSES.iter().map(|status| {
+            (
+                *status,
+                directory.join(format!("{key}.{}", status.as_u16())),
+            )
+        }),
+    );
+
+    for (status, path) in recordings {
+        if let Ok(body) = std::fs::read(path) {
+            tracing::trace!(%url, "Replaying recorded feeder gateway response");
+            return Ok(into_response(status, body.into()));
+        }
+    }
+
+    tracing::debug!(%url, "No recorded feeder gateway response");
+
+    Err(SequencerError::StarknetError(StarknetError {
+        code: KnownStarknetErrorCode::BlockNotFound.into(),
+        message: format!("No recorded response for {url}"),
+    }))
+}
+
+/// The file name under which the response to `url` is stored.
+fn key(url: &reqwest::Url) -> String {
+    let method = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .unwrap_or_default();
+    let key = match url.query() {
+        Some(query) => format!("{method}_{query}"),
+        None => method.to_owned(),
+    };
+
+    key.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+fn into_response(status: reqwest::StatusCode, body: bytes::Bytes) -> reqwest::Response {
+    http::Response::builder()
+        .status(status)
+        .body(body)
+        .expect("Status and body are valid")
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_is_a_valid_file_name() {
+        let url = reqwest::Url::parse(
+            "https://example.com/feeder_gateway/get_block?blockNumber=5&token=a%2Fb",
+        )
+        .unwrap();
+
+        assert_eq!(key(&url), "get_block_blockNumber_5_token_a_2Fb");
+    }
+}
@@ -0,0 +1,176 @@
+//! An adaptive limit on the number of concurrent sequencer requests.
+//!
+//! The limit is halved whenever the sequencer rate limits a request, and grows by one for every
+//! `limit` consecutive successful requests until it reaches the configured maximum again.
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+
+use starknet_gateway_types::error::SequencerError;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+pub(crate) struct RequestLimit {
+    semaphore: Semaphore,
+    max: usize,
+    state: Mutex<State>,
+}
+
+struct State {
+    /// The current limit on requests in flight.
+    limit: usize,
+    /// The number of permits which still need to be removed from the semaphore after the limit
+    /// was lowered. Permits in use can't be revoked, so they are forgotten once released instead.
+    debt: usize,
+    /// Successful requests since the limit last changed.
+    successes: usize,
+}
+
+/// Holds one of a [RequestLimit]'s permits.
+pub(crate) struct Permit<'a> {
+    permit: Option<SemaphorePermit<'a>>,
+    limit: &'a RequestLimit,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let mut state = self.limit.state.lock().unwrap();
+        if state.debt > 0 {
+            state.debt -= 1;
+            if let Some(permit) = self.permit.take() {
+                permit.forget();
+            }
+        }
+    }
+}
+
+impl RequestLimit {
+    pub fn new(max: NonZeroUsize) -> Self {
+        Self {
+            semaphore: Semaphore::new(max.get()),
+            max: max.get(),
+            state: Mutex::new(State {
+                limit: max.get(),
+                debt: 0,
+                successes: 0,
+            }),
+        }
+    }
+
+    /// The current limit on requests in flight.
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit
+    }
+
+    pub async fn acquire(&self) -> Permit<'_> {
+        let permit = self
+            .semaphore
+            .acquire()
+            .await
+            .expect("Semaphore is never closed");
+
+        Permit {
+            permit: Some(permit),
+            limit: self,
+        }
+    }
+
+    /// Adapts the limit to the outcome of a request.
+    pub fn record<T>(&self, result: &Result<T, SequencerError>) {
+        match result {
+            Ok(_) => self.on_success(),
+            Err(SequencerError::ReqwestError(e))
+                if e.status() == Some(reqwest::StatusCode::TOO_MANY_REQUESTS) =>
+            {
+                self.on_rate_limited()
+            }
+            // Other errors say nothing about the sequencer's capacity.
+            Err(_) => {}
+        }
+    }
+
+    fn on_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.limit == self.max {
+            return;
+        }
+
+        state.successes += 1;
+        if state.successes >= state.limit {
+            state.successes = 0;
+            state.limit += 1;
+            if state.debt > 0 {
+                state.debt -= 1;
+            } else {
+                self.semaphore.add_permits(1);
+            }
+            tracing::debug!(limit=%state.limit, "Increased sequencer request limit");
+        }
+    }
+
+    fn on_rate_limited(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.successes = 0;
+
+        let limit = (state.limit / 2).max(1);
+        if limit < state.limit {
+            state.debt += state.limit - limit;
+            state.limit = limit;
+            // Pay off as much of the debt as possible right away using the idle permits.
+            while state.debt > 0 {
+                match self.semaphore.try_acquire() {
+                    Ok(permit) => permit.forget(),
+                    Err(_) => break,
+                }
+                state.debt -= 1;
+            }
+            tracing::debug!(%limit, "Rate limited by the sequencer, decreased request limit");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn shrinks_once_permits_are_released() {
+        let limit = RequestLimit::new(NonZeroUsize::new(4).unwrap());
+
+        let permits = [
+            limit.acquire().await,
+            limit.acquire().await,
+            limit.acquire().await,
+        ];
+        limit.on_rate_limited();
+        assert_eq!(limit.limit(), 2);
+
+        // The idle permit is removed right away, the rest of the debt is paid off by releasing
+        // the permits in use.
+        assert_eq!(limit.semaphore.available_permits(), 0);
+        drop(permits);
+        assert_eq!(limit.semaphore.available_permits(), 2);
+
+        limit.on_success();
+        limit.on_success();
+        assert_eq!(limit.limit(), 3);
+        assert_eq!(limit.semaphore.available_permits(), 3);
+    }
+
+    #[test]
+    fn never_drops_below_one() {
+        let limit = RequestLimit::new(NonZeroUsize::new(2).unwrap());
+        limit.on_rate_limited();
+        limit.on_rate_limited();
+        assert_eq!(limit.limit(), 1);
+        assert_eq!(limit.semaphore.available_permits(), 1);
+    }
+
+    #[test]
+    fn never_grows_beyond_max() {
+        let limit = RequestLimit::new(NonZeroUsize::new(2).unwrap());
+        for _ in 0..10 {
+            limit.on_success();
+        }
+        assert_eq!(limit.limit(), 2);
+        assert_eq!(limit.semaphore.available_permits(), 2);
+    }
+}
@@ -1,4 +1,5 @@
 //! Structures used for deserializing replies from Starkware's sequencer REST API.
+use anyhow::Context;
 use pathfinder_common::{
     BlockCommitmentSignatureElem, BlockHash, BlockNumber, BlockTimestamp, ContractAddress,
     EthereumAddress, GasPrice, SequencerAddress, StarknetVersion, StateCommitment,
@@ -7,6 +8,7 @@ use pathfinder_common::{
 use pathfinder_serde::{EthereumAddressAsHexStr, GasPriceAsHexStr};
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
+use stark_hash::Felt;
 
 /// Used to deserialize replies to Starknet block requests.
 #[serde_as]
@@ -1265,13 +1267,62 @@ pub mod state_update {
     pub struct StateDiff {
         #[serde_as(as = "HashMap<_, Vec<_>>")]
         pub storage_diffs: HashMap<ContractAddress, Vec<StorageDiff>>,
+        #[serde(deserialize_with = "unique_contracts")]
         pub deployed_contracts: Vec<DeployedContract>,
         pub old_declared_contracts: HashSet<ClassHash>,
         pub declared_classes: Vec<DeclaredSierraClass>,
         pub nonces: HashMap<ContractAddress, ContractNonce>,
+        #[serde(deserialize_with = "unique_contracts")]
         pub replaced_classes: Vec<ReplacedClass>,
     }
 
+    /// A state diff entry which applies to a single contract.
+    trait ContractEntry {
+        fn address(&self) -> ContractAddress;
+    }
+
+    impl ContractEntry for DeployedContract {
+        fn address(&self) -> ContractAddress {
+            self.address
+        }
+    }
+
+    impl ContractEntry for ReplacedClass {
+        fn address(&self) -> ContractAddress {
+            self.address
+        }
+    }
+
+    /// Deserializes a list of contract entries, keeping only the last entry for each contract.
+    ///
+    /// State diffs are squashed per block, so a duplicate address should not occur. If it does,
+    /// the later entry replaces the earlier one in its position and a warning is logged, rather
+    /// than failing to sync the block.
+    fn unique_contracts<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+        T: Deserialize<'de> + ContractEntry,
+    {
+        let entries = Vec::<T>::deserialize(deserializer)?;
+
+        let mut unique: Vec<T> = Vec::with_capacity(entries.len());
+        let mut positions = HashMap::new();
+        for entry in entries {
+            match positions.get(&entry.address()) {
+                Some(&position) => {
+                    tracing::warn!(contract=%entry.address(), "Duplicate state diff entry for contract, keeping the last one");
+                    unique[position] = entry;
+                }
+                None => {
+                    positions.insert(entry.address(), unique.len());
+                    unique.push(entry);
+                }
+            }
+        }
+
+        Ok(unique)
+    }
+
     /// L2 storage diff.
     #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
     #[serde(deny_unknown_fields)]
@@ -1337,6 +1388,64 @@ pub mod state_update {
                 expected
             );
         }
+
+        #[test]
+        fn duplicate_contract_addresses_keep_the_last_entry() {
+            use super::StateDiff;
+            use pathfinder_common::macro_prelude::*;
+
+            let diff = |deployed: &str, replaced: &str| {
+                format!(
+                    r#"{{
+                        "storage_diffs": {{}},
+                        "deployed_contracts": [{deployed}],
+                        "old_declared_contracts": [],
+                        "declared_classes": [],
+                        "nonces": {{}},
+                        "replaced_classes": [{replaced}]
+                    }}"#
+                )
+            };
+            let entry = |address: &str, class_hash: &str| {
+                format!(r#"{{"address":"{address}","class_hash":"{class_hash}"}}"#)
+            };
+
+            let unique = diff(&entry("0x1", "0x10"), &entry("0x2", "0x20"));
+            serde_json::from_str::<StateDiff>(&unique).unwrap();
+
+            let duplicate = [
+                entry("0x1", "0x10"),
+                entry("0x2", "0x20"),
+                entry("0x1", "0x11"),
+            ]
+            .join(",");
+
+            let state_diff = serde_json::from_str::<StateDiff>(&diff(&duplicate, "")).unwrap();
+            assert_eq!(
+                state_diff
+                    .deployed_contracts
+                    .iter()
+                    .map(|x| (x.address, x.class_hash))
+                    .collect::<Vec<_>>(),
+                vec![
+                    (contract_address!("0x1"), class_hash!("0x11")),
+                    (contract_address!("0x2"), class_hash!("0x20")),
+                ]
+            );
+
+            let state_diff = serde_json::from_str::<StateDiff>(&diff("", &duplicate)).unwrap();
+            assert_eq!(
+                state_diff
+                    .replaced_classes
+                    .iter()
+                    .map(|x| (x.address, x.class_hash))
+                    .collect::<Vec<_>>(),
+                vec![
+                    (contract_address!("0x1"), class_hash!("0x11")),
+                    (contract_address!("0x2"), class_hash!("0x20")),
+                ]
+            );
+        }
     }
 }
 
@@ -1429,6 +1538,15 @@ pub mod add_transaction {
     }
 }
 
+/// Used to deserialize replies to Starknet fee estimation requests.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct FeeEstimate {
+    pub overall_fee: u128,
+    pub gas_price: u128,
+    pub gas_usage: u128,
+    pub unit: String,
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq)]
 pub struct BlockSignature {
     pub block_number: BlockNumber,
@@ -1442,6 +1560,38 @@ pub struct BlockSignatureInput {
     pub state_diff_commitment: StateDiffCommitment,
 }
 
+impl BlockSignature {
+    /// Verifies that the signature was produced by the sequencer holding the private key
+    /// matching `public_key`.
+    ///
+    /// The sequencer signs the Pedersen hash of the block hash and the state diff commitment.
+    pub fn verify(&self, public_key: Felt) -> anyhow::Result<()> {
+        use starknet_crypto::FieldElement;
+
+        let to_field_element = |felt: Felt| {
+            FieldElement::from_bytes_be(&felt.to_be_bytes())
+                .expect("Felt is within the field's range")
+        };
+
+        let message = stark_hash::stark_hash(
+            self.signature_input.block_hash.0,
+            self.signature_input.state_diff_commitment.0,
+        );
+
+        let valid = starknet_crypto::verify(
+            &to_field_element(public_key),
+            &to_field_element(message),
+            &to_field_element(self.signature[0].0),
+            &to_field_element(self.signature[1].0),
+        )
+        .context("Verifying block signature")?;
+
+        anyhow::ensure!(valid, "Invalid signature for block {}", self.block_number);
+
+        Ok(())
+    }
+}
+
 impl From<BlockSignature> for pathfinder_common::BlockCommitmentSignature {
     fn from(value: BlockSignature) -> Self {
         Self {
@@ -1608,6 +1758,59 @@ mod tests {
         assert_eq!(common, expected);
     }
 
+    #[test]
+    fn from_state_update_preserves_counts() {
+        use pathfinder_common::state_update::ContractClassUpdate;
+        use pathfinder_common::ContractAddress;
+
+        let gateway: super::StateUpdate = serde_json::from_str(
+            starknet_gateway_test_fixtures::v0_12_2::state_update::BLOCK_350000,
+        )
+        .unwrap();
+        let raw = gateway.state_diff.clone();
+        let common = pathfinder_common::StateUpdate::from(gateway);
+
+        let deployed = common
+            .contract_updates
+            .values()
+            .filter(|u| matches!(u.class, Some(ContractClassUpdate::Deploy(_))))
+            .count();
+        assert_eq!(deployed, raw.deployed_contracts.len());
+
+        let replaced = common
+            .contract_updates
+            .values()
+            .filter(|u| matches!(u.class, Some(ContractClassUpdate::Replace(_))))
+            .count();
+        assert_eq!(replaced, raw.replaced_classes.len());
+
+        let nonces = common
+            .contract_updates
+            .values()
+            .filter(|u| u.nonce.is_some())
+            .count();
+        assert_eq!(nonces, raw.nonces.len());
+
+        assert!(!raw.storage_diffs.is_empty());
+        for (address, diffs) in &raw.storage_diffs {
+            let storage = if *address == ContractAddress::ONE {
+                &common.system_contract_updates[address].storage
+            } else {
+                &common.contract_updates[address].storage
+            };
+            assert_eq!(storage.len(), diffs.len(), "contract {address}");
+        }
+
+        assert_eq!(
+            common.declared_cairo_classes.len(),
+            raw.old_declared_contracts.len()
+        );
+        assert_eq!(
+            common.declared_sierra_classes.len(),
+            raw.declared_classes.len()
+        );
+    }
+
     mod receipts {
         use crate::reply::transaction::{ExecutionStatus, Receipt};
 
@@ -1724,9 +1927,9 @@ mod tests {
     }
 
     mod block_signature {
-        use pathfinder_common::{
-            block_commitment_signature_elem, block_hash, state_diff_commitment, BlockNumber,
-        };
+        use pathfinder_common::macro_prelude::*;
+        use pathfinder_common::{BlockCommitmentSignatureElem, BlockNumber};
+        use stark_hash::Felt;
 
         use super::super::{BlockSignature, BlockSignatureInput, StateUpdate};
 
@@ -1775,5 +1978,62 @@ mod tests {
                 signature.signature_input.state_diff_commitment
             )
         }
+
+        /// Signs `input` as the sequencer would, returning the public key and signed reply.
+        fn sign(private_key: Felt, input: BlockSignatureInput) -> (Felt, BlockSignature) {
+            use starknet_crypto::FieldElement;
+
+            let private_key = FieldElement::from_bytes_be(&private_key.to_be_bytes()).unwrap();
+            let message = stark_hash::stark_hash(input.block_hash.0, input.state_diff_commitment.0);
+            let message = FieldElement::from_bytes_be(&message.to_be_bytes()).unwrap();
+
+            let k = starknet_crypto::rfc6979_generate_k(&message, &private_key, None);
+            let signature = starknet_crypto::sign(&private_key, &message, &k).unwrap();
+
+            let public_key = starknet_crypto::get_public_key(&private_key);
+            let public_key = Felt::from_be_bytes(public_key.to_bytes_be()).unwrap();
+
+            let signature = BlockSignature {
+                block_number: BlockNumber::new_or_panic(1),
+                signature: [
+                    BlockCommitmentSignatureElem(
+                        Felt::from_be_bytes(signature.r.to_bytes_be()).unwrap(),
+                    ),
+                    BlockCommitmentSignatureElem(
+                        Felt::from_be_bytes(signature.s.to_bytes_be()).unwrap(),
+                    ),
+                ],
+                signature_input: input,
+            };
+
+            (public_key, signature)
+        }
+
+        fn signature_input() -> BlockSignatureInput {
+            BlockSignatureInput {
+                block_hash: block_hash_bytes!(b"block hash"),
+                state_diff_commitment: state_diff_commitment_bytes!(b"state diff commitment"),
+            }
+        }
+
+        #[test]
+        fn correctly_signed_is_accepted() {
+            let (public_key, signature) = sign(felt!("0x1234"), signature_input());
+
+            signature.verify(public_key).unwrap();
+        }
+
+        #[test]
+        fn incorrectly_signed_is_rejected() {
+            let (public_key, mut signature) = sign(felt!("0x1234"), signature_input());
+
+            // Signed by a different sequencer.
+            let (other_public_key, _) = sign(felt!("0x5678"), signature_input());
+            signature.verify(other_public_key).unwrap_err();
+
+            // Signature does not cover the block.
+            signature.signature_input.block_hash = block_hash_bytes!(b"other block hash");
+            signature.verify(public_key).unwrap_err();
+        }
     }
 }
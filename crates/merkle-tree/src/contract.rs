@@ -9,7 +9,7 @@ use crate::{
 };
 use anyhow::Context;
 use bitvec::{prelude::Msb0, slice::BitSlice};
-use pathfinder_common::hash::PedersenHash;
+use pathfinder_common::hash::{FeltHash, PedersenHash};
 use pathfinder_common::trie::TrieNode;
 use pathfinder_common::{
     BlockNumber, ContractAddress, ContractRoot, ContractStateHash, StorageAddress,
@@ -17,7 +17,7 @@ use pathfinder_common::{
 };
 use pathfinder_storage::{Node, Transaction};
 use stark_hash::Felt;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::ops::ControlFlow;
 
 /// A [Patricia Merkle tree](MerkleTree) used to calculate commitments to a Starknet contract's storage.
@@ -25,8 +25,11 @@ use std::ops::ControlFlow;
 /// It maps a contract's [storage addresses](StorageAddress) to their [values](StorageValue).
 ///
 /// Tree data is persisted by a sqlite table 'tree_contracts'.
-pub struct ContractsStorageTree<'tx> {
-    tree: MerkleTree<PedersenHash, 251>,
+///
+/// The Pedersen implementation defaults to [PedersenHash] and can be swapped for another
+/// [FeltHash] backend using [with_hasher](ContractsStorageTree::with_hasher).
+pub struct ContractsStorageTree<'tx, H: FeltHash = PedersenHash> {
+    tree: MerkleTree<H, 251>,
     storage: ContractStorage<'tx>,
 }
 
@@ -64,11 +67,6 @@ impl<'tx> ContractsStorageTree<'tx> {
         Ok(Self { tree, storage })
     }
 
-    pub fn with_verify_hashes(mut self, verify_hashes: bool) -> Self {
-        self.tree = self.tree.with_verify_hashes(verify_hashes);
-        self
-    }
-
     /// Generates a proof for `key`. See [`MerkleTree::get_proof`].
     pub fn get_proof(
         tx: &'tx Transaction<'tx>,
@@ -93,6 +91,56 @@ impl<'tx> ContractsStorageTree<'tx> {
         MerkleTree::<PedersenHash, 251>::get_proof(root, &storage, key)
     }
 
+    /// Returns every non-zero storage slot of the contract as of `block`.
+    ///
+    /// This walks the contract's entire tree and reads each slot's value from the database, so
+    /// the cost grows with the size of the contract's storage and the result is held in memory.
+    /// Use [dfs](Self::dfs) to visit the slots without collecting them.
+    pub fn storage(
+        tx: &'tx Transaction<'tx>,
+        contract: ContractAddress,
+        block: BlockNumber,
+    ) -> anyhow::Result<BTreeMap<StorageAddress, StorageValue>> {
+        let mut tree = Self::load(tx, contract, block)?;
+
+        let mut keys = Vec::new();
+        tree.dfs(&mut |node, path| {
+            if let InternalNode::Leaf = node {
+                keys.push(Felt::from_bits(path).map(StorageAddress));
+            }
+            ControlFlow::<(), _>::Continue(Visit::ContinueDeeper)
+        })
+        .context("Walking contract storage tree")?;
+
+        keys.into_iter()
+            .map(|key| {
+                let key = key.context("Mapping leaf path to storage address")?;
+                let value = tx
+                    .storage_value(block.into(), contract, key)
+                    .context("Fetching storage value")?
+                    .with_context(|| format!("Storage value missing for leaf {key}"))?;
+                Ok((key, value))
+            })
+            .collect()
+    }
+}
+
+impl<'tx, H: FeltHash> ContractsStorageTree<'tx, H> {
+    /// Switches the tree to a different [FeltHash] backend. See [MerkleTree::with_hasher].
+    pub fn with_hasher<H2: FeltHash>(self) -> ContractsStorageTree<'tx, H2> {
+        ContractsStorageTree {
+            tree: self.tree.with_hasher(),
+            storage: self.storage,
+        }
+    }
+
+    pub fn with_verify_hashes(mut self, verify_hashes: bool) -> Self {
+        self.tree = self.tree.with_verify_hashes(verify_hashes);
+        self
+    }
+
+    /// Sets the [StorageValue] at `address`. The address forms the leaf's path in the tree
+    /// and the value is stored as the leaf itself.
     pub fn set(&mut self, address: StorageAddress, value: StorageValue) -> anyhow::Result<()> {
         let key = address.view_bits().to_owned();
         self.tree.set(&self.storage, key, value.0)
@@ -120,8 +168,11 @@ impl<'tx> ContractsStorageTree<'tx> {
 /// It maps each contract's [address](ContractAddress) to it's [state hash](ContractStateHash).
 ///
 /// Tree data is persisted by a sqlite table 'tree_global'.
-pub struct StorageCommitmentTree<'tx> {
-    tree: MerkleTree<PedersenHash, 251>,
+///
+/// The Pedersen implementation defaults to [PedersenHash] and can be swapped for another
+/// [FeltHash] backend using [with_hasher](StorageCommitmentTree::with_hasher).
+pub struct StorageCommitmentTree<'tx, H: FeltHash = PedersenHash> {
+    tree: MerkleTree<H, 251>,
     storage: StorageTrieStorage<'tx>,
 }
 
@@ -151,28 +202,6 @@ impl<'tx> StorageCommitmentTree<'tx> {
         Ok(Self { tree, storage })
     }
 
-    pub fn with_verify_hashes(mut self, verify_hashes: bool) -> Self {
-        self.tree = self.tree.with_verify_hashes(verify_hashes);
-        self
-    }
-
-    pub fn set(
-        &mut self,
-        address: ContractAddress,
-        value: ContractStateHash,
-    ) -> anyhow::Result<()> {
-        let key = address.view_bits().to_owned();
-        self.tree.set(&self.storage, key, value.0)
-    }
-
-    /// Commits the changes and calculates the new node hashes. Returns the new commitment and
-    /// any potentially newly created nodes.
-    pub fn commit(self) -> anyhow::Result<(StorageCommitment, HashMap<Felt, Node>)> {
-        let update = self.tree.commit(&self.storage)?;
-        let commitment = StorageCommitment(update.root);
-        Ok((commitment, update.nodes))
-    }
-
     /// Generates a proof for the given `key`. See [`MerkleTree::get_proof`].
     pub fn get_proof(
         tx: &'tx Transaction<'tx>,
@@ -194,6 +223,38 @@ impl<'tx> StorageCommitmentTree<'tx> {
 
         MerkleTree::<PedersenHash, 251>::get_proof(root, &storage, address.view_bits())
     }
+}
+
+impl<'tx, H: FeltHash> StorageCommitmentTree<'tx, H> {
+    /// Switches the tree to a different [FeltHash] backend. See [MerkleTree::with_hasher].
+    pub fn with_hasher<H2: FeltHash>(self) -> StorageCommitmentTree<'tx, H2> {
+        StorageCommitmentTree {
+            tree: self.tree.with_hasher(),
+            storage: self.storage,
+        }
+    }
+
+    pub fn with_verify_hashes(mut self, verify_hashes: bool) -> Self {
+        self.tree = self.tree.with_verify_hashes(verify_hashes);
+        self
+    }
+
+    pub fn set(
+        &mut self,
+        address: ContractAddress,
+        value: ContractStateHash,
+    ) -> anyhow::Result<()> {
+        let key = address.view_bits().to_owned();
+        self.tree.set(&self.storage, key, value.0)
+    }
+
+    /// Commits the changes and calculates the new node hashes. Returns the new commitment and
+    /// any potentially newly created nodes.
+    pub fn commit(self) -> anyhow::Result<(StorageCommitment, HashMap<Felt, Node>)> {
+        let update = self.tree.commit(&self.storage)?;
+        let commitment = StorageCommitment(update.root);
+        Ok((commitment, update.nodes))
+    }
 
     /// See [`MerkleTree::dfs`]
     pub fn dfs<B, F: FnMut(&InternalNode, &BitSlice<u8, Msb0>) -> ControlFlow<B, Visit>>(
@@ -268,3 +329,132 @@ impl crate::storage::Storage for StorageTrieStorage<'_> {
         Ok(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_storage::Storage;
+
+    #[test]
+    fn storage_address_is_path_and_value_is_leaf() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let address = storage_address!("0x123");
+        let value = storage_value!("0x456");
+
+        let mut tree = ContractsStorageTree::empty(&tx, contract_address!("0x1"));
+        tree.set(address, value).unwrap();
+        let (root, _) = tree.commit().unwrap();
+
+        // A single leaf is reached from the root by an edge spanning the full address.
+        let expected = stark_hash::stark_hash(value.0, *address.get()) + Felt::from_u64(251);
+        assert_eq!(root, ContractRoot(expected));
+
+        // Swapping the felts around must result in a different tree.
+        let mut swapped = ContractsStorageTree::empty(&tx, contract_address!("0x1"));
+        swapped
+            .set(
+                StorageAddress::new_or_panic(value.0),
+                StorageValue(*address.get()),
+            )
+            .unwrap();
+        let (swapped_root, _) = swapped.commit().unwrap();
+        assert_ne!(root, swapped_root);
+    }
+
+    #[test]
+    fn set_requires_typed_keys() {
+        // Compilation fails if either tree accepts a plain felt, or the other tree's key type.
+        fn assert_signatures<'tx>() {
+            let _: fn(
+                &mut ContractsStorageTree<'tx>,
+                StorageAddress,
+                StorageValue,
+            ) -> anyhow::Result<()> = ContractsStorageTree::set;
+            let _: fn(
+                &mut StorageCommitmentTree<'tx>,
+                ContractAddress,
+                ContractStateHash,
+            ) -> anyhow::Result<()> = StorageCommitmentTree::set;
+            let _: fn(
+                ContractAddress,
+                &HashMap<StorageAddress, StorageValue>,
+                Option<pathfinder_common::ContractNonce>,
+                Option<pathfinder_common::ClassHash>,
+                &Transaction<'_>,
+                bool,
+                BlockNumber,
+            )
+                -> anyhow::Result<crate::contract_state::ContractStateUpdateResult> =
+                crate::contract_state::update_contract_state;
+        }
+
+        assert_signatures();
+    }
+
+    #[test]
+    fn storage_at_block() {
+        use crate::contract_state::update_contract_state;
+        use pathfinder_common::{BlockHeader, StateUpdate};
+
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let contract = ContractAddress::ONE;
+        let blocks = [
+            vec![
+                (storage_address!("0x1"), storage_value!("0xa")),
+                (storage_address!("0x2"), storage_value!("0xb")),
+            ],
+            vec![
+                (storage_address!("0x2"), storage_value!("0xc")),
+                (storage_address!("0x3"), storage_value!("0xd")),
+            ],
+        ];
+
+        let mut header = BlockHeader::builder().finalize_with_hash(block_hash!("0xb0"));
+        for (i, updates) in blocks.iter().enumerate() {
+            if i > 0 {
+                header = header
+                    .child_builder()
+                    .finalize_with_hash(block_hash!("0xb1"));
+            }
+            tx.insert_block_header(&header).unwrap();
+
+            let state_update =
+                updates
+                    .iter()
+                    .fold(StateUpdate::default(), |state_update, (key, value)| {
+                        state_update.with_system_storage_update(contract, *key, *value)
+                    });
+            tx.insert_state_update(header.number, &state_update)
+                .unwrap();
+
+            let updates = updates.iter().copied().collect();
+            update_contract_state(contract, &updates, None, None, &tx, false, header.number)
+                .unwrap()
+                .insert(header.number, &tx)
+                .unwrap();
+        }
+
+        let genesis = ContractsStorageTree::storage(&tx, contract, BlockNumber::GENESIS).unwrap();
+        assert_eq!(genesis, blocks[0].iter().copied().collect());
+
+        let latest =
+            ContractsStorageTree::storage(&tx, contract, BlockNumber::new_or_panic(1)).unwrap();
+        let expected = BTreeMap::from([
+            (storage_address!("0x1"), storage_value!("0xa")),
+            (storage_address!("0x2"), storage_value!("0xc")),
+            (storage_address!("0x3"), storage_value!("0xd")),
+        ]);
+        assert_eq!(latest, expected);
+
+        let unknown =
+            ContractsStorageTree::storage(&tx, contract_address!("0x99"), header.number).unwrap();
+        assert!(unknown.is_empty());
+    }
+}
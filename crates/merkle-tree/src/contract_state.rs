@@ -1,3 +1,4 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
 
 use crate::ContractsStorageTree;
@@ -118,18 +119,76 @@ pub fn update_contract_state(
     })
 }
 
+/// The version which is the final element of every [ContractStateHash] preimage.
+pub const CONTRACT_STATE_HASH_VERSION: Felt = Felt::ZERO;
+
+/// The maximum number of entries in each thread's [calculate_contract_state_hash] memo.
+const STATE_HASH_MEMO_CAPACITY: usize = 1024;
+
+thread_local! {
+    /// State hashes of contracts without storage, which repeat for every contract sharing a class
+    /// and nonce.
+    static STATE_HASH_MEMO: RefCell<HashMap<(ClassHash, ContractNonce), ContractStateHash>> =
+        RefCell::new(HashMap::new());
+}
+
+#[cfg(test)]
+thread_local! {
+    /// The number of state hashes computed because they were missing from this thread's memo.
+    static STATE_HASH_MEMO_MISSES: std::cell::Cell<usize> = std::cell::Cell::new(0);
+}
+
 /// Calculates the contract state hash from its preimage.
+///
+/// Hashes of contracts without storage are memoized per thread.
 pub fn calculate_contract_state_hash(
     hash: ClassHash,
     root: ContractRoot,
     nonce: ContractNonce,
 ) -> ContractStateHash {
-    const CONTRACT_STATE_HASH_VERSION: Felt = Felt::ZERO;
+    if root != ContractRoot::ZERO {
+        return calculate_versioned_contract_state_hash(
+            hash,
+            root,
+            nonce,
+            CONTRACT_STATE_HASH_VERSION,
+        );
+    }
 
-    // The contract state hash is defined as H(H(H(hash, root), nonce), CONTRACT_STATE_HASH_VERSION)
+    STATE_HASH_MEMO.with(|memo| {
+        let mut memo = memo.borrow_mut();
+        if let Some(state_hash) = memo.get(&(hash, nonce)) {
+            return *state_hash;
+        }
+
+        #[cfg(test)]
+        STATE_HASH_MEMO_MISSES.with(|misses| misses.set(misses.get() + 1));
+
+        let state_hash =
+            calculate_versioned_contract_state_hash(hash, root, nonce, CONTRACT_STATE_HASH_VERSION);
+        // Dropping everything keeps the memo bounded, and it refills with the classes in use.
+        if memo.len() >= STATE_HASH_MEMO_CAPACITY {
+            memo.clear();
+        }
+        memo.insert((hash, nonce), state_hash);
+        state_hash
+    })
+}
+
+/// Calculates the contract state hash from its preimage using an explicit `version`.
+///
+/// All current contracts, Cairo 0 and Sierra alike, use [CONTRACT_STATE_HASH_VERSION]. Prefer
+/// [calculate_contract_state_hash] unless a different version is being introduced.
+pub fn calculate_versioned_contract_state_hash(
+    hash: ClassHash,
+    root: ContractRoot,
+    nonce: ContractNonce,
+    version: Felt,
+) -> ContractStateHash {
+    // The contract state hash is defined as H(H(H(hash, root), nonce), version)
     let hash = stark_hash(hash.0, root.0);
     let hash = stark_hash(hash, nonce.0);
-    let hash = stark_hash(hash, CONTRACT_STATE_HASH_VERSION);
+    let hash = stark_hash(hash, version);
 
     // Compare this with the HashChain construction used in the contract_hash: the number of
     // elements is not hashed to this hash, and this is supposed to be different.
@@ -138,9 +197,108 @@ pub fn calculate_contract_state_hash(
 
 #[cfg(test)]
 mod tests {
-    use super::calculate_contract_state_hash;
+    use super::{
+        calculate_contract_state_hash, calculate_versioned_contract_state_hash,
+        update_contract_state, CONTRACT_STATE_HASH_VERSION, STATE_HASH_MEMO_MISSES,
+    };
     use pathfinder_common::felt;
-    use pathfinder_common::{ClassHash, ContractNonce, ContractRoot, ContractStateHash};
+    use pathfinder_common::{
+        ClassHash, ContractAddress, ContractNonce, ContractRoot, ContractStateHash, StorageValue,
+    };
+    use stark_hash::Felt;
+
+    #[test]
+    fn latest_root_of_emptied_storage_is_zero() {
+        use pathfinder_common::macro_prelude::*;
+        use pathfinder_common::BlockNumber;
+
+        let mut db = pathfinder_storage::Storage::in_memory()
+            .unwrap()
+            .connection()
+            .unwrap();
+        let tx = db.transaction().unwrap();
+
+        let contract = contract_address_bytes!(b"contract");
+        let class = class_hash_bytes!(b"class");
+        let key = storage_address_bytes!(b"key");
+
+        let updates = [(key, storage_value_bytes!(b"value"))].into();
+        let result = update_contract_state(
+            contract,
+            &updates,
+            None,
+            Some(class),
+            &tx,
+            false,
+            BlockNumber::GENESIS,
+        )
+        .unwrap();
+        result.insert(BlockNumber::GENESIS, &tx).unwrap();
+        let root = tx.latest_contract_root(contract).unwrap().unwrap();
+        assert_ne!(root, ContractRoot::ZERO);
+
+        let block = BlockNumber::GENESIS + 1;
+        let updates = [(key, StorageValue::ZERO)].into();
+        let result =
+            update_contract_state(contract, &updates, None, Some(class), &tx, false, block)
+                .unwrap();
+        result.insert(block, &tx).unwrap();
+
+        let root = tx.latest_contract_root(contract).unwrap();
+        assert_eq!(root, Some(ContractRoot::ZERO));
+    }
+
+    #[test]
+    fn memoized_hash_of_contract_without_storage() {
+        use pathfinder_common::macro_prelude::*;
+        use pathfinder_common::BlockNumber;
+
+        let misses = || STATE_HASH_MEMO_MISSES.with(|misses| misses.get());
+
+        let mut db = pathfinder_storage::Storage::in_memory()
+            .unwrap()
+            .connection()
+            .unwrap();
+        let tx = db.transaction().unwrap();
+
+        // A block deploying many contracts of the same class, none of which have storage.
+        let class_hash = class_hash_bytes!(b"memoized class");
+        let expected = calculate_versioned_contract_state_hash(
+            class_hash,
+            ContractRoot::ZERO,
+            ContractNonce::ZERO,
+            CONTRACT_STATE_HASH_VERSION,
+        );
+        let before = misses();
+        for i in 0..100u64 {
+            // Address 0x1 is a system contract, which has no class.
+            let contract = ContractAddress(Felt::from_u64(i + 2));
+            let result = update_contract_state(
+                contract,
+                &Default::default(),
+                None,
+                Some(class_hash),
+                &tx,
+                false,
+                BlockNumber::GENESIS,
+            )
+            .unwrap();
+            assert_eq!(result.state_hash, expected);
+        }
+        assert_eq!(misses() - before, 1);
+
+        // Memoized hashes are keyed by the nonce as well.
+        let nonce = contract_nonce!("0x1");
+        let other = calculate_contract_state_hash(class_hash, ContractRoot::ZERO, nonce);
+        assert_ne!(other, expected);
+        assert_eq!(misses() - before, 2);
+
+        // Contracts with storage are never memoized.
+        let root = ContractRoot(felt!("0x1234"));
+        calculate_contract_state_hash(class_hash, root, nonce);
+        calculate_contract_state_hash(class_hash, root, nonce);
+        assert_eq!(misses() - before, 2);
+    }
 
     #[test]
     fn hash() {
@@ -159,4 +317,110 @@ mod tests {
 
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn versioned_hash_matches_known_vector() {
+        // The same contract state as in `hash`, which pins the current version. Changing the
+        // version would invalidate every state hash, so this must only break deliberately.
+        let root = felt!("0x4fb440e8ca9b74fc12a22ebffe0bc0658206337897226117b985434c239c028");
+        let root = ContractRoot(root);
+
+        let hash = felt!("0x2ff4903e17f87b298ded00c44bfeb22874c5f73be2ced8f1d9d9556fb509779");
+        let hash = ClassHash(hash);
+
+        let expected = felt!("0x7161b591c893836263a64f2a7e0d829c92f6956148a60ce5e99a3f55c7973f3");
+        let expected = ContractStateHash(expected);
+
+        let result = calculate_versioned_contract_state_hash(
+            hash,
+            root,
+            ContractNonce::ZERO,
+            CONTRACT_STATE_HASH_VERSION,
+        );
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn version_changes_hash() {
+        use super::{calculate_versioned_contract_state_hash, CONTRACT_STATE_HASH_VERSION};
+        use pathfinder_common::macro_prelude::*;
+
+        let class_hash = class_hash!("0x123");
+        let root = contract_root!("0x456");
+        let nonce = contract_nonce!("0x789");
+
+        let legacy = calculate_contract_state_hash(class_hash, root, nonce);
+        assert_eq!(
+            calculate_versioned_contract_state_hash(
+                class_hash,
+                root,
+                nonce,
+                CONTRACT_STATE_HASH_VERSION
+            ),
+            legacy
+        );
+
+        let versioned =
+            calculate_versioned_contract_state_hash(class_hash, root, nonce, felt!("0x1"));
+        assert_ne!(versioned, legacy);
+    }
+
+    mod genesis {
+        use super::super::update_contract_state;
+        use super::calculate_contract_state_hash;
+        use pathfinder_common::macro_prelude::*;
+        use pathfinder_common::{BlockNumber, ClassHash, ContractAddress, ContractNonce};
+        use pathfinder_storage::Storage;
+        use std::collections::HashMap;
+
+        #[test]
+        fn system_contract_needs_no_deployment() {
+            let storage = Storage::in_memory().unwrap();
+            let mut connection = storage.connection().unwrap();
+            let tx = connection.transaction().unwrap();
+
+            let updates = HashMap::from([(storage_address!("0x5"), storage_value!("0x6"))]);
+            let result = update_contract_state(
+                ContractAddress::ONE,
+                &updates,
+                None,
+                None,
+                &tx,
+                false,
+                BlockNumber::GENESIS,
+            )
+            .unwrap();
+
+            let root = result.root;
+            assert_ne!(root.0, stark_hash::Felt::ZERO);
+            assert_eq!(
+                result.state_hash,
+                calculate_contract_state_hash(ClassHash::ZERO, root, ContractNonce::ZERO)
+            );
+            result.insert(BlockNumber::GENESIS, &tx).unwrap();
+        }
+
+        #[test]
+        fn undeployed_contract_is_rejected() {
+            let storage = Storage::in_memory().unwrap();
+            let mut connection = storage.connection().unwrap();
+            let tx = connection.transaction().unwrap();
+
+            let updates = HashMap::from([(storage_address!("0x5"), storage_value!("0x6"))]);
+            let error = update_contract_state(
+                contract_address!("0x2"),
+                &updates,
+                None,
+                None,
+                &tx,
+                false,
+                BlockNumber::GENESIS,
+            )
+            .err()
+            .unwrap();
+
+            assert!(format!("{error:#}").contains("class hash is missing"));
+        }
+    }
 }
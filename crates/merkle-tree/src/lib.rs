@@ -1,5 +1,8 @@
 pub mod contract_state;
 pub mod merkle_node;
+pub mod proof;
+pub mod reverify;
+pub mod test_utils;
 pub mod tree;
 
 mod class;
@@ -0,0 +1,415 @@
+//! Verification of the merkle proofs returned by `pathfinder_getProof`.
+//!
+//! None of this requires a database, so it can be used by clients to check proofs against a state
+//! commitment they trust.
+use bitvec::prelude::Msb0;
+use bitvec::slice::BitSlice;
+use pathfinder_common::hash::PedersenHash;
+use pathfinder_common::trie::TrieNode;
+use pathfinder_common::{
+    ClassCommitment, ClassHash, ContractAddress, ContractNonce, ContractRoot, ContractStateHash,
+    StateCommitment, StorageAddress, StorageCommitment, StorageValue,
+};
+use stark_hash::Felt;
+
+use crate::contract_state::calculate_contract_state_hash;
+use crate::merkle_node::Direction;
+use crate::storage::Storage;
+use crate::tree::MerkleTree;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Membership {
+    Member,
+    NonMember,
+}
+
+/// Verifies that the key `key` with value `value` is indeed part of the MPT that has root
+/// `root`, given `proofs`.
+/// Supports proofs of non-membership as well as proof of membership: this function returns
+/// an enum corresponding to the membership of `value`, or returns `None` in case of a hash mismatch.
+/// The algorithm follows this logic:
+/// 1. init expected_hash <- root hash
+/// 2. loop over nodes: current <- nodes[i]
+///    1. verify the current node's hash matches expected_hash (if not then we have a bad proof)
+///    2. move towards the target - if current is:
+///       1. binary node then choose the child that moves towards the target, else if
+///       2. edge node then check the path against the target bits
+///          1. If it matches then proceed with the child, else
+///          2. if it does not match then we now have a proof that the target does not exist
+///    3. nibble off target bits according to which child you got in (2). If all bits are gone then you
+///       have reached the target and the child hash is the value you wanted and the proof is complete.
+///    4. set expected_hash <- to the child hash
+/// 3. check that all target bits are gone and the expected_hash is `value` (we should've reached
+///    the leaf)
+///
+/// An empty proof for a zero root proves non-membership, as the tree is empty.
+pub fn verify_proof(
+    root: Felt,
+    key: &BitSlice<u8, Msb0>,
+    value: Felt,
+    proofs: &[TrieNode],
+) -> Option<Membership> {
+    // Protect from ill-formed keys
+    if key.len() != 251 {
+        return None;
+    }
+
+    let mut expected_hash = root;
+    let mut remaining_path: &BitSlice<u8, Msb0> = key;
+
+    for proof_node in proofs.iter() {
+        // Hash mismatch? Return None.
+        if proof_node.hash::<PedersenHash>() != expected_hash {
+            return None;
+        }
+        match proof_node {
+            TrieNode::Binary { left, right } => {
+                // Protect from proofs which are longer than the key.
+                if remaining_path.is_empty() {
+                    return None;
+                }
+
+                // Direction will always correspond to the 0th index
+                // because we're removing bits on every iteration.
+                let direction = Direction::from(remaining_path[0]);
+
+                // Set the next hash to be the left or right hash,
+                // depending on the direction
+                expected_hash = match direction {
+                    Direction::Left => *left,
+                    Direction::Right => *right,
+                };
+
+                // Advance by a single bit
+                remaining_path = &remaining_path[1..];
+            }
+            TrieNode::Edge { child, path } => {
+                if path.len() > remaining_path.len() {
+                    return None;
+                }
+
+                if path != &remaining_path[..path.len()] {
+                    // If paths don't match, we've found a proof of non membership because we:
+                    // 1. Correctly moved towards the target insofar as is possible, and
+                    // 2. hashing all the nodes along the path does result in the root hash, which means
+                    // 3. the target definitely does not exist in this tree
+                    return Some(Membership::NonMember);
+                }
+
+                // Set the next hash to the child's hash
+                expected_hash = *child;
+
+                // Advance by the whole edge path
+                remaining_path = &remaining_path[path.len()..];
+            }
+        }
+    }
+
+    if root == Felt::ZERO && proofs.is_empty() {
+        return Some(Membership::NonMember);
+    }
+
+    // At this point, we should reach `value` ! A proof which stops short of the leaf proves
+    // nothing about it, even if it ends in a node whose hash happens to be `value`.
+    if remaining_path.is_empty() && expected_hash == value {
+        Some(Membership::Member)
+    } else {
+        // Hash mismatch. Return `None`.
+        None
+    }
+}
+
+/// The contract's state hash preimage, and the proof of a storage slot in its storage tree.
+#[derive(Debug, Clone, Copy)]
+pub struct ContractStorageProof<'a> {
+    pub class_hash: ClassHash,
+    pub nonce: ContractNonce,
+    pub root: ContractRoot,
+    pub storage_proof: &'a [TrieNode],
+}
+
+/// Verifies that the contract at `contract_address` has the state hash `contract_state_hash` in
+/// the global state with the given `state_commitment`.
+///
+/// The storage commitment tree's root is the first node of `contract_proof`, and it must combine
+/// with `class_commitment` into `state_commitment`.
+pub fn verify_contract_proof(
+    state_commitment: StateCommitment,
+    class_commitment: ClassCommitment,
+    contract_address: ContractAddress,
+    contract_proof: &[TrieNode],
+    contract_state_hash: ContractStateHash,
+) -> bool {
+    let Some(storage_root) = contract_proof.first() else {
+        return false;
+    };
+    let storage_commitment = StorageCommitment(storage_root.hash::<PedersenHash>());
+    if StateCommitment::calculate(storage_commitment, class_commitment) != state_commitment {
+        return false;
+    }
+
+    verify_proof(
+        storage_commitment.0,
+        contract_address.view_bits(),
+        contract_state_hash.0,
+        contract_proof,
+    ) == Some(Membership::Member)
+}
+
+/// Computes the root of a contract storage tree holding exactly `storage`.
+pub fn contract_root(
+    storage: impl IntoIterator<Item = (StorageAddress, StorageValue)>,
+) -> anyhow::Result<ContractRoot> {
+    /// The tree is built from scratch, so there is never anything to read.
+    struct NoStorage;
+
+    impl Storage for NoStorage {
+        fn get(&self, _: u64) -> anyhow::Result<Option<pathfinder_storage::StoredNode>> {
+            Ok(None)
+        }
+
+        fn hash(&self, _: u64) -> anyhow::Result<Option<Felt>> {
+            Ok(None)
+        }
+
+        fn leaf(&self, _: &BitSlice<u8, Msb0>) -> anyhow::Result<Option<Felt>> {
+            Ok(None)
+        }
+    }
+
+    let mut tree = MerkleTree::<PedersenHash, 251>::empty();
+    for (key, value) in storage {
+        tree.set(&NoStorage, key.view_bits().to_bitvec(), value.0)?;
+    }
+    let update = tree.commit(&NoStorage)?;
+
+    Ok(ContractRoot(update.root))
+}
+
+/// Verifies that `key` of the contract at `contract_address` holds `value` in the global state
+/// with the given `state_commitment`.
+///
+/// This checks both levels of the proof:
+/// 1. the contract's state hash, recomputed from `contract`, is a member of the storage commitment
+///    tree whose root is the first node of `contract_proof`, and that storage and class commitment
+///    combine into `state_commitment`, and
+/// 2. `value` is stored at `key` in the contract's storage tree with root `contract.root`.
+///
+/// A zero `value` is also accepted if the proof shows that `key` is absent from the storage tree,
+/// as unset storage reads as zero.
+pub fn verify_storage_proof(
+    state_commitment: StateCommitment,
+    class_commitment: ClassCommitment,
+    contract_address: ContractAddress,
+    contract_proof: &[TrieNode],
+    contract: ContractStorageProof<'_>,
+    key: StorageAddress,
+    value: StorageValue,
+) -> bool {
+    let contract_state_hash =
+        calculate_contract_state_hash(contract.class_hash, contract.root, contract.nonce);
+    if !verify_contract_proof(
+        state_commitment,
+        class_commitment,
+        contract_address,
+        contract_proof,
+        contract_state_hash,
+    ) {
+        return false;
+    }
+
+    let storage_membership = verify_proof(
+        contract.root.0,
+        key.view_bits(),
+        value.0,
+        contract.storage_proof,
+    );
+    match storage_membership {
+        Some(Membership::Member) => true,
+        Some(Membership::NonMember) => value == StorageValue::ZERO,
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::macro_prelude::*;
+
+    /// An edge node which leads straight from the root to the leaf at `key`.
+    fn leaf_edge(key: &BitSlice<u8, Msb0>, leaf: Felt) -> TrieNode {
+        TrieNode::Edge {
+            child: leaf,
+            path: key.to_bitvec(),
+        }
+    }
+
+    struct Fixture {
+        state_commitment: StateCommitment,
+        class_commitment: ClassCommitment,
+        address: ContractAddress,
+        contract_proof: Vec<TrieNode>,
+        class_hash: ClassHash,
+        nonce: ContractNonce,
+        root: ContractRoot,
+        storage_proof: Vec<TrieNode>,
+        key: StorageAddress,
+        value: StorageValue,
+    }
+
+    impl Fixture {
+        /// Two contracts whose addresses differ in the first bit, so that the contract proof is a
+        /// binary node followed by an edge. The contract's storage holds a single slot.
+        fn new() -> Self {
+            let address = contract_address!("0x123");
+            let key = storage_address!("0x456");
+            let value = storage_value!("0x789");
+            let class_hash = class_hash!("0xabc");
+            let nonce = contract_nonce!("0x1");
+
+            let storage_node = leaf_edge(key.view_bits(), value.0);
+            let root = ContractRoot(storage_node.hash::<PedersenHash>());
+
+            let state_hash = calculate_contract_state_hash(class_hash, root, nonce);
+            let contract_node = leaf_edge(&address.view_bits()[1..], state_hash.0);
+
+            let sibling_address = contract_address!(
+                "0x400000000000000000000000000000000000000000000000000000000000123"
+            );
+            let sibling_node = leaf_edge(&sibling_address.view_bits()[1..], felt!("0xdead"));
+
+            let root_node = TrieNode::Binary {
+                left: contract_node.hash::<PedersenHash>(),
+                right: sibling_node.hash::<PedersenHash>(),
+            };
+            let storage_commitment = StorageCommitment(root_node.hash::<PedersenHash>());
+            let class_commitment = class_commitment!("0xc1a55");
+
+            Self {
+                state_commitment: StateCommitment::calculate(storage_commitment, class_commitment),
+                class_commitment,
+                address,
+                contract_proof: vec![root_node, contract_node],
+                class_hash,
+                nonce,
+                root,
+                storage_proof: vec![storage_node],
+                key,
+                value,
+            }
+        }
+
+        fn verify(&self) -> bool {
+            verify_storage_proof(
+                self.state_commitment,
+                self.class_commitment,
+                self.address,
+                &self.contract_proof,
+                ContractStorageProof {
+                    class_hash: self.class_hash,
+                    nonce: self.nonce,
+                    root: self.root,
+                    storage_proof: &self.storage_proof,
+                },
+                self.key,
+                self.value,
+            )
+        }
+    }
+
+    #[test]
+    fn valid_proof() {
+        assert!(Fixture::new().verify());
+    }
+
+    #[test]
+    fn tampered_value() {
+        let mut fixture = Fixture::new();
+        fixture.value = storage_value!("0x78a");
+        assert!(!fixture.verify());
+    }
+
+    #[test]
+    fn tampered_sibling_hash() {
+        let mut fixture = Fixture::new();
+        let TrieNode::Binary { right, .. } = &mut fixture.contract_proof[0] else {
+            unreachable!("Root node is binary");
+        };
+        *right = felt!("0xbad");
+        assert!(!fixture.verify());
+    }
+
+    #[test]
+    fn tampered_contract_state() {
+        let mut fixture = Fixture::new();
+        fixture.nonce = contract_nonce!("0x2");
+        assert!(!fixture.verify());
+    }
+
+    #[test]
+    fn contract_root_matches_proof() {
+        let fixture = Fixture::new();
+        let root = contract_root([(fixture.key, fixture.value)]).unwrap();
+        assert_eq!(root, fixture.root);
+    }
+
+    #[test]
+    fn empty_contract_root() {
+        assert_eq!(contract_root([]).unwrap(), ContractRoot::ZERO);
+    }
+
+    #[test]
+    fn empty_storage_proof() {
+        let mut fixture = Fixture::new();
+        fixture.storage_proof = vec![];
+        fixture.value = StorageValue(fixture.root.0);
+        assert!(!fixture.verify());
+    }
+
+    #[test]
+    fn truncated_storage_proof() {
+        let key = storage_address!("0x456");
+        let value = felt!("0x789");
+
+        let lower = leaf_edge(&key.view_bits()[100..], value);
+        let upper = TrieNode::Edge {
+            child: lower.hash::<PedersenHash>(),
+            path: key.view_bits()[..100].to_bitvec(),
+        };
+        let root = upper.hash::<PedersenHash>();
+
+        let proof = vec![upper, lower];
+        assert_eq!(
+            verify_proof(root, key.view_bits(), value, &proof),
+            Some(Membership::Member)
+        );
+
+        // The truncated proof ends in the lower node, whose hash is not a value of the tree.
+        let lower_hash = proof[1].hash::<PedersenHash>();
+        assert_eq!(
+            verify_proof(root, key.view_bits(), lower_hash, &proof[..1]),
+            None
+        );
+    }
+
+    #[test]
+    fn empty_tree() {
+        let key = storage_address!("0x456");
+        assert_eq!(
+            verify_proof(Felt::ZERO, key.view_bits(), Felt::ZERO, &[]),
+            Some(Membership::NonMember)
+        );
+    }
+
+    #[test]
+    fn unset_slot_reads_as_zero() {
+        let mut fixture = Fixture::new();
+        fixture.key = storage_address!("0x457");
+
+        fixture.value = StorageValue::ZERO;
+        assert!(fixture.verify());
+
+        fixture.value = storage_value!("0x789");
+        assert!(!fixture.verify());
+    }
+}
@@ -0,0 +1,293 @@
+//! Offline re-verification of the state commitments stored in the database.
+//!
+//! Unlike sync, this requires no access to Ethereum or the Starknet gateway. Each block's
+//! commitments are recomputed purely from data already present in the database, which makes
+//! this useful for auditing a database's internal consistency.
+use anyhow::Context;
+use pathfinder_common::{
+    BlockNumber, ClassCommitment, ClassHash, ContractAddress, ContractStateHash, StateCommitment,
+    StorageCommitment,
+};
+use pathfinder_storage::{Storage, Transaction};
+use stark_hash::Felt;
+use std::ops::ControlFlow;
+
+use crate::contract_state::calculate_contract_state_hash;
+use crate::merkle_node::InternalNode;
+use crate::tree::Visit;
+use crate::{ClassCommitmentTree, StorageCommitmentTree};
+
+/// Describes which part of a block's stored state does not match its recomputed value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Divergence {
+    ContractStateHash {
+        contract: ContractAddress,
+        stored: Option<ContractStateHash>,
+        computed: ContractStateHash,
+    },
+    StorageCommitment {
+        stored: StorageCommitment,
+        computed: StorageCommitment,
+    },
+    ClassCommitment {
+        stored: ClassCommitment,
+        computed: ClassCommitment,
+    },
+    StateCommitment {
+        stored: StateCommitment,
+        computed: StateCommitment,
+    },
+}
+
+/// Re-verifies every block in the database in order, starting from genesis.
+///
+/// Returns the first block whose stored state does not match the recomputed state, or [None]
+/// if all blocks are consistent.
+pub fn reverify_all(storage: &Storage) -> anyhow::Result<Option<(BlockNumber, Divergence)>> {
+    let mut connection = storage
+        .connection()
+        .context("Creating database connection")?;
+
+    let latest = {
+        let tx = connection
+            .transaction()
+            .context("Creating database transaction")?;
+        tx.block_id(pathfinder_storage::BlockId::Latest)
+            .context("Fetching latest block number")?
+    };
+    let Some((latest, _)) = latest else {
+        return Ok(None);
+    };
+
+    for block in 0..=latest.get() {
+        let block = BlockNumber::new_or_panic(block);
+
+        let tx = connection
+            .transaction()
+            .context("Creating database transaction")?;
+        if let Some(divergence) =
+            reverify_block(&tx, block).with_context(|| format!("Re-verifying block {block}"))?
+        {
+            return Ok(Some((block, divergence)));
+        }
+
+        if block.get() % 1000 == 0 {
+            tracing::info!(%block, "Re-verified state");
+        }
+    }
+
+    Ok(None)
+}
+
+/// Re-verifies the blocks in the inclusive range `from..=to` in order, within a single database
+/// transaction.
+///
+/// Returns the first block whose stored state does not match the recomputed state, or [None]
+/// if all blocks in the range are consistent.
+pub fn reverify_range(
+    tx: &Transaction<'_>,
+    from: BlockNumber,
+    to: BlockNumber,
+) -> anyhow::Result<Option<(BlockNumber, Divergence)>> {
+    for block in from.get()..=to.get() {
+        let block = BlockNumber::new_or_panic(block);
+
+        if let Some(divergence) =
+            reverify_block(tx, block).with_context(|| format!("Re-verifying block {block}"))?
+        {
+            return Ok(Some((block, divergence)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Recomputes the contract state hashes, storage commitment, class commitment and state commitment
+/// for `block` from the stored data and compares them against the stored values.
+///
+/// The parent block's tries are assumed to be correct.
+pub fn reverify_block(
+    tx: &Transaction<'_>,
+    block: BlockNumber,
+) -> anyhow::Result<Option<Divergence>> {
+    let header = tx
+        .block_header(block.into())
+        .context("Fetching block header")?
+        .context("Block header missing")?;
+    let state_update = tx
+        .state_update(block.into())
+        .context("Fetching state update")?
+        .context("State update missing")?;
+
+    let mut storage_commitment_tree = match block.parent() {
+        Some(parent) => {
+            StorageCommitmentTree::load(tx, parent).context("Loading storage commitment tree")?
+        }
+        None => StorageCommitmentTree::empty(tx),
+    };
+
+    let contracts = state_update
+        .contract_updates
+        .keys()
+        .chain(state_update.system_contract_updates.keys());
+    for contract in contracts {
+        let class_hash = if contract == &ContractAddress::ONE {
+            // The system contract has no class hash.
+            ClassHash::ZERO
+        } else {
+            tx.contract_class_hash(block.into(), *contract)
+                .context("Fetching contract's class hash")?
+                .context("Contract's class hash is missing")?
+        };
+        let root = tx
+            .contract_root(block, *contract)
+            .context("Fetching contract root")?
+            .unwrap_or_default();
+        let nonce = tx
+            .contract_nonce(*contract, block.into())
+            .context("Fetching contract nonce")?
+            .unwrap_or_default();
+
+        let computed = calculate_contract_state_hash(class_hash, root, nonce);
+        let stored = tx
+            .contract_state_hash(block, *contract)
+            .context("Fetching contract state hash")?;
+
+        if stored != Some(computed) {
+            return Ok(Some(Divergence::ContractStateHash {
+                contract: *contract,
+                stored,
+                computed,
+            }));
+        }
+
+        storage_commitment_tree
+            .set(*contract, computed)
+            .context("Updating storage commitment tree")?;
+    }
+
+    let (storage_commitment, _) = storage_commitment_tree
+        .commit()
+        .context("Computing storage commitment")?;
+    if storage_commitment != header.storage_commitment {
+        return Ok(Some(Divergence::StorageCommitment {
+            stored: header.storage_commitment,
+            computed: storage_commitment,
+        }));
+    }
+
+    let mut class_commitment_tree = match block.parent() {
+        Some(parent) => {
+            ClassCommitmentTree::load(tx, parent).context("Loading class commitment tree")?
+        }
+        None => ClassCommitmentTree::empty(tx),
+    };
+
+    for (sierra, casm) in &state_update.declared_sierra_classes {
+        let leaf_hash = pathfinder_common::calculate_class_commitment_leaf_hash(*casm);
+        class_commitment_tree
+            .set(*sierra, leaf_hash)
+            .context("Updating class commitment tree")?;
+    }
+
+    let (class_commitment, _) = class_commitment_tree
+        .commit()
+        .context("Computing class commitment")?;
+    if class_commitment != header.class_commitment {
+        return Ok(Some(Divergence::ClassCommitment {
+            stored: header.class_commitment,
+            computed: class_commitment,
+        }));
+    }
+
+    let state_commitment = StateCommitment::calculate(storage_commitment, class_commitment);
+    if state_commitment != header.state_commitment {
+        return Ok(Some(Divergence::StateCommitment {
+            stored: header.state_commitment,
+            computed: state_commitment,
+        }));
+    }
+
+    Ok(None)
+}
+
+/// Walks every leaf of the storage commitment tree at `block` and returns the contracts whose
+/// [ContractStateHash] was never persisted.
+///
+/// The tree only stores the path to each leaf, with the leaf's value being looked up separately.
+/// A leaf without a persisted state hash therefore references a value we cannot reproduce.
+pub fn audit_contract_state_hashes(
+    tx: &Transaction<'_>,
+    block: BlockNumber,
+) -> anyhow::Result<Vec<ContractAddress>> {
+    let mut tree =
+        StorageCommitmentTree::load(tx, block).context("Loading storage commitment tree")?;
+
+    let mut leaves = Vec::new();
+    tree.dfs(&mut |node, path| {
+        if let InternalNode::Leaf = node {
+            leaves.push(Felt::from_bits(path).map(ContractAddress));
+        }
+        ControlFlow::<(), _>::Continue(Visit::ContinueDeeper)
+    })
+    .context("Walking storage commitment tree")?;
+
+    let mut orphans = Vec::new();
+    for contract in leaves {
+        let contract = contract.context("Mapping leaf path to contract address")?;
+        let state_hash = tx
+            .contract_state_hash(block, contract)
+            .context("Fetching contract state hash")?;
+        if state_hash.is_none() {
+            orphans.push(contract);
+        }
+    }
+
+    Ok(orphans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::macro_prelude::*;
+
+    #[test]
+    fn missing_contract_state_hash_is_detected() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let contract_0 = contract_address_bytes!(b"contract 0");
+        let contract_1 = contract_address_bytes!(b"contract 1");
+        let orphan = contract_address_bytes!(b"orphan");
+
+        let mut tree = StorageCommitmentTree::empty(&tx);
+        for (contract, state_hash) in [
+            (contract_0, contract_state_hash_bytes!(b"state 0")),
+            (contract_1, contract_state_hash_bytes!(b"state 1")),
+        ] {
+            tree.set(contract, state_hash).unwrap();
+            tx.insert_contract_state_hash(BlockNumber::GENESIS, contract, state_hash)
+                .unwrap();
+        }
+        let (commitment, nodes) = tree.commit().unwrap();
+        let root = tx.insert_storage_trie(commitment, &nodes).unwrap();
+        tx.insert_storage_root(BlockNumber::GENESIS, Some(root))
+            .unwrap();
+
+        // The next block's tree references a state hash which is never persisted.
+        let block = BlockNumber::GENESIS + 1;
+        let mut tree = StorageCommitmentTree::load(&tx, BlockNumber::GENESIS).unwrap();
+        tree.set(orphan, contract_state_hash_bytes!(b"orphan state"))
+            .unwrap();
+        let (commitment, nodes) = tree.commit().unwrap();
+        let root = tx.insert_storage_trie(commitment, &nodes).unwrap();
+        tx.insert_storage_root(block, Some(root)).unwrap();
+
+        let orphans = audit_contract_state_hashes(&tx, BlockNumber::GENESIS).unwrap();
+        assert!(orphans.is_empty(), "{orphans:?}");
+
+        let orphans = audit_contract_state_hashes(&tx, block).unwrap();
+        assert_eq!(orphans, vec![orphan]);
+    }
+}
@@ -0,0 +1,96 @@
+//! Fixtures of blocks whose stored commitments are consistent with their state updates.
+use pathfinder_common::macro_prelude::*;
+use pathfinder_common::{BlockHeader, BlockNumber, StateUpdate};
+use pathfinder_storage::Transaction;
+
+use crate::contract_state::update_contract_state;
+use crate::{ClassCommitmentTree, StorageCommitmentTree};
+
+/// Inserts a block for each of the `state_updates` in order, starting from genesis, along with the
+/// tries and commitments resulting from its state update. Declared Cairo classes are inserted with
+/// a placeholder definition.
+///
+/// If `tamper` is set, the storage commitment stored in that block's header is replaced with
+/// garbage.
+///
+/// Returns the inserted block headers.
+pub fn insert_blocks(
+    tx: &Transaction<'_>,
+    state_updates: &[StateUpdate],
+    tamper: Option<BlockNumber>,
+) -> Vec<BlockHeader> {
+    let mut headers: Vec<BlockHeader> = Vec::new();
+
+    for (i, state_update) in state_updates.iter().enumerate() {
+        let number = BlockNumber::new_or_panic(i as u64);
+
+        for class in &state_update.declared_cairo_classes {
+            tx.insert_cairo_class(*class, b"definition").unwrap();
+        }
+
+        let mut storage_commitment_tree = match number.parent() {
+            Some(parent) => StorageCommitmentTree::load(tx, parent).unwrap(),
+            None => StorageCommitmentTree::empty(tx),
+        };
+        let contract_updates = state_update
+            .contract_updates
+            .iter()
+            .map(|(contract, update)| {
+                let class = update.class.as_ref().map(|x| x.class_hash());
+                (contract, &update.storage, update.nonce, class)
+            });
+        let system_contract_updates = state_update
+            .system_contract_updates
+            .iter()
+            .map(|(contract, update)| (contract, &update.storage, None, None));
+        for (contract, storage, nonce, class) in contract_updates.chain(system_contract_updates) {
+            let result =
+                update_contract_state(*contract, storage, nonce, class, tx, false, number).unwrap();
+            storage_commitment_tree
+                .set(*contract, result.state_hash)
+                .unwrap();
+            result.insert(number, tx).unwrap();
+        }
+        let (storage_commitment, nodes) = storage_commitment_tree.commit().unwrap();
+        let root = (!storage_commitment.0.is_zero())
+            .then(|| tx.insert_storage_trie(storage_commitment, &nodes).unwrap());
+        tx.insert_storage_root(number, root).unwrap();
+
+        let mut class_commitment_tree = match number.parent() {
+            Some(parent) => ClassCommitmentTree::load(tx, parent).unwrap(),
+            None => ClassCommitmentTree::empty(tx),
+        };
+        for (sierra, casm) in &state_update.declared_sierra_classes {
+            let leaf_hash = pathfinder_common::calculate_class_commitment_leaf_hash(*casm);
+            tx.insert_class_commitment_leaf(number, &leaf_hash, casm)
+                .unwrap();
+            class_commitment_tree.set(*sierra, leaf_hash).unwrap();
+        }
+        let (class_commitment, nodes) = class_commitment_tree.commit().unwrap();
+        let root = (!class_commitment.0.is_zero())
+            .then(|| tx.insert_class_trie(class_commitment, &nodes).unwrap());
+        tx.insert_class_root(number, root).unwrap();
+
+        let builder = match headers.last() {
+            Some(parent) => parent.child_builder(),
+            None => BlockHeader::builder(),
+        };
+        let builder = builder
+            .with_storage_commitment(storage_commitment)
+            .with_class_commitment(class_commitment)
+            .with_calculated_state_commitment();
+        let builder = if tamper == Some(number) {
+            builder.with_storage_commitment(storage_commitment_bytes!(b"tampered"))
+        } else {
+            builder
+        };
+        let header = builder.finalize_with_hash(block_hash_bytes!(format!("{i}").as_bytes()));
+
+        tx.insert_block_header(&header).unwrap();
+        tx.insert_state_update(number, state_update).unwrap();
+
+        headers.push(header);
+    }
+
+    headers
+}
@@ -92,6 +92,18 @@ impl<H: FeltHash, const HEIGHT: usize> MerkleTree<H, HEIGHT> {
         self
     }
 
+    /// Switches the tree to a different [FeltHash] backend, e.g. an accelerated implementation
+    /// of the same hash function. Pending changes are kept and hashed by the new backend on
+    /// commit.
+    pub fn with_hasher<H2: FeltHash>(self) -> MerkleTree<H2, HEIGHT> {
+        MerkleTree {
+            root: self.root,
+            leaves: self.leaves,
+            _hasher: std::marker::PhantomData,
+            verify_hashes: self.verify_hashes,
+        }
+    }
+
     pub fn empty() -> Self {
         Self {
             root: None,
@@ -1650,102 +1662,17 @@ mod tests {
     }
 
     mod proofs {
+        use crate::proof::{verify_proof, Membership};
         use crate::storage::Storage;
         use crate::tree::tests::commit_and_persist;
-        use pathfinder_common::hash::PedersenHash;
         use pathfinder_common::trie::TrieNode;
 
-        use super::{Direction, TestStorage, TestTree};
+        use super::{TestStorage, TestTree};
         use bitvec::prelude::Msb0;
         use bitvec::slice::BitSlice;
         use pathfinder_common::felt;
         use stark_hash::Felt;
 
-        #[derive(Debug, PartialEq, Eq)]
-        pub enum Membership {
-            Member,
-            NonMember,
-        }
-
-        /// Verifies that the key `key` with value `value` is indeed part of the MPT that has root
-        /// `root`, given `proofs`.
-        /// Supports proofs of non-membership as well as proof of membership: this function returns
-        /// an enum corresponding to the membership of `value`, or returns `None` in case of a hash mismatch.
-        /// The algorithm follows this logic:
-        /// 1. init expected_hash <- root hash
-        /// 2. loop over nodes: current <- nodes[i]
-        ///    1. verify the current node's hash matches expected_hash (if not then we have a bad proof)
-        ///    2. move towards the target - if current is:
-        ///       1. binary node then choose the child that moves towards the target, else if
-        ///       2. edge node then check the path against the target bits
-        ///          1. If it matches then proceed with the child, else
-        ///          2. if it does not match then we now have a proof that the target does not exist
-        ///    3. nibble off target bits according to which child you got in (2). If all bits are gone then you
-        ///       have reached the target and the child hash is the value you wanted and the proof is complete.
-        ///    4. set expected_hash <- to the child hash
-        /// 3. check that the expected_hash is `value` (we should've reached the leaf)
-        fn verify_proof(
-            root: Felt,
-            key: &BitSlice<u8, Msb0>,
-            value: Felt,
-            proofs: &[TrieNode],
-        ) -> Option<Membership> {
-            // Protect from ill-formed keys
-            if key.len() != 251 {
-                return None;
-            }
-
-            let mut expected_hash = root;
-            let mut remaining_path: &BitSlice<u8, Msb0> = key;
-
-            for proof_node in proofs.iter() {
-                // Hash mismatch? Return None.
-                if proof_node.hash::<PedersenHash>() != expected_hash {
-                    return None;
-                }
-                match proof_node {
-                    TrieNode::Binary { left, right } => {
-                        // Direction will always correspond to the 0th index
-                        // because we're removing bits on every iteration.
-                        let direction = Direction::from(remaining_path[0]);
-
-                        // Set the next hash to be the left or right hash,
-                        // depending on the direction
-                        expected_hash = match direction {
-                            Direction::Left => *left,
-                            Direction::Right => *right,
-                        };
-
-                        // Advance by a single bit
-                        remaining_path = &remaining_path[1..];
-                    }
-                    TrieNode::Edge { child, path } => {
-                        if path != &remaining_path[..path.len()] {
-                            // If paths don't match, we've found a proof of non membership because we:
-                            // 1. Correctly moved towards the target insofar as is possible, and
-                            // 2. hashing all the nodes along the path does result in the root hash, which means
-                            // 3. the target definitely does not exist in this tree
-                            return Some(Membership::NonMember);
-                        }
-
-                        // Set the next hash to the child's hash
-                        expected_hash = *child;
-
-                        // Advance by the whole edge path
-                        remaining_path = &remaining_path[path.len()..];
-                    }
-                }
-            }
-
-            // At this point, we should reach `value` !
-            if expected_hash == value {
-                Some(Membership::Member)
-            } else {
-                // Hash mismatch. Return `None`.
-                None
-            }
-        }
-
         /// Structure representing a randomly generated tree.
         struct RandomTree {
             keys: Vec<Felt>,
@@ -2158,4 +2085,70 @@ mod tests {
             assert!(verified.is_none());
         }
     }
+
+    mod hasher_backends {
+        use super::*;
+        use stark_curve::{
+            AffinePoint, FieldElement, PEDERSEN_P0, PEDERSEN_P1, PEDERSEN_P2, PEDERSEN_P3,
+            PEDERSEN_P4,
+        };
+
+        /// A straightforward Pedersen hash implementation which does not use the precomputed
+        /// lookup tables of [stark_hash::stark_hash]. Stands in for an alternative backend.
+        struct ReferencePedersen;
+
+        impl FeltHash for ReferencePedersen {
+            fn hash(a: Felt, b: Felt) -> Felt {
+                let a = FieldElement::from(a).into_bits();
+                let b = FieldElement::from(b).into_bits();
+
+                let mut acc = PEDERSEN_P0;
+                acc.add(&PEDERSEN_P1.multiply(&a[..248]));
+                acc.add(&PEDERSEN_P2.multiply(&a[248..252]));
+                acc.add(&PEDERSEN_P3.multiply(&b[..248]));
+                acc.add(&PEDERSEN_P4.multiply(&b[248..252]));
+
+                Felt::from(AffinePoint::from(&acc).x)
+            }
+        }
+
+        fn populate<H: FeltHash>(tree: &mut MerkleTree<H, 251>, storage: &TestStorage) {
+            for i in 0..32u64 {
+                let key = Felt::from(i * 0x1234567);
+                tree.set(storage, key.view_bits().to_owned(), Felt::from(i + 1))
+                    .unwrap();
+            }
+        }
+
+        #[test]
+        fn produce_identical_roots() {
+            let mut default_storage = TestStorage::default();
+            let mut default_tree = TestTree::empty();
+            populate(&mut default_tree, &default_storage);
+            let (default_root, _) = commit_and_persist(default_tree, &mut default_storage);
+
+            let mut reference_storage = TestStorage::default();
+            let mut reference_tree = MerkleTree::<ReferencePedersen, 251>::empty();
+            populate(&mut reference_tree, &reference_storage);
+            let (reference_root, _) = commit_and_persist(reference_tree, &mut reference_storage);
+
+            assert_eq!(default_root, reference_root);
+        }
+
+        #[test]
+        fn switching_backend_keeps_pending_changes() {
+            let mut default_storage = TestStorage::default();
+            let mut default_tree = TestTree::empty();
+            populate(&mut default_tree, &default_storage);
+            let (default_root, _) = commit_and_persist(default_tree, &mut default_storage);
+
+            let mut storage = TestStorage::default();
+            let mut tree = TestTree::empty();
+            populate(&mut tree, &storage);
+            let tree = tree.with_hasher::<ReferencePedersen>();
+            let (root, _) = commit_and_persist(tree, &mut storage);
+
+            assert_eq!(root, default_root);
+        }
+    }
 }
@@ -0,0 +1,39 @@
+use anyhow::Context;
+use pathfinder_common::BlockNumber;
+use pathfinder_lib::state::bench::bench_update;
+
+/// Benchmark applying a single block's state update.
+///
+/// Replays the block from a feeder gateway recording, as made by `--gateway.record`, and applies
+/// its state update to a fresh copy of its parent state, repeatedly. Reports the p50/p95/p99
+/// durations of fetching the block from the replayed sequencer, applying the update to the tries
+/// and committing the database transaction.
+///
+/// The parent state is copied from the given parent database, which must end at the block before
+/// the benchmarked one. Without one, the recording must contain every block up to and including
+/// the benchmarked one, as the parent state is replayed from genesis first.
+///
+/// Usage:
+/// `cargo run --release -p pathfinder --example bench_update ./recording <block number> [iterations] [parent database]`
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let recording = std::env::args()
+        .nth(1)
+        .context("Missing recording directory argument")?;
+    let block = std::env::args()
+        .nth(2)
+        .context("Missing block number argument")?
+        .parse::<u64>()
+        .context("Parsing block number")?;
+    let block = BlockNumber::new(block).context("Block number is out of range")?;
+    let iterations = match std::env::args().nth(3) {
+        Some(iterations) => iterations.parse().context("Parsing iterations")?,
+        None => 100,
+    };
+    let parent = std::env::args().nth(4).map(Into::into);
+
+    let report = bench_update(recording.into(), block, iterations, parent).await?;
+    print!("{report}");
+
+    Ok(())
+}
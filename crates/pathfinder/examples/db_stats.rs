@@ -0,0 +1,41 @@
+use std::num::NonZeroU32;
+
+use anyhow::Context;
+use pathfinder_storage::{JournalMode, Storage};
+
+/// Reports the row count and approximate size of every table in a pathfinder database.
+///
+/// Pass `--vacuum` to rebuild the database file before reporting, which reclaims the space of
+/// deleted data. Pathfinder must not be running while vacuuming.
+///
+/// Usage:
+/// `cargo run --release -p pathfinder --example db_stats ./mainnet.sqlite [--vacuum]`
+fn main() -> anyhow::Result<()> {
+    let database_path = std::env::args().nth(1).unwrap();
+    let vacuum = std::env::args().nth(2).is_some_and(|arg| arg == "--vacuum");
+
+    let storage = Storage::migrate(database_path.into(), JournalMode::WAL)?
+        .create_pool(NonZeroU32::new(1).unwrap())
+        .unwrap();
+    let mut connection = storage.connection()?;
+
+    if vacuum {
+        let started = std::time::Instant::now();
+        connection.vacuum().context("Vacuuming database")?;
+        println!("Vacuumed database in {:?}", started.elapsed());
+    }
+
+    let tx = connection.transaction()?;
+    let stats = tx.table_statistics().context("Querying table statistics")?;
+
+    println!("{:<40} {:>15} {:>15}", "table", "rows", "MiB");
+    for table in stats {
+        let size = match table.bytes {
+            Some(bytes) => format!("{:.1}", bytes as f64 / (1024.0 * 1024.0)),
+            None => "n/a".to_owned(),
+        };
+        println!("{:<40} {:>15} {:>15}", table.name, table.rows, size);
+    }
+
+    Ok(())
+}
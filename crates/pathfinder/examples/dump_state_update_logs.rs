@@ -0,0 +1,69 @@
+use std::num::NonZeroU64;
+use std::time::Duration;
+
+use anyhow::Context;
+use pathfinder_ethereum::{core_addr, EthereumClient, StateUpdateLogFetcher};
+use primitive_types::H160;
+
+/// Dumps the raw `LogStateUpdate` events emitted by the Starknet core contract as JSON lines.
+///
+/// Does not touch the database, which makes it useful for checking what L1 exposes independently
+/// of how pathfinder processes it. The range is fetched in chunks to stay within the limits
+/// Ethereum providers place on log queries, and the chunks shrink if the provider is slow.
+///
+/// Usage:
+/// `cargo run --release -p pathfinder --example dump_state_update_logs mainnet <ethereum url> <from L1 block> <to L1 block>`
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    const CHUNK_SIZE: u64 = 1000;
+    const MAX_FETCH_DURATION: Duration = Duration::from_secs(10);
+
+    let chain_name = std::env::args().nth(1).unwrap();
+    let core_address = match chain_name.as_str() {
+        "mainnet" => core_addr::MAINNET,
+        "goerli" => core_addr::TESTNET,
+        "testnet2" => core_addr::TESTNET2,
+        "integration" => core_addr::INTEGRATION,
+        _ => panic!("Expected chain name: mainnet/goerli/testnet2/integration"),
+    };
+    let core_address = H160::from_slice(&core_address);
+
+    let url = std::env::args().nth(2).unwrap();
+    let url = reqwest::Url::parse(&url).context("Parsing Ethereum URL")?;
+    let from: u64 = std::env::args()
+        .nth(3)
+        .unwrap()
+        .parse()
+        .context("Parsing start block")?;
+    let to: u64 = std::env::args()
+        .nth(4)
+        .unwrap()
+        .parse()
+        .context("Parsing end block")?;
+    anyhow::ensure!(from <= to, "Start block must not be after the end block");
+
+    let ethereum = EthereumClient::new(url).context("Creating Ethereum client")?;
+
+    let mut fetcher = StateUpdateLogFetcher::new(
+        ethereum,
+        core_address,
+        NonZeroU64::new(CHUNK_SIZE).unwrap(),
+        MAX_FETCH_DURATION,
+    );
+
+    // Print the logs as they arrive instead of waiting for the whole range.
+    let mut chunk_start = from;
+    loop {
+        let chunk_end = to.min(chunk_start.saturating_add(fetcher.chunk_size() - 1));
+        for log in fetcher.fetch(chunk_start, chunk_end).await? {
+            println!("{}", log.to_json());
+        }
+
+        if chunk_end == to {
+            break;
+        }
+        chunk_start = chunk_end + 1;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use std::num::NonZeroU32;
+
+use anyhow::Context;
+use pathfinder_common::{BlockNumber, ContractAddress};
+use pathfinder_lib::state::export::export_contract_snapshot;
+use pathfinder_storage::{JournalMode, Storage};
+use stark_hash::Felt;
+
+/// Exports a contract's entire storage at a block as a JSON snapshot, which also contains a proof
+/// linking the contract to the block's state commitment. The recipient can verify the snapshot
+/// against a state commitment they trust using `ContractSnapshot::verify`.
+///
+/// Usage:
+/// `cargo run --release -p pathfinder --example export_contract ./mainnet.sqlite 0x123 1000 > snapshot.json`
+fn main() -> anyhow::Result<()> {
+    let database_path = std::env::args().nth(1).context("Missing database path")?;
+    let address = std::env::args()
+        .nth(2)
+        .context("Missing contract address")?;
+    let address = Felt::from_hex_str(&address).context("Parsing contract address")?;
+    let address = ContractAddress::new(address).context("Contract address out of range")?;
+    let block = std::env::args().nth(3).context("Missing block number")?;
+    let block = block.parse::<u64>().context("Parsing block number")?;
+    let block = BlockNumber::new(block).context("Block number out of range")?;
+
+    let storage = Storage::migrate(database_path.into(), JournalMode::WAL)?
+        .create_pool(NonZeroU32::new(1).unwrap())
+        .unwrap();
+    let mut connection = storage.connection()?;
+    let tx = connection.transaction()?;
+
+    let snapshot = export_contract_snapshot(&tx, address, block)?
+        .context("Contract is not deployed at this block")?;
+
+    serde_json::to_writer_pretty(std::io::stdout().lock(), &snapshot)
+        .context("Writing snapshot")?;
+    println!();
+
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use std::num::NonZeroU32;
+
+use anyhow::Context;
+use pathfinder_common::ContractAddress;
+use pathfinder_storage::{JournalMode, Storage};
+use stark_hash::Felt;
+
+/// Reports the block in which a contract was deployed, and the first block which wrote to its
+/// storage.
+///
+/// Usage:
+/// `cargo run --release -p pathfinder --example find_contract ./mainnet.sqlite 0x123`
+fn main() -> anyhow::Result<()> {
+    let database_path = std::env::args().nth(1).context("Missing database path")?;
+    let address = std::env::args()
+        .nth(2)
+        .context("Missing contract address")?;
+    let address = Felt::from_hex_str(&address).context("Parsing contract address")?;
+    let address = ContractAddress::new(address).context("Contract address out of range")?;
+
+    let storage = Storage::migrate(database_path.into(), JournalMode::WAL)?
+        .create_pool(NonZeroU32::new(1).unwrap())
+        .unwrap();
+    let mut connection = storage.connection()?;
+    let tx = connection.transaction()?;
+
+    let deployed_at = tx
+        .contract_deployed_at(address)
+        .context("Querying deployment block")?;
+    let first_write = tx
+        .first_storage_write(address)
+        .context("Querying first storage write")?;
+
+    let format = |block: Option<_>| match block {
+        Some(block) => format!("{block}"),
+        None => "none".to_owned(),
+    };
+    println!("deployed at block: {}", format(deployed_at));
+    println!("first storage write: {}", format(first_write));
+
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use std::num::NonZeroU32;
+
+use pathfinder_lib::state::reverify::reverify_all;
+use pathfinder_storage::{JournalMode, Storage};
+
+/// Re-verify the state commitments in a pathfinder database.
+///
+/// Replays every block from genesis, recomputing contract state hashes, the storage and class
+/// commitments and the state commitment from the stored data. This does not require any
+/// network access.
+///
+/// Usage:
+/// `cargo run --release -p pathfinder --example reverify_state ./mainnet.sqlite`
+fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt::init();
+
+    let database_path = std::env::args().nth(1).unwrap();
+    let storage = Storage::migrate(database_path.into(), JournalMode::WAL)?
+        .create_pool(NonZeroU32::new(1).unwrap())
+        .unwrap();
+
+    match reverify_all(&storage)? {
+        Some((block, divergence)) => {
+            println!("State diverges at block {block}: {divergence:?}");
+            std::process::exit(1);
+        }
+        None => println!("All blocks verified successfully"),
+    }
+
+    Ok(())
+}
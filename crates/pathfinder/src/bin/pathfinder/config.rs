@@ -1,9 +1,12 @@
 use clap::{CommandFactory, Parser};
 #[cfg(feature = "p2p")]
 use p2p::libp2p::Multiaddr;
-use pathfinder_common::AllowedOrigins;
+use pathfinder_common::{AllowedOrigins, BlockNumber};
 use pathfinder_storage::JournalMode;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use reqwest::Url;
+use stark_hash::Felt;
+use starknet_gateway_client::Recording;
 use std::collections::HashSet;
 use std::net::SocketAddr;
 use std::num::NonZeroUsize;
@@ -187,6 +190,18 @@ This should only be enabled for debugging purposes as it adds substantial proces
     )]
     verify_tree_node_data: bool,
 
+    #[arg(
+        long = "sync.verify-transaction-hashes",
+        long_help = r"When enabled, the hash of every synced transaction is computed locally and compared against the sequencer's.
+
+Transactions from Starknet versions whose hashes cannot be reproduced are skipped either way. Disabling this trusts the sequencer's transaction hashes.",
+        action = clap::ArgAction::Set,
+        default_value = "true",
+        env = "PATHFINDER_SYNC_VERIFY_TRANSACTION_HASHES",
+        value_name = "BOOL"
+    )]
+    verify_transaction_hashes: bool,
+
     #[arg(
         long = "rpc.batch-concurrency-limit",
         long_help = "Sets the concurrency limit for request batch processing. \
@@ -198,6 +213,231 @@ This should only be enabled for debugging purposes as it adds substantial proces
         default_value = "1"
     )]
     rpc_batch_concurrency_limit: NonZeroUsize,
+
+    #[arg(
+        long = "sync.tip-file",
+        long_help = r"Path to a file which will be updated with the latest synced block after every committed block.
+
+The file contains a JSON object with the block's number, state commitment and timestamp. It is replaced atomically so external tools can safely poll it without database access.",
+        value_name = "PATH",
+        value_hint = clap::ValueHint::FilePath,
+        env = "PATHFINDER_SYNC_TIP_FILE"
+    )]
+    tip_file: Option<PathBuf>,
+
+    #[arg(
+        long = "sync.block-sink-file",
+        long_help = r"Path to a file to which every committed block is appended as a line of JSON.
+
+Each line contains the block's number, hash, state commitment, storage diffs and deployed contracts. Blocks which fail to be written are retried with the next block, so a line may be written more than once.",
+        value_name = "PATH",
+        value_hint = clap::ValueHint::FilePath,
+        env = "PATHFINDER_SYNC_BLOCK_SINK_FILE"
+    )]
+    block_sink_file: Option<PathBuf>,
+
+    #[arg(
+        long = "sync.l1-to-l2-messages",
+        long_help = r"When enabled, messages sent from Ethereum to Starknet are read from the core contract's logs and stored, which lets them be served without the sequencer.
+
+Messages are stored from the Ethereum block which is finalized when sync starts, and take an additional `eth_getLogs` call per poll.",
+        action = clap::ArgAction::Set,
+        default_value = "false",
+        env = "PATHFINDER_SYNC_L1_TO_L2_MESSAGES",
+        value_name = "BOOL"
+    )]
+    sync_l1_to_l2_messages: bool,
+
+    #[arg(
+        long = "sync.stop-at-block",
+        long_help = r"Stop syncing once this block has been committed, and shut down.
+
+Useful for reproducible testing and bounded backfills.",
+        value_name = "BLOCK NUMBER",
+        value_parser = clap::value_parser!(u64).range(..=i64::MAX as u64),
+        env = "PATHFINDER_SYNC_STOP_AT_BLOCK"
+    )]
+    stop_at_block: Option<u64>,
+
+    #[arg(
+        long = "sync.slow-block-threshold",
+        long_help = r"Log a warning for every block which takes longer than this to process, along with a breakdown of where the time was spent.
+
+Useful for spotting problem blocks, such as very large state diffs, in production logs.",
+        value_name = "MILLISECONDS",
+        env = "PATHFINDER_SYNC_SLOW_BLOCK_THRESHOLD"
+    )]
+    slow_block_threshold: Option<std::num::NonZeroU64>,
+
+    #[arg(
+        long = "sync.root-mismatch-policy",
+        long_help = r"What to do when the sequencer's state update and block disagree on the state commitment.
+
+'abort' stops sync with an error. 'retry-with-backoff' downloads the block again after an exponentially increasing delay, which can help if the sequencer briefly serves inconsistent data.",
+        value_enum,
+        default_value = "abort",
+        env = "PATHFINDER_SYNC_ROOT_MISMATCH_POLICY"
+    )]
+    root_mismatch_policy: RootMismatchPolicy,
+
+    #[arg(
+        long = "sync.missing-class-hash-policy",
+        long_help = r"What to do when a state update touches a contract whose class hash is unknown.
+
+'fail' stops sync with an error. 'fetch-from-sequencer' asks the sequencer for the contract's class hash at that block and treats the contract as deployed with it.",
+        value_enum,
+        default_value = "fail",
+        env = "PATHFINDER_SYNC_MISSING_CLASS_HASH_POLICY"
+    )]
+    missing_class_hash_policy: MissingClassHashPolicy,
+
+    #[arg(
+        long = "sync.class-not-found-policy",
+        long_help = r"What to do when the sequencer does not know a class which a state update declares or deploys.
+
+'fail' stops sync with an error. 'retry-with-backoff' downloads the class again after an exponentially increasing delay for up to 5 minutes, which helps if the sequencer is still catching up.",
+        value_enum,
+        default_value = "fail",
+        env = "PATHFINDER_SYNC_CLASS_NOT_FOUND_POLICY"
+    )]
+    class_not_found_policy: ClassNotFoundPolicy,
+
+    #[arg(
+        long = "sync.confirmation-depth",
+        long_help = r"Only sync a block once the sequencer's latest block is at least this many blocks ahead of it.
+
+The sequencer's view of the most recent blocks can still change, so this avoids storing data which is later replaced. Blocks within this window are not available until they are deep enough, and pending data is not polled.",
+        value_name = "BLOCKS",
+        default_value = "0",
+        env = "PATHFINDER_SYNC_CONFIRMATION_DEPTH"
+    )]
+    confirmation_depth: u64,
+
+    #[arg(
+        long = "gateway.request-headers",
+        long_help = r"Comma separated list of HTTP headers which are sent with every request to the Starknet gateway and feeder gateway.
+
+This is useful for gateways or caching proxies which require an API key.
+
+Examples:
+    single: x-api-key:secret
+    a list: x-api-key:secret,x-client:pathfinder",
+        value_name = "HEADER LIST",
+        value_delimiter = ',',
+        env = "PATHFINDER_GATEWAY_REQUEST_HEADERS"
+    )]
+    gateway_request_headers: Vec<String>,
+
+    #[arg(
+        long = "storage.min-free-space",
+        long_help = r"Minimum free disk space, in MiB, required on the database's filesystem at startup.
+
+Pathfinder refuses to start if less space is available. This prevents running out of disk space in the middle of writing a block.",
+        value_name = "MiB",
+        env = "PATHFINDER_STORAGE_MIN_FREE_SPACE_MIB"
+    )]
+    min_free_space: Option<u64>,
+
+    #[arg(
+        long = "storage.wal-checkpoint-interval",
+        long_help = r"Checkpoint and truncate the SQLite write-ahead log after this many synced blocks.
+
+This bounds the size of the WAL file during long syncs. Checkpoints which are blocked by active readers are skipped until the next interval. By default SQLite's automatic checkpointing is relied upon.",
+        value_name = "BLOCKS",
+        env = "PATHFINDER_STORAGE_WAL_CHECKPOINT_INTERVAL"
+    )]
+    wal_checkpoint_interval: Option<std::num::NonZeroU64>,
+
+    #[arg(
+        long = "sync.checkpoint-interval",
+        long_help = r"Record a checkpoint in the database for every block whose number is a multiple of this interval.
+
+Each checkpoint contains the block number, state commitment, time and pathfinder version. After a crash this shows how far each version got and when, which helps post-mortems of upgrades done mid-sync.",
+        value_name = "BLOCKS",
+        env = "PATHFINDER_SYNC_CHECKPOINT_INTERVAL"
+    )]
+    sync_checkpoint_interval: Option<std::num::NonZeroU64>,
+
+    #[arg(
+        long = "gateway.fallback-urls",
+        long_help = r"Comma separated list of fallback sequencer base urls, in order of priority.
+
+If a gateway or feeder gateway request fails with a connection or server error, it is retried against each fallback in turn. Each url must serve both the '/gateway' and '/feeder_gateway' endpoints.",
+        value_name = "URL LIST",
+        value_delimiter = ',',
+        env = "PATHFINDER_GATEWAY_FALLBACK_URLS"
+    )]
+    gateway_fallback_urls: Vec<Url>,
+
+    #[arg(
+        long = "gateway.request-limit",
+        long_help = r"Maximum number of concurrent gateway and feeder gateway requests.
+
+The limit is shared by all of pathfinder's requests to the sequencer, which helps avoid being rate limited. Whenever the sequencer does rate limit a request the limit is halved, and it then gradually grows back to this value as requests succeed. Unlimited by default.",
+        value_name = "COUNT",
+        env = "PATHFINDER_GATEWAY_REQUEST_LIMIT"
+    )]
+    gateway_request_limit: Option<NonZeroUsize>,
+
+    #[arg(
+        long = "gateway.record",
+        long_help = r"Records every feeder gateway response to files in this directory.
+
+The recording can be replayed using '--gateway.replay', which makes a sync session reproducible offline. This is intended for debugging purposes only.",
+        value_name = "DIR",
+        env = "PATHFINDER_GATEWAY_RECORD",
+        conflicts_with = "gateway_replay"
+    )]
+    gateway_record: Option<PathBuf>,
+
+    #[arg(
+        long = "gateway.replay",
+        long_help = r"Serves feeder gateway requests from a directory recorded using '--gateway.record' instead of the network.
+
+Requests which were not recorded are answered as if the block does not exist. This is intended for debugging purposes only.",
+        value_name = "DIR",
+        env = "PATHFINDER_GATEWAY_REPLAY"
+    )]
+    gateway_replay: Option<PathBuf>,
+
+    #[arg(
+        long = "gateway.public-key",
+        long_help = r"The sequencer's public key, as a hex encoded felt.
+
+If set, the signature of every synced block is verified against this key and sync stops on blocks which are unsigned or incorrectly signed.",
+        value_name = "FELT",
+        value_parser = parse_felt,
+        env = "PATHFINDER_GATEWAY_PUBLIC_KEY"
+    )]
+    gateway_public_key: Option<Felt>,
+
+    #[arg(
+        long = "rpc.class-hash-index",
+        long_help = r"Keeps every contract's latest class hash in memory, which is used to serve `starknet_getClassHashAt` for the latest block.
+
+The index is built from the database on startup and grows with the number of deployed contracts.",
+        action = clap::ArgAction::Set,
+        default_value = "false",
+        env = "PATHFINDER_RPC_CLASS_HASH_INDEX",
+        value_name = "BOOL"
+    )]
+    rpc_class_hash_index: bool,
+
+    #[arg(
+        long = "rpc.class-cache-size",
+        long_help = "Caches up to this many parsed class definitions in memory, which are used to serve `starknet_getClassAt`. Disabled by default.",
+        env = "PATHFINDER_RPC_CLASS_CACHE_SIZE",
+        value_name = "CLASSES"
+    )]
+    rpc_class_cache_size: Option<NonZeroUsize>,
+
+    #[arg(
+        long = "rpc.admin-token",
+        long_help = "Serves the admin JSON-RPC methods, such as `admin_reverifyRange`, on /rpc/admin/v0.1 to requests which carry this token as their bearer token. The admin methods are disabled if this is not set.",
+        env = "PATHFINDER_RPC_ADMIN_TOKEN",
+        value_name = "TOKEN"
+    )]
+    rpc_admin_token: Option<String>,
 }
 
 #[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
@@ -220,6 +460,24 @@ impl Color {
     }
 }
 
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum RootMismatchPolicy {
+    Abort,
+    RetryWithBackoff,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum MissingClassHashPolicy {
+    Fail,
+    FetchFromSequencer,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum ClassNotFoundPolicy {
+    Fail,
+    RetryWithBackoff,
+}
+
 #[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
 pub enum RpcVersion {
     V03,
@@ -402,6 +660,41 @@ pub fn parse_cors_or_exit(input: Vec<String>) -> Option<AllowedOrigins> {
     }
 }
 
+fn parse_gateway_headers(inputs: Vec<String>) -> anyhow::Result<HeaderMap> {
+    use anyhow::Context;
+
+    inputs
+        .into_iter()
+        .map(|input| {
+            let (name, value) = input
+                .split_once(':')
+                .with_context(|| format!("Expected NAME:VALUE, got {input:?}"))?;
+
+            let name = HeaderName::from_bytes(name.trim().as_bytes())
+                .with_context(|| format!("Invalid header name in {input:?}"))?;
+            let value = HeaderValue::from_str(value.trim())
+                .with_context(|| format!("Invalid header value in {input:?}"))?;
+
+            Ok((name, value))
+        })
+        .collect()
+}
+
+pub fn parse_gateway_headers_or_exit(input: Vec<String>) -> HeaderMap {
+    use clap::error::ErrorKind;
+
+    match parse_gateway_headers(input) {
+        Ok(parsed) => parsed,
+        Err(error) => Cli::command()
+            .error(ErrorKind::ValueValidation, format!("{error:#}"))
+            .exit(),
+    }
+}
+
+fn parse_felt(input: &str) -> Result<Felt, String> {
+    Felt::from_hex_str(input).map_err(|e| e.to_string())
+}
+
 #[derive(Debug, thiserror::Error, PartialEq)]
 #[error("Invalid domain for CORS: {0}")]
 struct InvalidCorsDomainError(String);
@@ -434,7 +727,29 @@ pub struct Config {
     pub p2p: P2PConfig,
     pub debug: DebugConfig,
     pub verify_tree_hashes: bool,
+    pub verify_transaction_hashes: bool,
     pub rpc_batch_concurrency_limit: NonZeroUsize,
+    pub tip_file: Option<PathBuf>,
+    pub block_sink_file: Option<PathBuf>,
+    pub sync_l1_to_l2_messages: bool,
+    pub stop_at_block: Option<BlockNumber>,
+    pub root_mismatch_policy: RootMismatchPolicy,
+    pub missing_class_hash_policy: MissingClassHashPolicy,
+    pub class_not_found_policy: ClassNotFoundPolicy,
+    pub confirmation_depth: u64,
+    pub gateway_headers: HeaderMap,
+    /// Minimum free disk space in bytes.
+    pub min_free_space: Option<u64>,
+    pub wal_checkpoint_interval: Option<std::num::NonZeroU64>,
+    pub sync_checkpoint_interval: Option<std::num::NonZeroU64>,
+    pub gateway_fallback_urls: Vec<Url>,
+    pub gateway_request_limit: Option<NonZeroUsize>,
+    pub gateway_recording: Option<Recording>,
+    pub gateway_public_key: Option<Felt>,
+    pub rpc_class_hash_index: bool,
+    pub rpc_class_cache_size: Option<NonZeroUsize>,
+    pub rpc_admin_token: Option<String>,
+    pub slow_block_threshold: Option<std::time::Duration>,
 }
 
 pub struct Ethereum {
@@ -609,7 +924,36 @@ impl Config {
             p2p: P2PConfig::parse_or_exit(cli.p2p),
             debug: DebugConfig::parse(cli.debug),
             verify_tree_hashes: cli.verify_tree_node_data,
+            verify_transaction_hashes: cli.verify_transaction_hashes,
             rpc_batch_concurrency_limit: cli.rpc_batch_concurrency_limit,
+            tip_file: cli.tip_file,
+            block_sink_file: cli.block_sink_file,
+            sync_l1_to_l2_messages: cli.sync_l1_to_l2_messages,
+            stop_at_block: cli.stop_at_block.map(BlockNumber::new_or_panic),
+            root_mismatch_policy: cli.root_mismatch_policy,
+            missing_class_hash_policy: cli.missing_class_hash_policy,
+            class_not_found_policy: cli.class_not_found_policy,
+            confirmation_depth: cli.confirmation_depth,
+            gateway_headers: parse_gateway_headers_or_exit(cli.gateway_request_headers),
+            min_free_space: cli
+                .min_free_space
+                .map(|mib| mib.saturating_mul(1024 * 1024)),
+            wal_checkpoint_interval: cli.wal_checkpoint_interval,
+            sync_checkpoint_interval: cli.sync_checkpoint_interval,
+            gateway_fallback_urls: cli.gateway_fallback_urls,
+            gateway_request_limit: cli.gateway_request_limit,
+            gateway_recording: match (cli.gateway_record, cli.gateway_replay) {
+                (Some(directory), _) => Some(Recording::Record(directory)),
+                (None, Some(directory)) => Some(Recording::Replay(directory)),
+                (None, None) => None,
+            },
+            gateway_public_key: cli.gateway_public_key,
+            rpc_class_hash_index: cli.rpc_class_hash_index,
+            rpc_class_cache_size: cli.rpc_class_cache_size,
+            rpc_admin_token: cli.rpc_admin_token,
+            slow_block_threshold: cli
+                .slow_block_threshold
+                .map(|millis| std::time::Duration::from_millis(millis.get())),
         }
     }
 }
@@ -649,7 +993,23 @@ pub struct WebsocketConfig {
 #[cfg(test)]
 mod tests {
     use super::{AllowedOrigins, RpcCorsDomainsParseError};
-    use crate::config::parse_cors;
+    use crate::config::{parse_cors, parse_gateway_headers};
+
+    #[test]
+    fn parse_gateway_request_headers() {
+        let headers = parse_gateway_headers(vec![
+            "x-api-key:secret".to_owned(),
+            " x-client : pathfinder ".to_owned(),
+        ])
+        .unwrap();
+
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers["x-api-key"], "secret");
+        assert_eq!(headers["x-client"], "pathfinder");
+
+        parse_gateway_headers(vec!["missing separator".to_owned()]).unwrap_err();
+        parse_gateway_headers(vec!["invalid name:value".to_owned()]).unwrap_err();
+    }
 
     #[test]
     fn parse_cors_domains() {
@@ -9,6 +9,8 @@ use pathfinder_lib::{
     monitoring::{self},
     state,
 };
+use pathfinder_rpc::class_cache::ClassCache;
+use pathfinder_rpc::class_hash_index::ClassHashIndex;
 use pathfinder_rpc::context::WebsocketContext;
 use pathfinder_rpc::SyncState;
 use pathfinder_storage::Storage;
@@ -23,6 +25,7 @@ use tracing::info;
 use crate::config::NetworkConfig;
 
 mod config;
+mod progress;
 mod update;
 
 fn main() -> anyhow::Result<()> {
@@ -52,6 +55,10 @@ async fn async_main() -> anyhow::Result<()> {
 
     permission_check(&config.data_directory)?;
 
+    if let Some(minimum) = config.min_free_space {
+        disk_space_check(&config.data_directory, minimum, available_disk_space)?;
+    }
+
     let available_parallelism = std::thread::available_parallelism()?;
 
     rayon::ThreadPoolBuilder::new()
@@ -88,10 +95,23 @@ async fn async_main() -> anyhow::Result<()> {
             .context("Starting monitoring task")?;
     }
 
-    let pathfinder_context =
-        PathfinderContext::configure_and_proxy_check(network, config.data_directory)
-            .await
-            .context("Configuring pathfinder")?;
+    let mut pathfinder_context = PathfinderContext::configure_and_proxy_check(
+        network,
+        config.data_directory,
+        config.gateway_headers,
+    )
+    .await
+    .context("Configuring pathfinder")?;
+    pathfinder_context.gateway = pathfinder_context
+        .gateway
+        .with_fallback_base_urls(config.gateway_fallback_urls)
+        .context("Configuring fallback gateways")?;
+    if let Some(limit) = config.gateway_request_limit {
+        pathfinder_context.gateway = pathfinder_context.gateway.with_request_limit(limit);
+    }
+    if let Some(recording) = config.gateway_recording {
+        pathfinder_context.gateway = pathfinder_context.gateway.with_recording(recording);
+    }
 
     verify_networks(pathfinder_context.network, ethereum.chain)?;
 
@@ -119,7 +139,8 @@ Hint: This is usually caused by exceeding the file descriptor limit of your syst
         .expect("usize should cast to u32");
     let rpc_storage = std::cmp::max(10, max_rpc_connections / 8);
     let rpc_storage = NonZeroU32::new(rpc_storage).expect("A non-zero minimum is set");
-    let rpc_storage = storage_manager.create_pool(rpc_storage).context(
+    // RPC never writes, so it uses read-only connections which don't contend with sync.
+    let rpc_storage = storage_manager.create_read_only_pool(rpc_storage).context(
         r"Creating database connection pool for RPC
 
 Hint: This is usually caused by exceeding the file descriptor limit of your system.
@@ -152,6 +173,20 @@ Hint: This is usually caused by exceeding the file descriptor limit of your syst
     .await
     .context("Verifying database")?;
 
+    let class_hash_index = if config.rpc_class_hash_index {
+        let mut db_conn = sync_storage
+            .connection()
+            .context("Creating database connection")?;
+        let tx = db_conn
+            .transaction()
+            .context("Creating database transaction")?;
+        let index = ClassHashIndex::load(&tx).context("Building class hash index")?;
+        info!("Class hash index built.");
+        Some(index)
+    } else {
+        None
+    };
+
     let sync_state = Arc::new(SyncState::default());
 
     let (tx_pending, rx_pending) = tokio::sync::watch::channel(Default::default());
@@ -175,6 +210,16 @@ Hint: This is usually caused by exceeding the file descriptor limit of your syst
         context
     };
 
+    let context = match &class_hash_index {
+        Some(index) => context.with_class_hash_index(index.clone()),
+        None => context,
+    };
+
+    let context = match config.rpc_class_cache_size {
+        Some(size) => context.with_class_cache(ClassCache::new(size)),
+        None => context,
+    };
+
     let default_version = match config.rpc_root_version {
         config::RpcVersion::V03 => pathfinder_rpc::DefaultVersion::V03,
         config::RpcVersion::V04 => pathfinder_rpc::DefaultVersion::V04,
@@ -186,6 +231,10 @@ Hint: This is usually caused by exceeding the file descriptor limit of your syst
         Some(allowed_origins) => rpc_server.with_cors(allowed_origins),
         None => rpc_server,
     };
+    let rpc_server = match config.rpc_admin_token {
+        Some(token) => rpc_server.with_admin_token(token),
+        None => rpc_server,
+    };
 
     let (p2p_handle, sequencer) = start_p2p(
         pathfinder_context.network_id,
@@ -195,6 +244,10 @@ Hint: This is usually caused by exceeding the file descriptor limit of your syst
     )
     .await?;
 
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let (reload_tx, reload_rx) = tokio::sync::watch::channel(());
+    reload_on_sighup(reload_tx).context("Registering SIGHUP handler")?;
+
     let sync_context = SyncContext {
         storage: sync_storage,
         ethereum: ethereum.client,
@@ -210,13 +263,47 @@ Hint: This is usually caused by exceeding the file descriptor limit of your syst
             .then_some(std::time::Duration::from_secs(2)),
         // Currently p2p does not perform block hash and state commitment verification if p2p header lacks state commitment
         block_validation_mode: state::l2::BlockValidationMode::Strict,
+        root_mismatch_policy: match config.root_mismatch_policy {
+            config::RootMismatchPolicy::Abort => state::l2::RootMismatchPolicy::Abort,
+            config::RootMismatchPolicy::RetryWithBackoff => {
+                state::l2::RootMismatchPolicy::RetryWithBackoff
+            }
+        },
+        missing_class_hash_policy: match config.missing_class_hash_policy {
+            config::MissingClassHashPolicy::Fail => state::l2::MissingClassHashPolicy::Fail,
+            config::MissingClassHashPolicy::FetchFromSequencer => {
+                state::l2::MissingClassHashPolicy::FetchFromSequencer
+            }
+        },
+        class_not_found_policy: match config.class_not_found_policy {
+            config::ClassNotFoundPolicy::Fail => state::l2::ClassNotFoundPolicy::Fail,
+            config::ClassNotFoundPolicy::RetryWithBackoff => {
+                state::l2::ClassNotFoundPolicy::RetryWithBackoff
+            }
+        },
+        confirmation_depth: config.confirmation_depth,
+        sequencer_public_key: config.gateway_public_key,
+        verify_transaction_hashes: config.verify_transaction_hashes,
         websocket_txs: rpc_server.get_topic_broadcasters().cloned(),
         block_cache_size: 1_000,
         restart_delay: config.debug.restart_delay,
         verify_tree_hashes: config.verify_tree_hashes,
+        tip_file: config.tip_file,
+        wal_checkpoint_interval: config.wal_checkpoint_interval,
+        sync_checkpoint_interval: config.sync_checkpoint_interval,
+        stop_at_block: config.stop_at_block,
+        class_hash_index,
+        slow_block_threshold: config.slow_block_threshold,
+        shutdown: shutdown_rx,
+        reload: reload_rx,
+        block_sink: match config.block_sink_file {
+            Some(path) => Arc::new(state::sink::FileSink::new(path)),
+            None => Arc::new(state::sink::NoopSink),
+        },
+        l1_to_l2_messages: config.sync_l1_to_l2_messages,
     };
 
-    let sync_handle = tokio::spawn(state::sync(sync_context, state::l1::sync, state::l2::sync));
+    let mut sync_handle = tokio::spawn(state::sync(sync_context, state::l1::sync, state::l2::sync));
 
     let (rpc_handle, local_addr) = rpc_server
         .with_max_connections(config.max_rpc_connections.get())
@@ -227,13 +314,31 @@ Hint: This is usually caused by exceeding the file descriptor limit of your syst
 
     let update_handle = tokio::spawn(update::poll_github_for_releases());
 
+    if std::io::IsTerminal::is_terminal(&std::io::stdout()) {
+        tokio::spawn(progress::report_sync_progress(sync_state.clone()));
+    }
+
     // We are now ready.
     readiness.store(true, std::sync::atomic::Ordering::Relaxed);
 
     // Monitor our spawned process tasks.
     tokio::select! {
-        result = sync_handle => {
+        _ = tokio::signal::ctrl_c() => {
+            tracing::info!("Shutdown signal received, stopping sync");
+            let _ = shutdown_tx.send(true);
+            match sync_handle.await {
+                Ok(Ok(())) => tracing::info!("Sync stopped"),
+                Ok(Err(err)) => tracing::error!(reason=?err, "Sync stopped with an error"),
+                Err(err) => tracing::error!(%err, "Sync process failed to stop"),
+            }
+            return Ok(());
+        }
+        result = &mut sync_handle => {
             match result {
+                Ok(Ok(())) if config.stop_at_block.is_some() => {
+                    tracing::info!("Sync reached the configured stop block, shutting down");
+                    return Ok(());
+                }
                 Ok(task_result) => tracing::error!("Sync process ended unexpected with: {:?}", task_result),
                 Err(err) => tracing::error!("Sync process ended unexpected; failed to join task handle: {:?}", err),
             }
@@ -316,6 +421,78 @@ fn permission_check(base: &std::path::Path) -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// Ensures that at least `minimum` bytes are available on the filesystem containing `base`.
+///
+/// Failing to determine the available space only results in a warning.
+fn disk_space_check(
+    base: &std::path::Path,
+    minimum: u64,
+    available_space: impl FnOnce(&std::path::Path) -> std::io::Result<u64>,
+) -> anyhow::Result<()> {
+    let available = match available_space(base) {
+        Ok(available) => available,
+        Err(e) => {
+            tracing::warn!(error=%e, path=%base.display(), "Failed to determine available disk space, skipping check");
+            return Ok(());
+        }
+    };
+
+    const MIB: u64 = 1024 * 1024;
+    anyhow::ensure!(
+        available >= minimum,
+        "Only {} MiB of disk space is available in {}, but at least {} MiB is required. Free up disk space or lower the limit using --storage.min-free-space.",
+        available / MIB,
+        base.display(),
+        minimum / MIB
+    );
+
+    Ok(())
+}
+
+/// Reloads sync from the database on SIGHUP, e.g. after the database was truncated manually.
+#[cfg(unix)]
+fn reload_on_sighup(reload: tokio::sync::watch::Sender<()>) -> anyhow::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sighup = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while sighup.recv().await.is_some() {
+            tracing::info!("SIGHUP received, reloading sync from database");
+            if reload.send(()).is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn reload_on_sighup(_reload: tokio::sync::watch::Sender<()>) -> anyhow::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)]
+fn available_disk_space(path: &std::path::Path) -> std::io::Result<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+    // SAFETY: `statvfs` is a plain C struct for which all zeroes is a valid value, and
+    // `path` is a valid nul-terminated string which outlives the call.
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn available_disk_space(_path: &std::path::Path) -> std::io::Result<u64> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
 #[cfg(feature = "p2p")]
 async fn start_p2p(
     chain_id: ChainId,
@@ -469,6 +646,7 @@ mod pathfinder_context {
     use pathfinder_common::{Chain, ChainId};
     use pathfinder_ethereum::core_addr;
     use primitive_types::H160;
+    use reqwest::header::HeaderMap;
     use reqwest::Url;
     use starknet_gateway_client::Client as GatewayClient;
 
@@ -476,33 +654,42 @@ mod pathfinder_context {
         pub async fn configure_and_proxy_check(
             cfg: NetworkConfig,
             data_directory: PathBuf,
+            gateway_headers: HeaderMap,
         ) -> anyhow::Result<Self> {
             let context = match cfg {
                 NetworkConfig::Mainnet => Self {
                     network: Chain::Mainnet,
                     network_id: ChainId::MAINNET,
-                    gateway: GatewayClient::mainnet(),
+                    gateway: GatewayClient::mainnet()
+                        .with_default_headers(gateway_headers)
+                        .context("Creating gateway client")?,
                     database: data_directory.join("mainnet.sqlite"),
                     l1_core_address: H160::from(core_addr::MAINNET),
                 },
                 NetworkConfig::Testnet => Self {
                     network: Chain::Testnet,
                     network_id: ChainId::TESTNET,
-                    gateway: GatewayClient::testnet(),
+                    gateway: GatewayClient::testnet()
+                        .with_default_headers(gateway_headers)
+                        .context("Creating gateway client")?,
                     database: data_directory.join("goerli.sqlite"),
                     l1_core_address: H160::from(core_addr::TESTNET),
                 },
                 NetworkConfig::Testnet2 => Self {
                     network: Chain::Testnet2,
                     network_id: ChainId::TESTNET2,
-                    gateway: GatewayClient::testnet2(),
+                    gateway: GatewayClient::testnet2()
+                        .with_default_headers(gateway_headers)
+                        .context("Creating gateway client")?,
                     database: data_directory.join("testnet2.sqlite"),
                     l1_core_address: H160::from(core_addr::TESTNET2),
                 },
                 NetworkConfig::Integration => Self {
                     network: Chain::Integration,
                     network_id: ChainId::INTEGRATION,
-                    gateway: GatewayClient::integration(),
+                    gateway: GatewayClient::integration()
+                        .with_default_headers(gateway_headers)
+                        .context("Creating gateway client")?,
                     database: data_directory.join("integration.sqlite"),
                     l1_core_address: H160::from(core_addr::INTEGRATION),
                 },
@@ -510,9 +697,15 @@ mod pathfinder_context {
                     gateway,
                     feeder_gateway,
                     chain_id,
-                } => Self::configure_custom(gateway, feeder_gateway, chain_id, data_directory)
-                    .await
-                    .context("Configuring custom network")?,
+                } => Self::configure_custom(
+                    gateway,
+                    feeder_gateway,
+                    chain_id,
+                    data_directory,
+                    gateway_headers,
+                )
+                .await
+                .context("Configuring custom network")?,
             };
 
             Ok(context)
@@ -526,12 +719,14 @@ mod pathfinder_context {
             feeder: Url,
             chain_id: String,
             data_directory: PathBuf,
+            gateway_headers: HeaderMap,
         ) -> anyhow::Result<Self> {
             use stark_hash::Felt;
             use starknet_gateway_client::GatewayApi;
 
-            let gateway =
-                GatewayClient::with_urls(gateway, feeder).context("Creating gateway client")?;
+            let gateway = GatewayClient::with_urls(gateway, feeder)
+                .and_then(|client| client.with_default_headers(gateway_headers))
+                .context("Creating gateway client")?;
 
             let network_id =
                 ChainId(Felt::from_be_slice(chain_id.as_bytes()).context("Parsing chain ID")?);
@@ -645,3 +840,38 @@ async fn verify_database(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disk_space_check_below_minimum_fails() {
+        let err = disk_space_check(std::path::Path::new("db"), 100 * 1024 * 1024, |_| {
+            Ok(10 * 1024 * 1024)
+        })
+        .unwrap_err();
+
+        assert!(err.to_string().contains("Only 10 MiB"), "{err}");
+    }
+
+    #[test]
+    fn disk_space_check_above_minimum_passes() {
+        disk_space_check(std::path::Path::new("db"), 100, |_| Ok(100)).unwrap();
+    }
+
+    #[test]
+    fn disk_space_check_ignores_query_failure() {
+        disk_space_check(std::path::Path::new("db"), 100, |_| {
+            Err(std::io::ErrorKind::Unsupported.into())
+        })
+        .unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn available_disk_space_of_temp_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(available_disk_space(dir.path()).unwrap() > 0);
+    }
+}
@@ -0,0 +1,109 @@
+//! Reports sync progress while catching up to the chain head.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use pathfinder_common::BlockNumber;
+use pathfinder_rpc::v02::types::syncing::Syncing;
+use pathfinder_rpc::SyncState;
+
+/// Periodically prints the current and highest block, the sync rate and an ETA
+/// to stdout while sync is behind the chain head.
+///
+/// This is intended for interactive use only, and should not be spawned if stdout
+/// is not a terminal.
+pub async fn report_sync_progress(state: Arc<SyncState>) {
+    const INTERVAL: Duration = Duration::from_secs(10);
+
+    // The block and time from which the sync rate is measured.
+    let mut baseline: Option<(BlockNumber, Instant)> = None;
+
+    loop {
+        tokio::time::sleep(INTERVAL).await;
+
+        let (current, highest) = match &*state.status.read().await {
+            Syncing::Status(status) => (status.current.number, status.highest.number),
+            Syncing::False(_) => continue,
+        };
+
+        if current >= highest {
+            // Caught up, so restart the measurement if we ever fall behind again.
+            baseline = None;
+            continue;
+        }
+
+        let (start, started_at) = *baseline.get_or_insert((current, Instant::now()));
+        let elapsed = started_at.elapsed().as_secs_f64();
+        let blocks_per_second = if elapsed > 0.0 {
+            current.get().saturating_sub(start.get()) as f64 / elapsed
+        } else {
+            0.0
+        };
+
+        let remaining = highest.get() - current.get();
+        let eta = match eta(remaining, blocks_per_second) {
+            Some(eta) => format_duration(eta),
+            None => "unknown".to_owned(),
+        };
+        let percent = current.get() as f64 * 100.0 / highest.get() as f64;
+
+        println!(
+            "Syncing block {current}/{highest} ({percent:.1}%), {blocks_per_second:.2} blocks/s, ETA {eta}"
+        );
+    }
+}
+
+/// Estimates how long syncing `remaining` blocks takes at `blocks_per_second`.
+///
+/// Returns [None] if no progress is being made.
+fn eta(remaining: u64, blocks_per_second: f64) -> Option<Duration> {
+    if remaining == 0 {
+        return Some(Duration::ZERO);
+    }
+
+    if blocks_per_second <= 0.0 || !blocks_per_second.is_finite() {
+        return None;
+    }
+
+    let seconds = remaining as f64 / blocks_per_second;
+    Some(Duration::from_secs_f64(seconds))
+}
+
+/// Formats a duration as hours, minutes and seconds e.g. `1h 02m 03s`.
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+    if hours > 0 {
+        format!("{hours}h {minutes:02}m {seconds:02}s")
+    } else if minutes > 0 {
+        format!("{minutes}m {seconds:02}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eta_from_rate_and_remaining_blocks() {
+        assert_eq!(eta(100, 4.0), Some(Duration::from_secs(25)));
+        assert_eq!(eta(1, 0.5), Some(Duration::from_secs(2)));
+        assert_eq!(eta(0, 0.0), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn eta_without_progress_is_unknown() {
+        assert_eq!(eta(100, 0.0), None);
+        assert_eq!(eta(100, f64::NAN), None);
+    }
+
+    #[test]
+    fn duration_formatting() {
+        assert_eq!(format_duration(Duration::from_secs(5)), "5s");
+        assert_eq!(format_duration(Duration::from_secs(65)), "1m 05s");
+        assert_eq!(format_duration(Duration::from_secs(3723)), "1h 02m 03s");
+    }
+}
@@ -396,6 +396,16 @@ impl GatewayApi for HybridClient {
         self.as_sequencer().transaction(transaction_hash).await
     }
 
+    async fn class_hash_at(
+        &self,
+        contract_address: ContractAddress,
+        block: BlockId,
+    ) -> Result<ClassHash, SequencerError> {
+        self.as_sequencer()
+            .class_hash_at(contract_address, block)
+            .await
+    }
+
     async fn state_update(&self, block: BlockId) -> Result<StateUpdate, SequencerError> {
         use error::block_not_found;
 
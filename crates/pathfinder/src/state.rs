@@ -1,4 +1,7 @@
+pub mod bench;
 pub mod block_hash;
+pub mod export;
+pub mod reverify;
 mod sync;
 
-pub use sync::{l1, l2, sync, SyncContext};
+pub use sync::{l1, l2, sink, sync, SyncContext};
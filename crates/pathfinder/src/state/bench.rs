@@ -0,0 +1,421 @@
+//! Benchmarks applying a single block's state update, for tracking performance regressions
+//! in the merkle tree code.
+//!
+//! Blocks are replayed from a feeder gateway recording, as made by `--gateway.record`.
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use pathfinder_common::{BlockHeader, BlockNumber, StateUpdate};
+use pathfinder_storage::{JournalMode, Storage};
+use starknet_gateway_client::{Client, GatewayApi, Recording};
+use starknet_gateway_types::reply::{Block, MaybePendingBlock};
+
+/// Timings of a single [bench_update] iteration.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timings {
+    /// Time spent fetching the block and its state update from the replayed sequencer.
+    pub sequencer_fetch: Duration,
+    /// Time spent applying the state update to the storage and class tries.
+    pub tree_apply: Duration,
+    /// Time spent committing the database transaction.
+    pub db_commit: Duration,
+}
+
+impl Timings {
+    pub fn total(&self) -> Duration {
+        self.sequencer_fetch + self.tree_apply + self.db_commit
+    }
+}
+
+/// The results of a [bench_update] run.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub iterations: Vec<Timings>,
+}
+
+impl Report {
+    /// Returns the `p`th percentile (`0.0..=1.0`) of the given timing.
+    pub fn percentile(&self, p: f64, timing: impl Fn(&Timings) -> Duration) -> Duration {
+        let mut durations = self.iterations.iter().map(timing).collect::<Vec<_>>();
+        if durations.is_empty() {
+            return Duration::ZERO;
+        }
+        durations.sort();
+
+        let rank = (p * durations.len() as f64).ceil() as usize;
+        durations[rank.clamp(1, durations.len()) - 1]
+    }
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "iterations: {}", self.iterations.len())?;
+        writeln!(f, "{:<16}{:>12}{:>12}{:>12}", "timing", "p50", "p95", "p99")?;
+
+        let rows: [(&str, fn(&Timings) -> Duration); 4] = [
+            ("total", Timings::total),
+            ("sequencer fetch", |t| t.sequencer_fetch),
+            ("tree apply", |t| t.tree_apply),
+            ("db commit", |t| t.db_commit),
+        ];
+        for (name, timing) in rows {
+            writeln!(
+                f,
+                "{:<16}{:>12}{:>12}{:>12}",
+                name,
+                format!("{:.2?}", self.percentile(0.50, timing)),
+                format!("{:.2?}", self.percentile(0.95, timing)),
+                format!("{:.2?}", self.percentile(0.99, timing)),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Replays `block` from the feeder gateway `recording` and applies its state update on top of
+/// its parent state, `iterations` times.
+///
+/// The parent state is read from the `parent` database if one is given, which must end at the
+/// block before `block`. Otherwise the blocks before `block` are replayed from the recording into
+/// a fresh database. Either becomes a template which each iteration starts from a fresh copy of,
+/// so a large `parent` database makes for a slow, though not a skewed, benchmark. Neither the
+/// replay nor the copies are included in the timings.
+pub async fn bench_update(
+    recording: PathBuf,
+    block: BlockNumber,
+    iterations: usize,
+    parent: Option<PathBuf>,
+) -> anyhow::Result<Report> {
+    let sequencer = Client::mainnet().with_recording(Recording::Replay(recording));
+    let dir = tempfile::tempdir().context("Creating temporary directory")?;
+
+    // The state update opens a connection on each rayon thread, in addition to its own.
+    let pool_size = NonZeroU32::new(rayon::current_num_threads() as u32 + 1).unwrap();
+
+    let template = dir.path().join("template.sqlite");
+    match parent {
+        Some(parent) => copy_database(&parent, &template).context("Copying parent database")?,
+        None => replay(&sequencer, &template, block, pool_size).await?,
+    }
+
+    {
+        let storage = Storage::migrate(template.clone(), JournalMode::WAL)
+            .context("Opening template database")?
+            .create_pool(NonZeroU32::new(1).unwrap())
+            .context("Creating connection pool")?;
+        let mut connection = storage
+            .connection()
+            .context("Creating database connection")?;
+        let tx = connection
+            .transaction()
+            .context("Creating database transaction")?;
+        let latest = tx
+            .block_id(pathfinder_storage::BlockId::Latest)
+            .context("Fetching latest block")?
+            .map(|(number, _)| number);
+        anyhow::ensure!(
+            latest == block.parent(),
+            "Parent state ends at block {latest:?}, but block {block} was requested"
+        );
+    }
+
+    let mut report = Report::default();
+    for i in 0..iterations {
+        let database = dir.path().join(format!("{i}.sqlite"));
+        let timings = iteration(&sequencer, &template, &database, block, pool_size)
+            .await
+            .with_context(|| format!("Running iteration {i}"))?;
+        std::fs::remove_file(&database).context("Removing database")?;
+
+        report.iterations.push(timings);
+    }
+
+    Ok(report)
+}
+
+/// Copies the database at `source` to `destination`, including its write-ahead log if it has one.
+///
+/// The source database must not be written to while it is being copied.
+fn copy_database(source: &Path, destination: &Path) -> anyhow::Result<()> {
+    std::fs::copy(source, destination).with_context(|| format!("Copying {}", source.display()))?;
+
+    let wal = |path: &Path| {
+        let mut wal = path.as_os_str().to_owned();
+        wal.push("-wal");
+        PathBuf::from(wal)
+    };
+    if wal(source).exists() {
+        std::fs::copy(wal(source), wal(destination)).context("Copying write-ahead log")?;
+    }
+
+    Ok(())
+}
+
+/// Replays the blocks before `block` from the `sequencer` into a new `database`.
+async fn replay(
+    sequencer: &Client,
+    database: &Path,
+    block: BlockNumber,
+    pool_size: NonZeroU32,
+) -> anyhow::Result<()> {
+    let storage = Storage::migrate(database.to_owned(), JournalMode::WAL)
+        .context("Creating database")?
+        .create_pool(pool_size)
+        .context("Creating connection pool")?;
+    let mut connection = storage
+        .connection()
+        .context("Creating database connection")?;
+
+    for parent in 0..block.get() {
+        let parent = BlockNumber::new_or_panic(parent);
+        let (parent_block, state_update) = fetch(sequencer, parent)
+            .await
+            .with_context(|| format!("Fetching block {parent}"))?;
+
+        let tx = connection
+            .transaction()
+            .context("Creating database transaction")?;
+        let (storage_commitment, class_commitment) =
+            super::sync::update_starknet_state(&tx, &state_update, false, parent, storage.clone())
+                .with_context(|| format!("Applying state update of block {parent}"))?;
+
+        let header = BlockHeader::builder()
+            .with_number(parent)
+            .with_parent_hash(parent_block.parent_block_hash)
+            .with_storage_commitment(storage_commitment)
+            .with_class_commitment(class_commitment)
+            .with_state_commitment(parent_block.state_commitment)
+            .finalize_with_hash(parent_block.block_hash);
+        tx.insert_block_header(&header)
+            .context("Inserting block header")?;
+        tx.insert_state_update(parent, &state_update)
+            .context("Inserting state update")?;
+        tx.commit().context("Committing database transaction")?;
+    }
+
+    Ok(())
+}
+
+async fn iteration(
+    sequencer: &Client,
+    template: &Path,
+    database: &Path,
+    block: BlockNumber,
+    pool_size: NonZeroU32,
+) -> anyhow::Result<Timings> {
+    std::fs::copy(template, database).context("Copying template database")?;
+    let storage = Storage::migrate(database.to_owned(), JournalMode::WAL)
+        .context("Opening database")?
+        .create_pool(pool_size)
+        .context("Creating connection pool")?;
+    let mut connection = storage
+        .connection()
+        .context("Creating database connection")?;
+
+    let start = Instant::now();
+    let (block, state_update) = fetch(sequencer, block).await?;
+    let sequencer_fetch = start.elapsed();
+
+    let tx = connection
+        .transaction()
+        .context("Creating database transaction")?;
+
+    let start = Instant::now();
+    super::sync::update_starknet_state(
+        &tx,
+        &state_update,
+        false,
+        block.block_number,
+        storage.clone(),
+    )
+    .context("Applying state update")?;
+    let tree_apply = start.elapsed();
+
+    let start = Instant::now();
+    tx.commit().context("Committing database transaction")?;
+    let db_commit = start.elapsed();
+
+    Ok(Timings {
+        sequencer_fetch,
+        tree_apply,
+        db_commit,
+    })
+}
+
+/// Fetches a block and its state update, as sync does.
+async fn fetch(sequencer: &Client, block: BlockNumber) -> anyhow::Result<(Block, StateUpdate)> {
+    let block = match sequencer
+        .block(block.into())
+        .await
+        .context("Fetching block")?
+    {
+        MaybePendingBlock::Block(block) => block,
+        MaybePendingBlock::Pending(_) => anyhow::bail!("Sequencer returned a pending block"),
+    };
+    let state_update = sequencer
+        .state_update(block.block_hash.into())
+        .await
+        .context("Fetching state update")?;
+
+    Ok((block, state_update))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::BlockHash;
+
+    /// Records `blocks` in the format of a `--gateway.record` session.
+    fn record(directory: &Path, blocks: &[(BlockHash, serde_json::Value)]) {
+        for (i, (hash, state_diff)) in blocks.iter().enumerate() {
+            let parent_hash = match i {
+                0 => BlockHash::ZERO,
+                i => blocks[i - 1].0,
+            };
+            let block = serde_json::json!({
+                "block_hash": hash,
+                "block_number": i,
+                "parent_block_hash": parent_hash,
+                "state_commitment": "0x0",
+                "status": "ACCEPTED_ON_L2",
+                "timestamp": 0,
+                "transaction_receipts": [],
+                "transactions": [],
+            });
+            let state_update = serde_json::json!({
+                "block_hash": hash,
+                "new_root": "0x0",
+                "old_root": "0x0",
+                "state_diff": state_diff,
+            });
+
+            std::fs::write(
+                directory.join(format!("get_block_blockNumber_{i}")),
+                block.to_string(),
+            )
+            .unwrap();
+            std::fs::write(
+                directory.join(format!(
+                    "get_state_update_blockHash_{}",
+                    hash.0.to_hex_str()
+                )),
+                state_update.to_string(),
+            )
+            .unwrap();
+        }
+    }
+
+    /// Records a genesis block deploying a contract, and a block 1 updating its storage.
+    fn record_two_blocks(directory: &Path) {
+        let state_diff = |storage_diffs, deployed_contracts| {
+            serde_json::json!({
+                "storage_diffs": storage_diffs,
+                "deployed_contracts": deployed_contracts,
+                "old_declared_contracts": [],
+                "declared_classes": [],
+                "nonces": {},
+                "replaced_classes": [],
+            })
+        };
+        record(
+            directory,
+            &[
+                (
+                    block_hash_bytes!(b"genesis"),
+                    state_diff(
+                        serde_json::json!({}),
+                        serde_json::json!([{"address": "0x123", "class_hash": "0xabc"}]),
+                    ),
+                ),
+                (
+                    block_hash_bytes!(b"block 1"),
+                    state_diff(
+                        serde_json::json!({"0x123": [{"key": "0x1", "value": "0x2"}]}),
+                        serde_json::json!([]),
+                    ),
+                ),
+            ],
+        );
+    }
+
+    #[tokio::test]
+    async fn smoke() {
+        let recording = tempfile::tempdir().unwrap();
+        record_two_blocks(recording.path());
+
+        let report = bench_update(
+            recording.path().to_owned(),
+            BlockNumber::new_or_panic(1),
+            2,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.iterations.len(), 2);
+
+        let output = report.to_string();
+        assert!(output.contains("p50"), "{output}");
+        assert!(output.contains("sequencer fetch"), "{output}");
+        assert!(output.contains("tree apply"), "{output}");
+    }
+
+    #[tokio::test]
+    async fn starts_from_parent_database() {
+        let recording = tempfile::tempdir().unwrap();
+        record_two_blocks(recording.path());
+
+        let sequencer =
+            Client::mainnet().with_recording(Recording::Replay(recording.path().to_owned()));
+        let dir = tempfile::tempdir().unwrap();
+        let parent = dir.path().join("parent.sqlite");
+        let pool_size = NonZeroU32::new(rayon::current_num_threads() as u32 + 1).unwrap();
+        replay(&sequencer, &parent, BlockNumber::new_or_panic(1), pool_size)
+            .await
+            .unwrap();
+
+        let report = bench_update(
+            recording.path().to_owned(),
+            BlockNumber::new_or_panic(1),
+            1,
+            Some(parent.clone()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.iterations.len(), 1);
+
+        // The parent state must end right before the benchmarked block.
+        bench_update(
+            recording.path().to_owned(),
+            BlockNumber::GENESIS,
+            1,
+            Some(parent),
+        )
+        .await
+        .unwrap_err();
+    }
+
+    #[test]
+    fn percentile() {
+        let report = Report {
+            iterations: (1..=100)
+                .map(|ms| Timings {
+                    tree_apply: Duration::from_millis(ms),
+                    ..Default::default()
+                })
+                .collect(),
+        };
+
+        assert_eq!(
+            report.percentile(0.50, Timings::total),
+            Duration::from_millis(50)
+        );
+        assert_eq!(
+            report.percentile(0.99, Timings::total),
+            Duration::from_millis(99)
+        );
+    }
+}
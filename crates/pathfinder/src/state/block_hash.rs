@@ -41,63 +41,151 @@ pub fn verify_block_hash(
         return Ok(VerifyResult::NotVerifiable);
     }
 
-    let num_transactions: u64 = block
-        .transactions
-        .len()
-        .try_into()
-        .expect("too many transactions in block");
+    let (transaction_commitment, event_commitment) = calculate_commitments(block)?;
 
+    let block_sequencer_address = block
+        .sequencer_address
+        .unwrap_or(SequencerAddress(Felt::ZERO));
+
+    let verified = if meta_info.uses_pre_0_7_hash_algorithm(block.block_number) {
+        // The sequencer address is not part of the pre-0.7 block hash.
+        let block_hash = compute_hash(
+            block,
+            chain,
+            chain_id,
+            meta_info,
+            transaction_commitment,
+            event_commitment,
+            &block_sequencer_address,
+        )?;
+        block_hash == expected_block_hash
+    } else {
+        let mut verified = false;
+        for address in std::iter::once(&block_sequencer_address)
+            .chain(meta_info.fallback_sequencer_address.iter())
+        {
+            let block_hash = compute_hash(
+                block,
+                chain,
+                chain_id,
+                meta_info,
+                transaction_commitment,
+                event_commitment,
+                address,
+            )?;
+            if block_hash == expected_block_hash {
+                verified = true;
+                break;
+            }
+        }
+        verified
+    };
+
+    Ok(match verified {
+        false => VerifyResult::Mismatch,
+        true => VerifyResult::Match((transaction_commitment, event_commitment)),
+    })
+}
+
+/// Computes the block hash of `block` independently of the hash provided by the sequencer.
+///
+/// Returns [None] for blocks whose hash cannot be computed because the sequencer address
+/// used is unknown.
+///
+/// This includes blocks which do not include a sequencer address on chains where some such blocks
+/// were hashed using zero and others using an unpublished sequencer address. [verify_block_hash]
+/// can still check these against a known hash.
+pub fn compute_block_hash(
+    block: &Block,
+    chain: Chain,
+    chain_id: ChainId,
+) -> Result<Option<BlockHash>> {
+    let meta_info = meta::for_chain(chain);
+    if !meta_info.can_verify(block.block_number) {
+        return Ok(None);
+    }
+
+    // Pre-0.7 hashes do not include the sequencer address.
+    let sequencer_address = match block.sequencer_address {
+        Some(address) => address,
+        None if meta_info.uses_pre_0_7_hash_algorithm(block.block_number)
+            || meta_info.fallback_sequencer_address.is_none() =>
+        {
+            SequencerAddress(Felt::ZERO)
+        }
+        None => return Ok(None),
+    };
+    let (transaction_commitment, event_commitment) = calculate_commitments(block)?;
+
+    compute_hash(
+        block,
+        chain,
+        chain_id,
+        meta_info,
+        transaction_commitment,
+        event_commitment,
+        &sequencer_address,
+    )
+    .map(Some)
+}
+
+fn calculate_commitments(block: &Block) -> Result<(TransactionCommitment, EventCommitment)> {
     let transaction_final_hash_type =
         TransactionCommitmentFinalHashType::for_version(&block.starknet_version)?;
     let transaction_commitment =
         calculate_transaction_commitment(&block.transactions, transaction_final_hash_type)?;
     let event_commitment = calculate_event_commitment(&block.transaction_receipts)?;
 
-    let verified = if meta_info.uses_pre_0_7_hash_algorithm(block.block_number) {
+    Ok((transaction_commitment, event_commitment))
+}
+
+fn compute_hash(
+    block: &Block,
+    chain: Chain,
+    chain_id: ChainId,
+    meta_info: &meta::BlockHashMetaInfo,
+    transaction_commitment: TransactionCommitment,
+    event_commitment: EventCommitment,
+    sequencer_address: &SequencerAddress,
+) -> Result<BlockHash> {
+    let num_transactions: u64 = block
+        .transactions
+        .len()
+        .try_into()
+        .expect("too many transactions in block");
+
+    let hash = if meta_info.uses_pre_0_7_hash_algorithm(block.block_number) {
         anyhow::ensure!(
             chain != Chain::Custom,
             "Chain::Custom should not have any pre 0.7 block hashes"
         );
 
-        let block_hash = compute_final_hash_pre_0_7(
+        compute_final_hash_pre_0_7(
             block.block_number,
             block.state_commitment,
             num_transactions,
             transaction_commitment.0,
             block.parent_block_hash,
             chain_id,
-        );
-        block_hash == expected_block_hash
+        )
     } else {
         let num_events = number_of_events_in_block(block);
         let num_events: u64 = num_events.try_into().expect("too many events in block");
 
-        let block_sequencer_address = block
-            .sequencer_address
-            .unwrap_or(SequencerAddress(Felt::ZERO));
-
-        std::iter::once(&block_sequencer_address)
-            .chain(meta_info.fallback_sequencer_address.iter())
-            .any(|address| {
-                let block_hash = compute_final_hash(
-                    block.block_number,
-                    block.state_commitment,
-                    address,
-                    block.timestamp,
-                    num_transactions,
-                    transaction_commitment.0,
-                    num_events,
-                    event_commitment.0,
-                    block.parent_block_hash,
-                );
-                block_hash == expected_block_hash
-            })
+        compute_final_hash(
+            block.block_number,
+            block.state_commitment,
+            sequencer_address,
+            block.timestamp,
+            num_transactions,
+            transaction_commitment.0,
+            num_events,
+            event_commitment.0,
+            block.parent_block_hash,
+        )
     };
 
-    Ok(match verified {
-        false => VerifyResult::Mismatch,
-        true => VerifyResult::Match((transaction_commitment, event_commitment)),
-    })
+    Ok(hash)
 }
 
 mod meta {
@@ -613,4 +701,47 @@ mod tests {
             VerifyResult::Match(_)
         );
     }
+
+    #[test]
+    fn compute_block_hash_matches_known_hashes() {
+        let fixtures = [
+            starknet_gateway_test_fixtures::v0_9_0::block::GENESIS,
+            starknet_gateway_test_fixtures::v0_9_0::block::NUMBER_231579,
+        ];
+
+        for json in fixtures {
+            let block: Block = serde_json::from_str(json).unwrap();
+            let hash = compute_block_hash(&block, Chain::Testnet, ChainId::TESTNET).unwrap();
+            assert_eq!(hash, Some(block.block_hash), "block {}", block.block_number);
+        }
+    }
+
+    #[test]
+    fn compute_block_hash_not_verifiable() {
+        // Integration blocks before 0.7 were hashed with an unknown sequencer address.
+        let json = starknet_gateway_test_fixtures::v0_9_0::block::NUMBER_156000;
+        let mut block: Block = serde_json::from_str(json).unwrap();
+        block.block_number = BlockNumber::new_or_panic(50000);
+
+        let hash = compute_block_hash(&block, Chain::Integration, ChainId::INTEGRATION).unwrap();
+        assert_eq!(hash, None);
+    }
+
+    #[test]
+    fn compute_block_hash_without_sequencer_address() {
+        // Block 90000 was hashed using zero as the sequencer address, and block 156000 using the
+        // fallback address. Neither includes the address it was hashed with.
+        let fixtures = [
+            starknet_gateway_test_fixtures::v0_9_0::block::NUMBER_90000,
+            starknet_gateway_test_fixtures::v0_9_0::block::NUMBER_156000,
+        ];
+
+        for json in fixtures {
+            let block: Block = serde_json::from_str(json).unwrap();
+            assert_eq!(block.sequencer_address, None);
+
+            let hash = compute_block_hash(&block, Chain::Testnet, ChainId::TESTNET).unwrap();
+            assert_eq!(hash, None, "block {}", block.block_number);
+        }
+    }
 }
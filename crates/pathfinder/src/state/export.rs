@@ -0,0 +1,384 @@
+//! Exports the contract storage state at a block as newline delimited JSON, or a single
+//! contract's storage as a [ContractSnapshot] which can be verified without a database.
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use anyhow::Context;
+use pathfinder_common::trie::TrieNode;
+use pathfinder_common::{
+    BlockNumber, ClassCommitment, ClassHash, ContractAddress, ContractNonce, ContractRoot,
+    StateCommitment, StorageAddress, StorageValue,
+};
+use pathfinder_merkle_tree::contract_state::calculate_contract_state_hash;
+use pathfinder_merkle_tree::{proof, ContractsStorageTree, StorageCommitmentTree};
+use pathfinder_storage::Transaction;
+use stark_hash::Felt;
+
+/// A single line of the [export_state_stream] output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct StorageEntry {
+    pub address: ContractAddress,
+    pub key: StorageAddress,
+    pub value: StorageValue,
+}
+
+/// Writes every non-zero storage entry as of `block` to `writer`, one JSON [StorageEntry]
+/// per line.
+///
+/// Entries are streamed from the database to the writer, so memory use is bounded regardless of
+/// the state's size. Returns the number of entries written.
+pub fn export_state_stream(
+    tx: &Transaction<'_>,
+    block: BlockNumber,
+    mut writer: impl Write,
+) -> anyhow::Result<usize> {
+    let mut count = 0;
+
+    tx.storage_entries_at(block, |address, key, value| {
+        // Zero values are deleted entries.
+        if value == StorageValue::ZERO {
+            return Ok(());
+        }
+
+        serde_json::to_writer(
+            &mut writer,
+            &StorageEntry {
+                address,
+                key,
+                value,
+            },
+        )
+        .context("Serializing storage entry")?;
+        writer.write_all(b"\n").context("Writing storage entry")?;
+        count += 1;
+
+        Ok(())
+    })
+    .context("Streaming storage entries")?;
+
+    writer.flush().context("Flushing writer")?;
+
+    Ok(count)
+}
+
+/// A contract's entire storage at a block, along with a proof which links it to the block's
+/// state commitment.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ContractSnapshot {
+    pub block: BlockNumber,
+    pub contract_address: ContractAddress,
+    pub class_hash: ClassHash,
+    pub nonce: ContractNonce,
+    pub root: ContractRoot,
+    pub storage: BTreeMap<StorageAddress, StorageValue>,
+    /// Combined with the storage commitment, which is the root of `contract_proof`, this yields
+    /// the state commitment.
+    pub class_commitment: ClassCommitment,
+    /// The path from the storage commitment tree's root to the contract's state hash.
+    pub contract_proof: Vec<ProofNode>,
+}
+
+/// A serializable [TrieNode].
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProofNode {
+    Binary {
+        left: Felt,
+        right: Felt,
+    },
+    Edge {
+        child: Felt,
+        path: Felt,
+        length: usize,
+    },
+}
+
+impl From<TrieNode> for ProofNode {
+    fn from(node: TrieNode) -> Self {
+        match node {
+            TrieNode::Binary { left, right } => Self::Binary { left, right },
+            TrieNode::Edge { child, path } => Self::Edge {
+                child,
+                path: Felt::from_bits(&path).expect("Edge paths fit into a felt"),
+                length: path.len(),
+            },
+        }
+    }
+}
+
+impl TryFrom<&ProofNode> for TrieNode {
+    type Error = anyhow::Error;
+
+    fn try_from(node: &ProofNode) -> Result<Self, Self::Error> {
+        let node = match node {
+            ProofNode::Binary { left, right } => TrieNode::Binary {
+                left: *left,
+                right: *right,
+            },
+            ProofNode::Edge {
+                child,
+                path,
+                length,
+            } => {
+                let bits = path.view_bits();
+                anyhow::ensure!(*length <= bits.len(), "Edge path length out of range");
+                TrieNode::Edge {
+                    child: *child,
+                    path: bits[bits.len() - length..].to_bitvec(),
+                }
+            }
+        };
+
+        Ok(node)
+    }
+}
+
+impl ContractSnapshot {
+    /// Verifies that this snapshot is the contract's complete storage in the global state with
+    /// the given `state_commitment`.
+    ///
+    /// This does not require a database, so the recipient of a snapshot only needs to trust the
+    /// state commitment.
+    pub fn verify(&self, state_commitment: StateCommitment) -> anyhow::Result<bool> {
+        let root = proof::contract_root(self.storage.iter().map(|(k, v)| (*k, *v)))
+            .context("Computing contract root")?;
+        if root != self.root {
+            return Ok(false);
+        }
+
+        let contract_proof = self
+            .contract_proof
+            .iter()
+            .map(TrieNode::try_from)
+            .collect::<anyhow::Result<Vec<_>>>()
+            .context("Decoding contract proof")?;
+        let contract_state_hash = calculate_contract_state_hash(self.class_hash, root, self.nonce);
+
+        Ok(proof::verify_contract_proof(
+            state_commitment,
+            self.class_commitment,
+            self.contract_address,
+            &contract_proof,
+            contract_state_hash,
+        ))
+    }
+}
+
+/// Creates a [ContractSnapshot] of `contract` at `block`, or returns `None` if the contract is
+/// not deployed at that block.
+pub fn export_contract_snapshot(
+    tx: &Transaction<'_>,
+    contract: ContractAddress,
+    block: BlockNumber,
+) -> anyhow::Result<Option<ContractSnapshot>> {
+    let header = tx
+        .block_header(block.into())
+        .context("Fetching block header")?
+        .context("Block not found")?;
+
+    let Some(class_hash) = tx
+        .contract_class_hash(block.into(), contract)
+        .context("Querying contract's class hash")?
+    else {
+        return Ok(None);
+    };
+    let nonce = tx
+        .contract_nonce(contract, block.into())
+        .context("Querying contract's nonce")?
+        .unwrap_or_default();
+    let root = tx
+        .contract_root(block, contract)
+        .context("Querying contract's root")?
+        .unwrap_or_default();
+
+    let storage =
+        ContractsStorageTree::storage(tx, contract, block).context("Reading contract's storage")?;
+    let contract_proof = StorageCommitmentTree::get_proof(tx, block, &contract)
+        .context("Creating contract proof")?
+        .into_iter()
+        .map(ProofNode::from)
+        .collect();
+
+    Ok(Some(ContractSnapshot {
+        block,
+        contract_address: contract,
+        class_hash,
+        nonce,
+        root,
+        storage,
+        class_commitment: header.class_commitment,
+        contract_proof,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::{BlockHeader, StateUpdate};
+    use std::collections::HashSet;
+
+    #[test]
+    fn roundtrip() {
+        let storage = pathfinder_storage::Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let contract_0 = contract_address_bytes!(b"contract 0");
+        let contract_1 = contract_address_bytes!(b"contract 1");
+
+        let header_0 = BlockHeader::builder().finalize_with_hash(block_hash_bytes!(b"0"));
+        let header_1 = header_0
+            .child_builder()
+            .finalize_with_hash(block_hash_bytes!(b"1"));
+        let header_2 = header_1
+            .child_builder()
+            .finalize_with_hash(block_hash_bytes!(b"2"));
+
+        let diff_0 = StateUpdate::default()
+            .with_storage_update(
+                contract_0,
+                storage_address_bytes!(b"key 0"),
+                storage_value_bytes!(b"value 0"),
+            )
+            .with_storage_update(
+                contract_0,
+                storage_address_bytes!(b"key 1"),
+                storage_value_bytes!(b"value 1"),
+            );
+        let diff_1 = StateUpdate::default()
+            .with_storage_update(
+                contract_0,
+                storage_address_bytes!(b"key 0"),
+                storage_value_bytes!(b"value 0 updated"),
+            )
+            .with_storage_update(
+                contract_0,
+                storage_address_bytes!(b"key 1"),
+                StorageValue::ZERO,
+            )
+            .with_storage_update(
+                contract_1,
+                storage_address_bytes!(b"key 2"),
+                storage_value_bytes!(b"value 2"),
+            );
+        // Should not be included in an export of block 1.
+        let diff_2 = StateUpdate::default().with_storage_update(
+            contract_1,
+            storage_address_bytes!(b"key 2"),
+            storage_value_bytes!(b"value 2 updated"),
+        );
+
+        for (header, diff) in [(header_0, diff_0), (header_1, diff_1), (header_2, diff_2)] {
+            tx.insert_block_header(&header).unwrap();
+            tx.insert_state_update(header.number, &diff).unwrap();
+        }
+
+        let mut buffer = Vec::new();
+        let count = export_state_stream(&tx, BlockNumber::new_or_panic(1), &mut buffer).unwrap();
+
+        let result = std::str::from_utf8(&buffer)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<StorageEntry>(line).unwrap())
+            .collect::<HashSet<_>>();
+
+        let expected = HashSet::from([
+            StorageEntry {
+                address: contract_0,
+                key: storage_address_bytes!(b"key 0"),
+                value: storage_value_bytes!(b"value 0 updated"),
+            },
+            StorageEntry {
+                address: contract_1,
+                key: storage_address_bytes!(b"key 2"),
+                value: storage_value_bytes!(b"value 2"),
+            },
+        ]);
+
+        assert_eq!(count, expected.len());
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn contract_snapshot_verifies() {
+        let storage = pathfinder_storage::Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+
+        let class = class_hash_bytes!(b"class");
+        let contract_0 = contract_address_bytes!(b"contract 0");
+        let contract_1 = contract_address_bytes!(b"contract 1");
+
+        let state_updates = [
+            StateUpdate::default()
+                .with_declared_cairo_class(class)
+                .with_deployed_contract(contract_0, class)
+                .with_deployed_contract(contract_1, class)
+                .with_storage_update(
+                    contract_0,
+                    storage_address_bytes!(b"key 0"),
+                    storage_value_bytes!(b"value 0"),
+                )
+                .with_storage_update(
+                    contract_1,
+                    storage_address_bytes!(b"key 0"),
+                    storage_value_bytes!(b"value 1"),
+                ),
+            StateUpdate::default()
+                .with_contract_nonce(contract_0, contract_nonce!("0x1"))
+                .with_storage_update(
+                    contract_0,
+                    storage_address_bytes!(b"key 1"),
+                    storage_value_bytes!(b"value 2"),
+                ),
+        ];
+
+        let tx = connection.transaction().unwrap();
+        let headers = pathfinder_merkle_tree::test_utils::insert_blocks(&tx, &state_updates, None);
+        tx.commit().unwrap();
+        let state_commitment = headers.last().unwrap().state_commitment;
+
+        let tx = connection.transaction().unwrap();
+        let block = BlockNumber::new_or_panic(1);
+        let snapshot = export_contract_snapshot(&tx, contract_0, block)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            snapshot.storage,
+            BTreeMap::from([
+                (
+                    storage_address_bytes!(b"key 0"),
+                    storage_value_bytes!(b"value 0")
+                ),
+                (
+                    storage_address_bytes!(b"key 1"),
+                    storage_value_bytes!(b"value 2")
+                ),
+            ])
+        );
+        assert_eq!(snapshot.nonce, contract_nonce!("0x1"));
+
+        // The snapshot is shared as a file, so it must survive serialization.
+        let json = serde_json::to_vec(&snapshot).unwrap();
+        let snapshot = serde_json::from_slice::<ContractSnapshot>(&json).unwrap();
+        assert!(snapshot.verify(state_commitment).unwrap());
+
+        let mut tampered = snapshot.clone();
+        tampered.storage.insert(
+            storage_address_bytes!(b"key 0"),
+            storage_value_bytes!(b"value 1"),
+        );
+        assert!(!tampered.verify(state_commitment).unwrap());
+
+        assert!(!snapshot
+            .verify(state_commitment_bytes!(b"other state"))
+            .unwrap());
+
+        let undeployed = contract_address_bytes!(b"undeployed");
+        assert_eq!(
+            export_contract_snapshot(&tx, undeployed, block).unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,76 @@
+//! Offline re-verification of the state commitments stored in the database.
+//!
+//! The implementation lives in [pathfinder_merkle_tree::reverify], so that it can also be run by
+//! the RPC server.
+pub use pathfinder_merkle_tree::reverify::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::{BlockNumber, ContractAddress, StateUpdate};
+    use pathfinder_storage::Storage;
+
+    /// Creates a database with three blocks of state updates.
+    ///
+    /// If `tamper` is set, the storage commitment stored in that block's header is replaced
+    /// with garbage.
+    fn setup(tamper: Option<BlockNumber>) -> Storage {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let class = class_hash_bytes!(b"class");
+        let contract_0 = contract_address_bytes!(b"contract 0");
+        let contract_1 = contract_address_bytes!(b"contract 1");
+
+        let state_updates = [
+            StateUpdate::default()
+                .with_declared_cairo_class(class)
+                .with_deployed_contract(contract_0, class)
+                .with_storage_update(
+                    contract_0,
+                    storage_address_bytes!(b"key 0"),
+                    storage_value_bytes!(b"value 0"),
+                ),
+            StateUpdate::default()
+                .with_deployed_contract(contract_1, class)
+                .with_contract_nonce(contract_0, contract_nonce!("0x1"))
+                .with_system_storage_update(
+                    ContractAddress::ONE,
+                    storage_address_bytes!(b"key 1"),
+                    storage_value_bytes!(b"value 1"),
+                ),
+            StateUpdate::default().with_storage_update(
+                contract_1,
+                storage_address_bytes!(b"key 2"),
+                storage_value_bytes!(b"value 2"),
+            ),
+        ];
+        pathfinder_merkle_tree::test_utils::insert_blocks(&tx, &state_updates, tamper);
+        tx.commit().unwrap();
+
+        storage
+    }
+
+    #[test]
+    fn consistent_database_passes() {
+        let storage = setup(None);
+
+        let result = reverify_all(&storage).unwrap();
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn tampered_root_fails_at_that_block() {
+        let tampered = BlockNumber::new_or_panic(1);
+        let storage = setup(Some(tampered));
+
+        let (block, divergence) = reverify_all(&storage).unwrap().unwrap();
+        assert_eq!(block, tampered);
+        assert_matches::assert_matches!(
+            divergence,
+            Divergence::StorageCommitment { stored, .. } if stored == storage_commitment_bytes!(b"tampered")
+        );
+    }
+}
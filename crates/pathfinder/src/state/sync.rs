@@ -2,16 +2,18 @@ mod class;
 pub mod l1;
 pub mod l2;
 mod pending;
+pub mod sink;
 
 use anyhow::Context;
 use pathfinder_common::{
-    BlockCommitmentSignature, BlockHash, BlockHeader, BlockNumber, CasmHash, Chain, ChainId,
-    ClassCommitment, ClassHash, EventCommitment, GasPrice, SequencerAddress, SierraHash,
+    BlockCommitmentSignature, BlockHash, BlockHeader, BlockNumber, BlockTimestamp, CasmHash, Chain,
+    ChainId, ClassCommitment, ClassHash, EventCommitment, GasPrice, SequencerAddress, SierraHash,
     StateCommitment, StateUpdate, StorageCommitment, TransactionCommitment,
 };
-use pathfinder_ethereum::{EthereumApi, EthereumStateUpdate};
+use pathfinder_ethereum::{EthereumApi, EthereumStateUpdate, L1ToL2Message};
 use pathfinder_merkle_tree::contract_state::update_contract_state;
 use pathfinder_merkle_tree::{ClassCommitmentTree, StorageCommitmentTree};
+use pathfinder_rpc::class_hash_index::ClassHashIndex;
 use pathfinder_rpc::PendingData;
 use pathfinder_rpc::{
     v02::types::syncing::{self, NumberedBlock, Syncing},
@@ -25,6 +27,8 @@ use starknet_gateway_types::reply::Block;
 use starknet_gateway_types::reply::PendingBlock;
 
 use std::future::Future;
+use std::num::NonZeroU64;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 use std::time::Instant;
@@ -38,6 +42,8 @@ use tokio::sync::watch::Sender as WatchSender;
 #[derive(Debug)]
 pub enum SyncEvent {
     L1Update(EthereumStateUpdate),
+    /// Messages sent from Ethereum to Starknet were found in finalized Ethereum blocks.
+    L1ToL2Messages(Vec<L1ToL2Message>),
     /// New L2 [block update](StateUpdate) found.
     Block(
         (Box<Block>, (TransactionCommitment, EventCommitment)),
@@ -65,6 +71,136 @@ pub enum SyncEvent {
     Pending(Box<(PendingBlock, StateUpdate)>),
 }
 
+/// Time spent in each phase of applying a block to the database.
+///
+/// Time spent downloading the block is tracked separately in [l2::Timings].
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockTimings {
+    /// Updating the storage tries of each contract.
+    pub contract_tries: Duration,
+    /// Updating the global storage commitment tree.
+    pub storage_trie: Duration,
+    /// Updating the class commitment tree.
+    pub class_trie: Duration,
+    /// Inserting the block header, transactions, state update and signature.
+    pub insert: Duration,
+    /// Committing the database transaction.
+    pub commit: Duration,
+}
+
+impl BlockTimings {
+    pub fn total(&self) -> Duration {
+        self.contract_tries + self.storage_trie + self.class_trie + self.insert + self.commit
+    }
+}
+
+/// Identifies which state commitment check a [RootMismatch] originates from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RootSource {
+    /// The state commitment we computed from the state update does not match the block's.
+    Computed,
+    /// The sequencer's state update disagrees with its block on the state commitment.
+    SequencerState,
+    /// The sequencer's state update does not build on our current head's state commitment.
+    SequencerParent,
+}
+
+/// A state commitment check failed.
+///
+/// Returned wrapped in an [anyhow::Error], use `downcast_ref` to inspect it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RootMismatch {
+    pub source: RootSource,
+    pub expected: StateCommitment,
+    pub actual: StateCommitment,
+    pub block: BlockNumber,
+}
+
+impl std::fmt::Display for RootMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let what = match self.source {
+            RootSource::Computed => "Computed state root",
+            RootSource::SequencerState => "Sequencer state root",
+            RootSource::SequencerParent => "Sequencer parent state root",
+        };
+
+        write!(
+            f,
+            "{what} mismatch for block {}, expected {}, actual {}",
+            self.block, self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for RootMismatch {}
+
+/// A field in which a sequencer block disagrees with the L1 state update for that block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum L1Mismatch {
+    BlockNumber {
+        l1: BlockNumber,
+        l2: BlockNumber,
+    },
+    BlockHash {
+        l1: BlockHash,
+        l2: BlockHash,
+    },
+    StateRoot {
+        l1: StateCommitment,
+        l2: StateCommitment,
+    },
+}
+
+impl std::fmt::Display for L1Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            L1Mismatch::BlockNumber { l1, l2 } => {
+                write!(f, "L1/L2 block number mismatch, L1 {l1}, L2 {l2}")
+            }
+            L1Mismatch::BlockHash { l1, l2 } => {
+                write!(f, "L1/L2 block hash mismatch, L1 {l1}, L2 {l2}")
+            }
+            L1Mismatch::StateRoot { l1, l2 } => {
+                write!(f, "L1/L2 state root mismatch, L1 {l1}, L2 {l2}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for L1Mismatch {}
+
+/// Checks that a sequencer block is the block described by an L1 state update.
+///
+/// The block number is checked first, so that the block's state root is only compared against
+/// the state root which L1 reports for that same block.
+pub fn validate_sequencer_block(
+    block: &BlockHeader,
+    update_log: &EthereumStateUpdate,
+) -> Result<(), L1Mismatch> {
+    if block.number != update_log.block_number {
+        return Err(L1Mismatch::BlockNumber {
+            l1: update_log.block_number,
+            l2: block.number,
+        });
+    }
+
+    if block.hash != update_log.block_hash {
+        return Err(L1Mismatch::BlockHash {
+            l1: update_log.block_hash,
+            l2: block.hash,
+        });
+    }
+
+    if block.state_commitment != update_log.state_root {
+        return Err(L1Mismatch::StateRoot {
+            l1: update_log.state_root,
+            l2: block.state_commitment,
+        });
+    }
+
+    Ok(())
+}
+
 pub struct SyncContext<G, E> {
     pub storage: Storage,
     pub ethereum: E,
@@ -77,10 +213,44 @@ pub struct SyncContext<G, E> {
     pub pending_data: WatchSender<Arc<PendingData>>,
     pub pending_poll_interval: Option<Duration>,
     pub block_validation_mode: l2::BlockValidationMode,
+    pub root_mismatch_policy: l2::RootMismatchPolicy,
+    pub missing_class_hash_policy: l2::MissingClassHashPolicy,
+    pub class_not_found_policy: l2::ClassNotFoundPolicy,
+    /// Blocks are only synced once they are at least this many blocks behind the sequencer's
+    /// latest block.
+    pub confirmation_depth: u64,
+    /// If set, block signatures are verified against this sequencer public key.
+    pub sequencer_public_key: Option<Felt>,
+    pub verify_transaction_hashes: bool,
     pub websocket_txs: Option<TopicBroadcasters>,
     pub block_cache_size: usize,
     pub restart_delay: Duration,
     pub verify_tree_hashes: bool,
+    /// If set, the latest committed block's number, state commitment and timestamp
+    /// are written to this file after every L2 update.
+    pub tip_file: Option<PathBuf>,
+    /// If set, the WAL is checkpointed and truncated after this many committed blocks.
+    pub wal_checkpoint_interval: Option<NonZeroU64>,
+    /// If set, a [checkpoint](pathfinder_storage::Checkpoint) is recorded for every block whose
+    /// number is a multiple of this interval.
+    pub sync_checkpoint_interval: Option<NonZeroU64>,
+    /// If set, sync completes successfully once this block has been committed. Blocks
+    /// beyond it are ignored.
+    pub stop_at_block: Option<BlockNumber>,
+    /// If set, kept up to date with the class hash of every contract as blocks are committed.
+    pub class_hash_index: Option<ClassHashIndex>,
+    /// If set, a warning is logged for every block whose processing takes longer than this.
+    pub slow_block_threshold: Option<Duration>,
+    /// Sync exits once this is set to `true`, cancelling any in-flight network calls.
+    pub shutdown: tokio::sync::watch::Receiver<bool>,
+    /// Sync restarts from the latest block in the database whenever this changes, e.g. after the
+    /// database was truncated manually.
+    pub reload: tokio::sync::watch::Receiver<()>,
+    /// Every committed block is published to this sink.
+    pub block_sink: Arc<dyn sink::BlockSink>,
+    /// If set, messages sent from Ethereum to Starknet are read from the core contract's logs and
+    /// stored.
+    pub l1_to_l2_messages: bool,
 }
 
 impl<G, E> From<&SyncContext<G, E>> for L1SyncContext<E>
@@ -93,6 +263,9 @@ where
             chain: value.chain,
             core_address: value.core_address,
             poll_interval: value.head_poll_interval,
+            l1_to_l2_messages: value
+                .l1_to_l2_messages
+                .then(crate::state::l1::MessageCursor::default),
         }
     }
 }
@@ -110,7 +283,13 @@ where
             head_poll_interval: value.head_poll_interval,
             pending_poll_interval: value.pending_poll_interval,
             block_validation_mode: value.block_validation_mode,
+            root_mismatch_policy: value.root_mismatch_policy,
+            missing_class_hash_policy: value.missing_class_hash_policy,
+            class_not_found_policy: value.class_not_found_policy,
+            confirmation_depth: value.confirmation_depth,
             storage: value.storage.clone(),
+            sequencer_public_key: value.sequencer_public_key,
+            verify_transaction_hashes: value.verify_transaction_hashes,
         }
     }
 }
@@ -151,10 +330,26 @@ where
         pending_data,
         pending_poll_interval: _,
         block_validation_mode: _,
+        root_mismatch_policy: _,
+        missing_class_hash_policy: _,
+        class_not_found_policy: _,
+        confirmation_depth: _,
+        sequencer_public_key: _,
+        verify_transaction_hashes: _,
         websocket_txs: _,
         block_cache_size,
         restart_delay,
         verify_tree_hashes: _,
+        tip_file,
+        wal_checkpoint_interval,
+        sync_checkpoint_interval,
+        stop_at_block,
+        class_hash_index,
+        slow_block_threshold,
+        mut shutdown,
+        mut reload,
+        block_sink,
+        l1_to_l2_messages: _,
     } = context;
 
     let mut db_conn = storage
@@ -162,7 +357,7 @@ where
         .context("Creating database connection")?;
 
     // TODO: consider increasing the capacity.
-    let (event_sender, event_receiver) = mpsc::channel(2);
+    let (mut event_sender, event_receiver) = mpsc::channel(2);
 
     let l2_head = tokio::task::block_in_place(|| -> anyhow::Result<_> {
         let tx = db_conn.transaction()?;
@@ -181,7 +376,7 @@ where
         BlockHash(Felt::ZERO),
         StateCommitment(Felt::ZERO),
     ));
-    let _status_sync = tokio::spawn(update_sync_status_latest(
+    let status_sync = tokio::spawn(update_sync_status_latest(
         Arc::clone(&state),
         sequencer.clone(),
         starting_block_hash,
@@ -210,10 +405,17 @@ where
     let consumer_context = ConsumerContext {
         storage,
         state,
-        pending_data,
+        pending_data: Arc::new(pending_data),
         verify_tree_hashes: context.verify_tree_hashes,
+        tip_file,
+        wal_checkpoint_interval,
+        sync_checkpoint_interval,
+        stop_at_block,
+        class_hash_index,
+        slow_block_threshold,
+        block_publisher: sink::Publisher::new(block_sink),
     };
-    let mut consumer_handle = tokio::spawn(consumer(event_receiver, consumer_context));
+    let mut consumer_handle = tokio::spawn(consumer(event_receiver, consumer_context.clone()));
 
     /// Delay before restarting L1 or L2 tasks if they fail. This delay helps prevent DoS if these
     /// tasks are crashing.
@@ -224,6 +426,77 @@ where
 
     loop {
         tokio::select! {
+            Ok(_) = shutdown.wait_for(|&shutdown| shutdown) => {
+                // Blocks are downloaded in full by the producers before the consumer commits them
+                // in a single database transaction. Aborting the producers therefore cancels any
+                // in-flight network calls without leaving a partially applied block behind, and
+                // an aborted consumer either completes or rolls back its current transaction.
+                tracing::info!("Shutting down sync");
+                status_sync.abort();
+                l1_handle.abort();
+                l2_handle.abort();
+                consumer_handle.abort();
+
+                let handles = [
+                    ("L1 sync", l1_handle),
+                    ("L2 sync", l2_handle),
+                    ("Sync consumer", consumer_handle),
+                ];
+                for (name, handle) in handles {
+                    match handle.await {
+                        Ok(Ok(())) => {
+                            tracing::debug!("{name} task exited gracefully");
+                        },
+                        Ok(Err(e)) => {
+                            tracing::debug!(reason=?e, "{name} task terminated with an error");
+                        }
+                        Err(e) if e.is_cancelled() => {
+                            tracing::debug!("{name} task cancelled succesfully");
+                        },
+                        Err(panic) => {
+                            tracing::error!(%panic, "{name} task panic'd");
+                        }
+                    }
+                }
+
+                return Ok(());
+            },
+            Ok(()) = reload.changed() => {
+                // Restart all sync tasks, as the producers and the consumer each track the
+                // latest block in memory. Events already queued were produced on top of the old
+                // head, so they are dropped along with the channel.
+                tracing::info!("Reloading sync state from database");
+                l1_handle.abort();
+                l2_handle.abort();
+                consumer_handle.abort();
+                let _ = (&mut l1_handle).await;
+                let _ = (&mut l2_handle).await;
+                let _ = (&mut consumer_handle).await;
+
+                let l2_head = tokio::task::block_in_place(|| {
+                    let tx = db_conn.transaction()?;
+                    if let Some(index) = &consumer_context.class_hash_index {
+                        index.reload(&tx).context("Reloading class hash index")?;
+                    }
+                    tx.block_header(pathfinder_storage::BlockId::Latest)
+                })
+                .context("Query L2 head from database")?
+                .map(|block| (block.number, block.hash, block.state_commitment));
+
+                let latest_blocks = latest_n_blocks(&mut db_conn, block_cache_size).await.context("Fetching latest blocks from storage")?;
+                let block_chain = BlockChain::with_capacity(1_000, latest_blocks);
+
+                let (sender, receiver) = mpsc::channel(2);
+                event_sender = sender;
+                l1_handle = tokio::spawn(l1_sync(event_sender.clone(), l1_context.clone()));
+                l2_handle = tokio::spawn(l2_sync(event_sender.clone(), l2_context.clone(), l2_head, block_chain));
+                consumer_handle = tokio::spawn(consumer(receiver, consumer_context.clone()));
+
+                match l2_head {
+                    Some((number, ..)) => tracing::info!(head=%number, "Sync reloaded from database"),
+                    None => tracing::info!("Sync reloaded from empty database"),
+                }
+            },
             l1_producer_result = &mut l1_handle => {
                 match l1_producer_result.context("Join L1 sync process handle")? {
                     Ok(()) => {
@@ -269,6 +542,7 @@ where
                 tracing::info!("L2 sync process restarted.");
             },
             consumer_result = &mut consumer_handle => {
+                let consumer_finished = matches!(consumer_result, Ok(Ok(())));
                 match consumer_result {
                     Ok(Ok(())) => {
                         tracing::debug!("Sync consumer task exited gracefully");
@@ -319,17 +593,30 @@ where
                     }
                 }
 
+                if consumer_finished && stop_at_block.is_some() {
+                    tracing::info!("Sync reached the configured stop block");
+                    return Ok(());
+                }
+
                 anyhow::bail!("Sync process terminated");
             }
         }
     }
 }
 
+#[derive(Clone)]
 struct ConsumerContext {
     pub storage: Storage,
     pub state: Arc<SyncState>,
-    pub pending_data: WatchSender<Arc<PendingData>>,
+    pub pending_data: Arc<WatchSender<Arc<PendingData>>>,
     pub verify_tree_hashes: bool,
+    pub tip_file: Option<PathBuf>,
+    pub wal_checkpoint_interval: Option<NonZeroU64>,
+    pub sync_checkpoint_interval: Option<NonZeroU64>,
+    pub stop_at_block: Option<BlockNumber>,
+    pub class_hash_index: Option<ClassHashIndex>,
+    pub slow_block_threshold: Option<Duration>,
+    pub block_publisher: sink::Publisher,
 }
 
 async fn consumer(mut events: Receiver<SyncEvent>, context: ConsumerContext) -> anyhow::Result<()> {
@@ -338,11 +625,19 @@ async fn consumer(mut events: Receiver<SyncEvent>, context: ConsumerContext) ->
         state,
         pending_data,
         verify_tree_hashes,
+        tip_file,
+        wal_checkpoint_interval,
+        sync_checkpoint_interval,
+        stop_at_block,
+        class_hash_index,
+        slow_block_threshold,
+        block_publisher,
     } = context;
 
     let mut last_block_start = std::time::Instant::now();
     let mut block_time_avg = std::time::Duration::ZERO;
     const BLOCK_TIME_WEIGHT: f32 = 0.05;
+    let mut blocks_since_checkpoint = 0;
 
     let mut db_conn = storage
         .connection()
@@ -362,6 +657,11 @@ async fn consumer(mut events: Receiver<SyncEvent>, context: ConsumerContext) ->
     })
     .context("Fetching latest block time")?;
 
+    if stop_at_block.is_some_and(|stop| next_number > stop) {
+        tracing::info!("Stop block has already been synced");
+        return Ok(());
+    }
+
     while let Some(event) = events.recv().await {
         use SyncEvent::*;
         match event {
@@ -369,11 +669,35 @@ async fn consumer(mut events: Receiver<SyncEvent>, context: ConsumerContext) ->
                 l1_update(&mut db_conn, &update).await?;
                 tracing::info!("L1 sync updated to block {}", update.block_number);
             }
+            L1ToL2Messages(messages) => {
+                tokio::task::block_in_place(|| {
+                    let transaction = db_conn
+                        .transaction()
+                        .context("Create database transaction")?;
+                    transaction
+                        .insert_l1_to_l2_messages(&messages)
+                        .context("Insert L1 to L2 messages")?;
+                    transaction.commit().context("Commit database transaction")
+                })?;
+                tracing::debug!(count=%messages.len(), "Stored L1 to L2 messages");
+            }
             Block((block, (tx_comm, ev_comm)), state_update, signature, timings) => {
                 if block.block_number < next_number {
                     tracing::debug!("Ignoring duplicate block {}", block.block_number);
                     continue;
                 }
+                if stop_at_block.is_some_and(|stop| block.block_number > stop) {
+                    tracing::debug!("Ignoring block {} beyond stop block", block.block_number);
+                    continue;
+                }
+                // Applying a block on top of a gap would corrupt the state tries, so this is
+                // fatal rather than something to skip over.
+                anyhow::ensure!(
+                    block.block_number == next_number,
+                    "Received block {} out of order, expected block {}",
+                    block.block_number,
+                    next_number
+                );
 
                 let block_number = block.block_number;
                 let block_hash = block.block_hash;
@@ -383,8 +707,22 @@ async fn consumer(mut events: Receiver<SyncEvent>, context: ConsumerContext) ->
                     .iter()
                     .map(|x| x.1.storage.len())
                     .sum();
+                let class_updates = class_hash_index.as_ref().map(|_| {
+                    state_update
+                        .contract_updates
+                        .iter()
+                        .filter_map(|(address, update)| {
+                            Some((*address, update.class.as_ref()?.class_hash()))
+                        })
+                        .collect::<Vec<_>>()
+                });
+                let committed_block = sink::CommittedBlock::new(
+                    block_number,
+                    state_update.state_commitment,
+                    &state_update,
+                );
                 let update_t = std::time::Instant::now();
-                l2_update(
+                let (state_commitment, block_timings) = l2_update(
                     &mut db_conn,
                     *block,
                     tx_comm,
@@ -396,13 +734,98 @@ async fn consumer(mut events: Receiver<SyncEvent>, context: ConsumerContext) ->
                 )
                 .await
                 .with_context(|| format!("Update L2 state to {block_number}"))?;
+
+                if let (Some(index), Some(class_updates)) = (&class_hash_index, class_updates) {
+                    index.update(block_number, class_updates);
+                }
+
+                block_publisher.publish(sink::CommittedBlock {
+                    state_commitment,
+                    ..committed_block
+                });
+
+                if let Some(tip_file) = &tip_file {
+                    let tip = Tip {
+                        block_number,
+                        state_commitment,
+                        timestamp: block_timestamp,
+                    };
+                    // The tip file is informational only, so failing to write it should not halt sync.
+                    if let Err(e) = write_tip_file(tip_file, &tip) {
+                        tracing::warn!(path=%tip_file.display(), error=?e, "Failed to write tip file");
+                    }
+                }
+
+                if sync_checkpoint_interval
+                    .is_some_and(|interval| block_number.get() % interval.get() == 0)
+                {
+                    // Checkpoints are diagnostic only, so failing to record one should not halt sync.
+                    if let Err(e) = tokio::task::block_in_place(|| {
+                        checkpoint(&mut db_conn, block_number, state_commitment)
+                    }) {
+                        tracing::warn!(%block_number, error=?e, "Failed to record sync checkpoint");
+                    }
+                }
+
+                if let Some(interval) = wal_checkpoint_interval {
+                    blocks_since_checkpoint += 1;
+                    if blocks_since_checkpoint >= interval.get() {
+                        blocks_since_checkpoint = 0;
+                        // A busy checkpoint is simply retried after the next interval so as to not
+                        // block readers.
+                        match tokio::task::block_in_place(|| db_conn.wal_checkpoint()) {
+                            Ok(true) => tracing::debug!("WAL checkpoint completed"),
+                            Ok(false) => tracing::debug!("WAL checkpoint busy, skipping"),
+                            Err(e) => tracing::warn!(error=?e, "WAL checkpoint failed"),
+                        }
+                    }
+                }
+
                 let block_time = last_block_start.elapsed();
                 let update_t = update_t.elapsed();
                 last_block_start = std::time::Instant::now();
 
+                if slow_block_threshold.is_some_and(|threshold| update_t > threshold) {
+                    tracing::warn!(
+                        %block_number,
+                        processing=?update_t,
+                        storage_updates,
+                        block_download=?timings.block_download,
+                        state_diff_download=?timings.state_diff_download,
+                        class_declaration=?timings.class_declaration,
+                        signature_download=?timings.signature_download,
+                        contract_tries=?block_timings.contract_tries,
+                        storage_trie=?block_timings.storage_trie,
+                        class_trie=?block_timings.class_trie,
+                        insert=?block_timings.insert,
+                        commit=?block_timings.commit,
+                        "Slow block"
+                    );
+                }
+
                 block_time_avg = block_time_avg.mul_f32(1.0 - BLOCK_TIME_WEIGHT)
                     + block_time.mul_f32(BLOCK_TIME_WEIGHT);
 
+                let now_timestamp = time::OffsetDateTime::now_utc().unix_timestamp() as u64;
+                let latency = seconds_behind_tip(block_timestamp, now_timestamp);
+                *state.seconds_behind_tip.write().await = Some(latency);
+
+                if block_timestamp.get() < latest_timestamp.get() {
+                    tracing::warn!(
+                        %block_number,
+                        timestamp=%block_timestamp.get(),
+                        previous=%latest_timestamp.get(),
+                        "Block timestamp went backwards"
+                    );
+                }
+                if block_timestamp.get() > now_timestamp + MAX_CLOCK_SKEW.as_secs() {
+                    tracing::warn!(
+                        %block_number,
+                        seconds_ahead=%(block_timestamp.get() - now_timestamp),
+                        "Block timestamp is ahead of the local clock, check the system time"
+                    );
+                }
+
                 // Update sync status
                 match &mut *state.status.write().await {
                     Syncing::False(_) => {}
@@ -414,13 +837,20 @@ async fn consumer(mut events: Receiver<SyncEvent>, context: ConsumerContext) ->
                         if status.highest.number <= block_number {
                             status.highest = status.current;
                             metrics::gauge!("highest_block", block_number.get() as f64);
+
+                            // Being at the sequencer's head while far behind the local clock
+                            // points at clock skew or a stalled sequencer.
+                            if latency > MAX_TIP_LAG.as_secs() {
+                                tracing::warn!(
+                                    %block_number,
+                                    seconds_behind_tip=%latency,
+                                    "Latest block is far behind the local clock, check the system time"
+                                );
+                            }
                         }
                     }
                 }
 
-                let now_timestamp = time::OffsetDateTime::now_utc().unix_timestamp() as u64;
-                let latency = now_timestamp.saturating_sub(block_timestamp.get());
-
                 let download_time = (timings.block_download
                     + timings.class_declaration
                     + timings.state_diff_download
@@ -429,10 +859,30 @@ async fn consumer(mut events: Receiver<SyncEvent>, context: ConsumerContext) ->
 
                 metrics::gauge!("block_download", download_time);
                 metrics::gauge!("block_processing", update_t.as_secs_f64());
+                metrics::gauge!(
+                    "block_processing_contract_tries",
+                    block_timings.contract_tries.as_secs_f64()
+                );
+                metrics::gauge!(
+                    "block_processing_storage_trie",
+                    block_timings.storage_trie.as_secs_f64()
+                );
+                metrics::gauge!(
+                    "block_processing_class_trie",
+                    block_timings.class_trie.as_secs_f64()
+                );
+                metrics::gauge!(
+                    "block_processing_insert",
+                    block_timings.insert.as_secs_f64()
+                );
+                metrics::gauge!(
+                    "block_processing_commit",
+                    block_timings.commit.as_secs_f64()
+                );
                 metrics::gauge!("block_latency", latency as f64);
                 metrics::gauge!(
                     "block_time",
-                    (block_timestamp.get() - latest_timestamp.get()) as f64
+                    block_timestamp.get().saturating_sub(latest_timestamp.get()) as f64
                 );
                 latest_timestamp = block_timestamp;
                 next_number += 1;
@@ -461,12 +911,36 @@ async fn consumer(mut events: Receiver<SyncEvent>, context: ConsumerContext) ->
                                 );
                     }
                 }
+
+                if stop_at_block == Some(block_number) {
+                    tracing::info!(%block_number, "Reached stop block");
+                    return Ok(());
+                }
             }
             Reorg(reorg_tail) => {
                 l2_reorg(&mut db_conn, reorg_tail)
                     .await
                     .with_context(|| format!("Reorg L2 state to {reorg_tail:?}"))?;
 
+                if let Some(index) = &class_hash_index {
+                    tokio::task::block_in_place(|| {
+                        let tx = db_conn
+                            .transaction()
+                            .context("Creating database transaction")?;
+                        index.reload(&tx)
+                    })
+                    .context("Reloading class hash index after reorg")?;
+                }
+
+                if let Some(tip_file) = &tip_file {
+                    // As after a commit, a stale tip file should not halt sync.
+                    if let Err(e) = tokio::task::block_in_place(|| {
+                        rewrite_tip_file_after_reorg(&mut db_conn, tip_file)
+                    }) {
+                        tracing::warn!(path=%tip_file.display(), error=?e, "Failed to rewrite tip file after reorg");
+                    }
+                }
+
                 next_number = reorg_tail;
 
                 let new_head = match reorg_tail {
@@ -549,6 +1023,21 @@ async fn consumer(mut events: Receiver<SyncEvent>, context: ConsumerContext) ->
     Ok(())
 }
 
+/// Block timestamps further than this ahead of the local clock indicate clock skew.
+const MAX_CLOCK_SKEW: Duration = Duration::from_secs(60);
+
+/// The latest block lagging the local clock by more than this while sync is at the
+/// sequencer's head indicates clock skew or a stalled sequencer.
+const MAX_TIP_LAG: Duration = Duration::from_secs(60 * 60);
+
+/// Estimates how far behind the chain tip the node is, based on the latest block's timestamp
+/// and the current unix time in seconds.
+///
+/// Timestamps ahead of the local clock count as zero lag.
+fn seconds_behind_tip(block_timestamp: BlockTimestamp, now: u64) -> u64 {
+    now.saturating_sub(block_timestamp.get())
+}
+
 async fn latest_n_blocks(
     connection: &mut Connection,
     n: usize,
@@ -672,21 +1161,23 @@ async fn l1_update(
             .upsert_l1_state(update)
             .context("Insert update")?;
 
-        let l2_hash = transaction
-            .block_id(update.block_number.into())
-            .context("Fetching block hash")?
-            .map(|(_, hash)| hash);
+        let l2_header = transaction
+            .block_header(update.block_number.into())
+            .context("Fetching block header")?;
 
-        if let Some(l2_hash) = l2_hash {
-            if l2_hash == update.block_hash {
-                transaction
-                    .update_l1_l2_pointer(Some(update.block_number))
-                    .context("Updating L1-L2 pointer")?;
-                tracing::info!(block=?update.block_number, "Updated L1/L2 match");
-            } else {
-                tracing::warn!(block_number=?update.block_number, L1=?update.block_hash, L2=?l2_hash, "L1/L2 block hash mismatch");
-                if let Some(matching_block_number) = transaction.l1_l2_pointer()? {
-                    tracing::warn!(block_number=?matching_block_number, "Most recent L1/L2 block hash match")
+        if let Some(l2_header) = l2_header {
+            match validate_sequencer_block(&l2_header, update) {
+                Ok(()) => {
+                    transaction
+                        .update_l1_l2_pointer(Some(update.block_number))
+                        .context("Updating L1-L2 pointer")?;
+                    tracing::info!(block=?update.block_number, "Updated L1/L2 match");
+                }
+                Err(mismatch) => {
+                    tracing::warn!(block_number=?update.block_number, %mismatch, "L1/L2 mismatch");
+                    if let Some(matching_block_number) = transaction.l1_l2_pointer()? {
+                        tracing::warn!(block_number=?matching_block_number, "Most recent L1/L2 block match")
+                    }
                 }
             }
         }
@@ -708,38 +1199,68 @@ async fn l2_update(
     // we need this so that we can create extra read-only transactions for
     // parallel contract state updates
     storage: Storage,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<(StateCommitment, BlockTimings)> {
     tokio::task::block_in_place(move || {
+        let mut timings = BlockTimings::default();
         let transaction = connection
             .transaction_with_behavior(TransactionBehavior::Immediate)
             .context("Create database transaction")?;
-        let (storage_commitment, class_commitment) = update_starknet_state(
+
+        if let Some(parent) = block.block_number.parent() {
+            let head_commitment = transaction
+                .block_header(parent.into())
+                .context("Querying parent block header")?
+                .context("Parent block header is missing")?
+                .state_commitment;
+
+            // In p2p the state commitment can be missing, which is marked as 0.
+            if state_update.parent_state_commitment != head_commitment
+                && !(cfg!(feature = "p2p")
+                    && state_update.parent_state_commitment == StateCommitment::ZERO)
+            {
+                return Err(RootMismatch {
+                    source: RootSource::SequencerParent,
+                    expected: head_commitment,
+                    actual: state_update.parent_state_commitment,
+                    block: block.block_number,
+                }
+                .into());
+            }
+        }
+
+        let (storage_commitment, class_commitment) = update_starknet_state_timed(
             &transaction,
             &state_update,
             verify_tree_hashes,
             block.block_number,
             storage,
+            &mut timings,
         )
         .context("Updating Starknet state")?;
         let state_commitment = StateCommitment::calculate(storage_commitment, class_commitment);
 
         // Ensure that roots match.. what should we do if it doesn't? For now the whole sync process ends..
         #[cfg(not(feature = "p2p"))]
-        anyhow::ensure!(
-            state_commitment == block.state_commitment,
-            "State root mismatch"
-        );
+        let roots_match = state_commitment == block.state_commitment;
 
         // FIXME EEEEEEE does not work for non-proxy nodeeeeeees
         // In p2p the state commitment can be missing, which is marked as 0.
         // Once signature support is added this way of verifying state commitment will be deprecated.
         #[cfg(feature = "p2p")]
-        anyhow::ensure!(
-            block.state_commitment == StateCommitment::ZERO
-                || state_commitment == block.state_commitment,
-            "State root mismatch"
-        );
+        let roots_match = block.state_commitment == StateCommitment::ZERO
+            || state_commitment == block.state_commitment;
+
+        if !roots_match {
+            return Err(RootMismatch {
+                source: RootSource::Computed,
+                expected: block.state_commitment,
+                actual: state_commitment,
+                block: block.block_number,
+            }
+            .into());
+        }
 
+        let t_insert = Instant::now();
         let transaction_count = block.transactions.len();
         let event_count = block
             .transaction_receipts
@@ -812,20 +1333,105 @@ async fn l2_update(
                 .l1_state_at_number(header.number)
                 .context("Query L1 state")?
             {
-                if l1_state.block_hash == header.hash {
+                if validate_sequencer_block(&header, &l1_state).is_ok() {
                     transaction
                         .update_l1_l2_pointer(Some(header.number))
                         .context("Update L1-L2 head")?;
                 }
             }
         }
+        timings.insert = t_insert.elapsed();
 
-        transaction.commit().context("Commit database transaction")
-    })?;
+        let t_commit = Instant::now();
+        transaction
+            .commit()
+            .context("Commit database transaction")?;
+        timings.commit = t_commit.elapsed();
+
+        Ok((state_commitment, timings))
+    })
+}
+
+/// The latest committed L2 block, as written to the tip file.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Tip {
+    block_number: BlockNumber,
+    state_commitment: StateCommitment,
+    timestamp: BlockTimestamp,
+}
+
+/// Atomically replaces the contents of `path` with `tip`.
+///
+/// The data is first written to a temporary file in the same directory, which is
+/// then renamed over the target. This ensures readers never observe a partial write.
+fn write_tip_file(path: &Path, tip: &Tip) -> anyhow::Result<()> {
+    use std::io::Write;
+
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let mut file =
+        tempfile::NamedTempFile::new_in(directory).context("Creating temporary tip file")?;
+    serde_json::to_writer(&mut file, tip).context("Serializing tip")?;
+    file.flush().context("Flushing temporary tip file")?;
+    file.persist(path).context("Renaming temporary tip file")?;
 
     Ok(())
 }
 
+/// Points the tip file at the new head after a reorg, or removes it if the reorg purged every
+/// block.
+fn rewrite_tip_file_after_reorg(connection: &mut Connection, path: &Path) -> anyhow::Result<()> {
+    let tx = connection
+        .transaction()
+        .context("Creating database transaction")?;
+    let head = tx
+        .block_header(pathfinder_storage::BlockId::Latest)
+        .context("Fetching latest block header")?;
+
+    match head {
+        Some(head) => write_tip_file(
+            path,
+            &Tip {
+                block_number: head.number,
+                state_commitment: head.state_commitment,
+                timestamp: head.timestamp,
+            },
+        ),
+        None => match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(e).context("Removing tip file")
+            }
+            _ => Ok(()),
+        },
+    }
+}
+
+/// Records that this version of pathfinder has synced up to `block_number`, for post-mortems of
+/// crashes which happen after upgrading mid-sync.
+fn checkpoint(
+    connection: &mut Connection,
+    block_number: BlockNumber,
+    state_commitment: StateCommitment,
+) -> anyhow::Result<()> {
+    let transaction = connection
+        .transaction()
+        .context("Create database transaction")?;
+
+    transaction
+        .insert_checkpoint(&pathfinder_storage::Checkpoint {
+            block_number,
+            state_commitment,
+            timestamp: time::OffsetDateTime::now_utc().unix_timestamp() as u64,
+            version: pathfinder_common::consts::VERGEN_GIT_DESCRIBE.to_owned(),
+        })
+        .context("Inserting checkpoint")?;
+
+    transaction.commit().context("Commit database transaction")
+}
+
 async fn l2_reorg(connection: &mut Connection, reorg_tail: BlockNumber) -> anyhow::Result<()> {
     tokio::task::block_in_place(move || {
         let transaction = connection
@@ -879,7 +1485,7 @@ async fn l2_reorg(connection: &mut Connection, reorg_tail: BlockNumber) -> anyho
     })
 }
 
-fn update_starknet_state(
+pub(crate) fn update_starknet_state(
     transaction: &Transaction<'_>,
     state_update: &StateUpdate,
     verify_hashes: bool,
@@ -888,15 +1494,28 @@ fn update_starknet_state(
     // parallel contract state updates
     storage: Storage,
 ) -> anyhow::Result<(StorageCommitment, ClassCommitment)> {
-    use rayon::prelude::*;
+    update_starknet_state_timed(
+        transaction,
+        state_update,
+        verify_hashes,
+        block,
+        storage,
+        &mut BlockTimings::default(),
+    )
+}
 
-    let mut storage_commitment_tree = match block.parent() {
-        Some(parent) => StorageCommitmentTree::load(transaction, parent)
-            .context("Loading storage commitment tree")?,
-        None => StorageCommitmentTree::empty(transaction),
-    }
-    .with_verify_hashes(verify_hashes);
+/// Same as [update_starknet_state], but records the time spent on each trie in `timings`.
+fn update_starknet_state_timed(
+    transaction: &Transaction<'_>,
+    state_update: &StateUpdate,
+    verify_hashes: bool,
+    block: BlockNumber,
+    storage: Storage,
+    timings: &mut BlockTimings,
+) -> anyhow::Result<(StorageCommitment, ClassCommitment)> {
+    use rayon::prelude::*;
 
+    let t_contracts = Instant::now();
     let (send, recv) = std::sync::mpsc::channel();
 
     rayon::scope(|s| {
@@ -932,6 +1551,15 @@ fn update_starknet_state(
     });
 
     let contract_update_results = recv.recv().context("Panic on rayon thread")??;
+    timings.contract_tries += t_contracts.elapsed();
+
+    let t_storage = Instant::now();
+    let mut storage_commitment_tree = match block.parent() {
+        Some(parent) => StorageCommitmentTree::load(transaction, parent)
+            .context("Loading storage commitment tree")?,
+        None => StorageCommitmentTree::empty(transaction),
+    }
+    .with_verify_hashes(verify_hashes);
 
     for contract_update_result in contract_update_results.into_iter() {
         storage_commitment_tree
@@ -945,7 +1573,11 @@ fn update_starknet_state(
             .context("Inserting contract update result")?;
     }
 
+    // System contract tries are updated in between storage commitment tree updates, and are
+    // accounted for separately.
+    let mut system_contract_tries = Duration::ZERO;
     for (contract, update) in &state_update.system_contract_updates {
+        let t_contract = Instant::now();
         let update_result = update_contract_state(
             *contract,
             &update.storage,
@@ -956,6 +1588,7 @@ fn update_starknet_state(
             block,
         )
         .context("Update system contract state")?;
+        system_contract_tries += t_contract.elapsed();
 
         storage_commitment_tree
             .set(*contract, update_result.state_hash)
@@ -971,6 +1604,21 @@ fn update_starknet_state(
         .commit()
         .context("Apply storage commitment tree updates")?;
 
+    // A block without contract updates cannot change the storage commitment. If it does anyway,
+    // the tree is broken and continuing would only persist the corruption.
+    if state_update.contract_updates.is_empty() && state_update.system_contract_updates.is_empty() {
+        let parent_commitment = match block.parent() {
+            Some(parent) => parent_storage_commitment(transaction, parent)
+                .context("Querying parent storage commitment")?,
+            None => StorageCommitment::ZERO,
+        };
+
+        anyhow::ensure!(
+            storage_commitment == parent_commitment,
+            "Storage commitment changed from {parent_commitment} to {storage_commitment} without any contract updates"
+        );
+    }
+
     let root_idx = if !storage_commitment.0.is_zero() {
         let root_idx = transaction
             .insert_storage_trie(storage_commitment, &nodes)
@@ -984,8 +1632,11 @@ fn update_starknet_state(
     transaction
         .insert_storage_root(block, root_idx)
         .context("Inserting storage root index")?;
+    timings.contract_tries += system_contract_tries;
+    timings.storage_trie += t_storage.elapsed().saturating_sub(system_contract_tries);
 
     // Add new Sierra classes to class commitment tree.
+    let t_class = Instant::now();
     let mut class_commitment_tree = match block.parent() {
         Some(parent) => ClassCommitmentTree::load(transaction, parent)
             .context("Loading class commitment tree")?,
@@ -1010,6 +1661,20 @@ fn update_starknet_state(
         .commit()
         .context("Apply class commitment tree updates")?;
 
+    // Likewise for the class commitment, which only changes when Sierra classes are declared.
+    if state_update.declared_sierra_classes.is_empty() {
+        let parent_commitment = match block.parent() {
+            Some(parent) => parent_class_commitment(transaction, parent)
+                .context("Querying parent class commitment")?,
+            None => ClassCommitment::ZERO,
+        };
+
+        anyhow::ensure!(
+            class_commitment == parent_commitment,
+            "Class commitment changed from {parent_commitment} to {class_commitment} without any declared Sierra classes"
+        );
+    }
+
     let class_root_idx = if !class_commitment.0.is_zero() {
         let class_root_idx = transaction
             .insert_class_trie(class_commitment, &nodes)
@@ -1023,24 +1688,58 @@ fn update_starknet_state(
     transaction
         .insert_class_root(block, class_root_idx)
         .context("Inserting class root index")?;
+    timings.class_trie += t_class.elapsed();
 
     Ok((storage_commitment, class_commitment))
 }
 
+/// The storage commitment as of `block`, read from its persisted trie root.
+fn parent_storage_commitment(
+    transaction: &Transaction<'_>,
+    block: BlockNumber,
+) -> anyhow::Result<StorageCommitment> {
+    let Some(index) = transaction.storage_root_index(block)? else {
+        return Ok(StorageCommitment::ZERO);
+    };
+
+    let hash = transaction
+        .storage_trie_node_hash(index)?
+        .context("Storage trie root node is missing")?;
+
+    Ok(StorageCommitment(hash))
+}
+
+/// The class commitment as of `block`, read from its persisted trie root.
+fn parent_class_commitment(
+    transaction: &Transaction<'_>,
+    block: BlockNumber,
+) -> anyhow::Result<ClassCommitment> {
+    let Some(index) = transaction.class_root_index(block)? else {
+        return Ok(ClassCommitment::ZERO);
+    };
+
+    let hash = transaction
+        .class_trie_node_hash(index)?
+        .context("Class trie root node is missing")?;
+
+    Ok(ClassCommitment(hash))
+}
+
 #[cfg(test)]
 mod tests {
     use super::l2;
-    use crate::state::sync::{consumer, ConsumerContext, SyncEvent};
+    use crate::state::sync::{consumer, ConsumerContext, RootMismatch, RootSource, SyncEvent};
     use pathfinder_common::{
         felt_bytes, BlockHash, BlockHeader, BlockNumber, ClassHash, EventCommitment, SierraHash,
         StateCommitment, StateUpdate, TransactionCommitment,
     };
     use pathfinder_common::{macro_prelude::*, BlockCommitmentSignature};
     use pathfinder_rpc::SyncState;
-    use pathfinder_storage::Storage;
+    use pathfinder_storage::{JournalMode, Storage};
     use stark_hash::Felt;
     use starknet_gateway_types::reply;
     use starknet_gateway_types::reply::Block;
+    use std::num::NonZeroU64;
     use std::sync::Arc;
 
     /// Generate some arbitrary block chain data from genesis onwards.
@@ -1134,8 +1833,15 @@ mod tests {
         let context = ConsumerContext {
             storage,
             state: Arc::new(SyncState::default()),
-            pending_data: tx,
+            pending_data: Arc::new(tx),
             verify_tree_hashes: false,
+            tip_file: None,
+            wal_checkpoint_interval: None,
+            sync_checkpoint_interval: None,
+            stop_at_block: None,
+            class_hash_index: None,
+            slow_block_threshold: None,
+            block_publisher: Default::default(),
         };
 
         consumer(event_rx, context).await.unwrap();
@@ -1157,71 +1863,1028 @@ mod tests {
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn reorg() {
+    async fn tip_file_contains_latest_committed_block() {
         let storage = Storage::in_memory().unwrap();
         let mut connection = storage.connection().unwrap();
 
         let (event_tx, event_rx) = tokio::sync::mpsc::channel(100);
 
-        // Send block updates, followed by a reorg to genesis.
         for (a, b, c, d) in generate_block_data() {
             event_tx.send(SyncEvent::Block(a, b, c, d)).await.unwrap();
         }
-        event_tx
-            .send(SyncEvent::Reorg(BlockNumber::new_or_panic(2)))
-            .await
-            .unwrap();
-        // Close the event channel which allows the consumer task to exit.
         drop(event_tx);
 
+        let tip_dir = tempfile::tempdir().unwrap();
+        let tip_file = tip_dir.path().join("tip.json");
+
         let (tx, _rx) = tokio::sync::watch::channel(Default::default());
         let context = ConsumerContext {
             storage,
             state: Arc::new(SyncState::default()),
-            pending_data: tx,
+            pending_data: Arc::new(tx),
             verify_tree_hashes: false,
+            tip_file: Some(tip_file.clone()),
+            wal_checkpoint_interval: None,
+            sync_checkpoint_interval: None,
+            stop_at_block: None,
+            class_hash_index: None,
+            slow_block_threshold: None,
+            block_publisher: Default::default(),
         };
 
         consumer(event_rx, context).await.unwrap();
 
         let tx = connection.transaction().unwrap();
-        let genesis_exists = tx.block_exists(BlockNumber::GENESIS.into()).unwrap();
-        assert!(genesis_exists);
-
-        let block_1_exists = tx
-            .block_exists(BlockNumber::new_or_panic(1).into())
+        let latest = tx
+            .block_header(pathfinder_storage::BlockId::Latest)
+            .unwrap()
             .unwrap();
-        assert!(block_1_exists);
 
-        let block_2_exists = tx
-            .block_exists(BlockNumber::new_or_panic(2).into())
-            .unwrap();
-        assert!(!block_2_exists);
+        let tip = std::fs::read(&tip_file).unwrap();
+        let tip: super::Tip = serde_json::from_slice(&tip).unwrap();
+
+        assert_eq!(tip.block_number, latest.number);
+        assert_eq!(tip.state_commitment, latest.state_commitment);
+        assert_eq!(tip.timestamp, latest.timestamp);
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn blocks_are_not_skipped_after_a_reorg() {
-        // A bug caused reorg'd block numbers to be skipped. This
-        // was due to the expected block number not being updated
-        // when handling the reorg.
+    async fn tip_file_is_rewritten_on_reorg() {
         let storage = Storage::in_memory().unwrap();
         let mut connection = storage.connection().unwrap();
 
         let (event_tx, event_rx) = tokio::sync::mpsc::channel(100);
 
-        // Send block updates, followed by a reorg removing block 2.
-        // Then republish block 2, which should succeed.
-        let blocks = generate_block_data();
-        let block2 = blocks[2].clone();
-        for (a, b, c, d) in blocks {
+        for (a, b, c, d) in generate_block_data() {
             event_tx.send(SyncEvent::Block(a, b, c, d)).await.unwrap();
         }
         event_tx
-            .send(SyncEvent::Reorg(block2.0 .0.block_number))
+            .send(SyncEvent::Reorg(BlockNumber::new_or_panic(1)))
             .await
             .unwrap();
-        // This previously failed as the expected next block number was never
-        // updated after a reorg, causing the reorg'd block numbers to be considered
+        drop(event_tx);
+
+        let tip_dir = tempfile::tempdir().unwrap();
+        let tip_file = tip_dir.path().join("tip.json");
+
+        let (tx, _rx) = tokio::sync::watch::channel(Default::default());
+        let context = ConsumerContext {
+            storage: storage.clone(),
+            state: Arc::new(SyncState::default()),
+            pending_data: Arc::new(tx),
+            verify_tree_hashes: false,
+            tip_file: Some(tip_file.clone()),
+            wal_checkpoint_interval: None,
+            sync_checkpoint_interval: None,
+            stop_at_block: None,
+            class_hash_index: None,
+            slow_block_threshold: None,
+            block_publisher: Default::default(),
+        };
+
+        consumer(event_rx, context.clone()).await.unwrap();
+
+        let tx = connection.transaction().unwrap();
+        let genesis = tx
+            .block_header(BlockNumber::GENESIS.into())
+            .unwrap()
+            .unwrap();
+        drop(tx);
+
+        let tip = std::fs::read(&tip_file).unwrap();
+        let tip: super::Tip = serde_json::from_slice(&tip).unwrap();
+
+        assert_eq!(tip.block_number, BlockNumber::GENESIS);
+        assert_eq!(tip.state_commitment, genesis.state_commitment);
+        assert_eq!(tip.timestamp, genesis.timestamp);
+
+        // Purging every block leaves no tip to report.
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(1);
+        event_tx
+            .send(SyncEvent::Reorg(BlockNumber::GENESIS))
+            .await
+            .unwrap();
+        drop(event_tx);
+
+        consumer(event_rx, context).await.unwrap();
+
+        assert!(!tip_file.exists());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn consumer_stops_at_block() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(100);
+        for (a, b, c, d) in generate_block_data() {
+            event_tx.send(SyncEvent::Block(a, b, c, d)).await.unwrap();
+        }
+        // The event channel is intentionally kept open, so the consumer may only exit because
+        // the stop block was reached.
+
+        let stop_at_block = BlockNumber::new_or_panic(1);
+        let (tx, _rx) = tokio::sync::watch::channel(Default::default());
+        let context = ConsumerContext {
+            storage,
+            state: Arc::new(SyncState::default()),
+            pending_data: Arc::new(tx),
+            verify_tree_hashes: false,
+            tip_file: None,
+            wal_checkpoint_interval: None,
+            sync_checkpoint_interval: None,
+            stop_at_block: Some(stop_at_block),
+            class_hash_index: None,
+            slow_block_threshold: None,
+            block_publisher: Default::default(),
+        };
+
+        consumer(event_rx, context).await.unwrap();
+
+        let tx = connection.transaction().unwrap();
+        let latest = tx.block_id(pathfinder_storage::BlockId::Latest).unwrap();
+        assert_eq!(latest.map(|(number, _)| number), Some(stop_at_block));
+
+        drop(event_tx);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn committed_blocks_are_published() {
+        use super::sink::{tests::MemorySink, Publisher};
+
+        let storage = Storage::in_memory().unwrap();
+
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(100);
+        let block_data = generate_block_data();
+        let expected = block_data
+            .iter()
+            .take(2)
+            .map(|((block, _), _, _, _)| (block.block_number, block.block_hash))
+            .collect::<Vec<_>>();
+        for (a, b, c, d) in block_data.into_iter().take(2) {
+            event_tx.send(SyncEvent::Block(a, b, c, d)).await.unwrap();
+        }
+        drop(event_tx);
+
+        let sink = Arc::new(MemorySink::default());
+        let (tx, _rx) = tokio::sync::watch::channel(Default::default());
+        let context = ConsumerContext {
+            storage,
+            state: Arc::new(SyncState::default()),
+            pending_data: Arc::new(tx),
+            verify_tree_hashes: false,
+            tip_file: None,
+            wal_checkpoint_interval: None,
+            sync_checkpoint_interval: None,
+            stop_at_block: None,
+            class_hash_index: None,
+            slow_block_threshold: None,
+            block_publisher: Publisher::new(sink.clone()),
+        };
+
+        consumer(event_rx, context).await.unwrap();
+
+        let published = sink
+            .wait_for(expected.len())
+            .iter()
+            .map(|block| (block.number, block.hash))
+            .collect::<Vec<_>>();
+        assert_eq!(published, expected);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn block_timings_are_recorded() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let ((block, (tx_comm, ev_comm)), state_update, signature, _) =
+            generate_block_data().into_iter().next().unwrap();
+
+        let start = std::time::Instant::now();
+        let (_, timings) = super::l2_update(
+            &mut connection,
+            *block,
+            tx_comm,
+            ev_comm,
+            *state_update,
+            *signature,
+            false,
+            storage.clone(),
+        )
+        .await
+        .unwrap();
+        let elapsed = start.elapsed();
+
+        assert!(!timings.contract_tries.is_zero());
+        assert!(!timings.storage_trie.is_zero());
+        assert!(!timings.class_trie.is_zero());
+        assert!(!timings.insert.is_zero());
+        assert!(!timings.commit.is_zero());
+
+        // Only opening the transaction and checking the state commitment are not timed, which
+        // is negligible for real blocks but not for these empty ones, so no lower bound is checked.
+        assert!(timings.total() <= elapsed, "{timings:?} vs {elapsed:?}");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn slow_blocks_are_logged() {
+        /// Collects the formatted log output.
+        #[derive(Clone, Default)]
+        struct Logs(Arc<std::sync::Mutex<Vec<u8>>>);
+
+        impl std::io::Write for Logs {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        async fn sync_with_threshold(threshold: std::time::Duration) -> String {
+            let logs = Logs::default();
+            let writer = logs.clone();
+            let subscriber = tracing_subscriber::fmt()
+                .with_writer(move || writer.clone())
+                .with_max_level(tracing::Level::WARN)
+                .with_ansi(false)
+                .finish();
+            let _guard = tracing::subscriber::set_default(subscriber);
+
+            let (event_tx, event_rx) = tokio::sync::mpsc::channel(100);
+            for (a, b, c, d) in generate_block_data() {
+                event_tx.send(SyncEvent::Block(a, b, c, d)).await.unwrap();
+            }
+            drop(event_tx);
+
+            let (tx, _rx) = tokio::sync::watch::channel(Default::default());
+            let context = ConsumerContext {
+                storage: Storage::in_memory().unwrap(),
+                state: Arc::new(SyncState::default()),
+                pending_data: Arc::new(tx),
+                verify_tree_hashes: false,
+                tip_file: None,
+                wal_checkpoint_interval: None,
+                sync_checkpoint_interval: None,
+                stop_at_block: None,
+                class_hash_index: None,
+                slow_block_threshold: Some(threshold),
+                block_publisher: Default::default(),
+            };
+            consumer(event_rx, context).await.unwrap();
+
+            let logs = logs.0.lock().unwrap().clone();
+            String::from_utf8(logs).unwrap()
+        }
+
+        // Every block is slower than a zero threshold.
+        let logs = sync_with_threshold(std::time::Duration::ZERO).await;
+        assert_eq!(logs.matches("Slow block").count(), 3);
+        assert!(logs.contains("block_number=0"));
+        assert!(logs.contains("block_download="));
+
+        let logs = sync_with_threshold(std::time::Duration::from_secs(3600)).await;
+        assert!(!logs.contains("Slow block"), "{logs}");
+    }
+
+    #[test]
+    fn seconds_behind_tip() {
+        use pathfinder_common::BlockTimestamp;
+
+        let timestamp = BlockTimestamp::new_or_panic(1_700_000_000);
+        assert_eq!(super::seconds_behind_tip(timestamp, 1_700_000_090), 90);
+        assert_eq!(super::seconds_behind_tip(timestamp, 1_700_000_000), 0);
+        // A block from the future due to clock skew must not underflow.
+        assert_eq!(super::seconds_behind_tip(timestamp, 1_699_999_000), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn consumer_records_seconds_behind_tip() {
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(100);
+        for (a, b, c, d) in generate_block_data() {
+            event_tx.send(SyncEvent::Block(a, b, c, d)).await.unwrap();
+        }
+        drop(event_tx);
+
+        let state = Arc::new(SyncState::default());
+        let (tx, _rx) = tokio::sync::watch::channel(Default::default());
+        let context = ConsumerContext {
+            storage: Storage::in_memory().unwrap(),
+            state: state.clone(),
+            pending_data: Arc::new(tx),
+            verify_tree_hashes: false,
+            tip_file: None,
+            wal_checkpoint_interval: None,
+            sync_checkpoint_interval: None,
+            stop_at_block: None,
+            class_hash_index: None,
+            slow_block_threshold: None,
+            block_publisher: Default::default(),
+        };
+
+        let before = time::OffsetDateTime::now_utc().unix_timestamp() as u64;
+        consumer(event_rx, context).await.unwrap();
+        let after = time::OffsetDateTime::now_utc().unix_timestamp() as u64;
+
+        // The generated blocks all have a zero timestamp.
+        let lag = state.seconds_behind_tip.read().await.unwrap();
+        assert!((before..=after).contains(&lag), "{lag}");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn consumer_rejects_block_gap() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(100);
+        // Skip block 1.
+        for (a, b, c, d) in generate_block_data().into_iter().step_by(2) {
+            event_tx.send(SyncEvent::Block(a, b, c, d)).await.unwrap();
+        }
+        drop(event_tx);
+
+        let (tx, _rx) = tokio::sync::watch::channel(Default::default());
+        let context = ConsumerContext {
+            storage,
+            state: Arc::new(SyncState::default()),
+            pending_data: Arc::new(tx),
+            verify_tree_hashes: false,
+            tip_file: None,
+            wal_checkpoint_interval: None,
+            sync_checkpoint_interval: None,
+            stop_at_block: None,
+            class_hash_index: None,
+            slow_block_threshold: None,
+            block_publisher: Default::default(),
+        };
+
+        let error = consumer(event_rx, context).await.unwrap_err();
+        assert!(error.to_string().contains("out of order"), "{error:?}");
+
+        // Block 2 must not have been applied.
+        let tx = connection.transaction().unwrap();
+        let latest = tx.block_id(pathfinder_storage::BlockId::Latest).unwrap();
+        assert_eq!(latest.map(|(number, _)| number), Some(BlockNumber::GENESIS));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn computed_root_mismatch() {
+        let storage = Storage::in_memory().unwrap();
+
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(100);
+        let ((mut block, commitments), state_update, signature, timings) =
+            generate_block_data().into_iter().next().unwrap();
+        // The empty state update results in a zero state commitment.
+        block.state_commitment = state_commitment_bytes!(b"wrong root");
+        event_tx
+            .send(SyncEvent::Block(
+                (block, commitments),
+                state_update,
+                signature,
+                timings,
+            ))
+            .await
+            .unwrap();
+        drop(event_tx);
+
+        let (tx, _rx) = tokio::sync::watch::channel(Default::default());
+        let context = ConsumerContext {
+            storage,
+            state: Arc::new(SyncState::default()),
+            pending_data: Arc::new(tx),
+            verify_tree_hashes: false,
+            tip_file: None,
+            wal_checkpoint_interval: None,
+            sync_checkpoint_interval: None,
+            stop_at_block: None,
+            class_hash_index: None,
+            slow_block_threshold: None,
+            block_publisher: Default::default(),
+        };
+
+        let error = consumer(event_rx, context).await.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<RootMismatch>(),
+            Some(&RootMismatch {
+                source: RootSource::Computed,
+                expected: state_commitment_bytes!(b"wrong root"),
+                actual: StateCommitment::ZERO,
+                block: BlockNumber::GENESIS,
+            })
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn computed_root_mismatch_leaves_database_unchanged() {
+        let storage = Storage::in_memory().unwrap();
+
+        let row_counts = |storage: &Storage| {
+            let mut connection = storage.connection().unwrap();
+            let tx = connection.transaction().unwrap();
+            tx.table_statistics()
+                .unwrap()
+                .into_iter()
+                .map(|table| (table.name, table.rows))
+                .collect::<Vec<_>>()
+        };
+        let before = row_counts(&storage);
+
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(100);
+        let ((mut block, commitments), state_update, signature, timings) =
+            generate_block_data().into_iter().next().unwrap();
+        // Storage updates cause contract and storage commitment trie nodes to be written
+        // before the root is checked.
+        let contract = contract_address!("0x123");
+        let state_update = Box::new(
+            state_update
+                .with_deployed_contract(contract, class_hash!("0xabc"))
+                .with_storage_update(contract, storage_address!("0x1"), storage_value!("0x2"))
+                .with_system_storage_update(
+                    pathfinder_common::ContractAddress::ONE,
+                    storage_address!("0x3"),
+                    storage_value!("0x4"),
+                ),
+        );
+        block.state_commitment = state_commitment_bytes!(b"wrong root");
+        event_tx
+            .send(SyncEvent::Block(
+                (block, commitments),
+                state_update,
+                signature,
+                timings,
+            ))
+            .await
+            .unwrap();
+        drop(event_tx);
+
+        let (tx, _rx) = tokio::sync::watch::channel(Default::default());
+        let context = ConsumerContext {
+            storage: storage.clone(),
+            state: Arc::new(SyncState::default()),
+            pending_data: Arc::new(tx),
+            verify_tree_hashes: false,
+            tip_file: None,
+            wal_checkpoint_interval: None,
+            sync_checkpoint_interval: None,
+            stop_at_block: None,
+            class_hash_index: None,
+            slow_block_threshold: None,
+            block_publisher: Default::default(),
+        };
+
+        let error = consumer(event_rx, context).await.unwrap_err();
+        assert!(error.downcast_ref::<RootMismatch>().is_some());
+
+        assert_eq!(row_counts(&storage), before);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn sequencer_parent_root_mismatch() {
+        let storage = Storage::in_memory().unwrap();
+
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(100);
+        let mut blocks = generate_block_data().into_iter();
+        let (a, b, c, d) = blocks.next().unwrap();
+        event_tx.send(SyncEvent::Block(a, b, c, d)).await.unwrap();
+        // Block 1's state update claims to build on a different state than genesis.
+        let (a, state_update, c, d) = blocks.next().unwrap();
+        let state_update = Box::new(
+            state_update.with_parent_state_commitment(state_commitment_bytes!(b"wrong root")),
+        );
+        event_tx
+            .send(SyncEvent::Block(a, state_update, c, d))
+            .await
+            .unwrap();
+        drop(event_tx);
+
+        let (tx, _rx) = tokio::sync::watch::channel(Default::default());
+        let context = ConsumerContext {
+            storage,
+            state: Arc::new(SyncState::default()),
+            pending_data: Arc::new(tx),
+            verify_tree_hashes: false,
+            tip_file: None,
+            wal_checkpoint_interval: None,
+            sync_checkpoint_interval: None,
+            stop_at_block: None,
+            class_hash_index: None,
+            slow_block_threshold: None,
+            block_publisher: Default::default(),
+        };
+
+        let error = consumer(event_rx, context).await.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<RootMismatch>(),
+            Some(&RootMismatch {
+                source: RootSource::SequencerParent,
+                expected: StateCommitment::ZERO,
+                actual: state_commitment_bytes!(b"wrong root"),
+                block: BlockNumber::new_or_panic(1),
+            })
+        );
+    }
+
+    /// A sequencer whose requests never complete.
+    #[derive(Clone)]
+    struct SlowSequencer;
+
+    #[async_trait::async_trait]
+    impl starknet_gateway_client::GatewayApi for SlowSequencer {
+        async fn block(
+            &self,
+            _: pathfinder_common::BlockId,
+        ) -> Result<reply::MaybePendingBlock, starknet_gateway_types::error::SequencerError>
+        {
+            std::future::pending().await
+        }
+
+        async fn head(
+            &self,
+        ) -> Result<(BlockNumber, BlockHash), starknet_gateway_types::error::SequencerError>
+        {
+            std::future::pending().await
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl starknet_gateway_client::GossipApi for SlowSequencer {}
+
+    #[derive(Clone)]
+    struct NoEthereum;
+
+    #[async_trait::async_trait]
+    impl pathfinder_ethereum::EthereumApi for NoEthereum {
+        async fn get_starknet_state(
+            &self,
+            _: &primitive_types::H160,
+        ) -> anyhow::Result<pathfinder_ethereum::EthereumStateUpdate> {
+            std::future::pending().await
+        }
+
+        async fn get_chain(&self) -> anyhow::Result<pathfinder_common::EthereumChain> {
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn shutdown_cancels_network_calls() {
+        use pathfinder_common::{Chain, ChainId};
+
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+
+        let (pending_data, _rx) = tokio::sync::watch::channel(Default::default());
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let (_reload_tx, reload_rx) = tokio::sync::watch::channel(());
+        let context = super::SyncContext {
+            storage,
+            ethereum: NoEthereum,
+            chain: Chain::Testnet,
+            chain_id: ChainId::TESTNET,
+            core_address: Default::default(),
+            sequencer: SlowSequencer,
+            state: Arc::new(SyncState::default()),
+            head_poll_interval: std::time::Duration::from_secs(1),
+            pending_data,
+            pending_poll_interval: None,
+            block_validation_mode: l2::BlockValidationMode::Strict,
+            root_mismatch_policy: Default::default(),
+            missing_class_hash_policy: Default::default(),
+            class_not_found_policy: Default::default(),
+            confirmation_depth: 0,
+            sequencer_public_key: None,
+            verify_transaction_hashes: true,
+            websocket_txs: None,
+            block_cache_size: 100,
+            restart_delay: std::time::Duration::ZERO,
+            verify_tree_hashes: false,
+            tip_file: None,
+            wal_checkpoint_interval: None,
+            sync_checkpoint_interval: None,
+            stop_at_block: None,
+            class_hash_index: None,
+            slow_block_threshold: None,
+            shutdown: shutdown_rx,
+            reload: reload_rx,
+            block_sink: Arc::new(super::sink::NoopSink),
+            l1_to_l2_messages: false,
+        };
+
+        let handle = tokio::spawn(super::sync(
+            context,
+            super::l1::sync,
+            l2::sync::<SlowSequencer>,
+        ));
+
+        // Let the L2 sync task start downloading the genesis block.
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        shutdown_tx.send(true).unwrap();
+
+        tokio::time::timeout(std::time::Duration::from_secs(2), handle)
+            .await
+            .expect("Sync should exit promptly")
+            .unwrap()
+            .unwrap();
+
+        let tx = connection.transaction().unwrap();
+        let latest = tx.block_id(pathfinder_storage::BlockId::Latest).unwrap();
+        assert_eq!(latest, None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reload_resumes_from_truncated_database() {
+        use pathfinder_common::{Chain, ChainId};
+
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+
+        let tx = connection.transaction().unwrap();
+        let mut header = BlockHeader::builder().finalize_with_hash(BlockHash(Felt::from_u64(100)));
+        tx.insert_block_header(&header).unwrap();
+        for i in 1..=3 {
+            header = header
+                .child_builder()
+                .finalize_with_hash(BlockHash(Felt::from_u64(100 + i)));
+            tx.insert_block_header(&header).unwrap();
+        }
+        tx.commit().unwrap();
+
+        let (pending_data, _rx) = tokio::sync::watch::channel(Default::default());
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let (reload_tx, reload_rx) = tokio::sync::watch::channel(());
+        let context = super::SyncContext {
+            storage,
+            ethereum: NoEthereum,
+            chain: Chain::Testnet,
+            chain_id: ChainId::TESTNET,
+            core_address: Default::default(),
+            sequencer: SlowSequencer,
+            state: Arc::new(SyncState::default()),
+            head_poll_interval: std::time::Duration::from_secs(1),
+            pending_data,
+            pending_poll_interval: None,
+            block_validation_mode: l2::BlockValidationMode::Strict,
+            root_mismatch_policy: Default::default(),
+            missing_class_hash_policy: Default::default(),
+            class_not_found_policy: Default::default(),
+            confirmation_depth: 0,
+            sequencer_public_key: None,
+            verify_transaction_hashes: true,
+            websocket_txs: None,
+            block_cache_size: 100,
+            restart_delay: std::time::Duration::ZERO,
+            verify_tree_hashes: false,
+            tip_file: None,
+            wal_checkpoint_interval: None,
+            sync_checkpoint_interval: None,
+            stop_at_block: None,
+            class_hash_index: None,
+            slow_block_threshold: None,
+            shutdown: shutdown_rx,
+            reload: reload_rx,
+            block_sink: Arc::new(super::sink::NoopSink),
+            l1_to_l2_messages: false,
+        };
+
+        // Reports the head each L2 sync task was started from.
+        let (head_tx, mut head_rx) = tokio::sync::mpsc::unbounded_channel();
+        let head_tx: &'static _ = Box::leak(Box::new(head_tx));
+
+        let handle = tokio::spawn(super::sync(
+            context,
+            |_, _| std::future::pending::<anyhow::Result<()>>(),
+            move |_, _, head: Option<(BlockNumber, BlockHash, StateCommitment)>, _| async move {
+                head_tx.send(head.map(|(number, ..)| number)).unwrap();
+                std::future::pending::<anyhow::Result<()>>().await
+            },
+        ));
+
+        let timeout = std::time::Duration::from_secs(2);
+        let head = tokio::time::timeout(timeout, head_rx.recv()).await.unwrap();
+        assert_eq!(head, Some(Some(BlockNumber::new_or_panic(3))));
+
+        let tx = connection.transaction().unwrap();
+        tx.purge_block(BlockNumber::new_or_panic(3)).unwrap();
+        tx.purge_block(BlockNumber::new_or_panic(2)).unwrap();
+        tx.commit().unwrap();
+
+        reload_tx.send(()).unwrap();
+        let head = tokio::time::timeout(timeout, head_rx.recv()).await.unwrap();
+        assert_eq!(head, Some(Some(BlockNumber::new_or_panic(1))));
+
+        shutdown_tx.send(true).unwrap();
+        tokio::time::timeout(timeout, handle)
+            .await
+            .expect("Sync should exit promptly")
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn l1_update_is_persisted_without_sequencer_block() {
+        use pathfinder_ethereum::EthereumStateUpdate;
+
+        let storage = Storage::in_memory().unwrap();
+
+        let update = EthereumStateUpdate {
+            state_root: state_commitment_bytes!(b"l1 root"),
+            block_number: BlockNumber::new_or_panic(10),
+            block_hash: block_hash_bytes!(b"l1 block hash"),
+            ethereum_block_number: Some(1000),
+        };
+
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(1);
+        event_tx
+            .send(SyncEvent::L1Update(update.clone()))
+            .await
+            .unwrap();
+        drop(event_tx);
+
+        // The consumer has no access to the sequencer, and no L2 block is available.
+        let (tx, _rx) = tokio::sync::watch::channel(Default::default());
+        let context = ConsumerContext {
+            storage: storage.clone(),
+            state: Arc::new(SyncState::default()),
+            pending_data: Arc::new(tx),
+            verify_tree_hashes: false,
+            tip_file: None,
+            wal_checkpoint_interval: None,
+            sync_checkpoint_interval: None,
+            stop_at_block: None,
+            class_hash_index: None,
+            slow_block_threshold: None,
+            block_publisher: Default::default(),
+        };
+
+        consumer(event_rx, context).await.unwrap();
+
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        assert_eq!(
+            tx.l1_state_at_number(update.block_number).unwrap(),
+            Some(update.clone())
+        );
+        // Sequencer sourced data is simply absent.
+        assert_eq!(tx.block_header(update.block_number.into()).unwrap(), None);
+        assert_eq!(tx.l1_l2_pointer().unwrap(), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn l1_to_l2_messages_are_persisted() {
+        use pathfinder_common::{EntryPoint, EthereumAddress, L1ToL2MessageNonce};
+        use pathfinder_ethereum::{EthereumOrigin, L1ToL2Message};
+        use primitive_types::{H160, H256};
+
+        let storage = Storage::in_memory().unwrap();
+
+        let message = L1ToL2Message {
+            from_address: EthereumAddress(H160::from_low_u64_be(0xabc)),
+            to_address: contract_address_bytes!(b"recipient"),
+            selector: EntryPoint(felt_bytes!(b"selector")),
+            payload: vec![],
+            nonce: L1ToL2MessageNonce(Felt::from_u64(1)),
+            fee: None,
+            origin: EthereumOrigin {
+                block_number: 1000,
+                block_hash: H256::from_low_u64_be(0xa1),
+                transaction_hash: H256::from_low_u64_be(0xb1),
+                log_index: 3,
+            },
+        };
+
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(1);
+        event_tx
+            .send(SyncEvent::L1ToL2Messages(vec![message.clone()]))
+            .await
+            .unwrap();
+        drop(event_tx);
+
+        let (tx, _rx) = tokio::sync::watch::channel(Default::default());
+        let context = ConsumerContext {
+            storage: storage.clone(),
+            state: Arc::new(SyncState::default()),
+            pending_data: Arc::new(tx),
+            verify_tree_hashes: false,
+            tip_file: None,
+            wal_checkpoint_interval: None,
+            sync_checkpoint_interval: None,
+            stop_at_block: None,
+            class_hash_index: None,
+            slow_block_threshold: None,
+            block_publisher: Default::default(),
+        };
+
+        consumer(event_rx, context).await.unwrap();
+
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+        assert_eq!(
+            tx.l1_to_l2_message(message.message_hash()).unwrap(),
+            Some(message)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn sync_checkpoints_are_recorded_at_interval() {
+        let storage = Storage::in_memory().unwrap();
+
+        let blocks = generate_block_data();
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(100);
+        for (a, b, c, d) in blocks.clone() {
+            event_tx.send(SyncEvent::Block(a, b, c, d)).await.unwrap();
+        }
+        drop(event_tx);
+
+        let (tx, _rx) = tokio::sync::watch::channel(Default::default());
+        let context = ConsumerContext {
+            storage: storage.clone(),
+            state: Arc::new(SyncState::default()),
+            pending_data: Arc::new(tx),
+            verify_tree_hashes: false,
+            tip_file: None,
+            wal_checkpoint_interval: None,
+            sync_checkpoint_interval: NonZeroU64::new(2),
+            stop_at_block: None,
+            class_hash_index: None,
+            slow_block_threshold: None,
+            block_publisher: Default::default(),
+        };
+
+        consumer(event_rx, context).await.unwrap();
+
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+        let checkpoints = tx.checkpoints().unwrap();
+
+        let expected = blocks
+            .iter()
+            .map(|((block, _), _, _, _)| (block.block_number, block.state_commitment))
+            .filter(|(number, _)| number.get() % 2 == 0)
+            .collect::<Vec<_>>();
+        let actual = checkpoints
+            .iter()
+            .map(|checkpoint| (checkpoint.block_number, checkpoint.state_commitment))
+            .collect::<Vec<_>>();
+        assert_eq!(actual, expected);
+        assert_eq!(expected.len(), 2);
+
+        for checkpoint in checkpoints {
+            assert_eq!(
+                checkpoint.version,
+                pathfinder_common::consts::VERGEN_GIT_DESCRIBE
+            );
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn wal_is_checkpointed_after_interval() {
+        let dir = tempfile::tempdir().unwrap();
+        let database_path = dir.path().join("test.sqlite");
+        let storage = Storage::migrate(database_path.clone(), JournalMode::WAL)
+            .unwrap()
+            .create_pool(std::num::NonZeroU32::new(5).unwrap())
+            .unwrap();
+        let wal_path = dir.path().join("test.sqlite-wal");
+
+        let blocks = generate_block_data();
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(100);
+        for (a, b, c, d) in blocks.clone() {
+            event_tx.send(SyncEvent::Block(a, b, c, d)).await.unwrap();
+        }
+        drop(event_tx);
+
+        let (tx, _rx) = tokio::sync::watch::channel(Default::default());
+        let context = ConsumerContext {
+            storage: storage.clone(),
+            state: Arc::new(SyncState::default()),
+            pending_data: Arc::new(tx),
+            verify_tree_hashes: false,
+            tip_file: None,
+            wal_checkpoint_interval: NonZeroU64::new(blocks.len() as u64),
+            sync_checkpoint_interval: None,
+            stop_at_block: None,
+            class_hash_index: None,
+            slow_block_threshold: None,
+            block_publisher: Default::default(),
+        };
+
+        consumer(event_rx, context).await.unwrap();
+
+        // The checkpoint ran after the final block, so the WAL must be empty.
+        let wal_size = std::fs::metadata(&wal_path).unwrap().len();
+        assert_eq!(wal_size, 0);
+
+        // Sanity check: the WAL does grow without checkpointing.
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::migrate(dir.path().join("test.sqlite"), JournalMode::WAL)
+            .unwrap()
+            .create_pool(std::num::NonZeroU32::new(5).unwrap())
+            .unwrap();
+
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(100);
+        for (a, b, c, d) in blocks {
+            event_tx.send(SyncEvent::Block(a, b, c, d)).await.unwrap();
+        }
+        drop(event_tx);
+
+        let (tx, _rx) = tokio::sync::watch::channel(Default::default());
+        let context = ConsumerContext {
+            storage: storage.clone(),
+            state: Arc::new(SyncState::default()),
+            pending_data: Arc::new(tx),
+            verify_tree_hashes: false,
+            tip_file: None,
+            wal_checkpoint_interval: None,
+            sync_checkpoint_interval: None,
+            stop_at_block: None,
+            class_hash_index: None,
+            slow_block_threshold: None,
+            block_publisher: Default::default(),
+        };
+
+        consumer(event_rx, context).await.unwrap();
+
+        let wal_size = std::fs::metadata(dir.path().join("test.sqlite-wal"))
+            .unwrap()
+            .len();
+        assert!(wal_size > 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reorg() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(100);
+
+        // Send block updates, followed by a reorg to genesis.
+        for (a, b, c, d) in generate_block_data() {
+            event_tx.send(SyncEvent::Block(a, b, c, d)).await.unwrap();
+        }
+        event_tx
+            .send(SyncEvent::Reorg(BlockNumber::new_or_panic(2)))
+            .await
+            .unwrap();
+        // Close the event channel which allows the consumer task to exit.
+        drop(event_tx);
+
+        let (tx, _rx) = tokio::sync::watch::channel(Default::default());
+        let context = ConsumerContext {
+            storage,
+            state: Arc::new(SyncState::default()),
+            pending_data: Arc::new(tx),
+            verify_tree_hashes: false,
+            tip_file: None,
+            wal_checkpoint_interval: None,
+            sync_checkpoint_interval: None,
+            stop_at_block: None,
+            class_hash_index: None,
+            slow_block_threshold: None,
+            block_publisher: Default::default(),
+        };
+
+        consumer(event_rx, context).await.unwrap();
+
+        let tx = connection.transaction().unwrap();
+        let genesis_exists = tx.block_exists(BlockNumber::GENESIS.into()).unwrap();
+        assert!(genesis_exists);
+
+        let block_1_exists = tx
+            .block_exists(BlockNumber::new_or_panic(1).into())
+            .unwrap();
+        assert!(block_1_exists);
+
+        let block_2_exists = tx
+            .block_exists(BlockNumber::new_or_panic(2).into())
+            .unwrap();
+        assert!(!block_2_exists);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn blocks_are_not_skipped_after_a_reorg() {
+        // A bug caused reorg'd block numbers to be skipped. This
+        // was due to the expected block number not being updated
+        // when handling the reorg.
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(100);
+
+        // Send block updates, followed by a reorg removing block 2.
+        // Then republish block 2, which should succeed.
+        let blocks = generate_block_data();
+        let block2 = blocks[2].clone();
+        for (a, b, c, d) in blocks {
+            event_tx.send(SyncEvent::Block(a, b, c, d)).await.unwrap();
+        }
+        event_tx
+            .send(SyncEvent::Reorg(block2.0 .0.block_number))
+            .await
+            .unwrap();
+        // This previously failed as the expected next block number was never
+        // updated after a reorg, causing the reorg'd block numbers to be considered
         // duplicates and skipped - breaking sync.
         event_tx
             .send(SyncEvent::Block(block2.0, block2.1, block2.2, block2.3))
@@ -1234,8 +2897,15 @@ mod tests {
         let context = ConsumerContext {
             storage,
             state: Arc::new(SyncState::default()),
-            pending_data: tx,
+            pending_data: Arc::new(tx),
             verify_tree_hashes: false,
+            tip_file: None,
+            wal_checkpoint_interval: None,
+            sync_checkpoint_interval: None,
+            stop_at_block: None,
+            class_hash_index: None,
+            slow_block_threshold: None,
+            block_publisher: Default::default(),
         };
 
         consumer(event_rx, context).await.unwrap();
@@ -1277,8 +2947,15 @@ mod tests {
         let context = ConsumerContext {
             storage,
             state: Arc::new(SyncState::default()),
-            pending_data: tx,
+            pending_data: Arc::new(tx),
             verify_tree_hashes: false,
+            tip_file: None,
+            wal_checkpoint_interval: None,
+            sync_checkpoint_interval: None,
+            stop_at_block: None,
+            class_hash_index: None,
+            slow_block_threshold: None,
+            block_publisher: Default::default(),
         };
 
         consumer(event_rx, context).await.unwrap();
@@ -1312,8 +2989,15 @@ mod tests {
         let context = ConsumerContext {
             storage,
             state: Arc::new(SyncState::default()),
-            pending_data: tx,
+            pending_data: Arc::new(tx),
             verify_tree_hashes: false,
+            tip_file: None,
+            wal_checkpoint_interval: None,
+            sync_checkpoint_interval: None,
+            stop_at_block: None,
+            class_hash_index: None,
+            slow_block_threshold: None,
+            block_publisher: Default::default(),
         };
 
         consumer(event_rx, context).await.unwrap();
@@ -1350,8 +3034,15 @@ mod tests {
         let context = ConsumerContext {
             storage,
             state: Arc::new(SyncState::default()),
-            pending_data: tx,
+            pending_data: Arc::new(tx),
             verify_tree_hashes: false,
+            tip_file: None,
+            wal_checkpoint_interval: None,
+            sync_checkpoint_interval: None,
+            stop_at_block: None,
+            class_hash_index: None,
+            slow_block_threshold: None,
+            block_publisher: Default::default(),
         };
 
         consumer(event_rx, context).await.unwrap();
@@ -1385,10 +3076,166 @@ mod tests {
         let context = ConsumerContext {
             storage,
             state: Arc::new(SyncState::default()),
-            pending_data: tx,
+            pending_data: Arc::new(tx),
             verify_tree_hashes: false,
+            tip_file: None,
+            wal_checkpoint_interval: None,
+            sync_checkpoint_interval: None,
+            stop_at_block: None,
+            class_hash_index: None,
+            slow_block_threshold: None,
+            block_publisher: Default::default(),
         };
 
         consumer(event_rx, context).await.unwrap();
     }
+
+    mod validate_sequencer_block {
+        use super::super::{validate_sequencer_block, L1Mismatch};
+        use super::*;
+        use pathfinder_ethereum::EthereumStateUpdate;
+
+        fn block_and_update_log() -> (BlockHeader, EthereumStateUpdate) {
+            let block = BlockHeader::builder()
+                .with_number(BlockNumber::new_or_panic(5))
+                .with_state_commitment(state_commitment_bytes!(b"root"))
+                .finalize_with_hash(block_hash_bytes!(b"hash"));
+
+            let update_log = EthereumStateUpdate {
+                state_root: block.state_commitment,
+                block_number: block.number,
+                block_hash: block.hash,
+                ethereum_block_number: None,
+            };
+
+            (block, update_log)
+        }
+
+        #[test]
+        fn matching() {
+            let (block, update_log) = block_and_update_log();
+
+            assert_eq!(validate_sequencer_block(&block, &update_log), Ok(()));
+        }
+
+        #[test]
+        fn block_number_mismatch() {
+            let (block, mut update_log) = block_and_update_log();
+            update_log.block_number = BlockNumber::new_or_panic(6);
+
+            assert_eq!(
+                validate_sequencer_block(&block, &update_log),
+                Err(L1Mismatch::BlockNumber {
+                    l1: update_log.block_number,
+                    l2: block.number,
+                })
+            );
+        }
+
+        #[test]
+        fn block_hash_mismatch() {
+            let (block, mut update_log) = block_and_update_log();
+            update_log.block_hash = block_hash_bytes!(b"other hash");
+
+            assert_eq!(
+                validate_sequencer_block(&block, &update_log),
+                Err(L1Mismatch::BlockHash {
+                    l1: update_log.block_hash,
+                    l2: block.hash,
+                })
+            );
+        }
+
+        #[test]
+        fn state_root_mismatch() {
+            let (block, mut update_log) = block_and_update_log();
+            update_log.state_root = state_commitment_bytes!(b"other root");
+
+            assert_eq!(
+                validate_sequencer_block(&block, &update_log),
+                Err(L1Mismatch::StateRoot {
+                    l1: update_log.state_root,
+                    l2: block.state_commitment,
+                })
+            );
+        }
+    }
+
+    mod commitments {
+        use super::super::update_starknet_state;
+        use super::*;
+
+        #[test]
+        fn commitments_only_change_with_state_changes() {
+            let storage = Storage::in_memory().unwrap();
+            let mut connection = storage.connection().unwrap();
+
+            let state_updates = [
+                StateUpdate::default().with_storage_update(
+                    contract_address_bytes!(b"contract"),
+                    storage_address_bytes!(b"key"),
+                    storage_value_bytes!(b"value 0"),
+                ),
+                StateUpdate::default(),
+                StateUpdate::default().with_storage_update(
+                    contract_address_bytes!(b"contract"),
+                    storage_address_bytes!(b"key"),
+                    storage_value_bytes!(b"value 1"),
+                ),
+            ];
+
+            let commitments = state_updates
+                .iter()
+                .enumerate()
+                .map(|(i, state_update)| {
+                    let tx = connection.transaction().unwrap();
+                    let commitments = update_starknet_state(
+                        &tx,
+                        state_update,
+                        false,
+                        BlockNumber::new_or_panic(i as u64),
+                        storage.clone(),
+                    )
+                    .unwrap();
+                    tx.commit().unwrap();
+                    commitments
+                })
+                .collect::<Vec<_>>();
+
+            // The empty block keeps its parent's commitments.
+            assert_eq!(commitments[1], commitments[0]);
+            // Whereas the storage update changes the storage commitment.
+            assert_ne!(commitments[2].0, commitments[1].0);
+            assert_eq!(commitments[2].1, commitments[1].1);
+        }
+
+        #[test]
+        fn state_diff_reproduces_sequencer_root() {
+            use pathfinder_common::ClassCommitment;
+            use starknet_gateway_test_fixtures::v0_11_0::state_update::GENESIS;
+
+            let state_update: reply::StateUpdate = serde_json::from_str(GENESIS).unwrap();
+            let expected = state_update.new_root;
+            let state_update = StateUpdate::from(state_update);
+
+            let storage = Storage::in_memory().unwrap();
+            let mut connection = storage.connection().unwrap();
+            let tx = connection.transaction().unwrap();
+
+            let (storage_commitment, class_commitment) = update_starknet_state(
+                &tx,
+                &state_update,
+                true,
+                BlockNumber::GENESIS,
+                storage.clone(),
+            )
+            .unwrap();
+
+            assert_eq!(class_commitment, ClassCommitment::ZERO);
+            assert_eq!(
+                StateCommitment::calculate(storage_commitment, class_commitment),
+                expected
+            );
+        }
+    }
 }
@@ -1,3 +1,4 @@
+use std::sync::{Arc, Mutex};
 use std::{num::NonZeroU64, time::Duration};
 
 use pathfinder_common::Chain;
@@ -8,6 +9,10 @@ use tokio::sync::mpsc;
 
 use crate::state::sync::SyncEvent;
 
+/// The maximum number of Ethereum blocks whose `LogMessageToL2` events are fetched at once, which
+/// keeps catching up within the limits Ethereum providers place on log queries.
+const MESSAGE_LOG_CHUNK_SIZE: u64 = 1000;
+
 #[derive(Clone)]
 pub struct L1SyncContext<EthereumClient> {
     pub ethereum: EthereumClient,
@@ -15,10 +20,23 @@ pub struct L1SyncContext<EthereumClient> {
     /// The Starknet core contract address on Ethereum
     pub core_address: H160,
     pub poll_interval: Duration,
+    /// If set, messages sent from Ethereum to Starknet are synced as well.
+    pub l1_to_l2_messages: Option<MessageCursor>,
 }
 
+/// The last Ethereum block whose messages to Starknet have been synced.
+///
+/// Shared by restarts of L1 sync, so that no messages are skipped while it restarts. Messages are
+/// synced from the Ethereum block which is finalized when L1 sync first runs, as older ones are
+/// not needed to follow the chain.
+#[derive(Clone, Default)]
+pub struct MessageCursor(Arc<Mutex<Option<u64>>>);
+
 /// Syncs L1 state update logs. Emits [Ethereum state update](EthereumStateUpdate)
 /// which should be handled to update storage and respond to queries.
+///
+/// If enabled, also emits the messages sent from Ethereum to Starknet, up to the finalized
+/// Ethereum block.
 pub async fn sync<T>(
     tx_event: mpsc::Sender<SyncEvent>,
     context: L1SyncContext<T>,
@@ -31,6 +49,7 @@ where
         chain: _,
         core_address,
         poll_interval,
+        l1_to_l2_messages,
     } = context;
 
     let mut previous = EthereumStateUpdate::default();
@@ -45,7 +64,25 @@ where
         .when(|_| true)
         .await?;
 
-        if previous != state_update {
+        if let (Some(cursor), Some(finalized)) =
+            (&l1_to_l2_messages, state_update.ethereum_block_number)
+        {
+            // Messages are retried with the next poll, so failing to fetch them should not hold
+            // up state updates.
+            if let Err(e) =
+                sync_messages(&ethereum, &core_address, cursor, finalized, &tx_event).await
+            {
+                tracing::warn!(error=?e, "Failed to sync L1 to L2 messages");
+            }
+        }
+
+        // The finalized Ethereum block advances with every poll, so it is ignored when checking
+        // for a new Starknet state. This keeps the Ethereum block at which a state was first seen.
+        let current = EthereumStateUpdate {
+            ethereum_block_number: previous.ethereum_block_number,
+            ..state_update.clone()
+        };
+        if previous != current {
             previous = state_update.clone();
             tx_event.send(SyncEvent::L1Update(state_update)).await?;
         }
@@ -53,3 +90,172 @@ where
         tokio::time::sleep(poll_interval).await;
     }
 }
+
+/// Emits the messages sent to Starknet in the Ethereum blocks after `cursor`, up to and including
+/// `finalized`.
+async fn sync_messages<T: EthereumApi>(
+    ethereum: &T,
+    core_address: &H160,
+    cursor: &MessageCursor,
+    finalized: u64,
+    tx_event: &mpsc::Sender<SyncEvent>,
+) -> anyhow::Result<()> {
+    let synced = *cursor.0.lock().unwrap();
+    let mut from = synced.map_or(finalized, |synced| synced + 1);
+
+    while from <= finalized {
+        let to = finalized.min(from + MESSAGE_LOG_CHUNK_SIZE - 1);
+        let messages = ethereum
+            .get_message_to_l2_logs(core_address, from, to)
+            .await?;
+        if !messages.is_empty() {
+            tx_event.send(SyncEvent::L1ToL2Messages(messages)).await?;
+        }
+
+        *cursor.0.lock().unwrap() = Some(to);
+        from = to + 1;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::{
+        BlockNumber, EntryPoint, EthereumAddress, EthereumChain, L1ToL2MessageNonce,
+    };
+    use pathfinder_ethereum::{EthereumOrigin, L1ToL2Message};
+    use primitive_types::H256;
+    use stark_hash::Felt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Fails the first call as if the Ethereum node was restarting.
+    #[derive(Clone, Default)]
+    struct RestartingEthereum {
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl EthereumApi for RestartingEthereum {
+        async fn get_starknet_state(&self, _: &H160) -> anyhow::Result<EthereumStateUpdate> {
+            match self.calls.fetch_add(1, Ordering::Relaxed) {
+                0 => anyhow::bail!("Connection refused"),
+                _ => Ok(EthereumStateUpdate {
+                    state_root: state_commitment_bytes!(b"root"),
+                    block_number: BlockNumber::new_or_panic(10),
+                    block_hash: block_hash_bytes!(b"hash"),
+                    ethereum_block_number: Some(100),
+                }),
+            }
+        }
+
+        async fn get_chain(&self) -> anyhow::Result<EthereumChain> {
+            unimplemented!()
+        }
+
+        async fn get_message_to_l2_logs(
+            &self,
+            _: &H160,
+            _: u64,
+            _: u64,
+        ) -> anyhow::Result<Vec<L1ToL2Message>> {
+            unimplemented!()
+        }
+    }
+
+    /// Sends a single message in the first Ethereum block of every range queried, and records the
+    /// ranges.
+    #[derive(Clone, Default)]
+    struct MessagingEthereum {
+        ranges: Arc<Mutex<Vec<(u64, u64)>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl EthereumApi for MessagingEthereum {
+        async fn get_starknet_state(&self, _: &H160) -> anyhow::Result<EthereumStateUpdate> {
+            unimplemented!()
+        }
+
+        async fn get_chain(&self) -> anyhow::Result<EthereumChain> {
+            unimplemented!()
+        }
+
+        async fn get_message_to_l2_logs(
+            &self,
+            _: &H160,
+            from: u64,
+            to: u64,
+        ) -> anyhow::Result<Vec<L1ToL2Message>> {
+            self.ranges.lock().unwrap().push((from, to));
+            Ok(vec![L1ToL2Message {
+                from_address: EthereumAddress(H160::zero()),
+                to_address: contract_address!("0x1234"),
+                selector: EntryPoint(Felt::from_u64(0x5678)),
+                payload: vec![],
+                nonce: L1ToL2MessageNonce(Felt::from_u64(from)),
+                fee: None,
+                origin: EthereumOrigin {
+                    block_number: from,
+                    block_hash: H256::zero(),
+                    transaction_hash: H256::zero(),
+                    log_index: 0,
+                },
+            }])
+        }
+    }
+
+    #[tokio::test]
+    async fn messages_are_synced_from_the_cursor_in_chunks() {
+        let (tx_event, mut rx_event) = mpsc::channel(10);
+        let ethereum = MessagingEthereum::default();
+        let cursor = MessageCursor::default();
+
+        // Starts from the finalized block, and then only fetches newly finalized blocks.
+        for finalized in [100, 100, 2100] {
+            sync_messages(&ethereum, &H160::zero(), &cursor, finalized, &tx_event)
+                .await
+                .unwrap();
+        }
+        drop(tx_event);
+
+        let ranges = ethereum.ranges.lock().unwrap().clone();
+        assert_eq!(ranges, vec![(100, 100), (101, 1100), (1101, 2100)]);
+        assert_eq!(*cursor.0.lock().unwrap(), Some(2100));
+
+        let mut blocks = Vec::new();
+        while let Some(event) = rx_event.recv().await {
+            let SyncEvent::L1ToL2Messages(messages) = event else {
+                panic!("Expected messages");
+            };
+            blocks.extend(messages.iter().map(|message| message.origin.block_number));
+        }
+        assert_eq!(blocks, vec![100, 101, 1101]);
+    }
+
+    #[tokio::test]
+    async fn survives_connection_failure() {
+        let (tx_event, mut rx_event) = mpsc::channel(1);
+        let ethereum = RestartingEthereum::default();
+        let context = L1SyncContext {
+            ethereum: ethereum.clone(),
+            chain: Chain::Testnet,
+            core_address: H160::zero(),
+            poll_interval: Duration::from_millis(10),
+            l1_to_l2_messages: None,
+        };
+
+        let _jh = tokio::spawn(sync(tx_event, context));
+
+        let event = tokio::time::timeout(Duration::from_secs(5), rx_event.recv())
+            .await
+            .expect("L1 sync should recover from the failure")
+            .unwrap();
+        assert!(matches!(
+            event,
+            SyncEvent::L1Update(update) if update.block_number == BlockNumber::new_or_panic(10)
+        ));
+        assert!(ethereum.calls.load(Ordering::Relaxed) >= 2);
+    }
+}
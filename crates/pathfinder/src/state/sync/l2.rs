@@ -1,14 +1,15 @@
 use crate::state::block_hash::{verify_block_hash, VerifyResult};
 use crate::state::sync::class::{download_class, DownloadedClass};
-use crate::state::sync::{pending, SyncEvent};
+use crate::state::sync::{pending, RootMismatch, RootSource, SyncEvent};
 use anyhow::{anyhow, Context};
 use pathfinder_common::state_update::ContractClassUpdate;
 use pathfinder_common::{
-    BlockHash, BlockNumber, Chain, ChainId, ClassHash, EventCommitment, StarknetVersion,
-    StateCommitment, StateUpdate, TransactionCommitment,
+    BlockHash, BlockId, BlockNumber, Chain, ChainId, ClassHash, ContractAddress, EventCommitment,
+    StarknetVersion, StateCommitment, StateUpdate, TransactionCommitment,
 };
 use pathfinder_rpc::{BlockHeader, TopicBroadcasters};
 use pathfinder_storage::Storage;
+use stark_hash::Felt;
 use starknet_gateway_client::GatewayApi;
 use starknet_gateway_types::{
     error::SequencerError,
@@ -90,9 +91,66 @@ pub struct L2SyncContext<GatewayClient> {
     pub head_poll_interval: Duration,
     pub pending_poll_interval: Option<Duration>,
     pub block_validation_mode: BlockValidationMode,
+    pub root_mismatch_policy: RootMismatchPolicy,
+    pub missing_class_hash_policy: MissingClassHashPolicy,
+    pub class_not_found_policy: ClassNotFoundPolicy,
+    /// Blocks are only downloaded once the sequencer's latest block is at least this many
+    /// blocks ahead of them.
+    pub confirmation_depth: u64,
     pub storage: Storage,
+    /// If set, block signatures are verified against this sequencer public key.
+    pub sequencer_public_key: Option<Felt>,
+    /// Whether transaction hashes are recomputed and checked against the sequencer's.
+    pub verify_transaction_hashes: bool,
 }
 
+/// How L2 sync reacts when the sequencer's state update and block disagree on the state
+/// commitment.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RootMismatchPolicy {
+    /// Fail with a [RootMismatch] error.
+    #[default]
+    Abort,
+    /// Download the block and its state update again after an exponentially increasing delay,
+    /// since the sequencer may briefly serve inconsistent data.
+    RetryWithBackoff,
+}
+
+/// How L2 sync reacts to a state update which touches a contract whose class hash is unknown,
+/// i.e. the contract has neither been deployed in this state update nor in the database.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MissingClassHashPolicy {
+    /// Leave the state update as is, which fails once the state update is applied.
+    #[default]
+    Fail,
+    /// Ask the sequencer for the contract's class hash at the block, and treat the contract as
+    /// deployed with that class.
+    FetchFromSequencer,
+}
+
+/// How L2 sync reacts when the sequencer does not know a class which a state update declares or
+/// deploys. This can happen briefly while the sequencer is still catching up.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ClassNotFoundPolicy {
+    /// Fail, treating the class as permanently unavailable.
+    #[default]
+    Fail,
+    /// Download the class again after an exponentially increasing delay, until a timeout of a
+    /// few minutes expires.
+    RetryWithBackoff,
+}
+
+/// The first delay used by [RootMismatchPolicy::RetryWithBackoff].
+const ROOT_MISMATCH_DELAY: Duration = Duration::from_secs(1);
+/// The maximum delay used by [RootMismatchPolicy::RetryWithBackoff].
+const MAX_ROOT_MISMATCH_DELAY: Duration = Duration::from_secs(60);
+/// The first delay used by [ClassNotFoundPolicy::RetryWithBackoff].
+const CLASS_NOT_FOUND_DELAY: Duration = Duration::from_secs(1);
+/// The maximum delay used by [ClassNotFoundPolicy::RetryWithBackoff].
+const MAX_CLASS_NOT_FOUND_DELAY: Duration = Duration::from_secs(30);
+/// How long [ClassNotFoundPolicy::RetryWithBackoff] keeps retrying before giving up.
+const CLASS_NOT_FOUND_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
 pub async fn sync<GatewayClient>(
     tx_event: mpsc::Sender<SyncEvent>,
     context: L2SyncContext<GatewayClient>,
@@ -110,9 +168,20 @@ where
         head_poll_interval,
         pending_poll_interval,
         block_validation_mode,
+        root_mismatch_policy,
+        missing_class_hash_policy,
+        class_not_found_policy,
+        confirmation_depth,
         storage,
+        sequencer_public_key,
+        verify_transaction_hashes,
     } = context;
 
+    let mut root_mismatch_delay = ROOT_MISMATCH_DELAY;
+    let mut uncommitted_classes = UncommittedClasses::default();
+    // The sequencer's latest block as of the last confirmation check.
+    let mut sequencer_head = None;
+
     'outer: loop {
         // Get the next block from L2.
         let (next, head_meta) = match &head {
@@ -126,6 +195,12 @@ where
         let mut next_state_update = None;
 
         let (block, commitments) = loop {
+            if !is_confirmed(next, confirmation_depth, &mut sequencer_head, &sequencer).await? {
+                tracing::trace!(block=%next, depth=%confirmation_depth, "Waiting for confirmations");
+                tokio::time::sleep(head_poll_interval).await;
+                continue;
+            }
+
             match download_block(
                 next,
                 // Reuse the next full block if we got it for free when polling pending
@@ -135,6 +210,7 @@ where
                 head_meta.map(|h| h.1),
                 &sequencer,
                 block_validation_mode,
+                verify_transaction_hashes,
             )
             .await?
             {
@@ -171,6 +247,7 @@ where
                             &tx_event,
                             &sequencer,
                             block_validation_mode,
+                            verify_transaction_hashes,
                             &blocks,
                         )
                         .await
@@ -184,6 +261,8 @@ where
                         }
                         None => blocks.reset_to_genesis(),
                     }
+                    // The fork may be shorter than the chain we last saw.
+                    sequencer_head = None;
 
                     continue 'outer;
                 }
@@ -200,6 +279,7 @@ where
                     &tx_event,
                     &sequencer,
                     block_validation_mode,
+                    verify_transaction_hashes,
                     &blocks,
                 )
                 .await
@@ -218,7 +298,7 @@ where
         let block_hash = block.block_hash;
         let t_update = std::time::Instant::now();
 
-        let state_update = match next_state_update {
+        let mut state_update = match next_state_update {
             // Reuse the next full state update if we got it for free when polling pending
             Some(state_update) if state_update.block_hash == block_hash => state_update,
             // We were unlucky or poll pending is disabled
@@ -240,6 +320,43 @@ where
             block_hash.0,
             state_update.block_hash.0
         );
+
+        // In p2p the state commitment can be missing, which is marked as 0.
+        if state_update.state_commitment != block.state_commitment
+            && !(cfg!(feature = "p2p") && block.state_commitment == StateCommitment::ZERO)
+        {
+            let mismatch = RootMismatch {
+                source: RootSource::SequencerState,
+                expected: block.state_commitment,
+                actual: state_update.state_commitment,
+                block: block.block_number,
+            };
+
+            match root_mismatch_policy {
+                RootMismatchPolicy::Abort => return Err(mismatch.into()),
+                RootMismatchPolicy::RetryWithBackoff => {
+                    tracing::warn!(%mismatch, delay=?root_mismatch_delay, "Retrying block");
+                    tokio::time::sleep(root_mismatch_delay).await;
+                    root_mismatch_delay = (root_mismatch_delay * 2).min(MAX_ROOT_MISMATCH_DELAY);
+                    continue 'outer;
+                }
+            }
+        }
+        root_mismatch_delay = ROOT_MISMATCH_DELAY;
+
+        if missing_class_hash_policy == MissingClassHashPolicy::FetchFromSequencer {
+            fetch_missing_class_hashes(
+                next,
+                block.parent_block_hash,
+                &mut state_update,
+                &mut uncommitted_classes,
+                &sequencer,
+                storage.clone(),
+            )
+            .await
+            .with_context(|| format!("Fetching missing class hashes for block {next:?}"))?;
+            uncommitted_classes.insert(next, &state_update);
+        }
         let t_update = t_update.elapsed();
 
         // Download and emit newly declared classes.
@@ -250,6 +367,7 @@ where
             &tx_event,
             &block.starknet_version,
             storage.clone(),
+            class_not_found_policy,
         )
         .await
         .with_context(|| format!("Handling newly declared classes for block {next:?}"))?;
@@ -269,6 +387,11 @@ where
             signature.signature_input.block_hash.0,
             block_hash.0,
         );
+        if let Some(public_key) = sequencer_public_key {
+            signature
+                .verify(public_key)
+                .with_context(|| format!("Verifying signature for block {next:?}"))?;
+        }
         let signature = signature.into();
 
         head = Some((next, block_hash, state_update.state_commitment));
@@ -302,6 +425,134 @@ where
     }
 }
 
+/// Contracts whose class was set by blocks which have already been emitted, but which may not
+/// have been committed to the database yet.
+#[derive(Debug, Default)]
+struct UncommittedClasses(HashMap<ContractAddress, BlockNumber>);
+
+impl UncommittedClasses {
+    /// Records the contracts deployed or replaced by `block`.
+    fn insert(&mut self, block: BlockNumber, state_update: &StateUpdate) {
+        for (address, update) in &state_update.contract_updates {
+            if update.class.is_some() {
+                self.0.insert(*address, block);
+            }
+        }
+    }
+
+    /// Forgets contracts set by blocks which are either already committed, or were emitted at or
+    /// after `next` and have therefore been reorged away.
+    fn prune(&mut self, committed: Option<BlockNumber>, next: BlockNumber) {
+        self.0
+            .retain(|_, block| *block < next && committed.map_or(true, |c| *block > c));
+    }
+
+    fn contains(&self, address: &ContractAddress) -> bool {
+        self.0.contains_key(address)
+    }
+}
+
+/// Marks contracts in the state update, whose class hash is neither part of the state update,
+/// nor known to the database or an uncommitted earlier block, as deployed with the class hash the
+/// sequencer reports for them at this block.
+///
+/// A contract is only marked as deployed if the sequencer confirms that it did not exist at the
+/// parent block. Otherwise the state update is left as is.
+async fn fetch_missing_class_hashes(
+    next: BlockNumber,
+    parent_hash: BlockHash,
+    state_update: &mut StateUpdate,
+    uncommitted: &mut UncommittedClasses,
+    sequencer: &impl GatewayApi,
+    storage: Storage,
+) -> anyhow::Result<()> {
+    use starknet_gateway_types::error::KnownStarknetErrorCode;
+
+    uncommitted.prune(None, next);
+
+    let candidates = state_update
+        .contract_updates
+        .iter()
+        .filter(|(address, update)| {
+            update.class.is_none()
+                && **address != ContractAddress::ONE
+                && !uncommitted.contains(address)
+        })
+        .map(|(address, _)| *address)
+        .collect::<Vec<_>>();
+
+    if candidates.is_empty() {
+        return Ok(());
+    }
+
+    let (committed, missing) = tokio::task::spawn_blocking(move || {
+        let mut db_conn = storage
+            .connection()
+            .context("Creating database connection")?;
+        let tx = db_conn
+            .transaction()
+            .context("Creating database transaction")?;
+
+        // Read in the same transaction so that the class hashes match the committed head.
+        let committed = tx
+            .block_id(BlockId::Latest)
+            .context("Querying latest block")?
+            .map(|(number, _)| number);
+
+        let class_hashes = tx
+            .contract_class_hashes(BlockId::Latest, &candidates)
+            .context("Querying contract class hashes")?;
+
+        let missing = candidates
+            .into_iter()
+            .zip(class_hashes.into_iter())
+            .filter_map(|(address, class_hash)| class_hash.is_none().then_some(address))
+            .collect::<Vec<_>>();
+
+        anyhow::Ok((committed, missing))
+    })
+    .await
+    .context("Joining database task")?
+    .context("Querying database for missing class hashes")?;
+
+    uncommitted.prune(committed, next);
+
+    for address in missing {
+        // The genesis block has no parent at which the contract could have existed.
+        if next != BlockNumber::GENESIS {
+            match sequencer.class_hash_at(address, parent_hash.into()).await {
+                Ok(class_hash) => {
+                    tracing::warn!(contract=%address.0, class=%class_hash.0, block=%parent_hash.0, "Contract's class hash is missing, but the contract exists at the parent block");
+                    continue;
+                }
+                Err(SequencerError::StarknetError(e))
+                    if e.code == KnownStarknetErrorCode::UninitializedContract.into() => {}
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!(
+                            "Fetching class hash of contract {} at parent block",
+                            address.0
+                        )
+                    })
+                }
+            }
+        }
+
+        let class_hash = sequencer
+            .class_hash_at(address, state_update.block_hash.into())
+            .await
+            .with_context(|| format!("Fetching class hash of contract {}", address.0))?;
+
+        tracing::warn!(contract=%address.0, class=%class_hash.0, block=%state_update.block_hash.0, "Contract's class hash is missing, using the sequencer's");
+
+        if let Some(update) = state_update.contract_updates.get_mut(&address) {
+            update.class = Some(ContractClassUpdate::Deploy(class_hash));
+        }
+    }
+
+    Ok(())
+}
+
 /// Download and emit new contract classes.
 ///
 /// New classes can come from:
@@ -321,6 +572,7 @@ pub async fn download_new_classes(
     tx_event: &mpsc::Sender<SyncEvent>,
     version: &StarknetVersion,
     storage: Storage,
+    class_not_found_policy: ClassNotFoundPolicy,
 ) -> Result<(), anyhow::Error> {
     let deployed_classes = state_update
         .contract_updates
@@ -372,9 +624,10 @@ pub async fn download_new_classes(
     .context("Querying database for missing classes")?;
 
     for class_hash in require_downloading {
-        let class = download_class(sequencer, class_hash, version.clone())
-            .await
-            .with_context(|| format!("Downloading class {}", class_hash.0))?;
+        let class =
+            download_class_with_policy(sequencer, class_hash, version, class_not_found_policy)
+                .await
+                .with_context(|| format!("Downloading class {}", class_hash.0))?;
 
         match class {
             DownloadedClass::Cairo { definition, hash } => tx_event
@@ -423,6 +676,76 @@ pub async fn download_new_classes(
     Ok(())
 }
 
+/// Downloads a class, retrying according to `policy` while the sequencer does not know it.
+async fn download_class_with_policy(
+    sequencer: &impl GatewayApi,
+    class_hash: ClassHash,
+    version: &StarknetVersion,
+    policy: ClassNotFoundPolicy,
+) -> anyhow::Result<DownloadedClass> {
+    let deadline = tokio::time::Instant::now() + CLASS_NOT_FOUND_TIMEOUT;
+    let mut delay = CLASS_NOT_FOUND_DELAY;
+
+    loop {
+        match download_class(sequencer, class_hash, version.clone()).await {
+            Err(e)
+                if policy == ClassNotFoundPolicy::RetryWithBackoff
+                    && is_class_not_found(&e)
+                    && tokio::time::Instant::now() + delay <= deadline =>
+            {
+                tracing::warn!(%class_hash, ?delay, "Class not found by the sequencer, retrying");
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(MAX_CLASS_NOT_FOUND_DELAY);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Whether the sequencer failed to download a class because it does not know the class.
+fn is_class_not_found(error: &anyhow::Error) -> bool {
+    use starknet_gateway_types::error::KnownStarknetErrorCode;
+
+    match error.downcast_ref::<SequencerError>() {
+        Some(SequencerError::StarknetError(e)) => {
+            e.code == KnownStarknetErrorCode::UndeclaredClass.into()
+        }
+        Some(SequencerError::ReqwestError(e)) => e.status() == Some(reqwest::StatusCode::NOT_FOUND),
+        _ => false,
+    }
+}
+
+/// Whether `block` is at least `depth` blocks behind the sequencer's latest block.
+///
+/// The sequencer's view of recent blocks can still change, so syncing can be held back until
+/// they are buried deep enough.
+/// Checks whether `block` is at least `depth` blocks behind the sequencer's latest block.
+///
+/// The sequencer's latest block is cached in `sequencer_head`, and only fetched again once
+/// `block` is no longer confirmed by the cached value.
+async fn is_confirmed(
+    block: BlockNumber,
+    depth: u64,
+    sequencer_head: &mut Option<BlockNumber>,
+    sequencer: &impl GatewayApi,
+) -> anyhow::Result<bool> {
+    if depth == 0 {
+        return Ok(true);
+    }
+
+    if sequencer_head.is_some_and(|latest| block + depth <= latest) {
+        return Ok(true);
+    }
+
+    let (latest, _) = sequencer
+        .block_header(BlockId::Latest)
+        .await
+        .context("Fetching latest block header")?;
+    *sequencer_head = Some(latest);
+
+    Ok(block + depth <= latest)
+}
+
 enum DownloadBlock {
     Block(Box<Block>, (TransactionCommitment, EventCommitment)),
     AtHead,
@@ -438,6 +761,7 @@ pub enum BlockValidationMode {
     AllowMismatch,
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn download_block(
     block_number: BlockNumber,
     // Poll pending could exit when it encountered a finalized block, so we'd like to reuse it
@@ -447,6 +771,7 @@ async fn download_block(
     prev_block_hash: Option<BlockHash>,
     sequencer: &impl GatewayApi,
     mode: BlockValidationMode,
+    verify_transaction_hashes: bool,
 ) -> anyhow::Result<DownloadBlock> {
     use starknet_gateway_types::{
         error::KnownStarknetErrorCode::BlockNotFound, reply::MaybePendingBlock,
@@ -461,6 +786,14 @@ async fn download_block(
 
     let result = match result {
         Ok(MaybePendingBlock::Block(block)) => {
+            // Guards against a misbehaving cache in front of the sequencer.
+            anyhow::ensure!(
+                block.block_number == block_number,
+                "Sequencer returned block {} instead of the requested block {}",
+                block.block_number,
+                block_number
+            );
+
             let block = Box::new(block);
             // Check if block hash is correct.
             let verify_hash = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
@@ -531,7 +864,7 @@ async fn download_block(
     };
 
     match result {
-        Ok(DownloadBlock::Block(block, commitments)) => {
+        Ok(DownloadBlock::Block(block, commitments)) if verify_transaction_hashes => {
             use rayon::prelude::*;
 
             let (send, recv) = tokio::sync::oneshot::channel();
@@ -563,10 +896,13 @@ async fn download_block(
 
             Ok(DownloadBlock::Block(block, commitments))
         }
-        Ok(DownloadBlock::AtHead | DownloadBlock::Reorg) | Err(_) => result,
+        Ok(DownloadBlock::Block(..) | DownloadBlock::AtHead | DownloadBlock::Reorg) | Err(_) => {
+            result
+        }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn reorg(
     head: &(BlockNumber, BlockHash, StateCommitment),
     chain: Chain,
@@ -574,6 +910,7 @@ async fn reorg(
     tx_event: &mpsc::Sender<SyncEvent>,
     sequencer: &impl GatewayApi,
     mode: BlockValidationMode,
+    verify_transaction_hashes: bool,
     blocks: &BlockChain,
 ) -> anyhow::Result<Option<(BlockNumber, BlockHash, StateCommitment)>> {
     // Go back in history until we find an L2 block that does still exist.
@@ -598,6 +935,7 @@ async fn reorg(
             Some(previous.0),
             sequencer,
             mode,
+            verify_transaction_hashes,
         )
         .await
         .with_context(|| format!("Download block {previous_block_number} from sequencer"))?
@@ -628,12 +966,14 @@ async fn reorg(
 mod tests {
 
     mod sync {
-        use crate::state::l2::{BlockChain, L2SyncContext};
+        use crate::state::l2::{
+            BlockChain, L2SyncContext, MissingClassHashPolicy, RootMismatchPolicy,
+        };
         use pathfinder_common::macro_prelude::*;
         use pathfinder_common::BlockCommitmentSignature;
         use pathfinder_common::StateUpdate;
 
-        use super::super::{sync, BlockValidationMode, SyncEvent};
+        use super::super::{sync, BlockValidationMode, RootMismatch, RootSource, SyncEvent};
         use assert_matches::assert_matches;
         use pathfinder_common::{
             BlockHash, BlockId, BlockNumber, BlockTimestamp, Chain, ChainId, ClassHash,
@@ -824,7 +1164,13 @@ mod tests {
                 head_poll_interval: Duration::ZERO,
                 pending_poll_interval: None,
                 block_validation_mode: MODE,
+                root_mismatch_policy: Default::default(),
+                missing_class_hash_policy: Default::default(),
+                class_not_found_policy: Default::default(),
+                confirmation_depth: 0,
                 storage,
+                sequencer_public_key: None,
+                verify_transaction_hashes: true,
             };
 
             tokio::spawn(sync(
@@ -1182,7 +1528,13 @@ mod tests {
                     head_poll_interval: Duration::ZERO,
                     pending_poll_interval: None,
                     block_validation_mode: MODE,
+                    root_mismatch_policy: Default::default(),
+                    missing_class_hash_policy: Default::default(),
+                    class_not_found_policy: Default::default(),
+                    confirmation_depth: 0,
                     storage: Storage::in_memory().unwrap(),
+                    sequencer_public_key: None,
+                    verify_transaction_hashes: true,
                 };
 
                 let _jh = tokio::spawn(sync(
@@ -1204,51 +1556,20 @@ mod tests {
                     assert_eq!(*state_update, *STATE_UPDATE1);
                 });
             }
-        }
-
-        mod errors {
-            use super::*;
-            use starknet_gateway_types::reply::Status;
-
-            #[tokio::test]
-            async fn invalid_block_status() {
-                let (tx_event, _rx_event) = tokio::sync::mpsc::channel(1);
-                let mut mock = MockGatewayApi::new();
-                let mut seq = mockall::Sequence::new();
-
-                // Block with a non-accepted status
-                let mut block = BLOCK0.clone();
-                block.status = Status::Reverted;
-                expect_block(&mut mock, &mut seq, BLOCK0_NUMBER.into(), Ok(block.into()));
-
-                let jh = spawn_sync_default(tx_event, mock);
-                let error = jh.await.unwrap().unwrap_err();
-                assert_eq!(
-                    &error.to_string(),
-                    "Rejecting block as its status is REVERTED, and only accepted blocks are allowed"
-                );
-            }
-        }
-
-        mod reorg {
-            use super::*;
-            use pretty_assertions::assert_eq;
 
             #[tokio::test]
-            // This reorg occurs at the genesis block, which is swapped for a new one.
-            //
-            // [block 0]
-            //
-            // Becomes:
-            //
-            // [block 0 v2]
-            //
-            async fn at_genesis_which_is_head() {
+            async fn waits_for_confirmations() {
                 let (tx_event, mut rx_event) = tokio::sync::mpsc::channel(1);
                 let mut mock = MockGatewayApi::new();
                 let mut seq = mockall::Sequence::new();
 
-                // Fetch the genesis block with respective state update and contracts
+                // Block #0 is one block deep, and therefore confirmed.
+                expect_block_header(
+                    &mut mock,
+                    &mut seq,
+                    BlockId::Latest,
+                    Ok((BLOCK1.block_number, BLOCK1.block_hash)),
+                );
                 expect_block(
                     &mut mock,
                     &mut seq,
@@ -1273,72 +1594,815 @@ mod tests {
                     BLOCK0_HASH.into(),
                     Ok(BLOCK0_SIGNATURE.clone()),
                 );
-
-                // Block #1 is not there
-                expect_block(
+                // Block #1 is the latest block, so it must not be downloaded.
+                expect_block_header(
                     &mut mock,
                     &mut seq,
-                    BLOCK1_NUMBER.into(),
-                    Err(block_not_found()),
+                    BlockId::Latest,
+                    Ok((BLOCK1.block_number, BLOCK1.block_hash)),
                 );
 
-                // L2 sync task is then looking if reorg occurred
-                // We indicate that reorg started at genesis
+                let context = L2SyncContext {
+                    broadcasters: Some(TopicBroadcasters::default()),
+                    sequencer: std::sync::Arc::new(mock),
+                    chain: Chain::Testnet,
+                    chain_id: ChainId::TESTNET,
+                    head_poll_interval: Duration::from_secs(3600),
+                    pending_poll_interval: None,
+                    block_validation_mode: MODE,
+                    root_mismatch_policy: Default::default(),
+                    missing_class_hash_policy: Default::default(),
+                    class_not_found_policy: Default::default(),
+                    confirmation_depth: 1,
+                    storage: Storage::in_memory().unwrap(),
+                    sequencer_public_key: None,
+                    verify_transaction_hashes: true,
+                };
+
+                let _jh = tokio::spawn(sync(
+                    tx_event,
+                    context,
+                    None,
+                    BlockChain::with_capacity(100, vec![]),
+                ));
+
+                assert_matches!(rx_event.recv().await.unwrap(),
+                    SyncEvent::CairoClass { hash, .. } => {
+                        assert_eq!(hash, CONTRACT0_HASH);
+                });
+                assert_matches!(rx_event.recv().await.unwrap(), SyncEvent::Block((block, _), _, _, _) => {
+                    assert_eq!(*block, *BLOCK0);
+                });
+
+                // Requesting block #1 would panic the mock and close the channel.
+                let next = tokio::time::timeout(Duration::from_millis(100), rx_event.recv()).await;
+                assert!(next.is_err(), "Unconfirmed block should not be synced");
+            }
+
+            #[tokio::test]
+            async fn sequencer_head_is_cached_between_confirmations() {
+                let (tx_event, mut rx_event) = tokio::sync::mpsc::channel(1);
+                let mut mock = MockGatewayApi::new();
+                let mut seq = mockall::Sequence::new();
+
+                // Blocks #0 and #1 are both confirmed by a single latest block query.
                 expect_block_header(
                     &mut mock,
                     &mut seq,
                     BlockId::Latest,
-                    Ok((BLOCK0_V2.block_number, BLOCK0_V2.block_hash)),
+                    Ok((BLOCK2.block_number, BLOCK2.block_hash)),
                 );
-
-                // Finally the L2 sync task is downloading the new genesis block
-                // from the fork with respective state update and contracts
                 expect_block(
                     &mut mock,
                     &mut seq,
                     BLOCK0_NUMBER.into(),
-                    Ok(BLOCK0_V2.clone().into()),
+                    Ok(BLOCK0.clone().into()),
                 );
                 expect_state_update(
                     &mut mock,
                     &mut seq,
-                    BLOCK0_HASH_V2.into(),
-                    Ok(STATE_UPDATE0_V2.clone()),
+                    BLOCK0_HASH.into(),
+                    Ok(STATE_UPDATE0.clone()),
                 );
                 expect_class_by_hash(
                     &mut mock,
                     &mut seq,
-                    CONTRACT0_HASH_V2,
-                    Ok(CONTRACT0_DEF_V2.clone()),
+                    CONTRACT0_HASH,
+                    Ok(CONTRACT0_DEF.clone()),
                 );
                 expect_signature(
                     &mut mock,
                     &mut seq,
-                    BLOCK0_HASH_V2.into(),
-                    Ok(BLOCK0_SIGNATURE_V2.clone()),
+                    BLOCK0_HASH.into(),
+                    Ok(BLOCK0_SIGNATURE.clone()),
                 );
-
-                // Indicate that we are still staying at the head - no new blocks
                 expect_block(
                     &mut mock,
                     &mut seq,
                     BLOCK1_NUMBER.into(),
-                    Err(block_not_found()),
+                    Ok(BLOCK1.clone().into()),
                 );
-
-                // Indicate that we are still staying at the head - the latest block matches our head
+                expect_state_update(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK1_HASH.into(),
+                    Ok(STATE_UPDATE1.clone()),
+                );
+                expect_class_by_hash(
+                    &mut mock,
+                    &mut seq,
+                    CONTRACT1_HASH,
+                    Ok(CONTRACT1_DEF.clone()),
+                );
+                expect_signature(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK1_HASH.into(),
+                    Ok(BLOCK1_SIGNATURE.clone()),
+                );
+                // Block #2 is beyond the cached head, so the latest block is queried again.
                 expect_block_header(
                     &mut mock,
                     &mut seq,
                     BlockId::Latest,
-                    Ok((BLOCK0_V2.block_number, BLOCK0_V2.block_hash)),
+                    Ok((BLOCK2.block_number, BLOCK2.block_hash)),
                 );
 
-                // Let's run the UUT
-                let _jh = spawn_sync_default(tx_event, mock);
+                let context = L2SyncContext {
+                    broadcasters: Some(TopicBroadcasters::default()),
+                    sequencer: std::sync::Arc::new(mock),
+                    chain: Chain::Testnet,
+                    chain_id: ChainId::TESTNET,
+                    head_poll_interval: Duration::from_secs(3600),
+                    pending_poll_interval: None,
+                    block_validation_mode: MODE,
+                    root_mismatch_policy: Default::default(),
+                    missing_class_hash_policy: Default::default(),
+                    class_not_found_policy: Default::default(),
+                    confirmation_depth: 1,
+                    storage: Storage::in_memory().unwrap(),
+                    sequencer_public_key: None,
+                    verify_transaction_hashes: true,
+                };
+
+                let _jh = tokio::spawn(sync(
+                    tx_event,
+                    context,
+                    None,
+                    BlockChain::with_capacity(100, vec![]),
+                ));
 
                 assert_matches!(rx_event.recv().await.unwrap(),
-                    SyncEvent::CairoClass{hash, ..} => {
+                    SyncEvent::CairoClass { hash, .. } => {
+                        assert_eq!(hash, CONTRACT0_HASH);
+                });
+                assert_matches!(rx_event.recv().await.unwrap(), SyncEvent::Block((block, _), _, _, _) => {
+                    assert_eq!(*block, *BLOCK0);
+                });
+                assert_matches!(rx_event.recv().await.unwrap(),
+                    SyncEvent::CairoClass { hash, .. } => {
+                        assert_eq!(hash, CONTRACT1_HASH);
+                });
+                assert_matches!(rx_event.recv().await.unwrap(), SyncEvent::Block((block, _), _, _, _) => {
+                    assert_eq!(*block, *BLOCK1);
+                });
+
+                // An unexpected request would panic the mock and close the channel.
+                let next = tokio::time::timeout(Duration::from_millis(100), rx_event.recv()).await;
+                assert!(next.is_err(), "Unconfirmed block should not be synced");
+            }
+        }
+
+        mod errors {
+            use super::*;
+            use starknet_gateway_types::reply::Status;
+
+            #[tokio::test]
+            async fn invalid_block_status() {
+                let (tx_event, _rx_event) = tokio::sync::mpsc::channel(1);
+                let mut mock = MockGatewayApi::new();
+                let mut seq = mockall::Sequence::new();
+
+                // Block with a non-accepted status
+                let mut block = BLOCK0.clone();
+                block.status = Status::Reverted;
+                expect_block(&mut mock, &mut seq, BLOCK0_NUMBER.into(), Ok(block.into()));
+
+                let jh = spawn_sync_default(tx_event, mock);
+                let error = jh.await.unwrap().unwrap_err();
+                assert_eq!(
+                    &error.to_string(),
+                    "Rejecting block as its status is REVERTED, and only accepted blocks are allowed"
+                );
+            }
+
+            #[tokio::test]
+            async fn pending_block_without_hash() {
+                let (tx_event, _rx_event) = tokio::sync::mpsc::channel(1);
+                let mut mock = MockGatewayApi::new();
+                let mut seq = mockall::Sequence::new();
+
+                // Pending blocks have no block hash to validate, so strict sync must reject them.
+                // Pending data is instead consumed by `poll_pending` which skips hash validation.
+                expect_block(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_NUMBER.into(),
+                    Ok(reply::MaybePendingBlock::Pending(
+                        reply::PendingBlock::default(),
+                    )),
+                );
+
+                let jh = spawn_sync_default(tx_event, mock);
+                let error = jh.await.unwrap().unwrap_err();
+                assert_eq!(&error.to_string(), "Sequencer returned `pending` block");
+            }
+
+            /// Block 0 with a transaction whose hash does not match its contents.
+            fn block0_with_bad_transaction_hash() -> reply::MaybePendingBlock {
+                use fake::{Fake, Faker};
+                use starknet_gateway_types::reply::transaction::{InvokeTransaction, Transaction};
+
+                let mut block = BLOCK0.clone();
+                block.transactions = vec![Transaction::Invoke(InvokeTransaction::V1(Faker.fake()))];
+                block.into()
+            }
+
+            #[tokio::test]
+            async fn transaction_hash_mismatch() {
+                let (tx_event, _rx_event) = tokio::sync::mpsc::channel(1);
+                let mut mock = MockGatewayApi::new();
+                let mut seq = mockall::Sequence::new();
+
+                expect_block(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_NUMBER.into(),
+                    Ok(block0_with_bad_transaction_hash()),
+                );
+
+                let jh = spawn_sync_default(tx_event, mock);
+                let error = jh.await.unwrap().unwrap_err();
+                assert!(error
+                    .to_string()
+                    .starts_with("Transaction hash mismatch: block 0 idx 0"));
+            }
+
+            #[tokio::test]
+            async fn transaction_hash_verification_disabled() {
+                let (tx_event, _rx_event) = tokio::sync::mpsc::channel(1);
+                let mut mock = MockGatewayApi::new();
+                let mut seq = mockall::Sequence::new();
+
+                expect_block(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_NUMBER.into(),
+                    Ok(block0_with_bad_transaction_hash()),
+                );
+                // Sync moves on to the state update, which stops it here.
+                expect_state_update(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_HASH.into(),
+                    Err(block_not_found()),
+                );
+
+                let context = L2SyncContext {
+                    broadcasters: Some(TopicBroadcasters::default()),
+                    sequencer: std::sync::Arc::new(mock),
+                    chain: Chain::Testnet,
+                    chain_id: ChainId::TESTNET,
+                    head_poll_interval: Duration::ZERO,
+                    pending_poll_interval: None,
+                    block_validation_mode: MODE,
+                    root_mismatch_policy: Default::default(),
+                    missing_class_hash_policy: Default::default(),
+                    class_not_found_policy: Default::default(),
+                    confirmation_depth: 0,
+                    storage: Storage::in_memory().unwrap(),
+                    sequencer_public_key: None,
+                    verify_transaction_hashes: false,
+                };
+
+                let error = sync(
+                    tx_event,
+                    context,
+                    None,
+                    BlockChain::with_capacity(100, vec![]),
+                )
+                .await
+                .unwrap_err();
+                assert_eq!(
+                    error.to_string(),
+                    "Fetch state diff for block BlockNumber(0) from sequencer"
+                );
+            }
+
+            #[tokio::test]
+            async fn wrong_block_number() {
+                let (tx_event, _rx_event) = tokio::sync::mpsc::channel(1);
+                let mut mock = MockGatewayApi::new();
+                let mut seq = mockall::Sequence::new();
+
+                expect_block(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_NUMBER.into(),
+                    Ok(BLOCK1.clone().into()),
+                );
+
+                let jh = spawn_sync_default(tx_event, mock);
+                let error = jh.await.unwrap().unwrap_err();
+                assert_eq!(
+                    &error.to_string(),
+                    "Sequencer returned block 1 instead of the requested block 0"
+                );
+            }
+
+            #[tokio::test]
+            async fn state_update_root_mismatch() {
+                let (tx_event, _rx_event) = tokio::sync::mpsc::channel(1);
+                let mut mock = MockGatewayApi::new();
+                let mut seq = mockall::Sequence::new();
+
+                expect_block(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_NUMBER.into(),
+                    Ok(BLOCK0.clone().into()),
+                );
+                expect_state_update(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_HASH.into(),
+                    Ok(STATE_UPDATE0.clone().with_state_commitment(GLOBAL_ROOT0_V2)),
+                );
+
+                let jh = spawn_sync_default(tx_event, mock);
+                let error = jh.await.unwrap().unwrap_err();
+                assert_eq!(
+                    error.downcast_ref::<RootMismatch>(),
+                    Some(&RootMismatch {
+                        source: RootSource::SequencerState,
+                        expected: GLOBAL_ROOT0,
+                        actual: GLOBAL_ROOT0_V2,
+                        block: BLOCK0_NUMBER,
+                    })
+                );
+            }
+
+            #[tokio::test(start_paused = true)]
+            async fn state_update_root_mismatch_is_retried() {
+                let (tx_event, mut rx_event) = tokio::sync::mpsc::channel(1);
+                let mut mock = MockGatewayApi::new();
+                let mut seq = mockall::Sequence::new();
+
+                // The sequencer disagrees with itself once, and then agrees.
+                expect_block(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_NUMBER.into(),
+                    Ok(BLOCK0.clone().into()),
+                );
+                expect_state_update(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_HASH.into(),
+                    Ok(STATE_UPDATE0.clone().with_state_commitment(GLOBAL_ROOT0_V2)),
+                );
+                expect_block(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_NUMBER.into(),
+                    Ok(BLOCK0.clone().into()),
+                );
+                expect_state_update(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_HASH.into(),
+                    Ok(STATE_UPDATE0.clone()),
+                );
+                expect_class_by_hash(
+                    &mut mock,
+                    &mut seq,
+                    CONTRACT0_HASH,
+                    Ok(CONTRACT0_DEF.clone()),
+                );
+                expect_signature(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_HASH.into(),
+                    Ok(BLOCK0_SIGNATURE.clone()),
+                );
+
+                let context = L2SyncContext {
+                    broadcasters: None,
+                    sequencer: std::sync::Arc::new(mock),
+                    chain: Chain::Testnet,
+                    chain_id: ChainId::TESTNET,
+                    head_poll_interval: Duration::ZERO,
+                    pending_poll_interval: None,
+                    block_validation_mode: MODE,
+                    root_mismatch_policy: RootMismatchPolicy::RetryWithBackoff,
+                    missing_class_hash_policy: Default::default(),
+                    class_not_found_policy: Default::default(),
+                    confirmation_depth: 0,
+                    storage: Storage::in_memory().unwrap(),
+                    sequencer_public_key: None,
+                    verify_transaction_hashes: true,
+                };
+                let jh = tokio::spawn(sync(
+                    tx_event,
+                    context,
+                    None,
+                    BlockChain::with_capacity(100, vec![]),
+                ));
+
+                assert_matches!(rx_event.recv().await.unwrap(), SyncEvent::CairoClass { hash, .. } => {
+                    assert_eq!(hash, CONTRACT0_HASH);
+                });
+                assert_matches!(rx_event.recv().await.unwrap(), SyncEvent::Block((block, _), state_update, _, _) => {
+                    assert_eq!(*block, *BLOCK0);
+                    assert_eq!(*state_update, *STATE_UPDATE0);
+                });
+
+                jh.abort();
+            }
+        }
+
+        mod missing_class_hash {
+            use super::*;
+            use pretty_assertions::assert_eq;
+
+            #[tokio::test]
+            async fn fetched_from_sequencer() {
+                let (tx_event, mut rx_event) = tokio::sync::mpsc::channel(1);
+                let mut mock = MockGatewayApi::new();
+                let mut seq = mockall::Sequence::new();
+
+                // The state update writes to a contract which was never deployed.
+                let state_update = StateUpdate::default()
+                    .with_block_hash(BLOCK0_HASH)
+                    .with_state_commitment(GLOBAL_ROOT0)
+                    .with_storage_update(CONTRACT1_ADDR, STORAGE_KEY1, STORAGE_VAL1);
+
+                expect_block(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_NUMBER.into(),
+                    Ok(BLOCK0.clone().into()),
+                );
+                expect_state_update(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_HASH.into(),
+                    Ok(state_update.clone()),
+                );
+                mock.expect_class_hash_at()
+                    .withf(|address, block| {
+                        address == &CONTRACT1_ADDR && block == &BlockId::from(BLOCK0_HASH)
+                    })
+                    .times(1)
+                    .in_sequence(&mut seq)
+                    .return_once(|_, _| Ok(CONTRACT1_HASH));
+                expect_class_by_hash(
+                    &mut mock,
+                    &mut seq,
+                    CONTRACT1_HASH,
+                    Ok(CONTRACT1_DEF.clone()),
+                );
+                expect_signature(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_HASH.into(),
+                    Ok(BLOCK0_SIGNATURE.clone()),
+                );
+
+                let context = L2SyncContext {
+                    broadcasters: None,
+                    sequencer: std::sync::Arc::new(mock),
+                    chain: Chain::Testnet,
+                    chain_id: ChainId::TESTNET,
+                    head_poll_interval: Duration::ZERO,
+                    pending_poll_interval: None,
+                    block_validation_mode: MODE,
+                    root_mismatch_policy: Default::default(),
+                    missing_class_hash_policy: MissingClassHashPolicy::FetchFromSequencer,
+                    class_not_found_policy: Default::default(),
+                    confirmation_depth: 0,
+                    storage: Storage::in_memory().unwrap(),
+                    sequencer_public_key: None,
+                    verify_transaction_hashes: true,
+                };
+                let jh = tokio::spawn(sync(
+                    tx_event,
+                    context,
+                    None,
+                    BlockChain::with_capacity(100, vec![]),
+                ));
+
+                assert_matches!(rx_event.recv().await.unwrap(), SyncEvent::CairoClass { hash, .. } => {
+                    assert_eq!(hash, CONTRACT1_HASH);
+                });
+                assert_matches!(rx_event.recv().await.unwrap(), SyncEvent::Block((block, _), emitted, _, _) => {
+                    assert_eq!(*block, *BLOCK0);
+                    assert_eq!(
+                        *emitted,
+                        state_update.with_deployed_contract(CONTRACT1_ADDR, CONTRACT1_HASH)
+                    );
+                });
+
+                jh.abort();
+            }
+
+            /// Block 1 writes to a contract which was deployed in block 0. Block 0 has already
+            /// been emitted but not yet committed, so the database does not know the contract.
+            #[tokio::test]
+            async fn deployed_in_uncommitted_block() {
+                let (tx_event, mut rx_event) = tokio::sync::mpsc::channel(1);
+                let mut mock = MockGatewayApi::new();
+                let mut seq = mockall::Sequence::new();
+
+                let state_update1 = StateUpdate::default()
+                    .with_block_hash(BLOCK1_HASH)
+                    .with_state_commitment(GLOBAL_ROOT1)
+                    .with_parent_state_commitment(GLOBAL_ROOT0)
+                    .with_storage_update(CONTRACT0_ADDR, STORAGE_KEY0, STORAGE_VAL0_V2);
+
+                expect_block(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_NUMBER.into(),
+                    Ok(BLOCK0.clone().into()),
+                );
+                expect_state_update(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_HASH.into(),
+                    Ok(STATE_UPDATE0.clone()),
+                );
+                expect_class_by_hash(
+                    &mut mock,
+                    &mut seq,
+                    CONTRACT0_HASH,
+                    Ok(CONTRACT0_DEF.clone()),
+                );
+                expect_signature(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_HASH.into(),
+                    Ok(BLOCK0_SIGNATURE.clone()),
+                );
+                expect_block(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK1_NUMBER.into(),
+                    Ok(BLOCK1.clone().into()),
+                );
+                expect_state_update(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK1_HASH.into(),
+                    Ok(state_update1.clone()),
+                );
+                // No class hash is fetched from the sequencer.
+                expect_signature(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK1_HASH.into(),
+                    Ok(BLOCK1_SIGNATURE.clone()),
+                );
+
+                let context = L2SyncContext {
+                    broadcasters: None,
+                    sequencer: std::sync::Arc::new(mock),
+                    chain: Chain::Testnet,
+                    chain_id: ChainId::TESTNET,
+                    head_poll_interval: Duration::ZERO,
+                    pending_poll_interval: None,
+                    block_validation_mode: MODE,
+                    root_mismatch_policy: Default::default(),
+                    missing_class_hash_policy: MissingClassHashPolicy::FetchFromSequencer,
+                    class_not_found_policy: Default::default(),
+                    confirmation_depth: 0,
+                    storage: Storage::in_memory().unwrap(),
+                    sequencer_public_key: None,
+                    verify_transaction_hashes: true,
+                };
+                let jh = tokio::spawn(sync(
+                    tx_event,
+                    context,
+                    None,
+                    BlockChain::with_capacity(100, vec![]),
+                ));
+
+                assert_matches!(rx_event.recv().await.unwrap(), SyncEvent::CairoClass { hash, .. } => {
+                    assert_eq!(hash, CONTRACT0_HASH);
+                });
+                assert_matches!(rx_event.recv().await.unwrap(), SyncEvent::Block((block, _), emitted, _, _) => {
+                    assert_eq!(*block, *BLOCK0);
+                    assert_eq!(*emitted, *STATE_UPDATE0);
+                });
+                assert_matches!(rx_event.recv().await.unwrap(), SyncEvent::Block((block, _), emitted, _, _) => {
+                    assert_eq!(*block, *BLOCK1);
+                    assert_eq!(*emitted, state_update1);
+                });
+
+                jh.abort();
+            }
+
+            /// The sequencer reports that the contract already existed at the parent block, so it
+            /// must not be marked as deployed in this block.
+            #[tokio::test]
+            async fn existing_at_parent_block() {
+                let (tx_event, mut rx_event) = tokio::sync::mpsc::channel(1);
+                let mut mock = MockGatewayApi::new();
+                let mut seq = mockall::Sequence::new();
+
+                let state_update1 = StateUpdate::default()
+                    .with_block_hash(BLOCK1_HASH)
+                    .with_state_commitment(GLOBAL_ROOT1)
+                    .with_parent_state_commitment(GLOBAL_ROOT0)
+                    .with_storage_update(CONTRACT1_ADDR, STORAGE_KEY1, STORAGE_VAL1);
+
+                expect_block(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_NUMBER.into(),
+                    Ok(BLOCK0.clone().into()),
+                );
+                expect_state_update(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_HASH.into(),
+                    Ok(STATE_UPDATE0.clone()),
+                );
+                expect_class_by_hash(
+                    &mut mock,
+                    &mut seq,
+                    CONTRACT0_HASH,
+                    Ok(CONTRACT0_DEF.clone()),
+                );
+                expect_signature(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_HASH.into(),
+                    Ok(BLOCK0_SIGNATURE.clone()),
+                );
+                expect_block(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK1_NUMBER.into(),
+                    Ok(BLOCK1.clone().into()),
+                );
+                expect_state_update(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK1_HASH.into(),
+                    Ok(state_update1.clone()),
+                );
+                mock.expect_class_hash_at()
+                    .withf(|address, block| {
+                        address == &CONTRACT1_ADDR && block == &BlockId::from(BLOCK0_HASH)
+                    })
+                    .times(1)
+                    .in_sequence(&mut seq)
+                    .return_once(|_, _| Ok(CONTRACT1_HASH));
+                expect_signature(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK1_HASH.into(),
+                    Ok(BLOCK1_SIGNATURE.clone()),
+                );
+
+                let context = L2SyncContext {
+                    broadcasters: None,
+                    sequencer: std::sync::Arc::new(mock),
+                    chain: Chain::Testnet,
+                    chain_id: ChainId::TESTNET,
+                    head_poll_interval: Duration::ZERO,
+                    pending_poll_interval: None,
+                    block_validation_mode: MODE,
+                    root_mismatch_policy: Default::default(),
+                    missing_class_hash_policy: MissingClassHashPolicy::FetchFromSequencer,
+                    class_not_found_policy: Default::default(),
+                    confirmation_depth: 0,
+                    storage: Storage::in_memory().unwrap(),
+                    sequencer_public_key: None,
+                    verify_transaction_hashes: true,
+                };
+                let jh = tokio::spawn(sync(
+                    tx_event,
+                    context,
+                    None,
+                    BlockChain::with_capacity(100, vec![]),
+                ));
+
+                assert_matches!(rx_event.recv().await.unwrap(), SyncEvent::CairoClass { hash, .. } => {
+                    assert_eq!(hash, CONTRACT0_HASH);
+                });
+                assert_matches!(rx_event.recv().await.unwrap(), SyncEvent::Block((block, _), _, _, _) => {
+                    assert_eq!(*block, *BLOCK0);
+                });
+                assert_matches!(rx_event.recv().await.unwrap(), SyncEvent::Block((block, _), emitted, _, _) => {
+                    assert_eq!(*block, *BLOCK1);
+                    assert_eq!(*emitted, state_update1);
+                });
+
+                jh.abort();
+            }
+        }
+
+        mod reorg {
+            use super::*;
+            use pretty_assertions::assert_eq;
+
+            #[tokio::test]
+            // This reorg occurs at the genesis block, which is swapped for a new one.
+            //
+            // [block 0]
+            //
+            // Becomes:
+            //
+            // [block 0 v2]
+            //
+            async fn at_genesis_which_is_head() {
+                let (tx_event, mut rx_event) = tokio::sync::mpsc::channel(1);
+                let mut mock = MockGatewayApi::new();
+                let mut seq = mockall::Sequence::new();
+
+                // Fetch the genesis block with respective state update and contracts
+                expect_block(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_NUMBER.into(),
+                    Ok(BLOCK0.clone().into()),
+                );
+                expect_state_update(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_HASH.into(),
+                    Ok(STATE_UPDATE0.clone()),
+                );
+                expect_class_by_hash(
+                    &mut mock,
+                    &mut seq,
+                    CONTRACT0_HASH,
+                    Ok(CONTRACT0_DEF.clone()),
+                );
+                expect_signature(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_HASH.into(),
+                    Ok(BLOCK0_SIGNATURE.clone()),
+                );
+
+                // Block #1 is not there
+                expect_block(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK1_NUMBER.into(),
+                    Err(block_not_found()),
+                );
+
+                // L2 sync task is then looking if reorg occurred
+                // We indicate that reorg started at genesis
+                expect_block_header(
+                    &mut mock,
+                    &mut seq,
+                    BlockId::Latest,
+                    Ok((BLOCK0_V2.block_number, BLOCK0_V2.block_hash)),
+                );
+
+                // Finally the L2 sync task is downloading the new genesis block
+                // from the fork with respective state update and contracts
+                expect_block(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_NUMBER.into(),
+                    Ok(BLOCK0_V2.clone().into()),
+                );
+                expect_state_update(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_HASH_V2.into(),
+                    Ok(STATE_UPDATE0_V2.clone()),
+                );
+                expect_class_by_hash(
+                    &mut mock,
+                    &mut seq,
+                    CONTRACT0_HASH_V2,
+                    Ok(CONTRACT0_DEF_V2.clone()),
+                );
+                expect_signature(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK0_HASH_V2.into(),
+                    Ok(BLOCK0_SIGNATURE_V2.clone()),
+                );
+
+                // Indicate that we are still staying at the head - no new blocks
+                expect_block(
+                    &mut mock,
+                    &mut seq,
+                    BLOCK1_NUMBER.into(),
+                    Err(block_not_found()),
+                );
+
+                // Indicate that we are still staying at the head - the latest block matches our head
+                expect_block_header(
+                    &mut mock,
+                    &mut seq,
+                    BlockId::Latest,
+                    Ok((BLOCK0_V2.block_number, BLOCK0_V2.block_hash)),
+                );
+
+                // Let's run the UUT
+                let _jh = spawn_sync_default(tx_event, mock);
+
+                assert_matches!(rx_event.recv().await.unwrap(),
+                    SyncEvent::CairoClass{hash, ..} => {
                         assert_eq!(hash, CONTRACT0_HASH);
                 });
                 assert_matches!(rx_event.recv().await.unwrap(), SyncEvent::Block((block, _), state_update, _, _) => {
@@ -2304,6 +3368,321 @@ mod tests {
                     .unwrap_err();
             }
         }
+
+        /// Runs sync against a real [Client](starknet_gateway_client::Client) talking to a local
+        /// HTTP server which serves scripted sequencer replies. This also covers the HTTP and
+        /// parsing layers, which the [MockGatewayApi] based tests bypass.
+        mod scripted_sequencer {
+            use super::*;
+            use starknet_gateway_client::test_utils::response_from;
+            use starknet_gateway_client::Client;
+            use std::collections::HashMap;
+
+            #[derive(Clone)]
+            struct ScriptedBlock {
+                block: reply::Block,
+                state_update: reply::StateUpdate,
+                signature: serde_json::Value,
+            }
+
+            /// Returns the scripted reply to a `feeder_gateway` request, if there is one.
+            fn reply(
+                blocks: &[ScriptedBlock],
+                endpoint: &str,
+                query: &HashMap<String, String>,
+            ) -> Option<serde_json::Value> {
+                let by_number = |number: &str| match number {
+                    "latest" => blocks.last(),
+                    number => {
+                        let number = number.parse::<u64>().ok()?;
+                        blocks.iter().find(|b| b.block.block_number.get() == number)
+                    }
+                };
+                let by_hash = |hash: &str| {
+                    let hash = BlockHash(Felt::from_hex_str(hash).ok()?);
+                    blocks.iter().find(|b| b.block.block_hash == hash)
+                };
+
+                match endpoint {
+                    "get_block" => {
+                        let block = by_number(query.get("blockNumber")?)?;
+                        let reply = match query.get("headerOnly") {
+                            Some(_) => serde_json::json!({
+                                "block_hash": block.block.block_hash,
+                                "block_number": block.block.block_number,
+                            }),
+                            None => serde_json::to_value(&block.block).unwrap(),
+                        };
+                        Some(reply)
+                    }
+                    "get_state_update" => {
+                        let block = by_hash(query.get("blockHash")?)?;
+                        Some(serde_json::to_value(&block.state_update).unwrap())
+                    }
+                    "get_signature" => {
+                        let block = by_hash(query.get("blockHash")?)?;
+                        Some(block.signature.clone())
+                    }
+                    _ => None,
+                }
+            }
+
+            /// Serves the scripted blocks from the `feeder_gateway` endpoints used by sync. Blocks
+            /// which are not scripted are reported as not found.
+            fn serve(blocks: Vec<ScriptedBlock>) -> (JoinHandle<()>, Client) {
+                use warp::Filter;
+
+                let filter = warp::path!("feeder_gateway" / String)
+                    .and(warp::query::<HashMap<String, String>>())
+                    .map(move |endpoint: String, query: HashMap<String, String>| {
+                        let (body, status) = match reply(&blocks, &endpoint, &query) {
+                            Some(reply) => (reply.to_string(), 200),
+                            None => response_from(KnownStarknetErrorCode::BlockNotFound),
+                        };
+
+                        http::response::Builder::new().status(status).body(body)
+                    });
+
+                let (addr, serve_fut) = warp::serve(filter).bind_ephemeral(([127, 0, 0, 1], 0));
+                let server = tokio::spawn(serve_fut);
+                let client =
+                    Client::with_base_url(reqwest::Url::parse(&format!("http://{addr}")).unwrap())
+                        .unwrap();
+
+                (server, client)
+            }
+
+            #[tokio::test]
+            async fn syncs_a_single_block() {
+                let state_update = reply::StateUpdate {
+                    block_hash: BLOCK0_HASH,
+                    new_root: GLOBAL_ROOT0,
+                    old_root: StateCommitment::ZERO,
+                    state_diff: Default::default(),
+                };
+                let signature = serde_json::json!({
+                    "block_number": BLOCK0_NUMBER,
+                    "signature": BLOCK0_SIGNATURE.signature,
+                    "signature_input": {
+                        "block_hash": BLOCK0_HASH,
+                        "state_diff_commitment": BLOCK0_SIGNATURE.signature_input.state_diff_commitment,
+                    },
+                });
+                let (server, client) = serve(vec![ScriptedBlock {
+                    block: BLOCK0.clone(),
+                    state_update,
+                    signature,
+                }]);
+
+                let (tx_event, mut rx_event) = tokio::sync::mpsc::channel(1);
+                let context = L2SyncContext {
+                    broadcasters: None,
+                    sequencer: client,
+                    chain: Chain::Testnet,
+                    chain_id: ChainId::TESTNET,
+                    head_poll_interval: Duration::from_secs(60),
+                    pending_poll_interval: None,
+                    block_validation_mode: MODE,
+                    root_mismatch_policy: Default::default(),
+                    missing_class_hash_policy: Default::default(),
+                    class_not_found_policy: Default::default(),
+                    confirmation_depth: 0,
+                    storage: Storage::in_memory().unwrap(),
+                    sequencer_public_key: None,
+                    verify_transaction_hashes: true,
+                };
+                let sync = tokio::spawn(sync(
+                    tx_event,
+                    context,
+                    None,
+                    BlockChain::with_capacity(100, vec![]),
+                ));
+
+                assert_matches!(rx_event.recv().await.unwrap(), SyncEvent::Block((block, _), state_update, signature, _) => {
+                    assert_eq!(*block, *BLOCK0);
+                    assert_eq!(
+                        *state_update,
+                        StateUpdate::default()
+                            .with_block_hash(BLOCK0_HASH)
+                            .with_state_commitment(GLOBAL_ROOT0)
+                    );
+                    assert_eq!(*signature, BLOCK0_COMMITMENT_SIGNATURE);
+                });
+
+                sync.abort();
+                server.abort();
+            }
+        }
+
+        mod download_new_classes {
+            use super::*;
+            use crate::state::l2::{
+                download_new_classes, ClassNotFoundPolicy, CLASS_NOT_FOUND_TIMEOUT,
+                MAX_CLASS_NOT_FOUND_DELAY,
+            };
+
+            #[tokio::test]
+            async fn undeclared_deployed_class_is_fetched() {
+                let (tx_event, mut rx_event) = tokio::sync::mpsc::channel(1);
+                let mut mock = MockGatewayApi::new();
+                let mut seq = mockall::Sequence::new();
+
+                // The class is deployed without being declared first.
+                expect_class_by_hash(
+                    &mut mock,
+                    &mut seq,
+                    CONTRACT0_HASH,
+                    Ok(CONTRACT0_DEF.clone()),
+                );
+
+                let state_update =
+                    StateUpdate::default().with_deployed_contract(CONTRACT0_ADDR, CONTRACT0_HASH);
+                download_new_classes(
+                    &state_update,
+                    &mock,
+                    &tx_event,
+                    &StarknetVersion::default(),
+                    Storage::in_memory().unwrap(),
+                    ClassNotFoundPolicy::Fail,
+                )
+                .await
+                .unwrap();
+
+                assert_matches!(rx_event.recv().await.unwrap(),
+                    SyncEvent::CairoClass{hash, ..} => {
+                        assert_eq!(hash, CONTRACT0_HASH);
+                });
+            }
+
+            #[tokio::test]
+            async fn stored_class_is_not_fetched() {
+                let (tx_event, _rx_event) = tokio::sync::mpsc::channel(1);
+                // Any request to the sequencer would fail the test.
+                let mock = MockGatewayApi::new();
+
+                let storage = Storage::in_memory().unwrap();
+                let mut connection = storage.connection().unwrap();
+                let tx = connection.transaction().unwrap();
+                tx.insert_cairo_class(CONTRACT0_HASH, CONTRACT0_DEF.as_ref())
+                    .unwrap();
+                tx.commit().unwrap();
+
+                let state_update =
+                    StateUpdate::default().with_deployed_contract(CONTRACT0_ADDR, CONTRACT0_HASH);
+                download_new_classes(
+                    &state_update,
+                    &mock,
+                    &tx_event,
+                    &StarknetVersion::default(),
+                    storage,
+                    ClassNotFoundPolicy::Fail,
+                )
+                .await
+                .unwrap();
+            }
+
+            fn undeclared_class() -> SequencerError {
+                SequencerError::StarknetError(StarknetError {
+                    code: KnownStarknetErrorCode::UndeclaredClass.into(),
+                    message: String::new(),
+                })
+            }
+
+            #[tokio::test(start_paused = true)]
+            async fn class_not_found_is_retried() {
+                let (tx_event, mut rx_event) = tokio::sync::mpsc::channel(1);
+                let mut mock = MockGatewayApi::new();
+                let mut seq = mockall::Sequence::new();
+
+                // The sequencer is still catching up and only knows the class on the third try.
+                expect_class_by_hash(&mut mock, &mut seq, CONTRACT0_HASH, Err(undeclared_class()));
+                expect_class_by_hash(&mut mock, &mut seq, CONTRACT0_HASH, Err(undeclared_class()));
+                expect_class_by_hash(
+                    &mut mock,
+                    &mut seq,
+                    CONTRACT0_HASH,
+                    Ok(CONTRACT0_DEF.clone()),
+                );
+
+                let state_update =
+                    StateUpdate::default().with_deployed_contract(CONTRACT0_ADDR, CONTRACT0_HASH);
+                download_new_classes(
+                    &state_update,
+                    &mock,
+                    &tx_event,
+                    &StarknetVersion::default(),
+                    Storage::in_memory().unwrap(),
+                    ClassNotFoundPolicy::RetryWithBackoff,
+                )
+                .await
+                .unwrap();
+
+                assert_matches!(rx_event.recv().await.unwrap(),
+                    SyncEvent::CairoClass{hash, ..} => {
+                        assert_eq!(hash, CONTRACT0_HASH);
+                });
+            }
+
+            #[tokio::test]
+            async fn class_not_found_fails_by_default() {
+                let (tx_event, _rx_event) = tokio::sync::mpsc::channel(1);
+                let mut mock = MockGatewayApi::new();
+                let mut seq = mockall::Sequence::new();
+
+                expect_class_by_hash(&mut mock, &mut seq, CONTRACT0_HASH, Err(undeclared_class()));
+
+                let state_update =
+                    StateUpdate::default().with_deployed_contract(CONTRACT0_ADDR, CONTRACT0_HASH);
+                download_new_classes(
+                    &state_update,
+                    &mock,
+                    &tx_event,
+                    &StarknetVersion::default(),
+                    Storage::in_memory().unwrap(),
+                    Default::default(),
+                )
+                .await
+                .unwrap_err();
+            }
+
+            #[tokio::test(start_paused = true)]
+            async fn class_not_found_retries_are_bounded() {
+                let (tx_event, _rx_event) = tokio::sync::mpsc::channel(1);
+                use std::sync::atomic::{AtomicUsize, Ordering};
+
+                let mut mock = MockGatewayApi::new();
+                let attempts = std::sync::Arc::new(AtomicUsize::new(0));
+                let counter = attempts.clone();
+                mock.expect_pending_class_by_hash().returning(move |_| {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                    Err(undeclared_class())
+                });
+
+                let start = tokio::time::Instant::now();
+                let state_update =
+                    StateUpdate::default().with_deployed_contract(CONTRACT0_ADDR, CONTRACT0_HASH);
+                download_new_classes(
+                    &state_update,
+                    &mock,
+                    &tx_event,
+                    &StarknetVersion::default(),
+                    Storage::in_memory().unwrap(),
+                    ClassNotFoundPolicy::RetryWithBackoff,
+                )
+                .await
+                .unwrap_err();
+
+                // Retries stop once the next delay would overshoot the timeout, and delays are
+                // capped, so the retries use up all but the last delay of the timeout.
+                let elapsed = start.elapsed();
+                assert!(elapsed <= CLASS_NOT_FOUND_TIMEOUT, "{elapsed:?}");
+                assert!(
+                    elapsed >= CLASS_NOT_FOUND_TIMEOUT - MAX_CLASS_NOT_FOUND_DELAY,
+                    "{elapsed:?}"
+                );
+                assert!(attempts.load(Ordering::Relaxed) > 1);
+            }
+        }
     }
 
     mod block_chain {
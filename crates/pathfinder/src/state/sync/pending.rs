@@ -81,6 +81,8 @@ pub async fn poll_pending<S: GatewayApi + Clone + Send + 'static>(
                     &tx_event,
                     &block.starknet_version,
                     storage.clone(),
+                    // Failures only delay the pending data, so there is no point in waiting.
+                    super::l2::ClassNotFoundPolicy::Fail,
                 )
                 .await
                 {
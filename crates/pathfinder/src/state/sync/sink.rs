@@ -0,0 +1,295 @@
+//! Publishing of committed blocks to external consumers, such as data pipelines.
+//!
+//! Sync only knows about the [BlockSink] trait; message broker implementations can live out of
+//! tree. Blocks are published after they have been committed to the database, and a sink failing
+//! or hanging never halts sync. Instead, blocks are kept in a bounded retry buffer and published
+//! again along with the next committed block, which makes delivery at-least-once as long as the
+//! buffers do not overflow.
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+
+use anyhow::Context;
+use pathfinder_common::{
+    BlockHash, BlockNumber, ClassHash, ContractAddress, StateCommitment, StateUpdate,
+    StorageAddress, StorageValue,
+};
+
+/// The maximum number of blocks kept for republishing while a sink is failing. Once exceeded,
+/// the oldest blocks are dropped.
+const RETRY_BUFFER_CAPACITY: usize = 1000;
+
+/// The maximum number of blocks waiting to be handed to the sink. Once exceeded, as happens when
+/// the sink hangs, newly committed blocks are dropped.
+const QUEUE_CAPACITY: usize = 100;
+
+/// A block which has been committed to the database.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct CommittedBlock {
+    pub number: BlockNumber,
+    pub hash: BlockHash,
+    pub state_commitment: StateCommitment,
+    pub storage_diffs: HashMap<ContractAddress, HashMap<StorageAddress, StorageValue>>,
+    /// Contracts deployed in this block, along with their class.
+    pub deployed_contracts: HashMap<ContractAddress, ClassHash>,
+}
+
+impl CommittedBlock {
+    pub fn new(
+        number: BlockNumber,
+        state_commitment: StateCommitment,
+        state_update: &StateUpdate,
+    ) -> Self {
+        use pathfinder_common::state_update::ContractClassUpdate;
+
+        let storage_diffs = state_update
+            .contract_updates
+            .iter()
+            .filter(|(_, update)| !update.storage.is_empty())
+            .map(|(address, update)| (*address, update.storage.clone()))
+            .chain(
+                state_update
+                    .system_contract_updates
+                    .iter()
+                    .map(|(address, update)| (*address, update.storage.clone())),
+            )
+            .collect();
+
+        let deployed_contracts = state_update
+            .contract_updates
+            .iter()
+            .filter_map(|(address, update)| match update.class {
+                Some(ContractClassUpdate::Deploy(class_hash)) => Some((*address, class_hash)),
+                _ => None,
+            })
+            .collect();
+
+        Self {
+            number,
+            hash: state_update.block_hash,
+            state_commitment,
+            storage_diffs,
+            deployed_contracts,
+        }
+    }
+}
+
+/// A destination for committed blocks.
+pub trait BlockSink: Send + Sync {
+    fn publish(&self, block: &CommittedBlock) -> anyhow::Result<()>;
+}
+
+/// Discards all blocks. This is the default sink.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoopSink;
+
+impl BlockSink for NoopSink {
+    fn publish(&self, _block: &CommittedBlock) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Appends every block as a line of JSON to a file.
+#[derive(Clone, Debug)]
+pub struct FileSink {
+    path: PathBuf,
+}
+
+impl FileSink {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl BlockSink for FileSink {
+    fn publish(&self, block: &CommittedBlock) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(block).context("Serializing block")?;
+        line.push(b'\n');
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .context("Opening sink file")?;
+        file.write_all(&line).context("Writing to sink file")
+    }
+}
+
+/// Publishes committed blocks to a [BlockSink], buffering those which failed to publish.
+///
+/// The sink is called from a dedicated thread, which receives blocks through a bounded queue, so
+/// that a slow or hanging sink cannot hold up sync. Clones share the same queue, so that no blocks
+/// are lost when the consumer is restarted.
+#[derive(Clone, Default)]
+pub struct Publisher {
+    /// [None] if there is no sink to publish to.
+    queue: Option<SyncSender<CommittedBlock>>,
+}
+
+impl Publisher {
+    pub fn new(sink: Arc<dyn BlockSink>) -> Self {
+        let (queue, blocks) = std::sync::mpsc::sync_channel(QUEUE_CAPACITY);
+        std::thread::spawn(move || publish_blocks(sink.as_ref(), blocks));
+
+        Self { queue: Some(queue) }
+    }
+
+    /// Queues `block` for publishing after any previously queued or failed blocks, in order.
+    ///
+    /// Never blocks. If the queue is full because the sink is not keeping up, the block is dropped
+    /// instead.
+    pub fn publish(&self, block: CommittedBlock) {
+        let Some(queue) = &self.queue else {
+            return;
+        };
+
+        match queue.try_send(block) {
+            Ok(()) => {}
+            Err(TrySendError::Full(block)) => {
+                tracing::warn!(block=%block.number, "Block sink is not keeping up, dropping block");
+            }
+            Err(TrySendError::Disconnected(block)) => {
+                tracing::warn!(block=%block.number, "Block sink stopped, dropping block");
+            }
+        }
+    }
+}
+
+/// Publishes the received `blocks` to `sink` until all [Publisher]s are dropped. Failures are
+/// logged and the affected blocks are retried along with the next block received.
+fn publish_blocks(sink: &dyn BlockSink, blocks: Receiver<CommittedBlock>) {
+    let mut pending = VecDeque::new();
+
+    for block in blocks {
+        if pending.len() == RETRY_BUFFER_CAPACITY {
+            if let Some(dropped) = pending.pop_front() {
+                tracing::warn!(block=%dropped.number, "Block sink retry buffer is full, dropping block");
+            }
+        }
+        pending.push_back(block);
+
+        while let Some(block) = pending.front() {
+            if let Err(e) = sink.publish(block) {
+                tracing::warn!(block=%block.number, buffered=%pending.len(), error=?e, "Failed to publish block");
+                break;
+            }
+            pending.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use pathfinder_common::macro_prelude::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Condvar, Mutex};
+    use std::time::Duration;
+
+    /// Keeps all published blocks in memory.
+    #[derive(Default)]
+    pub(crate) struct MemorySink {
+        blocks: Mutex<Vec<CommittedBlock>>,
+        published: Condvar,
+        /// The number of calls to fail before publishing succeeds.
+        pub failures: AtomicUsize,
+    }
+
+    impl MemorySink {
+        /// Waits for `count` blocks to have been published and returns them.
+        pub fn wait_for(&self, count: usize) -> Vec<CommittedBlock> {
+            let (blocks, _) = self
+                .published
+                .wait_timeout_while(
+                    self.blocks.lock().unwrap(),
+                    Duration::from_secs(5),
+                    |blocks| blocks.len() < count,
+                )
+                .unwrap();
+            blocks.clone()
+        }
+    }
+
+    impl BlockSink for MemorySink {
+        fn publish(&self, block: &CommittedBlock) -> anyhow::Result<()> {
+            let failing = self
+                .failures
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+                .is_ok();
+            anyhow::ensure!(!failing, "Sink unavailable");
+
+            self.blocks.lock().unwrap().push(block.clone());
+            self.published.notify_all();
+            Ok(())
+        }
+    }
+
+    fn block(number: u64) -> CommittedBlock {
+        CommittedBlock::new(
+            BlockNumber::new_or_panic(number),
+            StateCommitment::ZERO,
+            &StateUpdate::default()
+                .with_block_hash(BlockHash(stark_hash::Felt::from_u64(number)))
+                .with_deployed_contract(contract_address!("0x1234"), class_hash!("0xabcd"))
+                .with_storage_update(
+                    contract_address!("0x1234"),
+                    storage_address!("0x1"),
+                    storage_value!("0x2"),
+                ),
+        )
+    }
+
+    #[test]
+    fn failed_blocks_are_retried_in_order() {
+        let sink = Arc::new(MemorySink::default());
+        let publisher = Publisher::new(sink.clone());
+
+        // Fails publishing block 0, and then again when retrying it along with block 1.
+        sink.failures.store(2, Ordering::Relaxed);
+        publisher.publish(block(0));
+        publisher.publish(block(1));
+        publisher.publish(block(2));
+
+        assert_eq!(sink.wait_for(3), vec![block(0), block(1), block(2)]);
+    }
+
+    #[test]
+    fn hanging_sink_does_not_block() {
+        /// Blocks until the test ends.
+        struct HangingSink(Mutex<std::sync::mpsc::Receiver<()>>);
+
+        impl BlockSink for HangingSink {
+            fn publish(&self, _block: &CommittedBlock) -> anyhow::Result<()> {
+                let _ = self.0.lock().unwrap().recv();
+                Ok(())
+            }
+        }
+
+        let (_release, hang) = std::sync::mpsc::channel();
+        let publisher = Publisher::new(Arc::new(HangingSink(Mutex::new(hang))));
+
+        // Fills the queue, after which blocks are dropped instead of waiting for the sink.
+        for number in 0..QUEUE_CAPACITY as u64 + 10 {
+            publisher.publish(block(number));
+        }
+    }
+
+    #[test]
+    fn file_sink_writes_json_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("blocks.jsonl");
+        let sink = FileSink::new(path.clone());
+
+        sink.publish(&block(0)).unwrap();
+        sink.publish(&block(1)).unwrap();
+
+        let contents = std::fs::read_to_string(path).unwrap();
+        let numbers = contents
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["number"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(numbers, vec![serde_json::json!(0), serde_json::json!(1)]);
+    }
+}
@@ -0,0 +1,10 @@
+//! Methods for node operators, which are only served if an admin token is configured.
+use crate::jsonrpc::{RpcRouter, RpcRouterBuilder};
+
+pub(crate) mod methods;
+
+#[rustfmt::skip]
+pub fn register_routes() -> RpcRouterBuilder {
+    RpcRouter::builder("v0.1")
+        .register("admin_reverifyRange", methods::reverify_range)
+}
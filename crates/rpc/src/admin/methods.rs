@@ -0,0 +1,3 @@
+mod reverify_range;
+
+pub(crate) use reverify_range::reverify_range;
@@ -0,0 +1,172 @@
+use anyhow::Context;
+use pathfinder_common::BlockNumber;
+use pathfinder_storage::BlockId;
+
+use crate::context::RpcContext;
+
+#[derive(serde::Deserialize, Debug, PartialEq, Eq)]
+pub struct ReverifyRangeInput {
+    from: BlockNumber,
+    to: BlockNumber,
+}
+
+crate::error::generate_rpc_error_subset!(ReverifyRangeError: BlockNotFound);
+
+/// The number of blocks re-verified per database transaction. Reopening the transaction keeps a
+/// long range from holding back WAL checkpoints for the whole run.
+const BLOCKS_PER_TRANSACTION: u64 = 100;
+
+/// Re-verifies the state commitments of the blocks in the inclusive range `from..=to` against
+/// the data in the database, and returns the first block which diverges.
+///
+/// The blocks are re-verified in batches of [BLOCKS_PER_TRANSACTION], each in its own database
+/// transaction.
+pub async fn reverify_range(
+    context: RpcContext,
+    input: ReverifyRangeInput,
+) -> Result<Option<BlockNumber>, ReverifyRangeError> {
+    reverify_in_chunks(context, input, BLOCKS_PER_TRANSACTION).await
+}
+
+async fn reverify_in_chunks(
+    context: RpcContext,
+    input: ReverifyRangeInput,
+    blocks_per_transaction: u64,
+) -> Result<Option<BlockNumber>, ReverifyRangeError> {
+    if input.from > input.to {
+        return Err(ReverifyRangeError::Custom(anyhow::anyhow!(
+            "Range start {} is after its end {}",
+            input.from,
+            input.to
+        )));
+    }
+
+    let span = tracing::Span::current();
+
+    tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+
+        let mut db = context
+            .storage
+            .connection()
+            .context("Opening database connection")?;
+
+        let latest = db
+            .transaction()
+            .context("Creating database transaction")?
+            .block_id(BlockId::Latest)
+            .context("Fetching latest block number")?;
+        match latest {
+            Some((latest, _)) if latest >= input.to => {}
+            _ => return Err(ReverifyRangeError::BlockNotFound),
+        }
+
+        let mut from = input.from.get();
+        let divergence = loop {
+            let to = input.to.get().min(from + blocks_per_transaction - 1);
+            let tx = db.transaction().context("Creating database transaction")?;
+            let divergence = pathfinder_merkle_tree::reverify::reverify_range(
+                &tx,
+                BlockNumber::new_or_panic(from),
+                BlockNumber::new_or_panic(to),
+            )
+            .context("Re-verifying blocks")?;
+
+            if divergence.is_some() || to == input.to.get() {
+                break divergence;
+            }
+            from = to + 1;
+        };
+
+        if let Some((block, divergence)) = &divergence {
+            tracing::warn!(%block, ?divergence, "Stored state diverges from recomputed state");
+        }
+
+        Ok(divergence.map(|(block, _)| block))
+    })
+    .await
+    .context("Joining database task")?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::StateUpdate;
+    use pathfinder_storage::Storage;
+
+    /// Creates a database with three blocks, each deploying a contract.
+    ///
+    /// If `tamper` is set, the storage commitment stored in that block's header is replaced
+    /// with garbage.
+    fn setup(tamper: Option<BlockNumber>) -> RpcContext {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let class = class_hash_bytes!(b"class");
+        let state_updates = [
+            StateUpdate::default()
+                .with_declared_cairo_class(class)
+                .with_deployed_contract(contract_address_bytes!(b"contract 0"), class),
+            StateUpdate::default()
+                .with_deployed_contract(contract_address_bytes!(b"contract 1"), class),
+            StateUpdate::default()
+                .with_deployed_contract(contract_address_bytes!(b"contract 2"), class),
+        ];
+        pathfinder_merkle_tree::test_utils::insert_blocks(&tx, &state_updates, tamper);
+        tx.commit().unwrap();
+
+        RpcContext::for_tests().with_storage(storage)
+    }
+
+    fn input(from: u64, to: u64) -> ReverifyRangeInput {
+        ReverifyRangeInput {
+            from: BlockNumber::new_or_panic(from),
+            to: BlockNumber::new_or_panic(to),
+        }
+    }
+
+    #[tokio::test]
+    async fn intact_range() {
+        let context = setup(None);
+
+        let result = reverify_range(context, input(0, 2)).await.unwrap();
+        assert_eq!(result, None);
+    }
+
+    #[tokio::test]
+    async fn tampered_range() {
+        let tampered = BlockNumber::new_or_panic(1);
+
+        let result = reverify_range(setup(Some(tampered)), input(0, 2))
+            .await
+            .unwrap();
+        assert_eq!(result, Some(tampered));
+
+        // Blocks outside of the range are not checked.
+        let result = reverify_range(setup(Some(tampered)), input(2, 2))
+            .await
+            .unwrap();
+        assert_eq!(result, None);
+    }
+
+    #[tokio::test]
+    async fn range_spanning_several_transactions() {
+        let context = setup(None);
+        let result = reverify_in_chunks(context, input(0, 2), 1).await.unwrap();
+        assert_eq!(result, None);
+
+        let tampered = BlockNumber::new_or_panic(2);
+        let result = reverify_in_chunks(setup(Some(tampered)), input(0, 2), 2)
+            .await
+            .unwrap();
+        assert_eq!(result, Some(tampered));
+    }
+
+    #[tokio::test]
+    async fn range_beyond_latest_block() {
+        let error = reverify_range(setup(None), input(0, 3)).await.unwrap_err();
+        assert_matches::assert_matches!(error, ReverifyRangeError::BlockNotFound);
+    }
+}
@@ -0,0 +1,96 @@
+//! An in-memory cache of parsed class definitions.
+//!
+//! Class definitions are immutable per class hash, so cached entries never need to be invalidated.
+//! The cache is bounded by the number of classes it holds, evicting the least recently used class.
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+
+use pathfinder_common::ClassHash;
+
+use crate::v02::types::ContractClass;
+
+/// Maps class hashes to their parsed definitions.
+#[derive(Clone)]
+pub struct ClassCache(Arc<Mutex<lru::LruCache<ClassHash, ContractClass>>>);
+
+impl ClassCache {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self(Arc::new(Mutex::new(lru::LruCache::new(capacity))))
+    }
+
+    /// Returns the class from the cache, or loads and caches it using `load` on a miss.
+    ///
+    /// Classes which `load` does not find are not cached.
+    pub fn get_or_load(
+        &self,
+        class_hash: ClassHash,
+        load: impl FnOnce() -> anyhow::Result<Option<ContractClass>>,
+    ) -> anyhow::Result<Option<ContractClass>> {
+        if let Some(class) = self.0.lock().unwrap().get(&class_hash) {
+            return Ok(Some(class.clone()));
+        }
+
+        // The lock is not held while loading, so that a slow load does not block other readers.
+        // Concurrent misses for the same class may therefore load it more than once.
+        let class = load()?;
+        if let Some(class) = &class {
+            self.0.lock().unwrap().put(class_hash, class.clone());
+        }
+
+        Ok(class)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::macro_prelude::*;
+    use starknet_gateway_test_fixtures::class_definitions::CONTRACT_DEFINITION;
+
+    #[test]
+    fn second_read_is_served_from_cache() {
+        let cache = ClassCache::new(NonZeroUsize::new(1).unwrap());
+        let class = ContractClass::from_definition_bytes(CONTRACT_DEFINITION).unwrap();
+        let mut loads = 0;
+
+        for _ in 0..2 {
+            let cached = cache
+                .get_or_load(class_hash_bytes!(b"class"), || {
+                    loads += 1;
+                    Ok(Some(class.clone()))
+                })
+                .unwrap();
+            assert_eq!(cached, Some(class.clone()));
+        }
+        assert_eq!(loads, 1);
+
+        // Evicts the first class, which must then be loaded again.
+        cache
+            .get_or_load(class_hash_bytes!(b"other"), || Ok(Some(class.clone())))
+            .unwrap();
+        cache
+            .get_or_load(class_hash_bytes!(b"class"), || {
+                loads += 1;
+                Ok(Some(class.clone()))
+            })
+            .unwrap();
+        assert_eq!(loads, 2);
+    }
+
+    #[test]
+    fn missing_class_is_not_cached() {
+        let cache = ClassCache::new(NonZeroUsize::new(1).unwrap());
+        let mut loads = 0;
+
+        for _ in 0..2 {
+            let cached = cache
+                .get_or_load(class_hash_bytes!(b"class"), || {
+                    loads += 1;
+                    Ok(None)
+                })
+                .unwrap();
+            assert_eq!(cached, None);
+        }
+        assert_eq!(loads, 2);
+    }
+}
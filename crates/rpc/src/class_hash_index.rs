@@ -0,0 +1,189 @@
+//! An in-memory index of every contract's latest class hash.
+//!
+//! Serving `starknet_getClassHashAt` for the latest block from memory avoids a database query per
+//! request. The index is optional as it holds an entry for every deployed contract.
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use anyhow::Context;
+use pathfinder_common::{BlockNumber, ClassHash, ContractAddress};
+use pathfinder_storage::Transaction;
+
+/// Maps each contract to its class hash as of the index's head block.
+///
+/// Sync keeps the index up to date as blocks are committed. Lookups are only answered if the index
+/// is at the caller's latest block, and callers should fall back to the database otherwise.
+#[derive(Clone, Default)]
+pub struct ClassHashIndex(Arc<RwLock<Inner>>);
+
+#[derive(Default)]
+struct Inner {
+    head: Option<BlockNumber>,
+    /// Set if a block was missed, in which case the index no longer reflects `head`.
+    stale: bool,
+    class_hashes: HashMap<ContractAddress, ClassHash>,
+}
+
+impl ClassHashIndex {
+    /// Builds the index from the latest state in the database.
+    pub fn load(tx: &Transaction<'_>) -> anyhow::Result<Self> {
+        let index = Self::default();
+        index.reload(tx)?;
+        Ok(index)
+    }
+
+    /// Rebuilds the index from the latest state in the database, e.g. after a reorg.
+    pub fn reload(&self, tx: &Transaction<'_>) -> anyhow::Result<()> {
+        const PAGE_SIZE: usize = 10_000;
+
+        let head = tx
+            .block_id(pathfinder_storage::BlockId::Latest)
+            .context("Querying latest block")?
+            .map(|(number, _)| number);
+
+        let mut class_hashes = HashMap::new();
+        let mut offset = 0;
+        loop {
+            let page = tx
+                .contracts(offset, PAGE_SIZE)
+                .context("Querying contracts")?;
+            let count = page.len();
+            class_hashes.extend(page);
+
+            if count < PAGE_SIZE {
+                break;
+            }
+            offset += count;
+        }
+
+        let mut inner = self.0.write().unwrap();
+        inner.head = head;
+        inner.stale = false;
+        inner.class_hashes = class_hashes;
+
+        Ok(())
+    }
+
+    /// Applies the deployed and replaced classes of `block`.
+    ///
+    /// `block` must directly follow the index's head, otherwise the index is marked as stale until
+    /// it is [reloaded](Self::reload).
+    pub fn update(
+        &self,
+        block: BlockNumber,
+        classes: impl IntoIterator<Item = (ContractAddress, ClassHash)>,
+    ) {
+        let mut inner = self.0.write().unwrap();
+        if inner.stale {
+            return;
+        }
+
+        let expected = inner
+            .head
+            .map(|head| head + 1)
+            .unwrap_or(BlockNumber::GENESIS);
+        if block != expected {
+            tracing::debug!(%block, %expected, "Class hash index is stale");
+            inner.stale = true;
+            return;
+        }
+
+        inner.class_hashes.extend(classes);
+        inner.head = Some(block);
+    }
+
+    /// Returns the class hash of `contract` if the index is at block `latest`.
+    ///
+    /// [None] means that the index cannot answer, either because it is not at `latest` or because
+    /// the contract is not deployed.
+    pub fn get(&self, latest: BlockNumber, contract: ContractAddress) -> Option<ClassHash> {
+        let inner = self.0.read().unwrap();
+        if inner.stale || inner.head != Some(latest) {
+            return None;
+        }
+
+        inner.class_hashes.get(&contract).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::{BlockHeader, StateUpdate};
+    use pathfinder_storage::{BlockId, Storage};
+
+    #[test]
+    fn matches_database_and_tracks_new_blocks() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let contract0 = contract_address!("0x100");
+        let contract1 = contract_address!("0x200");
+        let contract2 = contract_address!("0x300");
+
+        let genesis = BlockHeader::builder().finalize_with_hash(block_hash!("0xb0"));
+        tx.insert_block_header(&genesis).unwrap();
+        tx.insert_state_update(
+            genesis.number,
+            &StateUpdate::default()
+                .with_deployed_contract(contract0, class_hash!("0xc0"))
+                .with_deployed_contract(contract1, class_hash!("0xc1")),
+        )
+        .unwrap();
+
+        let index = ClassHashIndex::load(&tx).unwrap();
+        for contract in [contract0, contract1, contract2] {
+            let expected = tx.contract_class_hash(BlockId::Latest, contract).unwrap();
+            assert_eq!(index.get(genesis.number, contract), expected);
+        }
+
+        let block1 = genesis
+            .child_builder()
+            .finalize_with_hash(block_hash!("0xb1"));
+        let state_update = StateUpdate::default()
+            .with_deployed_contract(contract2, class_hash!("0xc2"))
+            .with_replaced_class(contract0, class_hash!("0xc3"));
+        tx.insert_block_header(&block1).unwrap();
+        tx.insert_state_update(block1.number, &state_update)
+            .unwrap();
+
+        // The index does not answer for a block it has not seen yet.
+        assert_eq!(index.get(block1.number, contract0), None);
+
+        index.update(
+            block1.number,
+            state_update
+                .contract_updates
+                .iter()
+                .filter_map(|(address, update)| {
+                    Some((*address, update.class.as_ref()?.class_hash()))
+                }),
+        );
+        for contract in [contract0, contract1, contract2] {
+            let expected = tx.contract_class_hash(BlockId::Latest, contract).unwrap();
+            assert_eq!(index.get(block1.number, contract), expected);
+        }
+    }
+
+    #[test]
+    fn skipped_block_marks_index_stale() {
+        let index = ClassHashIndex::default();
+        let contract = contract_address!("0x100");
+
+        index.update(BlockNumber::GENESIS, [(contract, class_hash!("0xc0"))]);
+        assert_eq!(
+            index.get(BlockNumber::GENESIS, contract),
+            Some(class_hash!("0xc0"))
+        );
+
+        index.update(BlockNumber::new_or_panic(2), []);
+        assert_eq!(index.get(BlockNumber::GENESIS, contract), None);
+        assert_eq!(index.get(BlockNumber::new_or_panic(2), contract), None);
+
+        // Subsequent blocks are ignored until the index is reloaded.
+        index.update(BlockNumber::new_or_panic(3), []);
+        assert_eq!(index.get(BlockNumber::new_or_panic(3), contract), None);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::class_cache::ClassCache;
+use crate::class_hash_index::ClassHashIndex;
 use crate::gas_price;
 pub use crate::jsonrpc::websocket::WebsocketContext;
 use crate::pending::PendingData;
@@ -5,6 +7,7 @@ use crate::pending::PendingWatcher;
 use crate::SyncState;
 use pathfinder_common::ChainId;
 use pathfinder_storage::Storage;
+use starknet_gateway_client::FeeEstimateCache;
 use std::num::NonZeroUsize;
 use std::sync::Arc;
 
@@ -22,8 +25,14 @@ pub struct RpcContext {
     pub sequencer: SequencerClient,
     pub websocket: Option<WebsocketContext>,
     pub batch_concurrency_limit: NonZeroUsize,
+    pub class_hash_index: Option<ClassHashIndex>,
+    pub class_cache: Option<ClassCache>,
+    pub fee_estimate_cache: FeeEstimateCache,
 }
 
+/// The number of sequencer fee estimates kept by [RpcContext::fee_estimate_cache].
+const FEE_ESTIMATE_CACHE_SIZE: usize = 1024;
+
 impl RpcContext {
     pub fn new(
         storage: Storage,
@@ -45,6 +54,11 @@ impl RpcContext {
             sequencer,
             websocket: None,
             batch_concurrency_limit,
+            class_hash_index: None,
+            class_cache: None,
+            fee_estimate_cache: FeeEstimateCache::new(
+                NonZeroUsize::new(FEE_ESTIMATE_CACHE_SIZE).unwrap(),
+            ),
         }
     }
 
@@ -111,4 +125,20 @@ impl RpcContext {
             ..self
         }
     }
+
+    /// Serves latest block class hash queries from `index` instead of the database.
+    pub fn with_class_hash_index(self, index: ClassHashIndex) -> Self {
+        Self {
+            class_hash_index: Some(index),
+            ..self
+        }
+    }
+
+    /// Caches parsed class definitions in `cache`.
+    pub fn with_class_cache(self, cache: ClassCache) -> Self {
+        Self {
+            class_cache: Some(cache),
+            ..self
+        }
+    }
 }
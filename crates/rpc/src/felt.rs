@@ -58,6 +58,13 @@ impl From<RpcFelt> for Felt {
 #[derive(serde::Serialize)]
 pub struct RpcFelt251(RpcFelt);
 
+/// An RPC input wrapper around [Felt] which accepts decimal strings in addition to
+/// the `0x` prefixed hex strings accepted by [RpcFelt].
+///
+/// This is intended for interoperability with tooling which emits felts as decimals, and can be
+/// used on input fields with `#[serde_as(as = "RpcFeltOrDecimal")]`.
+pub struct RpcFeltOrDecimal(pub Felt);
+
 mod serialization {
     //! Blanket [serde::Serialize] and [serde_with::SerializeAs] implementations for [RpcFelt] and [RpcFelt251]
     //! supported types.
@@ -246,6 +253,60 @@ mod deserialization {
         }
     }
 
+    impl<'de, T> serde_with::DeserializeAs<'de, T> for RpcFeltOrDecimal
+    where
+        T: From<RpcFelt>,
+    {
+        fn deserialize_as<D>(deserializer: D) -> Result<T, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            use serde::Deserialize;
+
+            let felt: RpcFeltOrDecimal = Deserialize::deserialize(deserializer)?;
+
+            Ok(T::from(RpcFelt(felt.0)))
+        }
+    }
+
+    impl<'de> serde::Deserialize<'de> for RpcFeltOrDecimal {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            struct FeltVisitor;
+
+            impl<'de> serde::de::Visitor<'de> for FeltVisitor {
+                type Value = RpcFeltOrDecimal;
+
+                fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    formatter.write_str("a decimal string or a '0x' prefixed hex string")
+                }
+
+                fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+                where
+                    E: serde::de::Error,
+                {
+                    match v.as_bytes() {
+                        &[b'0', b'x', ..] => stark_hash::Felt::from_hex_str(v)
+                            .map_err(|e| serde::de::Error::custom(e))
+                            .map(RpcFeltOrDecimal),
+                        digits if !digits.is_empty() && digits.iter().all(u8::is_ascii_digit) => {
+                            pathfinder_serde::starkhash_from_dec_str(v)
+                                .map_err(|e| serde::de::Error::custom(e))
+                                .map(RpcFeltOrDecimal)
+                        }
+                        _ => Err(serde::de::Error::custom(
+                            "Expected a decimal string or a '0x' prefixed hex string",
+                        )),
+                    }
+                }
+            }
+
+            deserializer.deserialize_str(FeltVisitor)
+        }
+    }
+
     impl<'de, T> serde_with::DeserializeAs<'de, T> for RpcFelt251
     where
         T: From<RpcFelt251>,
@@ -278,3 +339,60 @@ mod deserialization {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod rpc_felt_or_decimal {
+        use super::*;
+        use pathfinder_common::felt;
+
+        fn parse(input: &str) -> Result<Felt, serde_json::Error> {
+            serde_json::from_value::<RpcFeltOrDecimal>(serde_json::json!(input)).map(|f| f.0)
+        }
+
+        #[test]
+        fn decimal() {
+            assert_eq!(parse("0").unwrap(), Felt::ZERO);
+            assert_eq!(parse("1234").unwrap(), felt!("0x4d2"));
+        }
+
+        #[test]
+        fn hex() {
+            assert_eq!(parse("0x4d2").unwrap(), felt!("0x4d2"));
+        }
+
+        #[test]
+        fn largest_decimal() {
+            // The field modulus minus one.
+            let value =
+                "3618502788666131213697322783095070105623107215331596699973092056135872020480";
+            assert_eq!(
+                parse(value).unwrap(),
+                felt!("0x800000000000011000000000000000000000000000000000000000000000000")
+            );
+        }
+
+        #[test]
+        fn out_of_range_decimal() {
+            // The field modulus.
+            let value =
+                "3618502788666131213697322783095070105623107215331596699973092056135872020481";
+            parse(value).unwrap_err();
+        }
+
+        #[test]
+        fn invalid() {
+            parse("").unwrap_err();
+            parse("abc").unwrap_err();
+            parse("-1").unwrap_err();
+            parse("12a").unwrap_err();
+        }
+
+        #[test]
+        fn rpc_felt_still_requires_hex() {
+            serde_json::from_value::<RpcFelt>(serde_json::json!("1234")).unwrap_err();
+        }
+    }
+}
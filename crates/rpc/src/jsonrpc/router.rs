@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 use std::num::NonZeroUsize;
 
+use anyhow::Context;
+
 use axum::async_trait;
 use axum::extract::State;
 use axum::http::StatusCode;
@@ -17,6 +19,10 @@ use crate::jsonrpc::error::RpcError;
 use crate::jsonrpc::request::{RawParams, RpcRequest};
 use crate::jsonrpc::response::{RpcResponse, RpcResult};
 
+/// How long a batch may read from a single database snapshot, after which its remaining requests
+/// read the database as normal.
+const MAX_BATCH_SNAPSHOT_DURATION: std::time::Duration = std::time::Duration::from_secs(5);
+
 #[derive(Clone)]
 pub struct RpcRouter {
     context: RpcContext,
@@ -67,6 +73,32 @@ impl RpcRouter {
         RpcRouterBuilder::new(version)
     }
 
+    /// Returns a router whose methods all read the database as it is now, even once sync commits
+    /// further blocks.
+    ///
+    /// Falls back to reading the database as normal if no snapshot can be taken.
+    async fn with_snapshot(&self) -> Self {
+        let storage = self.context.storage.clone();
+        let snapshot = tokio::task::spawn_blocking(move || storage.snapshot())
+            .await
+            .context("Database read panic or shutting down")
+            .and_then(|snapshot| snapshot);
+
+        match snapshot {
+            Ok(storage) => Self {
+                context: RpcContext {
+                    storage,
+                    ..self.context.clone()
+                },
+                ..self.clone()
+            },
+            Err(e) => {
+                tracing::debug!(error=%e, "Not running batch on a database snapshot");
+                self.clone()
+            }
+        }
+    }
+
     /// Parses and executes a request. Returns [None] if its a notification.
     async fn run_request<'a>(&self, request: &'a str) -> Option<RpcResponse<'a>> {
         let Ok(request) = serde_json::from_str::<RpcRequest<'_>>(request) else {
@@ -179,6 +211,28 @@ pub async fn rpc_handler(
                 return RpcResponse::INVALID_REQUEST.into_response();
             }
 
+            // Run the batch on a single snapshot of the database, so that a block committed by
+            // sync mid-batch cannot tear the batch's view of state.
+            //
+            // This comes at a cost: the batch's database reads share one connection and run one
+            // at a time whatever the concurrency limit, and the snapshot's read transaction holds
+            // back WAL checkpoints. The snapshot is therefore ended after
+            // `MAX_BATCH_SNAPSHOT_DURATION`, so that a batch waiting on slow gateway calls cannot
+            // hold it for long. Requests still running by then read the database as normal.
+            let state = state.with_snapshot().await;
+            let snapshot_timeout = {
+                let storage = state.context.storage.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(MAX_BATCH_SNAPSHOT_DURATION).await;
+                    tokio::task::spawn_blocking(move || {
+                        if let Err(e) = storage.end_snapshot() {
+                            tracing::warn!(error=%e, "Failed to end batch snapshot");
+                        }
+                    })
+                    .await
+                })
+            };
+
             let responses = run_concurrently(
                 state.context.batch_concurrency_limit,
                 requests.into_iter(),
@@ -188,6 +242,8 @@ pub async fn rpc_handler(
             .flatten()
             .collect::<Vec<RpcResponse<'_>>>();
 
+            snapshot_timeout.abort();
+
             // All requests were notifications.
             if responses.is_empty() {
                 return ().into_response();
@@ -779,6 +835,148 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn batch_runs_on_a_single_snapshot() {
+        use pathfinder_common::macro_prelude::*;
+        use pathfinder_common::{BlockHeader, BlockId};
+        use pathfinder_storage::{JournalMode, Storage};
+
+        #[derive(Debug, Deserialize)]
+        struct Input {
+            block_id: BlockId,
+        }
+
+        // Resolves the block id as a method reading the database would.
+        async fn resolve(context: RpcContext, input: Input) -> RpcResult {
+            let mut db = context.storage.connection().unwrap();
+            let tx = db.transaction().unwrap();
+            let number = tx
+                .block_id(input.block_id.try_into().unwrap())
+                .unwrap()
+                .map(|(number, _)| number);
+            Ok(json!(number))
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::migrate(dir.path().join("test.sqlite"), JournalMode::WAL)
+            .unwrap()
+            .create_pool(std::num::NonZeroU32::new(3).unwrap())
+            .unwrap();
+
+        let genesis = BlockHeader::builder().finalize_with_hash(block_hash_bytes!(b"genesis"));
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+        tx.insert_block_header(&genesis).unwrap();
+        tx.commit().unwrap();
+        drop(db);
+
+        // Commits a new block, as sync would. This writes through its own storage handle, which
+        // is not replaced by the batch's snapshot.
+        let sync_storage = storage.clone();
+        let commit = move |_: RpcContext| {
+            let storage = sync_storage.clone();
+            async move {
+                let mut db = storage.connection().unwrap();
+                let tx = db.transaction().unwrap();
+                let latest = tx
+                    .block_header(pathfinder_storage::BlockId::Latest)
+                    .unwrap()
+                    .unwrap();
+                let header = latest
+                    .child_builder()
+                    .finalize_with_hash(block_hash_bytes!(b"committed mid-batch"));
+                tx.insert_block_header(&header).unwrap();
+                tx.commit().unwrap();
+                Ok::<_, RpcError>(json!(header.number))
+            }
+        };
+
+        // Outlives the batch's snapshot.
+        async fn wait(_: RpcContext) -> RpcResult {
+            tokio::time::sleep(MAX_BATCH_SNAPSHOT_DURATION + std::time::Duration::from_secs(1))
+                .await;
+            Ok(Value::Null)
+        }
+
+        let router = RpcRouter::builder("vTEST")
+            .register("resolve", resolve)
+            .register("commit", commit)
+            .register("wait", wait)
+            .build(RpcContext::for_tests().with_storage(storage));
+        // Process the batch one request at a time so that the commit lands between the reads.
+        let router = RpcRouter {
+            context: RpcContext {
+                batch_concurrency_limit: NonZeroUsize::new(1).unwrap(),
+                ..router.context
+            },
+            ..router
+        };
+
+        let url = spawn_server(router).await;
+        let client = reqwest::Client::new();
+
+        let response = client
+            .post(url.clone())
+            .json(&json!([
+                {"jsonrpc": "2.0", "method": "resolve", "params": {"block_id": "latest"}, "id": 0},
+                {"jsonrpc": "2.0", "method": "commit", "id": 1},
+                {"jsonrpc": "2.0", "method": "resolve", "params": ["latest"], "id": 2},
+                {"jsonrpc": "2.0", "method": "resolve", "params": [{"block_number": 1}], "id": 3},
+            ]))
+            .send()
+            .await
+            .unwrap()
+            .json::<Value>()
+            .await
+            .unwrap();
+
+        // The block committed mid-batch is invisible to the rest of the batch.
+        let expected = json!([
+            {"jsonrpc": "2.0", "result": 0, "id": 0},
+            {"jsonrpc": "2.0", "result": 1, "id": 1},
+            {"jsonrpc": "2.0", "result": 0, "id": 2},
+            {"jsonrpc": "2.0", "result": null, "id": 3},
+        ]);
+        assert_eq!(response, expected);
+
+        // Later requests see the committed block.
+        let response = client
+            .post(url)
+            .json(&json!(
+                {"jsonrpc": "2.0", "method": "resolve", "params": {"block_id": "latest"}, "id": 4}
+            ))
+            .send()
+            .await
+            .unwrap()
+            .json::<Value>()
+            .await
+            .unwrap();
+
+        assert_eq!(response, json!({"jsonrpc": "2.0", "result": 1, "id": 4}));
+
+        // A batch outliving its snapshot sees blocks committed once the snapshot ended.
+        let response = client
+            .post(url)
+            .json(&json!([
+                {"jsonrpc": "2.0", "method": "commit", "id": 5},
+                {"jsonrpc": "2.0", "method": "wait", "id": 6},
+                {"jsonrpc": "2.0", "method": "resolve", "params": ["latest"], "id": 7},
+            ]))
+            .send()
+            .await
+            .unwrap()
+            .json::<Value>()
+            .await
+            .unwrap();
+
+        let expected = json!([
+            {"jsonrpc": "2.0", "result": 2, "id": 5},
+            {"jsonrpc": "2.0", "result": null, "id": 6},
+            {"jsonrpc": "2.0", "result": 2, "id": 7},
+        ]);
+        assert_eq!(response, expected);
+    }
+
     #[tokio::test]
     async fn rejects_non_json_content_header() {
         async fn always_success(_ctx: RpcContext) -> RpcResult {
@@ -1,4 +1,7 @@
 //! Starknet node JSON-RPC related modules.
+mod admin;
+pub mod class_cache;
+pub mod class_hash_index;
 pub mod context;
 mod error;
 mod executor;
@@ -16,6 +19,7 @@ pub mod v04;
 pub mod v05;
 
 pub use executor::compose_executor_transaction;
+pub use felt::RpcFeltOrDecimal;
 pub use pending::PendingData;
 
 use crate::jsonrpc::rpc_handler;
@@ -35,6 +39,7 @@ use std::{net::SocketAddr, result::Result};
 use tokio::sync::RwLock;
 use tokio::task::JoinHandle;
 use tower_http::cors::CorsLayer;
+use tower_http::validate_request::ValidateRequestHeaderLayer;
 
 const DEFAULT_MAX_CONNECTIONS: usize = 1024;
 
@@ -50,6 +55,8 @@ pub struct RpcServer {
     max_connections: usize,
     cors: Option<CorsLayer>,
     default_version: DefaultVersion,
+    /// Admin methods are only served if this is set, and require it as bearer token.
+    admin_token: Option<String>,
 }
 
 impl RpcServer {
@@ -60,6 +67,7 @@ impl RpcServer {
             max_connections: DEFAULT_MAX_CONNECTIONS,
             cors: None,
             default_version,
+            admin_token: None,
         }
     }
 
@@ -75,6 +83,15 @@ impl RpcServer {
         }
     }
 
+    /// Serves the admin methods on `/rpc/admin/v0.1`, to requests which carry `token` as their
+    /// bearer token.
+    pub fn with_admin_token(self, token: String) -> Self {
+        Self {
+            admin_token: Some(token),
+            ..self
+        }
+    }
+
     /// Starts the HTTP-RPC server.
     pub fn spawn(self) -> Result<(JoinHandle<anyhow::Result<()>>, SocketAddr), anyhow::Error> {
         use axum::routing::{get, post};
@@ -165,6 +182,16 @@ impl RpcServer {
             .route("/rpc/pathfinder/v0.1", post(rpc_handler))
             .with_state(pathfinder_routes);
 
+        let router = match &self.admin_token {
+            Some(token) => router
+                .route(
+                    "/rpc/admin/v0.1",
+                    post(rpc_handler).route_layer(ValidateRequestHeaderLayer::bearer(token)),
+                )
+                .with_state(admin::register_routes().build(self.context.clone())),
+            None => router,
+        };
+
         let router = if self.context.websocket.is_some() {
             router.route("/ws", get(websocket_handler))
         } else {
@@ -195,12 +222,17 @@ impl RpcServer {
 
 pub struct SyncState {
     pub status: RwLock<Syncing>,
+    /// How far the latest synced block's timestamp lags behind the local clock.
+    ///
+    /// [None] until the first block has been synced.
+    pub seconds_behind_tip: RwLock<Option<u64>>,
 }
 
 impl Default for SyncState {
     fn default() -> Self {
         Self {
             status: RwLock::new(Syncing::False(false)),
+            seconds_behind_tip: RwLock::new(None),
         }
     }
 }
@@ -822,6 +854,71 @@ mod tests {
         assert!(!status.is_success());
     }
 
+    #[tokio::test]
+    async fn batch_request_over_http() {
+        use pathfinder_common::macro_prelude::*;
+
+        let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let context = RpcContext::for_tests();
+        let (_jh, addr) = RpcServer::new(addr, context, DefaultVersion::V04)
+            .spawn()
+            .unwrap();
+
+        let url = format!("http://{addr}/rpc/v0.4");
+        let request = json!([
+            {
+                "jsonrpc": "2.0",
+                "method": "starknet_blockNumber",
+                "id": 0,
+            },
+            {
+                "jsonrpc": "2.0",
+                "method": "starknet_getStorageAt",
+                "params": {
+                    "contract_address": contract_address_bytes!(b"contract 1"),
+                    "key": storage_address_bytes!(b"storage addr 0"),
+                    "block_id": "latest",
+                },
+                "id": 1,
+            },
+            {
+                "jsonrpc": "2.0",
+                "method": "starknet_doesNotExist",
+                "id": 2,
+            },
+        ]);
+
+        let response: serde_json::Value = reqwest::Client::new()
+            .post(url)
+            .json(&request)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        // Responses are returned in request order, each with its own envelope.
+        let responses = response.as_array().unwrap();
+        assert_eq!(responses.len(), 3);
+
+        assert_eq!(responses[0]["jsonrpc"], "2.0");
+        assert_eq!(responses[0]["id"], 0);
+        assert_eq!(responses[0]["result"], 2);
+
+        assert_eq!(responses[1]["id"], 1);
+        let value =
+            stark_hash::Felt::from_hex_str(responses[1]["result"].as_str().unwrap()).unwrap();
+        assert_eq!(
+            pathfinder_common::StorageValue(value),
+            storage_value_bytes!(b"storage value 2")
+        );
+
+        assert_eq!(responses[2]["id"], 2);
+        assert_eq!(responses[2]["error"]["code"], -32601);
+        assert!(responses[2].get("result").is_none());
+    }
+
     #[rustfmt::skip]
     #[rstest::rstest]
     #[case::root_api  ("/", "v04/starknet_api_openrpc.json",       &[])]
@@ -958,4 +1055,59 @@ mod tests {
             panic!("{failures:#?} were marked as excluded but are actually present");
         }
     }
+
+    #[tokio::test]
+    async fn admin_routes_require_token() {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": "admin_reverifyRange",
+            "params": {"from": 0, "to": 0},
+            "id": 0,
+        });
+        let client = reqwest::Client::new();
+
+        let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let (_jh, addr) = RpcServer::new(addr, RpcContext::for_tests(), DefaultVersion::V04)
+            .with_admin_token("secret".to_owned())
+            .spawn()
+            .unwrap();
+        let url = format!("http://{addr}/rpc/admin/v0.1");
+
+        let res = client
+            .post(&url)
+            .bearer_auth("secret")
+            .json(&request)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::OK);
+        let res: serde_json::Value = res.json().await.unwrap();
+        assert_ne!(res["error"]["code"], json!(-32601), "Method not found");
+
+        let res = client
+            .post(&url)
+            .bearer_auth("wrong")
+            .json(&request)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        let res = client.post(&url).json(&request).send().await.unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+        // Without a token, the admin methods are not served at all.
+        let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let (_jh, addr) = RpcServer::new(addr, RpcContext::for_tests(), DefaultVersion::V04)
+            .spawn()
+            .unwrap();
+        let res = client
+            .post(format!("http://{addr}/rpc/admin/v0.1"))
+            .bearer_auth("secret")
+            .json(&request)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), reqwest::StatusCode::NOT_FOUND);
+    }
 }
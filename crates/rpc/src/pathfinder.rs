@@ -6,6 +6,9 @@ pub(crate) mod methods;
 pub fn register_routes() -> RpcRouterBuilder {
     RpcRouter::builder("v0.1")
         .register("pathfinder_version",              || { pathfinder_common::consts::VERGEN_GIT_DESCRIBE })
+        .register("pathfinder_estimateFee",          methods::estimate_fee)
+        .register("pathfinder_getClassMetadata",     methods::get_class_metadata)
         .register("pathfinder_getProof",             methods::get_proof)
         .register("pathfinder_getTransactionStatus", methods::get_transaction_status)
+        .register("pathfinder_syncStatus",           methods::sync_status)
 }
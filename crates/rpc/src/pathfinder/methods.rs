@@ -1,5 +1,11 @@
+mod estimate_fee;
+mod get_class_metadata;
 mod get_proof;
 mod get_transaction_status;
+mod sync_status;
 
+pub(crate) use estimate_fee::estimate_fee;
+pub(crate) use get_class_metadata::get_class_metadata;
 pub(crate) use get_proof::get_proof;
 pub(crate) use get_transaction_status::get_transaction_status;
+pub(crate) use sync_status::sync_status;
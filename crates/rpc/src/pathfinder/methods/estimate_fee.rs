@@ -0,0 +1,178 @@
+use anyhow::Context;
+use pathfinder_common::BlockId;
+use starknet_gateway_client::GatewayApi;
+use starknet_gateway_types::error::{SequencerError, StarknetError};
+use starknet_gateway_types::request::add_transaction::AddTransaction;
+
+use crate::context::RpcContext;
+use crate::v02::types::reply::FeeEstimate;
+
+#[derive(serde::Deserialize, Debug)]
+pub struct EstimateFeeInput {
+    /// The transaction in the sequencer's format, as it is forwarded unchanged.
+    transaction: AddTransaction,
+    block_id: BlockId,
+}
+
+#[derive(Debug)]
+pub enum EstimateFeeError {
+    BlockNotFound,
+    GatewayError(StarknetError),
+    Internal(anyhow::Error),
+}
+
+impl From<anyhow::Error> for EstimateFeeError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Internal(e)
+    }
+}
+
+impl From<SequencerError> for EstimateFeeError {
+    fn from(e: SequencerError) -> Self {
+        match e {
+            SequencerError::StarknetError(e) => Self::GatewayError(e),
+            other => Self::Internal(other.into()),
+        }
+    }
+}
+
+impl From<EstimateFeeError> for crate::error::ApplicationError {
+    fn from(x: EstimateFeeError) -> Self {
+        match x {
+            EstimateFeeError::BlockNotFound => Self::BlockNotFound,
+            EstimateFeeError::GatewayError(e) => Self::GatewayError(e),
+            EstimateFeeError::Internal(e) => Self::Internal(e),
+        }
+    }
+}
+
+/// Forwards a fee estimation to the sequencer.
+///
+/// Estimates for blocks other than pending are cached by the block's state commitment, so an
+/// identical request against the same state is not forwarded again.
+pub async fn estimate_fee(
+    context: RpcContext,
+    input: EstimateFeeInput,
+) -> Result<FeeEstimate, EstimateFeeError> {
+    let estimate = match input.block_id {
+        // The pending state has no commitment to key the cache by.
+        BlockId::Pending => {
+            context
+                .sequencer
+                .estimate_fee(input.transaction, BlockId::Pending)
+                .await?
+        }
+        block_id => {
+            let storage = context.storage.clone();
+            let span = tracing::Span::current();
+            let header = tokio::task::spawn_blocking(move || {
+                let _g = span.enter();
+
+                let mut db = storage
+                    .connection()
+                    .context("Opening database connection")?;
+                let tx = db.transaction().context("Creating database transaction")?;
+
+                tx.block_header(block_id).context("Fetching block header")
+            })
+            .await
+            .context("Joining database task")??
+            .ok_or(EstimateFeeError::BlockNotFound)?;
+
+            // Forwarded with the block's hash, so that the sequencer estimates on exactly the
+            // state the cache entry is keyed by.
+            context
+                .fee_estimate_cache
+                .estimate_fee(
+                    &context.sequencer,
+                    input.transaction,
+                    header.hash.into(),
+                    header.state_commitment,
+                )
+                .await?
+        }
+    };
+
+    Ok(FeeEstimate {
+        gas_consumed: estimate.gas_usage.into(),
+        gas_price: estimate.gas_price.into(),
+        overall_fee: estimate.overall_fee.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::{ContractAddress, Fee, TransactionNonce, TransactionVersion};
+    use starknet_gateway_types::request::add_transaction::InvokeFunction;
+
+    fn transaction() -> AddTransaction {
+        AddTransaction::Invoke(InvokeFunction {
+            version: TransactionVersion::ONE,
+            max_fee: Fee::ZERO,
+            signature: vec![],
+            nonce: Some(TransactionNonce::ZERO),
+            sender_address: ContractAddress::ONE,
+            entry_point_selector: None,
+            calldata: vec![],
+        })
+    }
+
+    #[tokio::test]
+    async fn identical_request_is_served_from_cache() {
+        let context = RpcContext::for_tests();
+        let latest = context
+            .storage
+            .connection()
+            .unwrap()
+            .transaction()
+            .unwrap()
+            .block_header(BlockId::Latest)
+            .unwrap()
+            .unwrap();
+
+        // The sequencer answers a single request only, any further one fails.
+        let (_jh, sequencer) =
+            starknet_gateway_client::test_utils::setup_with_varied_responses([(
+                format!(
+                    "/feeder_gateway/estimate_fee?blockHash={}",
+                    latest.hash.0.to_hex_str()
+                ),
+                [(
+                    r#"{"overall_fee":1000,"gas_price":10,"gas_usage":100,"unit":"wei"}"#
+                        .to_owned(),
+                    200,
+                )],
+            )]);
+        let context = RpcContext {
+            sequencer: sequencer.disable_retry_for_tests(),
+            ..context
+        };
+
+        let expected = FeeEstimate {
+            gas_consumed: 100.into(),
+            gas_price: 10.into(),
+            overall_fee: 1000.into(),
+        };
+        for _ in 0..2 {
+            let input = EstimateFeeInput {
+                transaction: transaction(),
+                block_id: BlockId::Latest,
+            };
+            let estimate = estimate_fee(context.clone(), input).await.unwrap();
+            assert_eq!(estimate, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn block_not_found() {
+        let context = RpcContext::for_tests();
+        let input = EstimateFeeInput {
+            transaction: transaction(),
+            block_id: BlockId::Number(pathfinder_common::BlockNumber::MAX),
+        };
+
+        let result = estimate_fee(context, input).await;
+        assert_matches::assert_matches!(result, Err(EstimateFeeError::BlockNotFound));
+    }
+}
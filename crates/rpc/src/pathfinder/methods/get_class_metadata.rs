@@ -0,0 +1,116 @@
+use anyhow::Context;
+use pathfinder_common::{BlockId, ClassHash};
+
+use crate::context::RpcContext;
+use crate::v02::method::get_class::{class_definition_at, GetClassError};
+
+#[derive(serde::Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GetClassMetadataInput {
+    block_id: BlockId,
+    class_hash: ClassHash,
+}
+
+/// Tells Cairo 0 and Sierra classes apart, which `starknet_getClass` does not.
+#[derive(serde::Serialize, Debug, PartialEq, Eq)]
+#[serde(tag = "language", rename_all = "snake_case")]
+pub enum ClassMetadata {
+    Cairo0,
+    Sierra {
+        contract_class_version: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        compiler_version: Option<String>,
+    },
+}
+
+pub async fn get_class_metadata(
+    context: RpcContext,
+    input: GetClassMetadataInput,
+) -> Result<ClassMetadata, GetClassError> {
+    /// Only the fields required to tell the class versions apart.
+    #[derive(serde::Deserialize)]
+    struct Definition {
+        contract_class_version: Option<String>,
+    }
+
+    let span = tracing::Span::current();
+    let jh = tokio::task::spawn_blocking(move || -> Result<ClassMetadata, GetClassError> {
+        let _g = span.enter();
+        let mut db = context
+            .storage
+            .connection()
+            .context("Opening database connection")?;
+        let tx = db.transaction().context("Creating database transaction")?;
+
+        let definition = class_definition_at(&context, &tx, input.block_id, input.class_hash)?;
+        let definition = serde_json::from_slice::<Definition>(&definition)
+            .context("Parsing class definition")?;
+
+        let metadata = match definition.contract_class_version {
+            Some(contract_class_version) => ClassMetadata::Sierra {
+                contract_class_version,
+                compiler_version: tx
+                    .casm_compiler_version(input.class_hash)
+                    .context("Fetching compiler version")?,
+            },
+            None => ClassMetadata::Cairo0,
+        };
+
+        Ok(metadata)
+    });
+
+    jh.await.context("Reading class from database")?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use pathfinder_common::macro_prelude::*;
+
+    #[tokio::test]
+    async fn cairo0() {
+        let context = RpcContext::for_tests();
+        let input = GetClassMetadataInput {
+            block_id: BlockId::Latest,
+            class_hash: class_hash_bytes!(b"class 0 hash"),
+        };
+
+        let metadata = get_class_metadata(context, input).await.unwrap();
+        assert_eq!(
+            serde_json::to_value(metadata).unwrap(),
+            serde_json::json!({"language": "cairo0"})
+        );
+    }
+
+    #[tokio::test]
+    async fn sierra() {
+        let context = RpcContext::for_tests();
+        let input = GetClassMetadataInput {
+            block_id: BlockId::Latest,
+            class_hash: class_hash_bytes!(b"class 2 hash (sierra)"),
+        };
+
+        let metadata = get_class_metadata(context, input).await.unwrap();
+        assert_eq!(
+            serde_json::to_value(metadata).unwrap(),
+            serde_json::json!({
+                "language": "sierra",
+                "contract_class_version": "0.1.0",
+                "compiler_version": "compiler version 123",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_class() {
+        let context = RpcContext::for_tests();
+        let input = GetClassMetadataInput {
+            block_id: BlockId::Latest,
+            class_hash: class_hash_bytes!(b"invalid"),
+        };
+
+        let error = get_class_metadata(context, input).await.unwrap_err();
+        assert_matches!(error, GetClassError::ClassHashNotFound);
+    }
+}
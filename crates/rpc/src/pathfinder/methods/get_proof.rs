@@ -5,6 +5,7 @@ use serde_with::skip_serializing_none;
 
 use crate::context::RpcContext;
 use pathfinder_common::{prelude::*, BlockId};
+use pathfinder_merkle_tree::contract_state::CONTRACT_STATE_HASH_VERSION;
 use pathfinder_merkle_tree::{ContractsStorageTree, StorageCommitmentTree};
 use stark_hash::Felt;
 
@@ -141,6 +142,8 @@ pub struct GetProofOutput {
 }
 
 /// Returns all the necessary data to trustlessly verify storage slots for a particular contract.
+///
+/// The output can be checked with [pathfinder_merkle_tree::proof::verify_storage_proof].
 pub async fn get_proof(
     context: RpcContext,
     input: GetProofInput,
@@ -243,7 +246,7 @@ pub async fn get_proof(
             class_hash,
             nonce,
             root: contract_root,
-            contract_state_hash_version: Felt::ZERO, // Currently, this is defined as 0. Might change in the future.
+            contract_state_hash_version: CONTRACT_STATE_HASH_VERSION,
             storage_proofs,
         };
 
@@ -260,6 +263,7 @@ pub async fn get_proof(
 
 #[cfg(test)]
 mod tests {
+    use pathfinder_common::hash::PedersenHash;
     use pathfinder_common::macro_prelude::*;
 
     use super::*;
@@ -278,4 +282,77 @@ mod tests {
         let err = get_proof(context, input).await.unwrap_err();
         assert_matches::assert_matches!(err, GetProofError::ProofLimitExceeded { .. });
     }
+
+    /// Follows `proof` from `root` along the path of `key`, returning the proven leaf value or
+    /// [None] if the proof is invalid or proves non-membership.
+    fn verify(root: Felt, key: Felt, proof: &ProofNodes) -> Option<Felt> {
+        let mut expected = root;
+        let mut remaining = key.view_bits();
+        for node in &proof.0 {
+            if node.hash::<PedersenHash>() != expected {
+                return None;
+            }
+
+            match node {
+                TrieNode::Binary { left, right } => {
+                    expected = if remaining[0] { *right } else { *left };
+                    remaining = &remaining[1..];
+                }
+                TrieNode::Edge { child, path } => {
+                    if path != &remaining[..path.len()] {
+                        return None;
+                    }
+                    expected = *child;
+                    remaining = &remaining[path.len()..];
+                }
+            }
+        }
+
+        remaining.is_empty().then_some(expected)
+    }
+
+    #[tokio::test]
+    async fn proofs_verify_against_state_commitment() {
+        use pathfinder_merkle_tree::contract_state::calculate_contract_state_hash;
+
+        let context = RpcContext::for_tests();
+        let contract = contract_address_bytes!(b"contract 1");
+        let key = storage_address_bytes!(b"storage addr 0");
+
+        let header = {
+            let mut db = context.storage.connection().unwrap();
+            let tx = db.transaction().unwrap();
+            tx.block_header(pathfinder_storage::BlockId::Latest)
+                .unwrap()
+                .unwrap()
+        };
+
+        let input = GetProofInput {
+            block_id: BlockId::Latest,
+            contract_address: contract,
+            keys: vec![key],
+        };
+        let output = get_proof(context, input).await.unwrap();
+        let data = output.contract_data.unwrap();
+
+        // The contract proof's root combined with the class commitment is the state commitment.
+        let storage_commitment =
+            StorageCommitment(output.contract_proof.0[0].hash::<PedersenHash>());
+        assert_eq!(
+            StateCommitment::calculate(storage_commitment, output.class_commitment.unwrap()),
+            header.state_commitment
+        );
+        assert_eq!(output.state_commitment, Some(header.state_commitment));
+
+        // The contract's leaf is its state hash, which commits to the contract's storage root.
+        let contract_state_hash =
+            verify(storage_commitment.0, contract.0, &output.contract_proof).unwrap();
+        assert_eq!(
+            contract_state_hash,
+            calculate_contract_state_hash(data.class_hash, data.root, data.nonce).0
+        );
+
+        let value = verify(data.root.0, key.0, &data.storage_proofs[0]).unwrap();
+        assert_eq!(value, storage_value_bytes!(b"storage value 2").0);
+    }
 }
@@ -0,0 +1,40 @@
+use crate::context::RpcContext;
+
+crate::error::generate_rpc_error_subset!(SyncStatusError);
+
+#[derive(serde::Serialize, Debug, PartialEq, Eq)]
+pub struct SyncStatusOutput {
+    /// How far the latest synced block's timestamp lags behind the node's clock, or [None] if
+    /// no block has been synced since the node started.
+    seconds_behind_tip: Option<u64>,
+}
+
+pub async fn sync_status(context: RpcContext) -> Result<SyncStatusOutput, SyncStatusError> {
+    let seconds_behind_tip = *context.sync_status.seconds_behind_tip.read().await;
+
+    Ok(SyncStatusOutput { seconds_behind_tip })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn seconds_behind_tip() {
+        let context = RpcContext::for_tests();
+
+        let result = sync_status(context.clone()).await.unwrap();
+        assert_eq!(
+            serde_json::to_value(result).unwrap(),
+            serde_json::json!({ "seconds_behind_tip": null })
+        );
+
+        *context.sync_status.seconds_behind_tip.write().await = Some(90);
+
+        let result = sync_status(context).await.unwrap();
+        assert_eq!(
+            serde_json::to_value(result).unwrap(),
+            serde_json::json!({ "seconds_behind_tip": 90 })
+        );
+    }
+}
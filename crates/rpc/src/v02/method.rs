@@ -6,7 +6,7 @@ pub(crate) mod call;
 mod chain_id;
 mod get_block;
 mod get_block_transaction_count;
-mod get_class;
+pub(crate) mod get_class;
 mod get_class_at;
 mod get_class_hash_at;
 mod get_nonce;
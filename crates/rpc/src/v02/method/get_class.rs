@@ -25,41 +25,7 @@ pub async fn get_class(
             .context("Opening database connection")?;
         let tx = db.transaction().context("Creating database transaction")?;
 
-        let is_pending = if input.block_id.is_pending() {
-            context
-                .pending_data
-                .get(&tx)
-                .context("Querying pending data")?
-                .state_update
-                .class_is_declared(input.class_hash)
-        } else {
-            false
-        };
-
-        // Map block id to the storage variant.
-        let block_id = match input.block_id {
-            BlockId::Pending => pathfinder_storage::BlockId::Latest,
-            other => other.try_into().expect("Only pending cast should fail"),
-        };
-
-        // Check that block exists
-        let block_exists = tx.block_exists(block_id)?;
-        if !block_exists {
-            return Err(GetClassError::BlockNotFound);
-        }
-
-        // If the class is declared in the pending block, then we shouldn't check the class's
-        // declaration point.
-        let definition = if is_pending {
-            tx.class_definition(input.class_hash)
-        } else {
-            tx.class_definition_at(block_id, input.class_hash)
-        }
-        .context("Fetching class definition")?;
-
-        let Some(definition) = definition else {
-            return Err(GetClassError::ClassHashNotFound);
-        };
+        let definition = class_definition_at(&context, &tx, input.block_id, input.class_hash)?;
 
         let class = ContractClass::from_definition_bytes(&definition)
             .context("Parsing class definition")?;
@@ -70,6 +36,48 @@ pub async fn get_class(
     jh.await.context("Reading class from database")?
 }
 
+/// Returns the definition of a class which has been declared at `block_id`.
+pub(crate) fn class_definition_at(
+    context: &RpcContext,
+    tx: &pathfinder_storage::Transaction<'_>,
+    block_id: BlockId,
+    class_hash: ClassHash,
+) -> Result<Vec<u8>, GetClassError> {
+    let is_pending = if block_id.is_pending() {
+        context
+            .pending_data
+            .get(tx)
+            .context("Querying pending data")?
+            .state_update
+            .class_is_declared(class_hash)
+    } else {
+        false
+    };
+
+    // Map block id to the storage variant.
+    let block_id = match block_id {
+        BlockId::Pending => pathfinder_storage::BlockId::Latest,
+        other => other.try_into().expect("Only pending cast should fail"),
+    };
+
+    // Check that block exists
+    let block_exists = tx.block_exists(block_id)?;
+    if !block_exists {
+        return Err(GetClassError::BlockNotFound);
+    }
+
+    // If the class is declared in the pending block, then we shouldn't check the class's
+    // declaration point.
+    let definition = if is_pending {
+        tx.class_definition(class_hash)
+    } else {
+        tx.class_definition_at(block_id, class_hash)
+    }
+    .context("Fetching class definition")?;
+
+    definition.ok_or(GetClassError::ClassHashNotFound)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -208,6 +216,38 @@ mod tests {
         assert_matches!(error, GetClassError::ClassHashNotFound);
     }
 
+    #[tokio::test]
+    async fn deployed_contract_class() {
+        let context = RpcContext::for_tests();
+
+        let (class_hash, definition) = {
+            let mut db = context.storage.connection().unwrap();
+            let tx = db.transaction().unwrap();
+            let class_hash = tx
+                .contract_class_hash(
+                    pathfinder_storage::BlockId::Latest,
+                    contract_address_bytes!(b"contract 0"),
+                )
+                .unwrap()
+                .unwrap();
+            let definition = tx.class_definition(class_hash).unwrap().unwrap();
+            (class_hash, definition)
+        };
+
+        let class = super::get_class(
+            context,
+            GetClassInput {
+                block_id: BlockId::Latest,
+                class_hash,
+            },
+        )
+        .await
+        .unwrap();
+
+        let expected = ContractClass::from_definition_bytes(&definition).unwrap();
+        assert_eq!(class, expected);
+    }
+
     #[tokio::test]
     async fn at_number() {
         use pathfinder_common::BlockNumber;
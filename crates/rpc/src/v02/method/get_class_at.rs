@@ -56,13 +56,21 @@ pub async fn get_class_at(
                 .ok_or(GetClassAtError::ContractNotFound)?,
         };
 
-        let definition = tx
-            .class_definition(class_hash)
-            .context("Fetching class definition")?
-            .context("Class definition missing from database")?;
+        let load = || -> anyhow::Result<Option<ContractClass>> {
+            tx.class_definition(class_hash)
+                .context("Fetching class definition")?
+                .map(|definition| {
+                    ContractClass::from_definition_bytes(&definition)
+                        .context("Parsing class definition")
+                })
+                .transpose()
+        };
 
-        let class = ContractClass::from_definition_bytes(&definition)
-            .context("Parsing class definition")?;
+        let class = match &context.class_cache {
+            Some(cache) => cache.get_or_load(class_hash, load),
+            None => load(),
+        }?
+        .context("Class definition missing from database")?;
 
         Ok(class)
     });
@@ -194,6 +202,31 @@ mod tests {
         assert_matches!(error, GetClassAtError::ContractNotFound);
     }
 
+    #[tokio::test]
+    async fn served_from_class_cache() {
+        use crate::class_cache::ClassCache;
+
+        let cache = ClassCache::new(std::num::NonZeroUsize::new(10).unwrap());
+        let context = RpcContext::for_tests().with_class_cache(cache.clone());
+
+        let input = || GetClassAtInput {
+            block_id: BlockId::Latest,
+            contract_address: contract_address_bytes!(b"contract 0"),
+        };
+        let class = super::get_class_at(context.clone(), input()).await.unwrap();
+
+        // The first read populated the cache, so the database is not read again.
+        let cached = cache
+            .get_or_load(class_hash_bytes!(b"class 0 hash"), || {
+                panic!("Class should be cached")
+            })
+            .unwrap();
+        assert_eq!(cached, Some(class.clone()));
+
+        let second = super::get_class_at(context, input()).await.unwrap();
+        assert_eq!(second, class);
+    }
+
     #[tokio::test]
     async fn number() {
         use pathfinder_common::BlockNumber;
@@ -49,6 +49,19 @@ pub async fn get_class_hash_at(
             other => other.try_into().expect("Only pending cast should fail"),
         };
 
+        if let (pathfinder_storage::BlockId::Latest, Some(index)) =
+            (block_id, &context.class_hash_index)
+        {
+            let latest = tx
+                .block_id(block_id)
+                .context("Querying latest block")?
+                .ok_or(GetClassHashAtError::BlockNotFound)?;
+
+            if let Some(class_hash) = index.get(latest.0, input.contract_address) {
+                return Ok(GetClassHashOutput(class_hash));
+            }
+        }
+
         // Check for block existence.
         if !tx.block_exists(block_id)? {
             return Err(GetClassHashAtError::BlockNotFound);
@@ -147,6 +160,34 @@ mod tests {
         assert_eq!(result.0, expected);
     }
 
+    #[tokio::test]
+    async fn latest_from_class_hash_index() {
+        use crate::class_hash_index::ClassHashIndex;
+
+        let context = RpcContext::for_tests();
+        let index = {
+            let mut db = context.storage.connection().unwrap();
+            let tx = db.transaction().unwrap();
+            ClassHashIndex::load(&tx).unwrap()
+        };
+        let context = context.with_class_hash_index(index);
+
+        let input = GetClassHashAtInput {
+            block_id: BlockId::Latest,
+            contract_address: contract_address_bytes!(b"contract 0"),
+        };
+        let result = get_class_hash_at(context.clone(), input).await.unwrap();
+        assert_eq!(result.0, class_hash_bytes!(b"class 0 hash"));
+
+        // Contracts which are not in the index still fall back to the database.
+        let input = GetClassHashAtInput {
+            block_id: BlockId::Latest,
+            contract_address: contract_address!("0xdeadbeef"),
+        };
+        let result = get_class_hash_at(context, input).await;
+        assert_matches!(result, Err(GetClassHashAtError::ContractNotFound));
+    }
+
     #[tokio::test]
     async fn at_block() {
         use pathfinder_common::BlockNumber;
@@ -35,6 +35,9 @@ pub fn register_routes() -> RpcRouterBuilder {
         .register("starknet_simulateTransaction"             ,v03_method::simulate_transaction)
         .register("starknet_estimateMessageFee"              ,v03_method::estimate_message_fee)
 
+        .register("pathfinder_estimateFee"                   ,crate::pathfinder::methods::estimate_fee)
+        .register("pathfinder_getClassMetadata"              ,crate::pathfinder::methods::get_class_metadata)
         .register("pathfinder_getProof"                      ,crate::pathfinder::methods::get_proof)
         .register("pathfinder_getTransactionStatus"          ,crate::pathfinder::methods::get_transaction_status)
+        .register("pathfinder_syncStatus"                    ,crate::pathfinder::methods::sync_status)
 }
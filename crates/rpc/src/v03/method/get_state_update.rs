@@ -141,11 +141,12 @@ pub(crate) mod types {
                     None => {}
                 }
 
-                let storage_entries = update
+                let mut storage_entries = update
                     .storage
                     .into_iter()
                     .map(|(key, value)| StorageEntry { key, value })
-                    .collect();
+                    .collect::<Vec<_>>();
+                storage_entries.sort_by_key(|x| x.key);
 
                 storage_diffs.push(StorageDiff {
                     address: contract_address,
@@ -154,11 +155,12 @@ pub(crate) mod types {
             }
 
             for (address, update) in value.system_contract_updates {
-                let storage_entries = update
+                let mut storage_entries = update
                     .storage
                     .into_iter()
                     .map(|(key, value)| StorageEntry { key, value })
-                    .collect();
+                    .collect::<Vec<_>>();
+                storage_entries.sort_by_key(|x| x.key);
 
                 storage_diffs.push(StorageDiff {
                     address,
@@ -166,6 +168,10 @@ pub(crate) mod types {
                 });
             }
 
+            // The updates are stored in hash maps, so sort them to give clients a stable order.
+            storage_diffs.sort_by_key(|x| x.address);
+            deployed_contracts.sort_by_key(|x| x.address);
+
             let declared_classes = value
                 .declared_sierra_classes
                 .into_iter()
@@ -436,7 +442,7 @@ mod tests {
     use serde_json::json;
 
     use pathfinder_common::macro_prelude::*;
-    use pathfinder_common::BlockNumber;
+    use pathfinder_common::{BlockNumber, StateCommitment};
 
     #[rstest::rstest]
     #[case::pending_by_position(json!(["pending"]), BlockId::Pending)]
@@ -518,6 +524,50 @@ mod tests {
         sort_assert_eq(result, in_storage[0].clone());
     }
 
+    #[tokio::test]
+    async fn historical_by_number() {
+        let (in_storage, ctx) = context_with_state_updates();
+
+        let result = get_state_update(
+            ctx,
+            GetStateUpdateInput {
+                block_id: BlockId::Number(BlockNumber::new_or_panic(1)),
+            },
+        )
+        .await
+        .unwrap();
+
+        // The diff of a past block, not the accumulated state as of the latest block.
+        assert_eq!(Some(result.old_root), in_storage[0].new_root);
+        sort_assert_eq(result, in_storage[1].clone());
+    }
+
+    #[tokio::test]
+    async fn old_root_is_parent_new_root() {
+        let (in_storage, ctx) = context_with_state_updates();
+
+        let mut results = Vec::new();
+        for number in 0..in_storage.len() as u64 {
+            let result = get_state_update(
+                ctx.clone(),
+                GetStateUpdateInput {
+                    block_id: BlockNumber::new_or_panic(number).into(),
+                },
+            )
+            .await
+            .unwrap();
+            results.push(result);
+        }
+
+        assert_eq!(results[0].old_root, StateCommitment::ZERO);
+        for (parent, child) in results.iter().zip(results.iter().skip(1)) {
+            assert_eq!(Some(child.old_root), parent.new_root);
+        }
+        for (result, expected) in results.iter().zip(in_storage.iter()) {
+            assert_eq!(result.new_root, expected.new_root);
+        }
+    }
+
     #[tokio::test]
     async fn by_hash() {
         let (in_storage, ctx) = context_with_state_updates();
@@ -564,6 +614,63 @@ mod tests {
         assert_eq!(result, Err(GetStateUpdateError::BlockNotFound));
     }
 
+    #[tokio::test]
+    async fn diffs_are_sorted() {
+        use pathfinder_common::{BlockHeader, ContractAddress, StorageAddress};
+        use stark_hash::Felt;
+
+        let storage = pathfinder_storage::Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let header = BlockHeader::builder()
+            .with_number(BlockNumber::GENESIS)
+            .finalize_with_hash(block_hash!("0xb00"));
+        tx.insert_block_header(&header).unwrap();
+
+        // Enough entries that the hash map iteration order is very unlikely to be sorted.
+        let mut state_update = pathfinder_common::StateUpdate::default()
+            .with_block_hash(header.hash)
+            .with_state_commitment(header.state_commitment);
+        for i in (1..=20u64).rev() {
+            let address = ContractAddress::new_or_panic(Felt::from_u64(0x100 + i));
+            state_update = state_update.with_deployed_contract(address, class_hash!("0xc1a55"));
+            for j in (1..=20u64).rev() {
+                state_update = state_update.with_storage_update(
+                    address,
+                    StorageAddress::new_or_panic(Felt::from_u64(j)),
+                    storage_value!("0x1"),
+                );
+            }
+        }
+        tx.insert_state_update(BlockNumber::GENESIS, &state_update)
+            .unwrap();
+        tx.commit().unwrap();
+
+        let context = RpcContext::for_tests().with_storage(storage);
+        let result = get_state_update(
+            context,
+            GetStateUpdateInput {
+                block_id: BlockId::Latest,
+            },
+        )
+        .await
+        .unwrap();
+
+        let diffs = &result.state_diff.storage_diffs;
+        assert_eq!(diffs.len(), 20);
+        assert!(diffs.windows(2).all(|w| w[0].address < w[1].address));
+        for diff in diffs {
+            let entries = &diff.storage_entries;
+            assert_eq!(entries.len(), 20);
+            assert!(entries.windows(2).all(|w| w[0].key < w[1].key));
+        }
+
+        let deployed = &result.state_diff.deployed_contracts;
+        assert_eq!(deployed.len(), 20);
+        assert!(deployed.windows(2).all(|w| w[0].address < w[1].address));
+    }
+
     #[tokio::test]
     async fn pending() {
         let context = RpcContext::for_tests_with_pending().await;
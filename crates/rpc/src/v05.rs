@@ -42,6 +42,9 @@ pub fn register_routes() -> RpcRouterBuilder {
         .register("starknet_traceBlockTransactions"          , method::trace_block_transactions)
         .register("starknet_traceTransaction"                , method::trace_transaction)
 
+        .register("pathfinder_estimateFee"                   , crate::pathfinder::methods::estimate_fee)
+        .register("pathfinder_getClassMetadata"              , crate::pathfinder::methods::get_class_metadata)
         .register("pathfinder_getProof"                      , crate::pathfinder::methods::get_proof)
         .register("pathfinder_getTransactionStatus"          , crate::pathfinder::methods::get_transaction_status)
+        .register("pathfinder_syncStatus"                    , crate::pathfinder::methods::sync_status)
 }
@@ -307,7 +307,7 @@ pub fn starkhash_to_dec_str(h: &Felt) -> String {
 }
 
 /// A helper conversion function. Only use with __sequencer API related types__.
-fn starkhash_from_dec_str(s: &str) -> Result<Felt, anyhow::Error> {
+pub fn starkhash_from_dec_str(s: &str) -> Result<Felt, anyhow::Error> {
     match BigUint::from_str(s) {
         Ok(b) => {
             let h = starkhash_from_biguint(b)?;
@@ -1,32 +1,44 @@
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use anyhow::Context;
 
 mod block;
+mod checkpoint;
 mod class;
 mod ethereum;
 mod event;
 mod reference;
 mod signature;
 mod state_update;
+mod stats;
 mod transaction;
 mod trie;
 
 // Re-export this so users don't require rusqlite as a direct dep.
 pub use rusqlite::TransactionBehavior;
 
+pub use block::{BlockRoots, ConflictingRoot, VerificationLevel, MAX_BLOCK_ROOTS_RANGE};
+
+pub use checkpoint::Checkpoint;
+
 pub use event::KEY_FILTER_LIMIT as EVENT_KEY_FILTER_LIMIT;
 pub use event::*;
 
+pub use stats::TableStatistics;
+
 pub use transaction::TransactionStatus;
 
-pub use trie::{Child, Node, StoredNode};
+pub use trie::{Child, DanglingContractRoot, Node, OrphanNodes, StoredNode};
 
 use pathfinder_common::{
     BlockCommitmentSignature, BlockHash, BlockHeader, BlockNumber, CasmHash, ClassCommitment,
     ClassCommitmentLeafHash, ClassHash, ContractAddress, ContractNonce, ContractRoot,
-    ContractStateHash, SierraHash, StateUpdate, StorageAddress, StorageCommitment, StorageValue,
-    TransactionHash,
+    ContractStateHash, SierraHash, StateCommitment, StateUpdate, StorageAddress, StorageCommitment,
+    StorageValue, TransactionHash,
 };
-use pathfinder_ethereum::EthereumStateUpdate;
+use pathfinder_ethereum::{EthereumStateUpdate, L1ToL2Message};
+use primitive_types::H256;
 use stark_hash::Felt;
 use starknet_gateway_types::reply::transaction as gateway;
 
@@ -34,28 +46,135 @@ use crate::BlockId;
 
 type PooledConnection = r2d2::PooledConnection<r2d2_sqlite::SqliteConnectionManager>;
 
-pub struct Connection(PooledConnection);
+pub struct Connection(ConnectionInner);
+
+enum ConnectionInner {
+    Pooled(PooledConnection),
+    Snapshot(Arc<Mutex<Snapshot>>),
+}
 
 impl Connection {
     pub(crate) fn from_inner(inner: PooledConnection) -> Self {
-        Self(inner)
+        Self(ConnectionInner::Pooled(inner))
+    }
+
+    pub(crate) fn from_snapshot(snapshot: Arc<Mutex<Snapshot>>) -> Self {
+        Self(ConnectionInner::Snapshot(snapshot))
     }
 
     pub fn transaction(&mut self) -> anyhow::Result<Transaction<'_>> {
-        let tx = self.0.transaction()?;
-        Ok(Transaction(tx))
+        self.transaction_with_behavior(TransactionBehavior::Deferred)
     }
 
+    /// Begins a transaction.
+    ///
+    /// A [snapshot](crate::Storage::snapshot) connection instead waits for the snapshot's
+    /// previous transaction to end, and reuses its read transaction whatever the `behavior`.
     pub fn transaction_with_behavior(
         &mut self,
         behavior: TransactionBehavior,
     ) -> anyhow::Result<Transaction<'_>> {
-        let tx = self.0.transaction_with_behavior(behavior)?;
-        Ok(Transaction(tx))
+        match &mut self.0 {
+            ConnectionInner::Pooled(connection) => {
+                let tx = connection.transaction_with_behavior(behavior)?;
+                Ok(Transaction(TransactionInner::Owned(tx)))
+            }
+            ConnectionInner::Snapshot(snapshot) => {
+                // A panic cannot leave the snapshot in an inconsistent state, as nothing is
+                // written through it.
+                let snapshot = snapshot.lock().unwrap_or_else(PoisonError::into_inner);
+                Ok(Transaction(TransactionInner::Snapshot(snapshot)))
+            }
+        }
+    }
+
+    /// Checkpoints the write-ahead log and truncates it to zero bytes.
+    ///
+    /// Returns `false` if the checkpoint could not complete because of concurrent readers or
+    /// writers, in which case the WAL is left as is. This is a no-op if the database is not in
+    /// WAL mode.
+    pub fn wal_checkpoint(&self) -> anyhow::Result<bool> {
+        let busy: i64 = self
+            .pooled()?
+            .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| row.get(0))?;
+        Ok(busy == 0)
+    }
+
+    /// Rebuilds the database file, reclaiming the space of deleted data.
+    ///
+    /// This can take a long time on large databases and temporarily requires up to twice the
+    /// database's size in free disk space.
+    pub fn vacuum(&self) -> anyhow::Result<()> {
+        self.pooled()?.execute_batch("VACUUM")?;
+        Ok(())
+    }
+
+    fn pooled(&self) -> anyhow::Result<&PooledConnection> {
+        match &self.0 {
+            ConnectionInner::Pooled(connection) => Ok(connection),
+            ConnectionInner::Snapshot(_) => anyhow::bail!("Not supported by snapshot connections"),
+        }
+    }
+}
+
+/// A connection with an open read transaction, which keeps seeing the state the database was in
+/// when the transaction started.
+///
+/// Once [ended](Snapshot::end), the connection reads the database as normal instead.
+pub(crate) struct Snapshot {
+    connection: PooledConnection,
+    open: bool,
+}
+
+impl Snapshot {
+    pub(crate) fn new(connection: PooledConnection) -> anyhow::Result<Self> {
+        connection
+            .execute_batch("PRAGMA query_only = ON; BEGIN")
+            .context("Beginning read transaction")?;
+        let snapshot = Self {
+            connection,
+            open: true,
+        };
+
+        // The state a transaction sees is only fixed by its first read.
+        snapshot
+            .connection
+            .query_row("SELECT count(1) FROM sqlite_master", [], |_| Ok(()))
+            .context("Reading schema")?;
+
+        Ok(snapshot)
+    }
+
+    /// Ends the read transaction, so that it no longer holds back WAL checkpoints.
+    pub(crate) fn end(&mut self) -> anyhow::Result<()> {
+        if self.open {
+            self.open = false;
+            self.connection
+                .execute_batch("ROLLBACK")
+                .context("Ending read transaction")?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        // The connection returns to the pool, where it must be usable as normal again.
+        if let Err(e) = self.end() {
+            tracing::warn!(error=%e, "Failed to end snapshot transaction");
+        }
+        if let Err(e) = self.connection.execute_batch("PRAGMA query_only = OFF") {
+            tracing::warn!(error=%e, "Failed to make snapshot connection writable");
+        }
     }
 }
 
-pub struct Transaction<'inner>(rusqlite::Transaction<'inner>);
+pub struct Transaction<'inner>(TransactionInner<'inner>);
+
+enum TransactionInner<'inner> {
+    Owned(rusqlite::Transaction<'inner>),
+    Snapshot(MutexGuard<'inner, Snapshot>),
+}
 
 impl<'inner> Transaction<'inner> {
     // The implementations here are intentionally kept as simple wrappers. This lets the real implementations
@@ -63,7 +182,7 @@ impl<'inner> Transaction<'inner> {
 
     #[cfg(test)]
     pub(crate) fn from_inner(tx: rusqlite::Transaction<'inner>) -> Self {
-        Self(tx)
+        Self(TransactionInner::Owned(tx))
     }
 
     pub fn insert_contract_state_hash(
@@ -83,6 +202,10 @@ impl<'inner> Transaction<'inner> {
         trie::contract_state_hash(self, block, contract)
     }
 
+    /// Inserts the block header.
+    ///
+    /// Re-inserting a header with the same state commitment is a no-op, while inserting a
+    /// different state commitment for an existing block number fails with [ConflictingRoot].
     pub fn insert_block_header(&self, header: &BlockHeader) -> anyhow::Result<()> {
         block::insert_block_header(self, header)
     }
@@ -91,6 +214,44 @@ impl<'inner> Transaction<'inner> {
         block::block_header(self, block)
     }
 
+    /// Returns the roots of the blocks in the inclusive range `from..=to`, ordered by block
+    /// number. Fails if the range spans more than [MAX_BLOCK_ROOTS_RANGE] blocks.
+    pub fn block_roots(
+        &self,
+        from: BlockNumber,
+        to: BlockNumber,
+    ) -> anyhow::Result<Vec<BlockRoots>> {
+        block::block_roots(self, from, to)
+    }
+
+    /// Returns the state commitment of `block`'s parent, which is [StateCommitment::ZERO] for
+    /// the genesis block, or [None] if the parent does not exist.
+    pub fn parent_state_commitment(
+        &self,
+        block: BlockNumber,
+    ) -> anyhow::Result<Option<StateCommitment>> {
+        block::parent_state_commitment(self, block)
+    }
+
+    /// Sets the [VerificationLevel] of an existing block.
+    pub fn set_verification_level(
+        &self,
+        block: BlockNumber,
+        level: VerificationLevel,
+    ) -> anyhow::Result<()> {
+        block::set_verification_level(self, block, level)
+    }
+
+    pub fn verification_level(&self, block: BlockId) -> anyhow::Result<Option<VerificationLevel>> {
+        block::verification_level(self, block)
+    }
+
+    /// Returns the highest block whose state was verified by pathfinder itself, as opposed to
+    /// being trusted from an external source.
+    pub fn highest_fully_verified_block(&self) -> anyhow::Result<Option<(BlockNumber, BlockHash)>> {
+        block::highest_fully_verified_block(self)
+    }
+
     /// Removes all data related to this block.
     ///
     /// This includes block header, block body and state update information.
@@ -110,6 +271,22 @@ impl<'inner> Transaction<'inner> {
         block::block_is_l1_accepted(self, block)
     }
 
+    /// Returns the latest block accepted on L1.
+    pub fn latest_l1_block(&self) -> anyhow::Result<Option<(BlockNumber, BlockHash)>> {
+        block::latest_l1_block(self)
+    }
+
+    /// Returns the latest block accepted on L2. This includes blocks which are also accepted
+    /// on L1.
+    pub fn latest_l2_block(&self) -> anyhow::Result<Option<(BlockNumber, BlockHash)>> {
+        block::block_id(self, BlockId::Latest)
+    }
+
+    /// Returns the row count and approximate size of every table, ordered by table name.
+    pub fn table_statistics(&self) -> anyhow::Result<Vec<TableStatistics>> {
+        stats::table_statistics(self)
+    }
+
     pub fn update_l1_l2_pointer(&self, block: Option<BlockNumber>) -> anyhow::Result<()> {
         reference::update_l1_l2_pointer(self, block)
     }
@@ -129,10 +306,39 @@ impl<'inner> Transaction<'inner> {
         ethereum::l1_state_at_number(self, block)
     }
 
+    /// Returns the L1 state of each block in the inclusive range `from..=to` which has been
+    /// anchored on L1, ordered by block number.
+    pub fn l1_states(
+        &self,
+        from: BlockNumber,
+        to: BlockNumber,
+    ) -> anyhow::Result<Vec<EthereumStateUpdate>> {
+        ethereum::l1_states(self, from, to)
+    }
+
     pub fn latest_l1_state(&self) -> anyhow::Result<Option<EthereumStateUpdate>> {
         ethereum::latest_l1_state(self)
     }
 
+    /// Returns the Starknet state anchored at the greatest Ethereum block less than or equal to
+    /// `ethereum_block`.
+    pub fn l1_state_at_ethereum_block(
+        &self,
+        ethereum_block: u64,
+    ) -> anyhow::Result<Option<EthereumStateUpdate>> {
+        ethereum::l1_state_at_ethereum_block(self, ethereum_block)
+    }
+
+    /// Inserts messages sent from Ethereum to Starknet. Messages which are already stored are
+    /// ignored.
+    pub fn insert_l1_to_l2_messages(&self, messages: &[L1ToL2Message]) -> anyhow::Result<()> {
+        ethereum::insert_l1_to_l2_messages(self, messages)
+    }
+
+    pub fn l1_to_l2_message(&self, message_hash: H256) -> anyhow::Result<Option<L1ToL2Message>> {
+        ethereum::l1_to_l2_message(self, message_hash)
+    }
+
     /// Inserts the transaction, receipt and event data.
     pub fn insert_transaction_data(
         &self,
@@ -264,6 +470,19 @@ impl<'inner> Transaction<'inner> {
         class::class_definition(self, class_hash)
     }
 
+    /// Returns the version of the compiler which produced the stored CASM of a Sierra class.
+    pub fn casm_compiler_version(&self, class_hash: ClassHash) -> anyhow::Result<Option<String>> {
+        class::casm_compiler_version(self, class_hash)
+    }
+
+    /// Returns the size in bytes of the stored, compressed class definition without loading it.
+    pub fn compressed_class_definition_len(
+        &self,
+        class_hash: ClassHash,
+    ) -> anyhow::Result<Option<usize>> {
+        class::compressed_class_definition_len(self, class_hash)
+    }
+
     /// Returns the compressed class definition if it has been declared at `block_id`.
     pub fn compressed_class_definition_at(
         &self,
@@ -304,6 +523,16 @@ impl<'inner> Transaction<'inner> {
         state_update::contract_class_hash(self, block_id, contract_address)
     }
 
+    /// Returns the class hash of each contract at `block_id`, in the order of
+    /// `contract_addresses`. Contracts which are not deployed map to `None`.
+    pub fn contract_class_hashes(
+        &self,
+        block_id: BlockId,
+        contract_addresses: &[ContractAddress],
+    ) -> anyhow::Result<Vec<Option<ClassHash>>> {
+        state_update::contract_class_hashes(self, block_id, contract_addresses)
+    }
+
     /// Returns the compiled class hash for a class.
     pub fn casm_hash(&self, class_hash: ClassHash) -> anyhow::Result<Option<CasmHash>> {
         class::casm_hash(self, class_hash)
@@ -345,6 +574,21 @@ impl<'inner> Transaction<'inner> {
         trie::trie_storage::insert(self, root.0, nodes)
     }
 
+    /// Finds trie nodes which are not reachable from any stored root.
+    ///
+    /// This walks every trie and is intended as a diagnostic, not for regular use. It marks the
+    /// reachable nodes in a temporary table, so it is not supported by read-only connections.
+    pub fn find_orphan_nodes(&self) -> anyhow::Result<OrphanNodes> {
+        trie::find_orphan_nodes(self)
+    }
+
+    /// Finds contract roots which refer to a node missing from the contract tries.
+    ///
+    /// This scans every contract root and is intended as a diagnostic, not for regular use.
+    pub fn find_dangling_contract_roots(&self) -> anyhow::Result<Vec<DanglingContractRoot>> {
+        trie::find_dangling_contract_roots(self)
+    }
+
     pub fn class_trie_node(&self, index: u64) -> anyhow::Result<Option<StoredNode>> {
         trie::trie_class::node(self, index)
     }
@@ -393,6 +637,17 @@ impl<'inner> Transaction<'inner> {
         trie::contract_root(self, block, contract)
     }
 
+    /// Returns the contract's root as of its most recent update, or [None] if the
+    /// contract's storage has never been updated.
+    ///
+    /// A contract whose storage was emptied has a root of [ContractRoot::ZERO].
+    pub fn latest_contract_root(
+        &self,
+        contract: ContractAddress,
+    ) -> anyhow::Result<Option<ContractRoot>> {
+        trie::latest_contract_root(self, contract)
+    }
+
     pub fn insert_class_root(
         &self,
         block_number: BlockNumber,
@@ -439,6 +694,60 @@ impl<'inner> Transaction<'inner> {
         state_update::storage_value(self, block, contract_address, key)
     }
 
+    /// Returns the storage value at `key` in the historical state identified by `state_commitment`.
+    pub fn storage_value_at_state_commitment(
+        &self,
+        state_commitment: StateCommitment,
+        contract_address: ContractAddress,
+        key: StorageAddress,
+    ) -> anyhow::Result<Option<StorageValue>> {
+        state_update::storage_value_at_state_commitment(
+            self,
+            state_commitment,
+            contract_address,
+            key,
+        )
+    }
+
+    /// Returns the block in which `contract_address` was deployed.
+    pub fn contract_deployed_at(
+        &self,
+        contract_address: ContractAddress,
+    ) -> anyhow::Result<Option<BlockNumber>> {
+        state_update::contract_deployed_at(self, contract_address)
+    }
+
+    /// Returns the first block which wrote to the storage of `contract_address`.
+    pub fn first_storage_write(
+        &self,
+        contract_address: ContractAddress,
+    ) -> anyhow::Result<Option<BlockNumber>> {
+        state_update::first_storage_write(self, contract_address)
+    }
+
+    /// Returns a page of known contracts and their latest class hash, ordered by contract address.
+    pub fn contracts(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> anyhow::Result<Vec<(ContractAddress, ClassHash)>> {
+        state_update::contracts(self, offset, limit)
+    }
+
+    /// Streams every storage entry's latest value as of `block` to `f`, ordered by contract
+    /// address and storage address.
+    pub fn storage_entries_at(
+        &self,
+        block: BlockNumber,
+        f: impl FnMut(ContractAddress, StorageAddress, StorageValue) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        state_update::storage_entries_at(self, block, f)
+    }
+
+    pub fn contract_count(&self) -> anyhow::Result<usize> {
+        state_update::contract_count(self)
+    }
+
     pub fn contract_nonce(
         &self,
         contract_address: ContractAddress,
@@ -447,6 +756,8 @@ impl<'inner> Transaction<'inner> {
         state_update::contract_nonce(self, contract_address, block_id)
     }
 
+    /// Returns whether the contract has been deployed at or before `block_id`, without touching
+    /// the contract's state. Use this to reject requests for unknown contracts early.
     pub fn contract_exists(
         &self,
         contract_address: ContractAddress,
@@ -455,6 +766,15 @@ impl<'inner> Transaction<'inner> {
         state_update::contract_exists(self, contract_address, block_id)
     }
 
+    pub fn insert_checkpoint(&self, checkpoint: &Checkpoint) -> anyhow::Result<()> {
+        checkpoint::insert_checkpoint(self, checkpoint)
+    }
+
+    /// Returns all sync checkpoints in the order they were recorded.
+    pub fn checkpoints(&self) -> anyhow::Result<Vec<Checkpoint>> {
+        checkpoint::checkpoints(self)
+    }
+
     pub fn insert_signature(
         &self,
         block_number: BlockNumber,
@@ -463,11 +783,19 @@ impl<'inner> Transaction<'inner> {
         signature::insert_signature(self, block_number, signature)
     }
 
-    pub(self) fn inner(&self) -> &rusqlite::Transaction<'_> {
-        &self.0
+    pub(self) fn inner(&self) -> &rusqlite::Connection {
+        match &self.0 {
+            TransactionInner::Owned(tx) => tx,
+            TransactionInner::Snapshot(snapshot) => &snapshot.connection,
+        }
     }
 
+    /// Commits the transaction. This is a no-op for a [snapshot](crate::Storage::snapshot), which
+    /// cannot be written to.
     pub fn commit(self) -> anyhow::Result<()> {
-        Ok(self.0.commit()?)
+        match self.0 {
+            TransactionInner::Owned(tx) => Ok(tx.commit()?),
+            TransactionInner::Snapshot(_) => Ok(()),
+        }
     }
 }
@@ -1,12 +1,52 @@
 use anyhow::Context;
-use pathfinder_common::{BlockHash, BlockHeader, BlockNumber, StarknetVersion};
+use pathfinder_common::{
+    BlockHash, BlockHeader, BlockNumber, ClassCommitment, StarknetVersion, StateCommitment,
+    StorageCommitment,
+};
 
 use crate::{prelude::*, BlockId};
 
+/// Returned when inserting a block header for a block number which already exists
+/// with a different state commitment.
+///
+/// This indicates either an unhandled reorg or a bug, and callers should route it into
+/// their reorg handling.
+#[derive(Debug, thiserror::Error)]
+#[error("Block {number} already exists with state commitment {existing} but {new} was inserted")]
+pub struct ConflictingRoot {
+    pub number: BlockNumber,
+    pub existing: StateCommitment,
+    pub new: StateCommitment,
+}
+
 pub(super) fn insert_block_header(
     tx: &Transaction<'_>,
     header: &BlockHeader,
 ) -> anyhow::Result<()> {
+    let existing = tx
+        .inner()
+        .query_row(
+            "SELECT state_commitment FROM block_headers WHERE number = ?",
+            params![&header.number],
+            |row| row.get_state_commitment(0),
+        )
+        .optional()
+        .context("Querying for an existing block header")?;
+
+    match existing {
+        // Re-inserting an identical root is a no-op.
+        Some(existing) if existing == header.state_commitment => return Ok(()),
+        Some(existing) => {
+            return Err(ConflictingRoot {
+                number: header.number,
+                existing,
+                new: header.state_commitment,
+            }
+            .into())
+        }
+        None => {}
+    }
+
     // Intern the starknet version
     let version_id = intern_starknet_version(tx, &header.starknet_version)
         .context("Interning starknet version")?;
@@ -276,6 +316,179 @@ pub(super) fn block_header(
     Ok(Some(header))
 }
 
+/// The maximum number of blocks which can be requested from
+/// [block_roots](crate::Transaction::block_roots) at once.
+pub const MAX_BLOCK_ROOTS_RANGE: u64 = 1000;
+
+/// The state roots of a single block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockRoots {
+    pub number: BlockNumber,
+    pub hash: BlockHash,
+    pub storage_commitment: StorageCommitment,
+    pub class_commitment: ClassCommitment,
+    pub state_commitment: StateCommitment,
+}
+
+pub(super) fn block_roots(
+    tx: &Transaction<'_>,
+    from: BlockNumber,
+    to: BlockNumber,
+) -> anyhow::Result<Vec<BlockRoots>> {
+    if from > to {
+        return Ok(Vec::new());
+    }
+
+    let count = to.get() - from.get() + 1;
+    anyhow::ensure!(
+        count <= MAX_BLOCK_ROOTS_RANGE,
+        "Requested {count} blocks but at most {MAX_BLOCK_ROOTS_RANGE} are allowed"
+    );
+
+    let mut stmt = tx
+        .inner()
+        .prepare_cached(
+            "SELECT number, hash, storage_commitment, class_commitment, state_commitment
+            FROM block_headers WHERE number >= ? AND number <= ? ORDER BY number",
+        )
+        .context("Preparing statement")?;
+
+    let rows = stmt
+        .query_map(params![&from, &to], |row| {
+            Ok(BlockRoots {
+                number: row.get_block_number(0)?,
+                hash: row.get_block_hash(1)?,
+                storage_commitment: row.get_storage_commitment(2)?,
+                class_commitment: row.get_class_commitment(3)?,
+                state_commitment: row.get_state_commitment(4)?,
+            })
+        })
+        .context("Querying block roots")?;
+
+    rows.collect::<Result<_, _>>()
+        .context("Iterating over block roots")
+}
+
+pub(super) fn parent_state_commitment(
+    tx: &Transaction<'_>,
+    block: BlockNumber,
+) -> anyhow::Result<Option<StateCommitment>> {
+    let Some(parent) = block.parent() else {
+        return Ok(Some(StateCommitment::ZERO));
+    };
+
+    tx.inner()
+        .query_row(
+            "SELECT state_commitment FROM block_headers WHERE number = ?",
+            params![&parent],
+            |row| row.get_state_commitment(0),
+        )
+        .optional()
+        .context("Querying parent state commitment")
+}
+
+/// How much a block's state has been verified by pathfinder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerificationLevel {
+    /// The block's state was accepted from an external source (e.g. a checkpoint) without
+    /// recomputing it.
+    Trusted,
+    /// The block's state commitment was recomputed from its state diff and verified.
+    FullyVerified,
+}
+
+impl VerificationLevel {
+    fn to_sql_int(self) -> i64 {
+        match self {
+            VerificationLevel::Trusted => 0,
+            VerificationLevel::FullyVerified => 1,
+        }
+    }
+
+    fn from_sql_int(value: i64) -> anyhow::Result<Self> {
+        match value {
+            0 => Ok(VerificationLevel::Trusted),
+            1 => Ok(VerificationLevel::FullyVerified),
+            other => anyhow::bail!("Unknown verification level {other}"),
+        }
+    }
+}
+
+pub(super) fn set_verification_level(
+    tx: &Transaction<'_>,
+    block: BlockNumber,
+    level: VerificationLevel,
+) -> anyhow::Result<()> {
+    let updated = tx
+        .inner()
+        .execute(
+            "UPDATE block_headers SET verification_level = ? WHERE number = ?",
+            params![&level.to_sql_int(), &block],
+        )
+        .context("Updating verification level")?;
+
+    anyhow::ensure!(updated == 1, "Block {block} does not exist");
+
+    Ok(())
+}
+
+pub(super) fn verification_level(
+    tx: &Transaction<'_>,
+    block: BlockId,
+) -> anyhow::Result<Option<VerificationLevel>> {
+    let level = match block {
+        BlockId::Latest => tx.inner().query_row(
+            "SELECT verification_level FROM block_headers ORDER BY number DESC LIMIT 1",
+            [],
+            |row| row.get_i64(0),
+        ),
+        BlockId::Number(number) => tx.inner().query_row(
+            "SELECT verification_level FROM block_headers WHERE number = ?",
+            params![&number],
+            |row| row.get_i64(0),
+        ),
+        BlockId::Hash(hash) => tx.inner().query_row(
+            "SELECT verification_level FROM block_headers WHERE hash = ?",
+            params![&hash],
+            |row| row.get_i64(0),
+        ),
+    }
+    .optional()
+    .context("Querying verification level")?;
+
+    level.map(VerificationLevel::from_sql_int).transpose()
+}
+
+pub(super) fn highest_fully_verified_block(
+    tx: &Transaction<'_>,
+) -> anyhow::Result<Option<(BlockNumber, BlockHash)>> {
+    tx.inner()
+        .query_row(
+            "SELECT number, hash FROM block_headers WHERE verification_level = ?
+            ORDER BY number DESC LIMIT 1",
+            params![&VerificationLevel::FullyVerified.to_sql_int()],
+            |row| {
+                let number = row.get_block_number(0)?;
+                let hash = row.get_block_hash(1)?;
+                Ok((number, hash))
+            },
+        )
+        .optional()
+        .context("Querying highest fully verified block")
+}
+
+/// Returns the latest block which has been accepted on L1, i.e. the block pointed to by the
+/// L1-L2 pointer.
+pub(super) fn latest_l1_block(
+    tx: &Transaction<'_>,
+) -> anyhow::Result<Option<(BlockNumber, BlockHash)>> {
+    let Some(l1_l2) = tx.l1_l2_pointer().context("Querying L1-L2 pointer")? else {
+        return Ok(None);
+    };
+
+    block_id(tx, l1_l2.into())
+}
+
 pub(super) fn block_is_l1_accepted(tx: &Transaction<'_>, block: BlockId) -> anyhow::Result<bool> {
     let Some(l1_l2) = tx.l1_l2_pointer().context("Querying L1-L2 pointer")? else {
         return Ok(false);
@@ -358,6 +571,85 @@ mod tests {
         (connection, headers)
     }
 
+    #[test]
+    fn insert_same_root_is_idempotent() {
+        let (mut connection, headers) = setup();
+        let tx = connection.transaction().unwrap();
+        let latest = headers.last().unwrap();
+
+        tx.insert_block_header(latest).unwrap();
+
+        let result = tx.block_header(BlockId::Latest).unwrap().unwrap();
+        assert_eq!(&result, latest);
+    }
+
+    #[test]
+    fn insert_conflicting_root_fails() {
+        let (mut connection, headers) = setup();
+        let tx = connection.transaction().unwrap();
+        let latest = headers.last().unwrap();
+
+        let conflicting = BlockHeader {
+            state_commitment: state_commitment_bytes!(b"conflicting"),
+            ..latest.clone()
+        };
+
+        let err = tx.insert_block_header(&conflicting).unwrap_err();
+        let err = err.downcast_ref::<ConflictingRoot>().unwrap();
+        assert_eq!(err.number, latest.number);
+        assert_eq!(err.existing, latest.state_commitment);
+        assert_eq!(err.new, conflicting.state_commitment);
+
+        // The original header must be untouched.
+        let result = tx.block_header(BlockId::Latest).unwrap().unwrap();
+        assert_eq!(&result, latest);
+    }
+
+    #[test]
+    fn block_roots() {
+        let (mut connection, headers) = setup();
+        let tx = connection.transaction().unwrap();
+
+        let expected = headers
+            .iter()
+            .map(|h| BlockRoots {
+                number: h.number,
+                hash: h.hash,
+                storage_commitment: h.storage_commitment,
+                class_commitment: h.class_commitment,
+                state_commitment: h.state_commitment,
+            })
+            .collect::<Vec<_>>();
+
+        let result = tx
+            .block_roots(BlockNumber::GENESIS, headers.last().unwrap().number)
+            .unwrap();
+        assert_eq!(result, expected);
+
+        // Ranges past the head only return the existing blocks.
+        let result = tx
+            .block_roots(BlockNumber::new_or_panic(1), BlockNumber::new_or_panic(10))
+            .unwrap();
+        assert_eq!(result, expected[1..]);
+    }
+
+    #[test]
+    fn block_roots_range_too_large() {
+        let (mut connection, _) = setup();
+        let tx = connection.transaction().unwrap();
+
+        tx.block_roots(
+            BlockNumber::GENESIS,
+            BlockNumber::new_or_panic(MAX_BLOCK_ROOTS_RANGE - 1),
+        )
+        .unwrap();
+        tx.block_roots(
+            BlockNumber::GENESIS,
+            BlockNumber::new_or_panic(MAX_BLOCK_ROOTS_RANGE),
+        )
+        .unwrap_err();
+    }
+
     #[test]
     fn get_latest() {
         let (mut connection, headers) = setup();
@@ -459,4 +751,91 @@ mod tests {
         let l2_by_number = tx.block_is_l1_accepted(headers[1].number.into()).unwrap();
         assert!(!l2_by_number);
     }
+
+    #[test]
+    fn latest_l1_and_l2_blocks() {
+        let (mut connection, headers) = setup();
+        let tx = connection.transaction().unwrap();
+
+        let latest = headers.last().unwrap();
+        let l2 = tx.latest_l2_block().unwrap();
+        assert_eq!(l2, Some((latest.number, latest.hash)));
+
+        // Nothing has been accepted on L1 yet.
+        let l1 = tx.latest_l1_block().unwrap();
+        assert_eq!(l1, None);
+
+        tx.update_l1_l2_pointer(Some(headers[1].number)).unwrap();
+
+        let l1 = tx.latest_l1_block().unwrap();
+        assert_eq!(l1, Some((headers[1].number, headers[1].hash)));
+        let l2 = tx.latest_l2_block().unwrap();
+        assert_eq!(l2, Some((latest.number, latest.hash)));
+        assert!(tx.block_is_l1_accepted(headers[1].number.into()).unwrap());
+    }
+
+    #[test]
+    fn verification_level() {
+        let (mut connection, headers) = setup();
+        let tx = connection.transaction().unwrap();
+
+        // Blocks default to fully verified.
+        let latest = headers.last().unwrap();
+        assert_eq!(
+            tx.highest_fully_verified_block().unwrap(),
+            Some((latest.number, latest.hash))
+        );
+
+        // Mimic a chain started from a checkpoint: everything up to and including the
+        // checkpoint is trusted, and only the blocks after it are verified.
+        let checkpoint = &headers[1];
+        for header in &headers[..=1] {
+            tx.set_verification_level(header.number, VerificationLevel::Trusted)
+                .unwrap();
+        }
+
+        assert_eq!(
+            tx.verification_level(checkpoint.number.into()).unwrap(),
+            Some(VerificationLevel::Trusted)
+        );
+        assert_eq!(
+            tx.verification_level(headers[2].number.into()).unwrap(),
+            Some(VerificationLevel::FullyVerified)
+        );
+        assert_eq!(
+            tx.highest_fully_verified_block().unwrap(),
+            Some((latest.number, latest.hash))
+        );
+
+        // Only trusted blocks.
+        for header in &headers[2..] {
+            tx.set_verification_level(header.number, VerificationLevel::Trusted)
+                .unwrap();
+        }
+        assert_eq!(tx.highest_fully_verified_block().unwrap(), None);
+
+        let missing = latest.number + 1;
+        assert_eq!(tx.verification_level(missing.into()).unwrap(), None);
+        tx.set_verification_level(missing, VerificationLevel::Trusted)
+            .unwrap_err();
+    }
+
+    #[test]
+    fn parent_state_commitment() {
+        let (mut connection, headers) = setup();
+        let tx = connection.transaction().unwrap();
+
+        let result = tx.parent_state_commitment(BlockNumber::GENESIS).unwrap();
+        assert_eq!(result, Some(StateCommitment::ZERO));
+
+        for window in headers.windows(2) {
+            let result = tx.parent_state_commitment(window[1].number).unwrap();
+            assert_eq!(result, Some(window[0].state_commitment));
+        }
+
+        // The parent of the block after next does not exist.
+        let missing = headers.last().unwrap().number + 2;
+        let result = tx.parent_state_commitment(missing).unwrap();
+        assert_eq!(result, None);
+    }
 }
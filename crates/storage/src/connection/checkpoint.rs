@@ -0,0 +1,90 @@
+use anyhow::Context;
+use pathfinder_common::{BlockNumber, StateCommitment};
+
+use crate::prelude::*;
+
+/// A sync checkpoint, recording the state reached by a specific pathfinder version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub block_number: BlockNumber,
+    pub state_commitment: StateCommitment,
+    /// Unix timestamp in seconds at which the checkpoint was recorded.
+    pub timestamp: u64,
+    /// The version of the pathfinder binary which recorded the checkpoint.
+    pub version: String,
+}
+
+pub(super) fn insert_checkpoint(
+    tx: &Transaction<'_>,
+    checkpoint: &Checkpoint,
+) -> anyhow::Result<()> {
+    tx.inner()
+        .execute(
+            r"INSERT INTO checkpoints
+               ( block_number,  state_commitment,  timestamp,  version)
+        VALUES (:block_number, :state_commitment, :timestamp, :version)",
+            named_params! {
+                ":block_number": &checkpoint.block_number,
+                ":state_commitment": &checkpoint.state_commitment,
+                ":timestamp": &checkpoint.timestamp.try_into_sql_int()?,
+                ":version": &checkpoint.version,
+            },
+        )
+        .context("Inserting checkpoint")?;
+
+    Ok(())
+}
+
+pub(super) fn checkpoints(tx: &Transaction<'_>) -> anyhow::Result<Vec<Checkpoint>> {
+    let mut stmt = tx
+        .inner()
+        .prepare_cached(
+            "SELECT block_number, state_commitment, timestamp, version FROM checkpoints ORDER BY id",
+        )
+        .context("Preparing checkpoints query")?;
+
+    let checkpoints = stmt
+        .query_map([], |row| {
+            Ok(Checkpoint {
+                block_number: row.get_block_number(0)?,
+                state_commitment: row.get_state_commitment(1)?,
+                timestamp: row.get_i64(2)? as u64,
+                version: row.get(3)?,
+            })
+        })
+        .context("Querying checkpoints")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Iterating over checkpoints")?;
+
+    Ok(checkpoints)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::macro_prelude::*;
+
+    #[test]
+    fn checkpoints_are_listed_in_insertion_order() {
+        let mut db = crate::Storage::in_memory().unwrap().connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let first = Checkpoint {
+            block_number: BlockNumber::new_or_panic(10),
+            state_commitment: state_commitment_bytes!(b"root 10"),
+            timestamp: 1000,
+            version: "v0.1.0".to_owned(),
+        };
+        let second = Checkpoint {
+            block_number: BlockNumber::new_or_panic(20),
+            state_commitment: state_commitment_bytes!(b"root 20"),
+            timestamp: 2000,
+            version: "v0.2.0".to_owned(),
+        };
+
+        tx.insert_checkpoint(&first).unwrap();
+        tx.insert_checkpoint(&second).unwrap();
+
+        assert_eq!(tx.checkpoints().unwrap(), vec![first, second]);
+    }
+}
@@ -142,6 +142,40 @@ pub(super) fn class_definition(
     Ok(Some(definition))
 }
 
+pub(super) fn casm_compiler_version(
+    transaction: &Transaction<'_>,
+    class_hash: ClassHash,
+) -> anyhow::Result<Option<String>> {
+    transaction
+        .inner()
+        .query_row(
+            r"SELECT casm_compiler_versions.version FROM casm_definitions
+                JOIN casm_compiler_versions ON casm_compiler_versions.id = casm_definitions.compiler_version_id
+            WHERE casm_definitions.hash = ?",
+            params![&class_hash],
+            |row| row.get(0),
+        )
+        .optional()
+        .context("Querying for CASM compiler version")
+}
+
+pub(super) fn compressed_class_definition_len(
+    transaction: &Transaction<'_>,
+    class_hash: ClassHash,
+) -> anyhow::Result<Option<usize>> {
+    let len = transaction
+        .inner()
+        .query_row(
+            "SELECT length(definition) FROM class_definitions WHERE hash = ?",
+            params![&class_hash],
+            |row| row.get_i64(0),
+        )
+        .optional()
+        .context("Querying for class definition length")?;
+
+    Ok(len.map(|len| len as usize))
+}
+
 pub(super) fn compressed_class_definition_at(
     tx: &Transaction<'_>,
     block_id: BlockId,
@@ -451,6 +485,55 @@ mod tests {
         assert_eq!(definition, cairo_definition);
     }
 
+    #[test]
+    fn definition_is_stored_compressed() {
+        let mut connection = Storage::in_memory().unwrap().connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let cairo_hash = class_hash_bytes!(b"cairo hash");
+        let cairo_definition = br#"{"program":"repetitive bytecode"}"#.repeat(100);
+
+        insert_cairo_class(&tx, cairo_hash, &cairo_definition).unwrap();
+
+        let stored: Vec<u8> = tx
+            .inner()
+            .query_row(
+                "SELECT definition FROM class_definitions WHERE hash = ?",
+                params![&cairo_hash],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(stored.len() < cairo_definition.len());
+
+        let definition = class_definition(&tx, cairo_hash).unwrap().unwrap();
+        assert_eq!(definition, cairo_definition);
+    }
+
+    #[test]
+    fn compressed_definition_len() {
+        let mut connection = Storage::in_memory().unwrap().connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let cairo_hash = class_hash_bytes!(b"cairo hash");
+        let cairo_definition = br#"{"program":"repetitive bytecode"}"#.repeat(100);
+        insert_cairo_class(&tx, cairo_hash, &cairo_definition).unwrap();
+
+        let stored: Vec<u8> = tx
+            .inner()
+            .query_row(
+                "SELECT definition FROM class_definitions WHERE hash = ?",
+                params![&cairo_hash],
+                |row| row.get(0),
+            )
+            .unwrap();
+
+        let len = compressed_class_definition_len(&tx, cairo_hash).unwrap();
+        assert_eq!(len, Some(stored.len()));
+
+        let len = compressed_class_definition_len(&tx, class_hash_bytes!(b"missing")).unwrap();
+        assert_eq!(len, None);
+    }
+
     #[test]
     fn insert_sierra() {
         let mut connection = Storage::in_memory().unwrap().connection().unwrap();
@@ -502,6 +585,31 @@ mod tests {
         assert_eq!(definition, sierra_definition);
     }
 
+    #[test]
+    fn compiler_version() {
+        let mut connection = Storage::in_memory().unwrap().connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let sierra_hash = sierra_hash_bytes!(b"sierra hash");
+        insert_sierra_class(
+            &tx,
+            &sierra_hash,
+            b"example sierra program",
+            &casm_hash_bytes!(b"casm hash"),
+            b"compiled sierra program",
+            "1.1.0",
+        )
+        .unwrap();
+        let cairo_hash = class_hash_bytes!(b"cairo hash");
+        insert_cairo_class(&tx, cairo_hash, b"example cairo program").unwrap();
+
+        let version = casm_compiler_version(&tx, ClassHash(sierra_hash.0)).unwrap();
+        assert_eq!(version, Some("1.1.0".to_owned()));
+
+        let version = casm_compiler_version(&tx, cairo_hash).unwrap();
+        assert_eq!(version, None);
+    }
+
     #[test]
     fn compiled_class_leaves() {
         let mut connection = Storage::in_memory().unwrap().connection().unwrap();
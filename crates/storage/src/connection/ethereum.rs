@@ -1,26 +1,46 @@
-use pathfinder_common::BlockNumber;
-use pathfinder_ethereum::EthereumStateUpdate;
+use anyhow::Context;
+use pathfinder_common::{
+    BlockNumber, EntryPoint, EthereumAddress, Fee, L1ToL2MessageNonce, L1ToL2MessagePayloadElem,
+};
+use pathfinder_ethereum::{EthereumOrigin, EthereumStateUpdate, L1ToL2Message};
+use primitive_types::{H160, H256};
+use stark_hash::Felt;
 
 use crate::prelude::*;
 
+/// Inserts or replaces the L1 state of a Starknet block.
+///
+/// If the Starknet state is unchanged, the Ethereum block at which it was first seen is kept.
 pub(super) fn upsert_l1_state(
     tx: &Transaction<'_>,
     update: &EthereumStateUpdate,
 ) -> anyhow::Result<()> {
     tx.inner().execute(
-        r"INSERT OR REPLACE INTO l1_state (
+        r"INSERT INTO l1_state (
                     starknet_block_number,
                     starknet_block_hash,
-                    starknet_state_root
+                    starknet_state_root,
+                    ethereum_block_number
                 ) VALUES (
                     :starknet_block_number,
                     :starknet_block_hash,
-                    :starknet_state_root
-                )",
+                    :starknet_state_root,
+                    :ethereum_block_number
+                )
+                ON CONFLICT(starknet_block_number) DO UPDATE SET
+                    ethereum_block_number = CASE
+                        WHEN starknet_block_hash = excluded.starknet_block_hash
+                            AND starknet_state_root = excluded.starknet_state_root
+                        THEN COALESCE(ethereum_block_number, excluded.ethereum_block_number)
+                        ELSE excluded.ethereum_block_number
+                    END,
+                    starknet_block_hash = excluded.starknet_block_hash,
+                    starknet_state_root = excluded.starknet_state_root",
         named_params! {
             ":starknet_block_number": &update.block_number,
             ":starknet_block_hash": &update.block_hash,
             ":starknet_state_root": &update.state_root,
+            ":ethereum_block_number": &update.ethereum_block_number,
         },
     )?;
 
@@ -33,18 +53,21 @@ pub(super) fn l1_state_at_number(
 ) -> anyhow::Result<Option<EthereumStateUpdate>> {
     tx.inner()
         .query_row(
-            r"SELECT starknet_block_number, starknet_block_hash, starknet_state_root FROM l1_state 
+            r"SELECT starknet_block_number, starknet_block_hash, starknet_state_root, ethereum_block_number
+            FROM l1_state 
             WHERE starknet_block_number = ?",
             params![&block],
             |row| {
                 let block_number = row.get_block_number(0)?;
                 let block_hash = row.get_block_hash(1)?;
                 let state_root = row.get_state_commitment(2)?;
+                let ethereum_block_number = row.get_optional_i64(3)?.map(|n| n as u64);
 
                 Ok(EthereumStateUpdate {
                     state_root,
                     block_number,
                     block_hash,
+                    ethereum_block_number,
                 })
             },
         )
@@ -52,10 +75,40 @@ pub(super) fn l1_state_at_number(
         .map_err(|e| e.into())
 }
 
+pub(super) fn l1_states(
+    tx: &Transaction<'_>,
+    from: BlockNumber,
+    to: BlockNumber,
+) -> anyhow::Result<Vec<EthereumStateUpdate>> {
+    let mut stmt = tx.inner().prepare_cached(
+        r"SELECT starknet_block_number, starknet_block_hash, starknet_state_root, ethereum_block_number
+            FROM l1_state
+        WHERE starknet_block_number >= ? AND starknet_block_number <= ?
+        ORDER BY starknet_block_number",
+    )?;
+
+    let rows = stmt.query_map(params![&from, &to], |row| {
+        let block_number = row.get_block_number(0)?;
+        let block_hash = row.get_block_hash(1)?;
+        let state_root = row.get_state_commitment(2)?;
+        let ethereum_block_number = row.get_optional_i64(3)?.map(|n| n as u64);
+
+        Ok(EthereumStateUpdate {
+            state_root,
+            block_number,
+            block_hash,
+            ethereum_block_number,
+        })
+    })?;
+
+    rows.collect::<Result<_, _>>().map_err(|e| e.into())
+}
+
 pub(super) fn latest_l1_state(tx: &Transaction<'_>) -> anyhow::Result<Option<EthereumStateUpdate>> {
     tx.inner()
         .query_row(
-            r"SELECT starknet_block_number, starknet_block_hash, starknet_state_root FROM l1_state 
+            r"SELECT starknet_block_number, starknet_block_hash, starknet_state_root, ethereum_block_number
+            FROM l1_state 
             ORDER BY starknet_block_number DESC
             LIMIT 1",
             [],
@@ -63,11 +116,158 @@ pub(super) fn latest_l1_state(tx: &Transaction<'_>) -> anyhow::Result<Option<Eth
                 let block_number = row.get_block_number(0)?;
                 let block_hash = row.get_block_hash(1)?;
                 let state_root = row.get_state_commitment(2)?;
+                let ethereum_block_number = row.get_optional_i64(3)?.map(|n| n as u64);
+
+                Ok(EthereumStateUpdate {
+                    state_root,
+                    block_number,
+                    block_hash,
+                    ethereum_block_number,
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| e.into())
+}
+
+/// Returns the Starknet state most recently anchored at or before Ethereum block
+/// `ethereum_block`.
+///
+/// States whose Ethereum block is unknown are ignored.
+pub(super) fn l1_state_at_ethereum_block(
+    tx: &Transaction<'_>,
+    ethereum_block: u64,
+) -> anyhow::Result<Option<EthereumStateUpdate>> {
+    tx.inner()
+        .query_row(
+            r"SELECT starknet_block_number, starknet_block_hash, starknet_state_root, ethereum_block_number
+            FROM l1_state
+            WHERE ethereum_block_number <= ?
+            ORDER BY ethereum_block_number DESC, starknet_block_number DESC
+            LIMIT 1",
+            params![&ethereum_block],
+            |row| {
+                let block_number = row.get_block_number(0)?;
+                let block_hash = row.get_block_hash(1)?;
+                let state_root = row.get_state_commitment(2)?;
+                let ethereum_block_number = row.get_optional_i64(3)?.map(|n| n as u64);
 
                 Ok(EthereumStateUpdate {
                     state_root,
                     block_number,
                     block_hash,
+                    ethereum_block_number,
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| e.into())
+}
+
+/// Inserts messages sent from Ethereum to Starknet, keyed by their message hash.
+///
+/// Messages which are already stored are left untouched, so logs may safely be fetched again.
+pub(super) fn insert_l1_to_l2_messages(
+    tx: &Transaction<'_>,
+    messages: &[L1ToL2Message],
+) -> anyhow::Result<()> {
+    let mut stmt = tx
+        .inner()
+        .prepare_cached(
+            r"INSERT OR IGNORE INTO l1_to_l2_messages (
+                    message_hash,
+                    from_address,
+                    to_address,
+                    selector,
+                    payload,
+                    nonce,
+                    fee,
+                    ethereum_block_number,
+                    ethereum_block_hash,
+                    ethereum_transaction_hash,
+                    ethereum_log_index
+                ) VALUES (
+                    :message_hash,
+                    :from_address,
+                    :to_address,
+                    :selector,
+                    :payload,
+                    :nonce,
+                    :fee,
+                    :ethereum_block_number,
+                    :ethereum_block_hash,
+                    :ethereum_transaction_hash,
+                    :ethereum_log_index
+                )",
+        )
+        .context("Preparing insert statement")?;
+
+    for message in messages {
+        let payload = message
+            .payload
+            .iter()
+            .flat_map(|elem| elem.0.to_be_bytes())
+            .collect::<Vec<_>>();
+
+        stmt.execute(named_params! {
+            ":message_hash": &message.message_hash().as_bytes(),
+            ":from_address": &message.from_address.0.as_bytes(),
+            ":to_address": &message.to_address,
+            ":selector": &message.selector,
+            ":payload": &payload,
+            ":nonce": &message.nonce,
+            ":fee": &message.fee,
+            ":ethereum_block_number": &message.origin.block_number,
+            ":ethereum_block_hash": &message.origin.block_hash.as_bytes(),
+            ":ethereum_transaction_hash": &message.origin.transaction_hash.as_bytes(),
+            ":ethereum_log_index": &message.origin.log_index,
+        })
+        .context("Inserting L1 to L2 message")?;
+    }
+
+    Ok(())
+}
+
+/// Returns the message sent from Ethereum to Starknet with the given message hash.
+pub(super) fn l1_to_l2_message(
+    tx: &Transaction<'_>,
+    message_hash: H256,
+) -> anyhow::Result<Option<L1ToL2Message>> {
+    tx.inner()
+        .query_row(
+            r"SELECT from_address, to_address, selector, payload, nonce, fee,
+                ethereum_block_number, ethereum_block_hash, ethereum_transaction_hash,
+                ethereum_log_index
+            FROM l1_to_l2_messages
+            WHERE message_hash = ?",
+            [message_hash.as_bytes()],
+            |row| {
+                let from_address = EthereumAddress(H160::from_slice(row.get_blob(0)?));
+                let to_address = row.get_contract_address(1)?;
+                let selector = EntryPoint(row.get_felt(2)?);
+                let payload = row
+                    .get_blob(3)?
+                    .chunks(32)
+                    .map(|elem| Felt::from_be_slice(elem).map(L1ToL2MessagePayloadElem))
+                    .collect::<Result<_, _>>()
+                    .map_err(|e| rusqlite::types::FromSqlError::Other(e.into()))?;
+                let nonce = L1ToL2MessageNonce(row.get_felt(4)?);
+                let fee = row.get_optional_felt(5)?.map(Fee);
+                let origin = EthereumOrigin {
+                    block_number: row.get_i64(6)? as u64,
+                    block_hash: H256::from_slice(row.get_blob(7)?),
+                    transaction_hash: H256::from_slice(row.get_blob(8)?),
+                    log_index: row.get_i64(9)? as u64,
+                };
+
+                Ok(L1ToL2Message {
+                    from_address,
+                    to_address,
+                    selector,
+                    payload,
+                    nonce,
+                    fee,
+                    origin,
                 })
             },
         )
@@ -81,7 +281,9 @@ mod tests {
 
     use crate::Storage;
     use pathfinder_common::macro_prelude::*;
-    use pathfinder_common::{BlockHash, StateCommitment};
+    use pathfinder_common::{
+        BlockHash, CallParam, StateCommitment, TransactionNonce, TransactionVersion,
+    };
     use pathfinder_ethereum::EthereumStateUpdate;
     use stark_hash::Felt;
 
@@ -93,6 +295,7 @@ mod tests {
                 state_root: StateCommitment(Felt::from_hex_str(&"3".repeat(i + 1)).unwrap()),
                 block_number: BlockNumber::GENESIS + i as u64,
                 block_hash: BlockHash(Felt::from_hex_str(&"F".repeat(i + 1)).unwrap()),
+                ethereum_block_number: Some(1000 + 10 * i as u64),
             })
             .collect::<Vec<_>>()
             .try_into()
@@ -156,6 +359,7 @@ mod tests {
             state_root: state_commitment!("0x1234"),
             block_number: BlockNumber::new_or_panic(10),
             block_hash: block_hash!("0xabdd"),
+            ethereum_block_number: Some(100),
         };
         upsert_l1_state(&tx, &original).unwrap();
 
@@ -163,6 +367,7 @@ mod tests {
             state_root: state_commitment!("0xabcdef"),
             block_number: original.block_number,
             block_hash: block_hash!("0xccdd22"),
+            ethereum_block_number: Some(200),
         };
         upsert_l1_state(&tx, &new_value).unwrap();
 
@@ -171,4 +376,138 @@ mod tests {
             .unwrap();
         assert_eq!(result, new_value);
     }
+
+    #[test]
+    fn range() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let updates = create_updates();
+        for update in updates.clone() {
+            upsert_l1_state(&tx, &update).unwrap();
+        }
+
+        let result = l1_states(&tx, BlockNumber::GENESIS, BlockNumber::MAX).unwrap();
+        assert_eq!(result, updates);
+
+        let result = l1_states(&tx, updates[1].block_number, updates[1].block_number).unwrap();
+        assert_eq!(result, &updates[1..2]);
+
+        let result = l1_states(&tx, updates[2].block_number + 1, BlockNumber::MAX).unwrap();
+        assert_eq!(result, Vec::new());
+    }
+
+    #[test]
+    fn upsert_of_unchanged_state_keeps_ethereum_block() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let original = create_updates()[0].clone();
+        upsert_l1_state(&tx, &original).unwrap();
+
+        let seen_later = EthereumStateUpdate {
+            ethereum_block_number: Some(5000),
+            ..original.clone()
+        };
+        upsert_l1_state(&tx, &seen_later).unwrap();
+
+        let result = l1_state_at_number(&tx, original.block_number)
+            .unwrap()
+            .unwrap();
+        assert_eq!(result, original);
+    }
+
+    #[test]
+    fn at_ethereum_block() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        // Anchored at Ethereum blocks 1000, 1010 and 1020.
+        let updates = create_updates();
+        for update in updates.clone() {
+            upsert_l1_state(&tx, &update).unwrap();
+        }
+
+        let result = l1_state_at_ethereum_block(&tx, 999).unwrap();
+        assert_eq!(result, None);
+
+        let result = l1_state_at_ethereum_block(&tx, 1000).unwrap();
+        assert_eq!(result.as_ref(), Some(&updates[0]));
+
+        let result = l1_state_at_ethereum_block(&tx, 1015).unwrap();
+        assert_eq!(result.as_ref(), Some(&updates[1]));
+
+        let result = l1_state_at_ethereum_block(&tx, u32::MAX as u64).unwrap();
+        assert_eq!(result.as_ref(), Some(&updates[2]));
+    }
+
+    #[test]
+    fn l1_to_l2_messages() {
+        use starknet_gateway_types::reply::transaction::L1HandlerTransaction;
+
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let message = L1ToL2Message {
+            from_address: EthereumAddress(H160::from_low_u64_be(0xae0ee0a6)),
+            to_address: contract_address!(
+                "0x73314940630fd6dcda0d772d4c972c4e0a9946bef9dabf4ef84eda8ef542b82"
+            ),
+            selector: entry_point!(
+                "0x2d757788a8d8d6f21d1cd40bce38a8222d70654214e96ff95d8086e684fbee5"
+            ),
+            payload: vec![
+                l1_to_l2_message_payload_elem!("0x123"),
+                l1_to_l2_message_payload_elem!("0x2386f26fc10000"),
+                l1_to_l2_message_payload_elem!("0x0"),
+            ],
+            nonce: l1_to_l2_message_nonce!("0x1a2b"),
+            fee: Some(fee!("0x3b9aca00")),
+            origin: EthereumOrigin {
+                block_number: 0x12,
+                block_hash: H256::from_low_u64_be(0xa1),
+                transaction_hash: H256::from_low_u64_be(0xb1),
+                log_index: 3,
+            },
+        };
+        // A message sent by an older core contract, which did not charge a fee.
+        let without_fee = L1ToL2Message {
+            payload: vec![],
+            nonce: l1_to_l2_message_nonce!("0x1a2c"),
+            fee: None,
+            ..message.clone()
+        };
+
+        let messages = [message, without_fee];
+        tx.insert_l1_to_l2_messages(&messages).unwrap();
+        // Inserting the same messages again is a no-op.
+        tx.insert_l1_to_l2_messages(&messages).unwrap();
+
+        for message in &messages {
+            let stored = tx.l1_to_l2_message(message.message_hash()).unwrap();
+            assert_eq!(stored.as_ref(), Some(message));
+
+            // The L1 handler transaction consuming the message must have the same hash.
+            let l1_handler = L1HandlerTransaction {
+                contract_address: message.to_address,
+                entry_point_selector: message.selector,
+                nonce: TransactionNonce(message.nonce.0),
+                calldata: std::iter::once(CallParam(
+                    Felt::from_be_slice(message.from_address.0.as_bytes()).unwrap(),
+                ))
+                .chain(message.payload.iter().map(|elem| CallParam(elem.0)))
+                .collect(),
+                transaction_hash: transaction_hash!("0x1"),
+                version: TransactionVersion::ZERO,
+            };
+            assert_eq!(l1_handler.calculate_message_hash(), message.message_hash());
+        }
+
+        let result = tx.l1_to_l2_message(H256::zero()).unwrap();
+        assert_eq!(result, None);
+    }
 }
@@ -28,6 +28,13 @@ pub(super) fn insert_state_update(
         .inner().prepare_cached("INSERT INTO contract_updates (block_number, contract_address, class_hash) VALUES (?, ?, ?)")
         .context("Preparing contract insert statement")?;
 
+    let mut query_deployed_class = tx
+        .inner()
+        .prepare_cached(
+            "SELECT class_hash FROM contract_updates WHERE contract_address = ? ORDER BY block_number LIMIT 1",
+        )
+        .context("Preparing deployed class query statement")?;
+
     let mut update_class_defs = tx
         .inner()
         .prepare_cached(
@@ -36,7 +43,29 @@ pub(super) fn insert_state_update(
         .context("Preparing class definition block number update statement")?;
 
     for (address, update) in &state_update.contract_updates {
-        if let Some(class_update) = &update.class {
+        if let Some(ContractClassUpdate::Deploy(class_hash)) = &update.class {
+            let deployed_class = query_deployed_class
+                .query_row(params![address], |row| row.get_class_hash(0))
+                .optional()
+                .context("Querying deployed class")?;
+
+            match deployed_class {
+                // Re-deploying an existing contract with the same class is a no-op, which
+                // can happen when replaying blocks after a reorg.
+                Some(deployed_class) if deployed_class == *class_hash => {}
+                Some(deployed_class) => anyhow::bail!(
+                    "Contract {} is already deployed with class {}, cannot redeploy it with class {}",
+                    address,
+                    deployed_class,
+                    class_hash
+                ),
+                None => {
+                    insert_contract
+                        .execute(params![&block_number, address, class_hash])
+                        .context("Inserting deployed contract")?;
+                }
+            }
+        } else if let Some(class_update) = &update.class {
             insert_contract
                 .execute(params![&block_number, address, &class_update.class_hash()])
                 .context("Inserting deployed contract")?;
@@ -308,6 +337,32 @@ pub(super) fn storage_value(
     .map_err(|e| e.into())
 }
 
+/// Returns the storage value of `contract_address` at `key` in the state identified by
+/// `state_commitment`.
+///
+/// Returns [None] if the slot was never set in that state, or if no block has this state
+/// commitment.
+pub(super) fn storage_value_at_state_commitment(
+    tx: &Transaction<'_>,
+    state_commitment: StateCommitment,
+    contract_address: ContractAddress,
+    key: StorageAddress,
+) -> anyhow::Result<Option<StorageValue>> {
+    // Consecutive blocks can share a state commitment, in which case they also share the state.
+    tx.inner()
+        .query_row(
+            r"SELECT storage_value FROM storage_updates
+                WHERE contract_address = ? AND storage_address = ? AND block_number <= (
+                    SELECT number FROM block_headers WHERE state_commitment = ? ORDER BY number LIMIT 1
+                )
+                ORDER BY block_number DESC LIMIT 1",
+            params![&contract_address, &key, &state_commitment],
+            |row| row.get_storage_value(0),
+        )
+        .optional()
+        .context("Querying storage value at state commitment")
+}
+
 pub(super) fn contract_exists(
     tx: &Transaction<'_>,
     contract_address: ContractAddress,
@@ -405,6 +460,167 @@ pub(super) fn contract_class_hash(
     .map_err(|e| e.into())
 }
 
+/// Batch version of [contract_class_hash] which resolves the block once and reuses a single
+/// prepared statement for all addresses.
+pub(super) fn contract_class_hashes(
+    tx: &Transaction<'_>,
+    block_id: BlockId,
+    contract_addresses: &[ContractAddress],
+) -> anyhow::Result<Vec<Option<ClassHash>>> {
+    let block_number = match block_id {
+        BlockId::Latest => BlockNumber::MAX,
+        BlockId::Number(number) => number,
+        BlockId::Hash(hash) => {
+            let number = tx
+                .inner()
+                .query_row(
+                    "SELECT number FROM canonical_blocks WHERE hash = ?",
+                    params![&hash],
+                    |row| row.get_block_number(0),
+                )
+                .optional()
+                .context("Querying block number")?;
+
+            match number {
+                Some(number) => number,
+                None => return Ok(vec![None; contract_addresses.len()]),
+            }
+        }
+    };
+
+    let mut stmt = tx
+        .inner()
+        .prepare_cached(
+            r"SELECT class_hash FROM contract_updates
+                WHERE contract_address = ? AND block_number <= ?
+                ORDER BY block_number DESC LIMIT 1",
+        )
+        .context("Preparing statement")?;
+
+    contract_addresses
+        .iter()
+        .map(|address| {
+            stmt.query_row(params![address, &block_number], |row| row.get_class_hash(0))
+                .optional()
+                .with_context(|| format!("Querying class hash of {address}"))
+        })
+        .collect()
+}
+
+pub(super) fn contract_deployed_at(
+    tx: &Transaction<'_>,
+    contract_address: ContractAddress,
+) -> anyhow::Result<Option<BlockNumber>> {
+    tx.inner()
+        .query_row(
+            r"SELECT block_number FROM contract_updates
+                WHERE contract_address = ?
+                ORDER BY block_number LIMIT 1",
+            params![&contract_address],
+            |row| row.get_block_number(0),
+        )
+        .optional()
+        .context("Querying contract deployment block")
+}
+
+pub(super) fn first_storage_write(
+    tx: &Transaction<'_>,
+    contract_address: ContractAddress,
+) -> anyhow::Result<Option<BlockNumber>> {
+    tx.inner()
+        .query_row(
+            r"SELECT block_number FROM storage_updates
+                WHERE contract_address = ?
+                ORDER BY block_number LIMIT 1",
+            params![&contract_address],
+            |row| row.get_block_number(0),
+        )
+        .optional()
+        .context("Querying first storage write block")
+}
+
+/// Returns a page of all known contracts and their latest class hash, ordered by
+/// contract address.
+pub(super) fn contracts(
+    tx: &Transaction<'_>,
+    offset: usize,
+    limit: usize,
+) -> anyhow::Result<Vec<(ContractAddress, ClassHash)>> {
+    // Sqlite takes bare columns from the row which satisfies MAX() in an aggregate query,
+    // so this selects the class hash of each contract's most recent update.
+    let mut stmt = tx
+        .inner()
+        .prepare_cached(
+            r"SELECT contract_address, class_hash, MAX(block_number) FROM contract_updates
+                GROUP BY contract_address
+                ORDER BY contract_address
+                LIMIT ? OFFSET ?",
+        )
+        .context("Preparing contract list query statement")?;
+
+    let contracts = stmt
+        .query_map(
+            rusqlite::params![limit.try_into_sql()?, offset.try_into_sql()?],
+            |row| {
+                let address = row.get_contract_address(0)?;
+                let class_hash = row.get_class_hash(1)?;
+
+                Ok((address, class_hash))
+            },
+        )
+        .context("Querying contract list")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Iterating over contract list rows")?;
+
+    Ok(contracts)
+}
+
+/// Calls `f` with every storage entry's latest value as of `block`, ordered by contract address
+/// and storage address.
+///
+/// Rows are streamed from the database, so memory use does not grow with the size of the state.
+pub(super) fn storage_entries_at(
+    tx: &Transaction<'_>,
+    block: BlockNumber,
+    mut f: impl FnMut(ContractAddress, StorageAddress, StorageValue) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    // See `contracts` for how MAX() selects the latest value.
+    let mut stmt = tx
+        .inner()
+        .prepare_cached(
+            r"SELECT contract_address, storage_address, storage_value, MAX(block_number) FROM storage_updates
+                WHERE block_number <= ?
+                GROUP BY contract_address, storage_address
+                ORDER BY contract_address, storage_address",
+        )
+        .context("Preparing storage entries query statement")?;
+
+    let mut rows = stmt
+        .query(params![&block])
+        .context("Querying storage entries")?;
+
+    while let Some(row) = rows.next().context("Iterating over storage entry rows")? {
+        let contract = row.get_contract_address(0)?;
+        let key = row.get_storage_address(1)?;
+        let value = row.get_storage_value(2)?;
+
+        f(contract, key, value)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the number of distinct contracts which have been deployed.
+pub(super) fn contract_count(tx: &Transaction<'_>) -> anyhow::Result<usize> {
+    tx.inner()
+        .query_row(
+            "SELECT COUNT(DISTINCT contract_address) FROM contract_updates",
+            [],
+            |row| row.get(0),
+        )
+        .context("Counting contracts")
+}
+
 #[cfg(test)]
 mod tests {
     use pathfinder_common::macro_prelude::*;
@@ -413,6 +629,107 @@ mod tests {
     use super::super::class::{casm_definition_at, casm_hash_at};
     use super::*;
 
+    #[test]
+    fn redeploy_with_same_class_is_noop() {
+        let mut db = crate::Storage::in_memory().unwrap().connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let contract = contract_address_bytes!(b"contract");
+        let class = class_hash_bytes!(b"class");
+
+        let header_0 = BlockHeader::builder().finalize_with_hash(block_hash_bytes!(b"0"));
+        let header_1 = header_0
+            .child_builder()
+            .finalize_with_hash(block_hash_bytes!(b"1"));
+
+        let diff = StateUpdate::default().with_deployed_contract(contract, class);
+        tx.insert_block_header(&header_0).unwrap();
+        tx.insert_state_update(header_0.number, &diff).unwrap();
+        tx.insert_block_header(&header_1).unwrap();
+        tx.insert_state_update(header_1.number, &diff).unwrap();
+
+        // The contract is still only deployed in the first block.
+        let result = super::state_update(&tx, header_1.number.into())
+            .unwrap()
+            .unwrap();
+        assert!(result.contract_updates.is_empty());
+        let result = super::state_update(&tx, header_0.number.into())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            result.contract_updates[&contract].class,
+            Some(ContractClassUpdate::Deploy(class))
+        );
+    }
+
+    #[test]
+    fn redeploy_with_different_class_is_an_error() {
+        let mut db = crate::Storage::in_memory().unwrap().connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let contract = contract_address_bytes!(b"contract");
+
+        let header_0 = BlockHeader::builder().finalize_with_hash(block_hash_bytes!(b"0"));
+        let header_1 = header_0
+            .child_builder()
+            .finalize_with_hash(block_hash_bytes!(b"1"));
+
+        tx.insert_block_header(&header_0).unwrap();
+        tx.insert_state_update(
+            header_0.number,
+            &StateUpdate::default().with_deployed_contract(contract, class_hash_bytes!(b"class")),
+        )
+        .unwrap();
+        tx.insert_block_header(&header_1).unwrap();
+        tx.insert_state_update(
+            header_1.number,
+            &StateUpdate::default()
+                .with_deployed_contract(contract, class_hash_bytes!(b"other class")),
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn contract_exists_from_deployment_block() {
+        let mut db = crate::Storage::in_memory().unwrap().connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let contract = contract_address_bytes!(b"contract");
+
+        let header_0 = BlockHeader::builder().finalize_with_hash(block_hash_bytes!(b"0"));
+        let header_1 = header_0
+            .child_builder()
+            .finalize_with_hash(block_hash_bytes!(b"1"));
+        let header_2 = header_1
+            .child_builder()
+            .finalize_with_hash(block_hash_bytes!(b"2"));
+
+        tx.insert_block_header(&header_0).unwrap();
+        tx.insert_state_update(header_0.number, &StateUpdate::default())
+            .unwrap();
+        tx.insert_block_header(&header_1).unwrap();
+        tx.insert_state_update(
+            header_1.number,
+            &StateUpdate::default().with_deployed_contract(contract, class_hash_bytes!(b"class")),
+        )
+        .unwrap();
+        tx.insert_block_header(&header_2).unwrap();
+        tx.insert_state_update(header_2.number, &StateUpdate::default())
+            .unwrap();
+
+        let exists = |block: BlockId| contract_exists(&tx, contract, block).unwrap();
+
+        assert!(!exists(header_0.number.into()));
+        assert!(!exists(header_0.hash.into()));
+        assert!(exists(header_1.number.into()));
+        assert!(exists(header_1.hash.into()));
+        assert!(exists(header_2.number.into()));
+        assert!(exists(BlockId::Latest));
+
+        let other = contract_address_bytes!(b"other");
+        assert!(!contract_exists(&tx, other, BlockId::Latest).unwrap());
+    }
+
     #[test]
     fn contract_class_hash() {
         let mut db = crate::Storage::in_memory().unwrap().connection().unwrap();
@@ -496,6 +813,177 @@ mod tests {
         assert_eq!(is_replaced, Some(replaced_class));
     }
 
+    #[test]
+    fn contract_class_hashes_match_individual_lookups() {
+        let mut db = crate::Storage::in_memory().unwrap().connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let class_a = class_hash!("0xa");
+        let class_b = class_hash!("0xb");
+        let contract_1 = contract_address!("0x1");
+        let contract_2 = contract_address!("0x2");
+        let undeployed = contract_address!("0x3");
+
+        let header_0 = BlockHeader::builder().finalize_with_hash(block_hash!("0xabc"));
+        let header_1 = header_0
+            .child_builder()
+            .finalize_with_hash(block_hash!("0xabcdef"));
+        let header_2 = header_1
+            .child_builder()
+            .finalize_with_hash(block_hash!("0xa111123"));
+
+        let diff_0 = StateUpdate::default()
+            .with_declared_cairo_class(class_a)
+            .with_declared_cairo_class(class_b)
+            .with_deployed_contract(contract_1, class_a);
+        let diff_1 = StateUpdate::default().with_deployed_contract(contract_2, class_b);
+        let diff_2 = StateUpdate::default().with_replaced_class(contract_1, class_b);
+
+        tx.insert_cairo_class(class_a, b"definition a").unwrap();
+        tx.insert_cairo_class(class_b, b"definition b").unwrap();
+
+        for (header, diff) in [
+            (&header_0, diff_0),
+            (&header_1, diff_1),
+            (&header_2, diff_2),
+        ] {
+            tx.insert_block_header(header).unwrap();
+            tx.insert_state_update(header.number, &diff).unwrap();
+        }
+
+        let addresses = [contract_1, contract_2, undeployed];
+        let block_ids = [
+            BlockId::Latest,
+            header_0.number.into(),
+            header_1.number.into(),
+            header_2.number.into(),
+            header_1.hash.into(),
+            block_hash!("0xdeadbeef").into(),
+        ];
+
+        for block_id in block_ids {
+            let batch = super::contract_class_hashes(&tx, block_id, &addresses).unwrap();
+            let individual = addresses
+                .iter()
+                .map(|address| super::contract_class_hash(&tx, block_id, *address).unwrap())
+                .collect::<Vec<_>>();
+
+            assert_eq!(batch, individual, "{block_id:?}");
+        }
+
+        let latest = super::contract_class_hashes(&tx, BlockId::Latest, &addresses).unwrap();
+        assert_eq!(latest, vec![Some(class_b), Some(class_b), None]);
+    }
+
+    #[test]
+    fn contract_deployment_and_first_storage_write() {
+        let mut db = crate::Storage::in_memory().unwrap().connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let contract = contract_address_bytes!(b"contract");
+        let other = contract_address_bytes!(b"other");
+
+        let header_0 = BlockHeader::builder().finalize_with_hash(block_hash_bytes!(b"0"));
+        let header_1 = header_0
+            .child_builder()
+            .finalize_with_hash(block_hash_bytes!(b"1"));
+        let header_2 = header_1
+            .child_builder()
+            .finalize_with_hash(block_hash_bytes!(b"2"));
+
+        let diff_0 =
+            StateUpdate::default().with_deployed_contract(other, class_hash_bytes!(b"class"));
+        let diff_1 =
+            StateUpdate::default().with_deployed_contract(contract, class_hash_bytes!(b"class"));
+        let diff_2 = StateUpdate::default().with_storage_update(
+            contract,
+            storage_address_bytes!(b"key"),
+            storage_value_bytes!(b"value"),
+        );
+
+        for (header, diff) in [
+            (&header_0, diff_0),
+            (&header_1, diff_1),
+            (&header_2, diff_2),
+        ] {
+            tx.insert_block_header(header).unwrap();
+            tx.insert_state_update(header.number, &diff).unwrap();
+        }
+
+        assert_eq!(
+            tx.contract_deployed_at(contract).unwrap(),
+            Some(header_1.number)
+        );
+        assert_eq!(
+            tx.first_storage_write(contract).unwrap(),
+            Some(header_2.number)
+        );
+
+        assert_eq!(
+            tx.contract_deployed_at(other).unwrap(),
+            Some(header_0.number)
+        );
+        assert_eq!(tx.first_storage_write(other).unwrap(), None);
+
+        let unknown = contract_address_bytes!(b"unknown");
+        assert_eq!(tx.contract_deployed_at(unknown).unwrap(), None);
+        assert_eq!(tx.first_storage_write(unknown).unwrap(), None);
+    }
+
+    #[test]
+    fn contracts_pagination() {
+        let mut db = crate::Storage::in_memory().unwrap().connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let class = class_hash_bytes!(b"class");
+        let replaced_class = class_hash_bytes!(b"replaced class");
+        let contracts = (0..7u8)
+            .map(|i| contract_address_bytes!(&[i + 1]))
+            .collect::<Vec<_>>();
+
+        let header_0 = BlockHeader::builder().finalize_with_hash(block_hash_bytes!(b"0"));
+        let header_1 = header_0
+            .child_builder()
+            .finalize_with_hash(block_hash_bytes!(b"1"));
+
+        // Deploy the contracts in reverse address order to check that the ordering is by address.
+        let diff_0 = contracts
+            .iter()
+            .rev()
+            .fold(StateUpdate::default(), |diff, contract| {
+                diff.with_deployed_contract(*contract, class)
+            });
+        let diff_1 = StateUpdate::default().with_replaced_class(contracts[3], replaced_class);
+
+        tx.insert_block_header(&header_0).unwrap();
+        tx.insert_block_header(&header_1).unwrap();
+        tx.insert_state_update(header_0.number, &diff_0).unwrap();
+        tx.insert_state_update(header_1.number, &diff_1).unwrap();
+
+        assert_eq!(super::contract_count(&tx).unwrap(), contracts.len());
+
+        let mut result = Vec::new();
+        let mut offset = 0;
+        loop {
+            let page = super::contracts(&tx, offset, 3).unwrap();
+            if page.is_empty() {
+                break;
+            }
+            offset += page.len();
+            result.extend(page);
+        }
+
+        let expected = contracts
+            .iter()
+            .enumerate()
+            .map(|(i, contract)| {
+                let class = if i == 3 { replaced_class } else { class };
+                (*contract, class)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(result, expected);
+    }
+
     #[test]
     fn state_update() {
         let mut db = crate::Storage::in_memory().unwrap().connection().unwrap();
@@ -736,4 +1224,57 @@ mod tests {
             assert_eq!(by_number, None);
         }
     }
+
+    #[test]
+    fn storage_value_at_state_commitment() {
+        let mut db = crate::Storage::in_memory().unwrap().connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let contract = contract_address_bytes!(b"contract");
+        let key = storage_address_bytes!(b"key");
+
+        let header_0 = BlockHeader::builder()
+            .with_state_commitment(state_commitment_bytes!(b"root 0"))
+            .finalize_with_hash(block_hash_bytes!(b"0"));
+        let header_1 = header_0
+            .child_builder()
+            .with_state_commitment(state_commitment_bytes!(b"root 1"))
+            .finalize_with_hash(block_hash_bytes!(b"1"));
+        let header_2 = header_1
+            .child_builder()
+            .with_state_commitment(state_commitment_bytes!(b"root 2"))
+            .finalize_with_hash(block_hash_bytes!(b"2"));
+
+        let diff_0 =
+            StateUpdate::default().with_storage_update(contract, key, storage_value_bytes!(b"0"));
+        let diff_2 =
+            StateUpdate::default().with_storage_update(contract, key, storage_value_bytes!(b"2"));
+
+        tx.insert_block_header(&header_0).unwrap();
+        tx.insert_block_header(&header_1).unwrap();
+        tx.insert_block_header(&header_2).unwrap();
+        tx.insert_state_update(header_0.number, &diff_0).unwrap();
+        tx.insert_state_update(header_1.number, &StateUpdate::default())
+            .unwrap();
+        tx.insert_state_update(header_2.number, &diff_2).unwrap();
+
+        let old =
+            super::storage_value_at_state_commitment(&tx, header_1.state_commitment, contract, key)
+                .unwrap();
+        assert_eq!(old, Some(storage_value_bytes!(b"0")));
+
+        let new =
+            super::storage_value_at_state_commitment(&tx, header_2.state_commitment, contract, key)
+                .unwrap();
+        assert_eq!(new, Some(storage_value_bytes!(b"2")));
+
+        let unknown = super::storage_value_at_state_commitment(
+            &tx,
+            state_commitment_bytes!(b"unknown"),
+            contract,
+            key,
+        )
+        .unwrap();
+        assert_eq!(unknown, None);
+    }
 }
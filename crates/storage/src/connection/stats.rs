@@ -0,0 +1,112 @@
+use anyhow::Context;
+
+use crate::prelude::*;
+
+/// The size of a single database table, as reported by [Transaction::table_statistics].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableStatistics {
+    pub name: String,
+    pub rows: u64,
+    /// Approximate size in bytes, including the table's indexes. This is `None` if SQLite was
+    /// built without the `dbstat` virtual table.
+    pub bytes: Option<u64>,
+}
+
+pub(super) fn table_statistics(tx: &Transaction<'_>) -> anyhow::Result<Vec<TableStatistics>> {
+    let mut stmt = tx
+        .inner()
+        .prepare(
+            "SELECT name FROM sqlite_master
+            WHERE type = 'table' AND name NOT LIKE 'sqlite_%'
+            ORDER BY name",
+        )
+        .context("Preparing table name statement")?;
+    let names = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .context("Querying table names")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Iterating over table names")?;
+
+    let sizes = table_sizes(tx).context("Querying table sizes")?;
+
+    names
+        .into_iter()
+        .map(|name| {
+            // Table names come from the schema itself, so quoting them is sufficient.
+            let rows: u64 = tx
+                .inner()
+                .query_row(&format!(r#"SELECT COUNT(*) FROM "{name}""#), [], |row| {
+                    row.get(0)
+                })
+                .with_context(|| format!("Counting rows of {name}"))?;
+
+            let bytes = sizes.as_ref().map(|sizes| {
+                sizes
+                    .iter()
+                    .filter(|(table, _)| table == &name)
+                    .map(|(_, bytes)| bytes)
+                    .sum()
+            });
+
+            Ok(TableStatistics { name, rows, bytes })
+        })
+        .collect()
+}
+
+/// Returns the total size in bytes of each table and its indexes, or `None` if the `dbstat`
+/// virtual table is not available.
+fn table_sizes(tx: &Transaction<'_>) -> anyhow::Result<Option<Vec<(String, u64)>>> {
+    let mut stmt = match tx.inner().prepare(
+        "SELECT coalesce(sqlite_master.tbl_name, dbstat.name), SUM(dbstat.pgsize)
+        FROM dbstat LEFT JOIN sqlite_master ON dbstat.name = sqlite_master.name
+        GROUP BY dbstat.name",
+    ) {
+        Ok(stmt) => stmt,
+        Err(rusqlite::Error::SqliteFailure(_, Some(msg))) if msg.contains("dbstat") => {
+            return Ok(None)
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let sizes = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Some(sizes))
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::{BlockHeader, StateUpdate};
+
+    use super::*;
+
+    #[test]
+    fn row_counts() {
+        let storage = crate::Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let genesis = BlockHeader::builder().finalize_with_hash(block_hash!("0xabc"));
+        let header = genesis
+            .child_builder()
+            .finalize_with_hash(block_hash!("0xdef"));
+        for header in [&genesis, &header] {
+            tx.insert_block_header(header).unwrap();
+            tx.insert_state_update(header.number, &StateUpdate::default())
+                .unwrap();
+        }
+
+        let stats = table_statistics(&tx).unwrap();
+        let rows = |name: &str| stats.iter().find(|s| s.name == name).unwrap().rows;
+
+        assert_eq!(rows("block_headers"), 2);
+        assert_eq!(rows("canonical_blocks"), 2);
+        assert_eq!(rows("contract_updates"), 0);
+
+        // Sorted by name and without SQLite's internal tables.
+        assert!(stats.windows(2).all(|w| w[0].name < w[1].name));
+        assert!(stats.iter().all(|s| !s.name.starts_with("sqlite_")));
+    }
+}
@@ -2,6 +2,7 @@ use std::collections::HashMap;
 
 use anyhow::Context;
 use bitvec::prelude::Msb0;
+use bitvec::slice::BitSlice;
 use bitvec::vec::BitVec;
 use pathfinder_common::prelude::*;
 use stark_hash::Felt;
@@ -75,6 +76,25 @@ pub(super) fn contract_root(
         .map_err(Into::into)
 }
 
+/// Returns the contract's root as of its most recent update.
+pub(super) fn latest_contract_root(
+    tx: &Transaction<'_>,
+    contract: ContractAddress,
+) -> anyhow::Result<Option<ContractRoot>> {
+    // A missing root index means the contract's storage was emptied, which leaves a zero root.
+    tx.inner()
+        .query_row(
+            r"SELECT trie_contracts.hash FROM (
+                SELECT root_index FROM contract_roots WHERE contract_address = ? ORDER BY block_number DESC LIMIT 1
+            ) AS latest LEFT JOIN trie_contracts ON trie_contracts.idx = latest.root_index",
+            params![&contract],
+            |row| row.get_optional_felt(0),
+        )
+        .optional()
+        .map(|root| root.map(|root| ContractRoot(root.unwrap_or_default())))
+        .map_err(Into::into)
+}
+
 pub(super) fn insert_class_root(
     tx: &Transaction<'_>,
     block_number: BlockNumber,
@@ -139,6 +159,156 @@ pub(super) fn insert_contract_root(
     Ok(())
 }
 
+/// Trie nodes which are not reachable from any stored root, by node index.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct OrphanNodes {
+    pub class: Vec<u64>,
+    pub contract: Vec<u64>,
+    pub storage: Vec<u64>,
+}
+
+impl OrphanNodes {
+    pub fn is_empty(&self) -> bool {
+        self.class.is_empty() && self.contract.is_empty() && self.storage.is_empty()
+    }
+}
+
+pub(super) fn find_orphan_nodes(tx: &Transaction<'_>) -> anyhow::Result<OrphanNodes> {
+    Ok(OrphanNodes {
+        class: orphan_nodes(tx, "trie_class", "class_roots").context("Class trie")?,
+        contract: orphan_nodes(tx, "trie_contracts", "contract_roots").context("Contract tries")?,
+        storage: orphan_nodes(tx, "trie_storage", "storage_roots").context("Storage trie")?,
+    })
+}
+
+/// A contract root which refers to a node that does not exist in the contract tries.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DanglingContractRoot {
+    pub block_number: BlockNumber,
+    pub contract: ContractAddress,
+    pub root_index: u64,
+}
+
+pub(super) fn find_dangling_contract_roots(
+    tx: &Transaction<'_>,
+) -> anyhow::Result<Vec<DanglingContractRoot>> {
+    // Empty contract tries are stored with a NULL root index, and have no node.
+    let mut stmt = tx
+        .inner()
+        .prepare(
+            r"SELECT contract_roots.block_number, contract_roots.contract_address, contract_roots.root_index
+                FROM contract_roots
+                LEFT JOIN trie_contracts ON trie_contracts.idx = contract_roots.root_index
+                WHERE contract_roots.root_index IS NOT NULL AND trie_contracts.idx IS NULL
+                ORDER BY contract_roots.block_number, contract_roots.contract_address",
+        )
+        .context("Preparing dangling contract roots query")?;
+
+    let dangling = stmt
+        .query_map([], |row| {
+            Ok(DanglingContractRoot {
+                block_number: row.get_block_number(0)?,
+                contract: row.get_contract_address(1)?,
+                root_index: row.get(2)?,
+            })
+        })
+        .context("Querying dangling contract roots")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Iterating over dangling contract roots")?;
+
+    Ok(dangling)
+}
+
+/// Marks every node of `table` reachable from a root in `roots_table` and returns the rest.
+///
+/// The tries hold far more nodes than fit in memory, so the reachable nodes are marked in a
+/// temporary table and the roots are streamed from the database. Only the path from the current
+/// root down to the node being visited is kept in memory.
+fn orphan_nodes(tx: &Transaction<'_>, table: &str, roots_table: &str) -> anyhow::Result<Vec<u64>> {
+    tx.inner()
+        .execute_batch(
+            "DROP TABLE IF EXISTS temp.reachable_nodes;
+            CREATE TEMP TABLE reachable_nodes (idx INTEGER PRIMARY KEY)",
+        )
+        .context("Creating reachable nodes table")?;
+
+    let orphans = mark_reachable_nodes(tx, table, roots_table).and_then(|_| {
+        let mut stmt = tx
+            .inner()
+            .prepare(&format!(
+                "SELECT idx FROM {table}
+                    WHERE idx NOT IN (SELECT idx FROM temp.reachable_nodes)
+                    ORDER BY idx"
+            ))
+            .context("Preparing orphan nodes query")?;
+        let orphans = stmt
+            .query_map([], |row| row.get::<_, u64>(0))
+            .context("Querying orphan nodes")?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Iterating over orphan nodes")?;
+        Ok(orphans)
+    });
+
+    tx.inner()
+        .execute("DROP TABLE temp.reachable_nodes", [])
+        .context("Dropping reachable nodes table")?;
+
+    orphans
+}
+
+fn mark_reachable_nodes(
+    tx: &Transaction<'_>,
+    table: &str,
+    roots_table: &str,
+) -> anyhow::Result<()> {
+    let mut roots_stmt = tx
+        .inner()
+        .prepare(&format!(
+            "SELECT DISTINCT root_index FROM {roots_table} WHERE root_index IS NOT NULL"
+        ))
+        .context("Preparing roots query")?;
+    let mut roots = roots_stmt.query([]).context("Querying roots")?;
+
+    let mut node_stmt = tx
+        .inner()
+        .prepare(&format!("SELECT data FROM {table} WHERE idx = ?"))
+        .context("Preparing node query")?;
+    let mut mark_stmt = tx
+        .inner()
+        .prepare("INSERT OR IGNORE INTO temp.reachable_nodes (idx) VALUES (?)")
+        .context("Preparing mark query")?;
+
+    let mut to_visit = Vec::new();
+    while let Some(root) = roots.next().context("Iterating over roots")? {
+        to_visit.push(root.get::<_, u64>(0)?);
+
+        while let Some(index) = to_visit.pop() {
+            let newly_marked = mark_stmt.execute(params![&index]).context("Marking node")? > 0;
+            if !newly_marked {
+                continue;
+            }
+
+            // A missing node is a different kind of corruption, which is not what we are after
+            // here.
+            let Some(data): Option<Vec<u8>> = node_stmt
+                .query_row(params![&index], |row| row.get(0))
+                .optional()
+                .context("Querying node")?
+            else {
+                continue;
+            };
+
+            match StoredNode::decode(&data).context("Decoding node")? {
+                StoredNode::Binary { left, right } => to_visit.extend([left, right]),
+                StoredNode::Edge { child, .. } => to_visit.push(child),
+                StoredNode::LeafBinary | StoredNode::LeafEdge { .. } => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
 mod macros {
     /// Generates the `insert`, `node` and `hash` trie functions for the given table name, within
     /// a module with the table name.
@@ -326,6 +496,8 @@ impl StoredNode {
 
                 let mut path = path.to_owned();
                 path.force_align();
+                // Padding must be zero, as it is validated when decoding.
+                path.set_uninitialized(false);
                 let mut path = path.into_vec();
                 path.push(path_length);
 
@@ -340,6 +512,8 @@ impl StoredNode {
 
                 let mut path = path.to_owned();
                 path.force_align();
+                // Padding must be zero, as it is validated when decoding.
+                path.set_uninitialized(false);
                 let mut path = path.into_vec();
                 path.push(path_length);
 
@@ -356,27 +530,81 @@ impl StoredNode {
 
         let node = match helper.0 {
             StoredSerde::Binary { left, right } => Self::Binary { left, right },
-            StoredSerde::Edge { child, mut path } => {
-                let path_length = path.pop().ok_or(bincode::error::DecodeError::Other(
-                    "Edge node's path length is missing",
-                ))?;
-                let mut path = bitvec::vec::BitVec::from_vec(path);
-                path.resize(path_length as usize, false);
-                Self::Edge { child, path }
-            }
+            StoredSerde::Edge { child, path } => Self::Edge {
+                child,
+                path: Self::decode_path(path)?,
+            },
             StoredSerde::LeafBinary => Self::LeafBinary,
-            StoredSerde::LeafEdge { mut path } => {
-                let path_length = path.pop().ok_or(bincode::error::DecodeError::Other(
-                    "Edge node's path length is missing",
-                ))?;
-                let mut path = bitvec::vec::BitVec::from_vec(path);
-                path.resize(path_length as usize, false);
-                Self::LeafEdge { path }
-            }
+            StoredSerde::LeafEdge { path } => Self::LeafEdge {
+                path: Self::decode_path(path)?,
+            },
         };
 
         Ok(node)
     }
+
+    /// Decodes an edge node's path, which is stored as its bits padded to a whole number of bytes,
+    /// followed by its length in bits.
+    ///
+    /// The padding must be zero, as set bits beyond the path's length point at corruption or an
+    /// encoding bug.
+    fn decode_path(mut path: Vec<u8>) -> Result<BitVec<u8, Msb0>, bincode::error::DecodeError> {
+        let path_length = path.pop().ok_or(bincode::error::DecodeError::Other(
+            "Edge node's path length is missing",
+        ))? as usize;
+        let mut path = BitVec::<u8, Msb0>::from_vec(path);
+
+        if path_length > path.len() {
+            return Err(bincode::error::DecodeError::Other(
+                "Edge node's path is shorter than its length",
+            ));
+        }
+        if path[path_length..].any() {
+            return Err(bincode::error::DecodeError::Other(
+                "Edge node's path has bits set beyond its length",
+            ));
+        }
+
+        path.truncate(path_length);
+        Ok(path)
+    }
+
+    /// Re-encodes an edge node whose path padding was written with set bits, returning [None] if
+    /// the node needs no changes.
+    ///
+    /// Older versions did not clear the padding when encoding a path sliced from a longer key, so
+    /// such nodes hold the key's following bits there and fail [StoredNode::decode].
+    pub(crate) fn clear_path_padding(
+        data: &[u8],
+    ) -> Result<Option<Vec<u8>>, bincode::error::DecodeError> {
+        fn clear(path: &mut Vec<u8>) -> bool {
+            let Some((&length, bits)) = path.split_last_mut() else {
+                return false;
+            };
+            let bits = BitSlice::<u8, Msb0>::from_slice_mut(bits);
+            match bits.get_mut(length as usize..) {
+                Some(padding) if padding.any() => {
+                    padding.fill(false);
+                    true
+                }
+                _ => false,
+            }
+        }
+
+        let (mut helper, _) =
+            bincode::borrow_decode_from_slice::<StoredSerde, _>(data, Self::CODEC_CFG)?;
+        let changed = match &mut helper {
+            StoredSerde::Edge { path, .. } | StoredSerde::LeafEdge { path } => clear(path),
+            StoredSerde::Binary { .. } | StoredSerde::LeafBinary => false,
+        };
+        if !changed {
+            return Ok(None);
+        }
+
+        let data = bincode::encode_to_vec(helper, Self::CODEC_CFG)
+            .map_err(|_| bincode::error::DecodeError::Other("Re-encoding node"))?;
+        Ok(Some(data))
+    }
 }
 
 #[cfg(test)]
@@ -520,6 +748,43 @@ mod tests {
         assert_eq!(result, None);
     }
 
+    #[test]
+    fn latest_contract_root() {
+        let mut db = crate::Storage::in_memory().unwrap().connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let c1 = contract_address_bytes!(b"first");
+        let c2 = contract_address_bytes!(b"untouched");
+
+        let root_node = Node::LeafBinary;
+        let mut nodes = HashMap::new();
+
+        let root0 = contract_root_bytes!(b"root 0");
+        nodes.insert(root0.0, root_node.clone());
+        let idx0 = trie_contracts::insert(&tx, root0.0, &nodes).unwrap();
+        insert_contract_root(&tx, BlockNumber::GENESIS, c1, Some(idx0)).unwrap();
+
+        let result = super::latest_contract_root(&tx, c1).unwrap();
+        assert_eq!(result, Some(root0));
+
+        let root1 = contract_root_bytes!(b"root 1");
+        nodes.clear();
+        nodes.insert(root1.0, root_node);
+        let idx1 = trie_contracts::insert(&tx, root1.0, &nodes).unwrap();
+        insert_contract_root(&tx, BlockNumber::GENESIS + 1, c1, Some(idx1)).unwrap();
+
+        let result = super::latest_contract_root(&tx, c1).unwrap();
+        assert_eq!(result, Some(root1));
+
+        let result = super::latest_contract_root(&tx, c2).unwrap();
+        assert_eq!(result, None);
+
+        // Emptied storage has no root node.
+        insert_contract_root(&tx, BlockNumber::GENESIS + 2, c1, None).unwrap();
+        let result = super::latest_contract_root(&tx, c1).unwrap();
+        assert_eq!(result, Some(ContractRoot::ZERO));
+    }
+
     #[test]
     fn contract_roots() {
         let mut db = crate::Storage::in_memory().unwrap().connection().unwrap();
@@ -610,6 +875,92 @@ mod tests {
         assert_eq!(hash1, None);
     }
 
+    #[test]
+    fn orphan_nodes() {
+        let mut db = crate::Storage::in_memory().unwrap().connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let leaf_a = felt_bytes!(b"leaf a");
+        let leaf_b = felt_bytes!(b"leaf b");
+        let root = felt_bytes!(b"root");
+        let nodes = HashMap::from([
+            (
+                root,
+                Node::Binary {
+                    left: Child::Hash(leaf_a),
+                    right: Child::Hash(leaf_b),
+                },
+            ),
+            (leaf_a, Node::LeafBinary),
+            (leaf_b, Node::LeafBinary),
+        ]);
+        let root_idx = trie_storage::insert(&tx, root, &nodes).unwrap();
+        insert_storage_root(&tx, BlockNumber::GENESIS, Some(root_idx)).unwrap();
+
+        let contract_root = felt_bytes!(b"contract root");
+        let nodes = HashMap::from([(contract_root, Node::LeafBinary)]);
+        let contract_idx = trie_contracts::insert(&tx, contract_root, &nodes).unwrap();
+        insert_contract_root(
+            &tx,
+            BlockNumber::GENESIS,
+            contract_address_bytes!(b"contract"),
+            Some(contract_idx),
+        )
+        .unwrap();
+
+        assert_eq!(find_orphan_nodes(&tx).unwrap(), OrphanNodes::default());
+
+        // A node which no root refers to.
+        let orphan = felt_bytes!(b"orphan");
+        let nodes = HashMap::from([(orphan, Node::LeafBinary)]);
+        let orphan_idx = trie_storage::insert(&tx, orphan, &nodes).unwrap();
+
+        let orphans = find_orphan_nodes(&tx).unwrap();
+        assert_eq!(
+            orphans,
+            OrphanNodes {
+                storage: vec![orphan_idx],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn dangling_contract_roots() {
+        let mut db = crate::Storage::in_memory().unwrap().connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let contract = contract_address_bytes!(b"contract");
+        let empty = contract_address_bytes!(b"empty");
+        let dangling = contract_address_bytes!(b"dangling");
+
+        let contract_root = felt_bytes!(b"contract root");
+        let nodes = HashMap::from([(contract_root, Node::LeafBinary)]);
+        let contract_idx = trie_contracts::insert(&tx, contract_root, &nodes).unwrap();
+        insert_contract_root(&tx, BlockNumber::GENESIS, contract, Some(contract_idx)).unwrap();
+        insert_contract_root(&tx, BlockNumber::GENESIS, empty, None).unwrap();
+
+        assert_eq!(find_dangling_contract_roots(&tx).unwrap(), vec![]);
+
+        let dangling_idx = contract_idx + 100;
+        insert_contract_root(
+            &tx,
+            BlockNumber::new_or_panic(1),
+            dangling,
+            Some(dangling_idx),
+        )
+        .unwrap();
+
+        assert_eq!(
+            find_dangling_contract_roots(&tx).unwrap(),
+            vec![DanglingContractRoot {
+                block_number: BlockNumber::new_or_panic(1),
+                contract: dangling,
+                root_index: dangling_idx,
+            }]
+        );
+    }
+
     #[rstest::rstest]
     #[case::binary(StoredNode::Binary {
         left: 12, right: 34
@@ -638,6 +989,63 @@ mod tests {
         assert_eq!(result, node);
     }
 
+    #[rstest::rstest]
+    #[case::bits_beyond_length(vec![0b1000_0001, 7])]
+    #[case::length_beyond_bits(vec![0b1000_0000, 9])]
+    #[case::length_missing(vec![])]
+    fn malformed_edge_path(#[case] path: Vec<u8>) {
+        for node in [
+            StoredSerde::Edge {
+                child: 123,
+                path: path.clone(),
+            },
+            StoredSerde::LeafEdge { path: path.clone() },
+        ] {
+            let mut buffer = vec![0; 256];
+            let length =
+                bincode::encode_into_slice(node, &mut buffer, StoredNode::CODEC_CFG).unwrap();
+            StoredNode::decode(&buffer[..length]).unwrap_err();
+        }
+    }
+
+    #[test]
+    fn legacy_padded_edge_path() {
+        // Older versions encoded paths sliced from a key without clearing the padding, leaving
+        // the key's remaining bits in it.
+        let key = bitvec::bitvec![u8, Msb0; 1, 0, 1, 0, 1, 1, 1, 1];
+        let mut legacy_path = key[..5].to_bitvec();
+        legacy_path.force_align();
+        let mut legacy_path = legacy_path.into_vec();
+        legacy_path.push(5);
+        assert_eq!(legacy_path, vec![0b1010_1111, 5]);
+
+        for legacy in [
+            StoredSerde::Edge {
+                child: 123,
+                path: legacy_path.clone(),
+            },
+            StoredSerde::LeafEdge {
+                path: legacy_path.clone(),
+            },
+        ] {
+            let legacy = bincode::encode_to_vec(legacy, StoredNode::CODEC_CFG).unwrap();
+            StoredNode::decode(&legacy).unwrap_err();
+
+            let fixed = StoredNode::clear_path_padding(&legacy).unwrap().unwrap();
+            let path = match StoredNode::decode(&fixed).unwrap() {
+                StoredNode::Edge { child, path } => {
+                    assert_eq!(child, 123);
+                    path
+                }
+                StoredNode::LeafEdge { path } => path,
+                other => panic!("Unexpected node {other:?}"),
+            };
+            assert_eq!(path, key[..5].to_bitvec());
+
+            assert_eq!(StoredNode::clear_path_padding(&fixed).unwrap(), None);
+        }
+    }
+
     mod trie_fns {
         use super::*;
         macros::create_trie_fns!(test_table);
@@ -9,6 +9,7 @@ mod connection;
 pub mod fake;
 mod params;
 mod schema;
+mod shard;
 pub mod test_utils;
 
 use std::num::NonZeroU32;
@@ -16,9 +17,11 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 pub use connection::*;
+pub use shard::ShardedStorage;
 
 use pathfinder_common::{BlockHash, BlockNumber};
 use rusqlite::functions::FunctionFlags;
+use rusqlite::OpenFlags;
 
 use anyhow::Context;
 use r2d2::Pool;
@@ -27,6 +30,15 @@ use r2d2_sqlite::SqliteConnectionManager;
 /// Sqlite key used for the PRAGMA user version.
 const VERSION_KEY: &str = "user_version";
 
+/// The `metadata` key of the oldest binary version which may open the database.
+const MIN_COMPATIBLE_BINARY_VERSION_KEY: &str = "min_compatible_binary_version";
+
+/// The oldest binary version which can safely open a database written by this binary.
+///
+/// This must be raised whenever older binaries would misinterpret data written by this one in a
+/// way which the schema revision alone does not catch.
+const MIN_COMPATIBLE_BINARY_VERSION: semver::Version = semver::Version::new(0, 9, 5);
+
 /// Specifies the [journal mode](https://sqlite.org/pragma.html#pragma_journal_mode)
 /// of the [Storage].
 #[derive(Clone, Copy)]
@@ -88,6 +100,8 @@ struct Inner {
     /// Uses [`Arc`] to allow _shallow_ [Storage] cloning
     database_path: Arc<PathBuf>,
     pool: Pool<SqliteConnectionManager>,
+    /// The connection shared by all connections of a [Storage::snapshot].
+    snapshot: Option<Arc<std::sync::Mutex<connection::Snapshot>>>,
 }
 
 pub struct StorageManager {
@@ -107,10 +121,48 @@ impl StorageManager {
         Ok(Storage(Inner {
             database_path: Arc::new(self.database_path.clone()),
             pool,
+            snapshot: None,
+        }))
+    }
+
+    /// Creates a pool of read-only connections to the database.
+    ///
+    /// These connections never take the write lock, so in [WAL mode](JournalMode::WAL) readers
+    /// see the last committed state without waiting on, or blocking, an open write transaction.
+    /// Any attempt to write through them fails.
+    ///
+    /// Reads which find the database busy wait up to [READ_ONLY_BUSY_TIMEOUT] before failing.
+    pub fn create_read_only_pool(&self, capacity: NonZeroU32) -> anyhow::Result<Storage> {
+        let journal_mode = self.journal_mode;
+        let pool_manager = SqliteConnectionManager::file(&self.database_path)
+            .with_flags(
+                OpenFlags::SQLITE_OPEN_READ_ONLY
+                    | OpenFlags::SQLITE_OPEN_URI
+                    | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+            )
+            .with_init(move |connection| {
+                setup_connection(connection, journal_mode)?;
+                connection.busy_timeout(READ_ONLY_BUSY_TIMEOUT)
+            });
+        let pool = Pool::builder()
+            .max_size(capacity.get())
+            .build(pool_manager)?;
+
+        Ok(Storage(Inner {
+            database_path: Arc::new(self.database_path.clone()),
+            pool,
+            snapshot: None,
         }))
     }
 }
 
+/// How long a read blocked by another connection's lock waits before it fails with
+/// `SQLITE_BUSY`.
+///
+/// Even in WAL mode a reader can briefly find the database busy, for example while a checkpoint
+/// resets the WAL. This matches the timeout of the read-write connections.
+const READ_ONLY_BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
 impl Storage {
     /// Performs the database schema migration and returns a [storage manager](StorageManager).
     ///
@@ -128,6 +180,14 @@ impl Storage {
         setup_connection(&mut connection, journal_mode)
             .context("Setting up database connection")?;
         migrate_database(&mut connection).context("Migrate database")?;
+        match binary_version() {
+            Some(version) => check_binary_compatibility(&mut connection, &version)
+                .context("Checking binary compatibility")?,
+            None => tracing::warn!(
+                version=%pathfinder_common::consts::VERGEN_GIT_DESCRIBE,
+                "Skipping database compatibility check, binary version is not a semantic version"
+            ),
+        }
         connection
             .close()
             .map_err(|(_connection, error)| error)
@@ -141,10 +201,57 @@ impl Storage {
 
     /// Returns a new Sqlite [Connection] to the database.
     pub fn connection(&self) -> anyhow::Result<Connection> {
+        if let Some(snapshot) = &self.0.snapshot {
+            return Ok(Connection::from_snapshot(snapshot.clone()));
+        }
+
         let conn = self.0.pool.get()?;
         Ok(Connection::from_inner(conn))
     }
 
+    /// Returns a read-only [Storage] whose connections all see the database as it is now, even
+    /// once other connections commit to it.
+    ///
+    /// The snapshot holds a single connection of this storage's pool until it and all of its
+    /// clones are dropped. Its connections share that connection, so only one of their
+    /// transactions can be open at a time and opening a second one blocks until the first is
+    /// dropped.
+    ///
+    /// Requires [WAL mode](JournalMode::WAL) for other connections to be able to commit while the
+    /// snapshot is held. WAL checkpoints cannot complete while it is held either, so callers
+    /// should [end](Storage::end_snapshot) it as soon as they no longer need a consistent view.
+    pub fn snapshot(&self) -> anyhow::Result<Self> {
+        if let Some(snapshot) = &self.0.snapshot {
+            return Ok(Self(Inner {
+                snapshot: Some(snapshot.clone()),
+                ..self.0.clone()
+            }));
+        }
+
+        let conn = self.0.pool.get()?;
+        let snapshot = connection::Snapshot::new(conn).context("Opening snapshot")?;
+
+        Ok(Self(Inner {
+            snapshot: Some(Arc::new(std::sync::Mutex::new(snapshot))),
+            ..self.0.clone()
+        }))
+    }
+
+    /// Ends this storage's [snapshot](Storage::snapshot), after which its connections read the
+    /// database as it is at the time of each read. Waits for an open transaction of the snapshot
+    /// to be dropped first.
+    ///
+    /// This is a no-op if this storage is not a snapshot.
+    pub fn end_snapshot(&self) -> anyhow::Result<()> {
+        match &self.0.snapshot {
+            Some(snapshot) => snapshot
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .end(),
+            None => Ok(()),
+        }
+    }
+
     /// Convenience function for tests to create an in-memory database.
     /// Equivalent to [Storage::migrate] with an in-memory backed database.
     // No longer cfg(test) because needed in benchmarks
@@ -339,6 +446,75 @@ fn migrate_database(connection: &mut rusqlite::Connection) -> anyhow::Result<()>
     Ok(())
 }
 
+/// The running binary's version, without any pre-release or build metadata so that development
+/// builds compare equal to the release they are based on.
+fn binary_version() -> Option<semver::Version> {
+    let version = pathfinder_common::consts::VERGEN_GIT_DESCRIBE;
+    let version = version.strip_prefix('v').unwrap_or(version);
+    let version = semver::Version::parse(version).ok()?;
+
+    Some(semver::Version::new(
+        version.major,
+        version.minor,
+        version.patch,
+    ))
+}
+
+/// Refuses to continue if the database was written by a newer binary which is incompatible with
+/// `binary_version`. Otherwise, raises the database's minimum compatible binary version to
+/// [MIN_COMPATIBLE_BINARY_VERSION] if it is lower.
+fn check_binary_compatibility(
+    connection: &mut rusqlite::Connection,
+    binary_version: &semver::Version,
+) -> anyhow::Result<()> {
+    use rusqlite::OptionalExtension;
+
+    let tx = connection
+        .transaction()
+        .context("Create database transaction")?;
+
+    let stored = tx
+        .query_row(
+            "SELECT value FROM metadata WHERE key = ?",
+            [MIN_COMPATIBLE_BINARY_VERSION_KEY],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .context("Querying minimum compatible binary version")?
+        .map(|version| {
+            semver::Version::parse(&version)
+                .with_context(|| format!("Parsing minimum compatible binary version {version}"))
+        })
+        .transpose()?;
+
+    if let Some(stored) = &stored {
+        if stored > binary_version {
+            tracing::error!(
+                version=%binary_version,
+                required=%stored,
+                "Database was written by a newer, incompatible version of pathfinder"
+            );
+            anyhow::bail!(
+                "Database requires pathfinder version {stored} or newer, but this is version {binary_version}"
+            );
+        }
+    }
+
+    if stored.as_ref() < Some(&MIN_COMPATIBLE_BINARY_VERSION) {
+        tx.execute(
+            "INSERT INTO metadata (key, value) VALUES (?, ?)
+                ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            [
+                MIN_COMPATIBLE_BINARY_VERSION_KEY,
+                &MIN_COMPATIBLE_BINARY_VERSION.to_string(),
+            ],
+        )
+        .context("Updating minimum compatible binary version")?;
+    }
+
+    tx.commit().context("Commit database transaction")
+}
+
 /// Returns the current schema version of the existing database,
 /// or `0` if database does not yet exist.
 fn schema_version(connection: &rusqlite::Connection) -> anyhow::Result<usize> {
@@ -392,6 +568,153 @@ mod tests {
         migrate_database(&mut conn).unwrap_err();
     }
 
+    #[test]
+    fn binary_compatibility_is_recorded() {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        setup_connection(&mut conn, JournalMode::Rollback).unwrap();
+        migrate_database(&mut conn).unwrap();
+
+        check_binary_compatibility(&mut conn, &MIN_COMPATIBLE_BINARY_VERSION).unwrap();
+
+        let stored = conn
+            .query_row(
+                "SELECT value FROM metadata WHERE key = ?",
+                [MIN_COMPATIBLE_BINARY_VERSION_KEY],
+                |row| row.get::<_, String>(0),
+            )
+            .unwrap();
+        assert_eq!(stored, MIN_COMPATIBLE_BINARY_VERSION.to_string());
+    }
+
+    #[test]
+    fn startup_fails_if_db_requires_newer_binary() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.sqlite");
+        Storage::migrate(path.clone(), JournalMode::WAL).unwrap();
+
+        // Mark the database as written by a future, incompatible version.
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute(
+            "UPDATE metadata SET value = '999.0.0' WHERE key = ?",
+            [MIN_COMPATIBLE_BINARY_VERSION_KEY],
+        )
+        .unwrap();
+        drop(conn);
+
+        let mut conn = rusqlite::Connection::open(&path).unwrap();
+        let error =
+            check_binary_compatibility(&mut conn, &MIN_COMPATIBLE_BINARY_VERSION).unwrap_err();
+        assert!(error.to_string().contains("999.0.0"), "{error}");
+
+        // The marker is never lowered by an older binary.
+        let stored = conn
+            .query_row(
+                "SELECT value FROM metadata WHERE key = ?",
+                [MIN_COMPATIBLE_BINARY_VERSION_KEY],
+                |row| row.get::<_, String>(0),
+            )
+            .unwrap();
+        assert_eq!(stored, "999.0.0");
+    }
+
+    #[test]
+    fn snapshot_does_not_see_later_commits() {
+        use pathfinder_common::macro_prelude::*;
+        use pathfinder_common::BlockHeader;
+
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::migrate(dir.path().join("test.sqlite"), JournalMode::WAL)
+            .unwrap()
+            .create_pool(NonZeroU32::new(3).unwrap())
+            .unwrap();
+
+        let genesis = BlockHeader::builder().finalize_with_hash(block_hash_bytes!(b"genesis"));
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+        tx.insert_block_header(&genesis).unwrap();
+        tx.commit().unwrap();
+
+        let snapshot = storage.snapshot().unwrap();
+
+        let child = genesis
+            .child_builder()
+            .finalize_with_hash(block_hash_bytes!(b"child"));
+        let tx = connection.transaction().unwrap();
+        tx.insert_block_header(&child).unwrap();
+        tx.commit().unwrap();
+
+        let latest = |storage: &Storage| {
+            let mut connection = storage.connection().unwrap();
+            let tx = connection.transaction().unwrap();
+            tx.block_id(BlockId::Latest).unwrap().unwrap().0
+        };
+        assert_eq!(latest(&snapshot), genesis.number);
+        assert_eq!(latest(&snapshot.clone()), genesis.number);
+        assert_eq!(latest(&storage), child.number);
+
+        // Snapshots are read-only.
+        let mut snapshot_connection = snapshot.connection().unwrap();
+        let tx = snapshot_connection.transaction().unwrap();
+        let grandchild = child
+            .child_builder()
+            .finalize_with_hash(block_hash_bytes!(b"grandchild"));
+        tx.insert_block_header(&grandchild).unwrap_err();
+        drop(tx);
+        drop(snapshot_connection);
+
+        drop(snapshot);
+        let tx = connection.transaction().unwrap();
+        tx.insert_block_header(&grandchild).unwrap();
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn ended_snapshot_sees_later_commits() {
+        use pathfinder_common::macro_prelude::*;
+        use pathfinder_common::BlockHeader;
+
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::migrate(dir.path().join("test.sqlite"), JournalMode::WAL)
+            .unwrap()
+            .create_pool(NonZeroU32::new(3).unwrap())
+            .unwrap();
+
+        let genesis = BlockHeader::builder().finalize_with_hash(block_hash_bytes!(b"genesis"));
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+        tx.insert_block_header(&genesis).unwrap();
+        tx.commit().unwrap();
+
+        let snapshot = storage.snapshot().unwrap();
+        let mut snapshot_connection = snapshot.connection().unwrap();
+
+        let child = genesis
+            .child_builder()
+            .finalize_with_hash(block_hash_bytes!(b"child"));
+        let tx = connection.transaction().unwrap();
+        tx.insert_block_header(&child).unwrap();
+        tx.commit().unwrap();
+
+        // Nothing holds back the checkpoint once the snapshot ended.
+        snapshot.clone().end_snapshot().unwrap();
+        assert!(connection.wal_checkpoint().unwrap());
+
+        // Connections taken before the snapshot ended read the latest state too.
+        let tx = snapshot_connection.transaction().unwrap();
+        assert_eq!(
+            tx.block_id(BlockId::Latest).unwrap().unwrap().0,
+            child.number
+        );
+
+        // Still read-only.
+        let grandchild = child
+            .child_builder()
+            .finalize_with_hash(block_hash_bytes!(b"grandchild"));
+        tx.insert_block_header(&grandchild).unwrap_err();
+
+        storage.end_snapshot().unwrap();
+    }
+
     #[test]
     fn foreign_keys_are_enforced() {
         let conn = rusqlite::Connection::open_in_memory().unwrap();
@@ -456,6 +779,127 @@ mod tests {
         assert_eq!(output.split(' ').count(), 256);
     }
 
+    #[test]
+    fn read_only_connection_reads_latest_block_during_write() {
+        use pathfinder_common::BlockHeader;
+
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let db_path = db_dir.path().join("read_only.sqlite");
+
+        let manager = Storage::migrate(db_path, JournalMode::WAL).unwrap();
+        let writer = manager.create_pool(NonZeroU32::new(1).unwrap()).unwrap();
+        let reader = manager
+            .create_read_only_pool(NonZeroU32::new(1).unwrap())
+            .unwrap();
+
+        let genesis = BlockHeader::builder()
+            .with_number(BlockNumber::GENESIS)
+            .finalize_with_hash(BlockHash(felt!("0xabc")));
+        let block1 = genesis
+            .child_builder()
+            .finalize_with_hash(BlockHash(felt!("0xdef")));
+
+        let mut writer = writer.connection().unwrap();
+        let tx = writer.transaction().unwrap();
+        tx.insert_block_header(&genesis).unwrap();
+        tx.commit().unwrap();
+
+        // Hold the write lock with block 1 inserted but not yet committed.
+        let pending = writer
+            .transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)
+            .unwrap();
+        pending.insert_block_header(&block1).unwrap();
+
+        let mut reader = reader.connection().unwrap();
+        let tx = reader.transaction().unwrap();
+        let latest = tx.latest_l2_block().unwrap();
+        assert_eq!(latest, Some((genesis.number, genesis.hash)));
+        tx.insert_block_header(&block1).unwrap_err();
+        drop(tx);
+
+        pending.commit().unwrap();
+
+        let tx = reader.transaction().unwrap();
+        let latest = tx.latest_l2_block().unwrap();
+        assert_eq!(latest, Some((block1.number, block1.hash)));
+    }
+
+    /// Holds an exclusive lock on the database at `db_path` until `release` is signalled.
+    fn lock_database(
+        db_path: PathBuf,
+        release: std::sync::mpsc::Receiver<()>,
+    ) -> std::thread::JoinHandle<()> {
+        let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            let mut connection = rusqlite::Connection::open(db_path).unwrap();
+            let tx = connection
+                .transaction_with_behavior(rusqlite::TransactionBehavior::Exclusive)
+                .unwrap();
+            locked_tx.send(()).unwrap();
+            release.recv().unwrap();
+            tx.commit().unwrap();
+        });
+        locked_rx.recv().unwrap();
+        handle
+    }
+
+    #[test]
+    fn read_only_connection_retries_when_busy() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let db_path = db_dir.path().join("busy.sqlite");
+
+        // In rollback mode an exclusive lock blocks readers.
+        let reader = Storage::migrate(db_path.clone(), JournalMode::Rollback)
+            .unwrap()
+            .create_read_only_pool(NonZeroU32::new(1).unwrap())
+            .unwrap();
+        let mut reader = reader.connection().unwrap();
+
+        let (release_tx, release_rx) = std::sync::mpsc::channel();
+        let lock = lock_database(db_path, release_rx);
+
+        // Release the lock while the reader is waiting, well after a few quick retries would have
+        // given up.
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(READ_ONLY_BUSY_TIMEOUT / 10);
+            release_tx.send(()).unwrap();
+        });
+
+        let tx = reader.transaction().unwrap();
+        let latest = tx.latest_l2_block().unwrap();
+        assert_eq!(latest, None);
+
+        release.join().unwrap();
+        lock.join().unwrap();
+    }
+
+    #[test]
+    fn read_only_connection_gives_up_when_busy() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let db_path = db_dir.path().join("busy.sqlite");
+
+        let reader = Storage::migrate(db_path.clone(), JournalMode::Rollback)
+            .unwrap()
+            .create_read_only_pool(NonZeroU32::new(1).unwrap())
+            .unwrap();
+        let mut reader = reader.connection().unwrap();
+
+        let (release_tx, release_rx) = std::sync::mpsc::channel();
+        let lock = lock_database(db_path, release_rx);
+
+        let tx = reader.transaction().unwrap();
+        let error = tx.latest_l2_block().unwrap_err();
+        let error = error.downcast_ref::<rusqlite::Error>().unwrap();
+        let rusqlite::Error::SqliteFailure(error, _) = error else {
+            panic!("Unexpected error: {error}");
+        };
+        assert_eq!(error.code, rusqlite::ErrorCode::DatabaseBusy);
+        drop(tx);
+
+        release_tx.send(()).unwrap();
+        lock.join().unwrap();
+    }
+
     #[test]
     fn rpc_test_db_is_migrated() {
         let mut source_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
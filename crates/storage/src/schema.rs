@@ -4,6 +4,13 @@ mod revision_0041;
 mod revision_0042;
 mod revision_0043;
 mod revision_0044;
+mod revision_0045;
+mod revision_0046;
+mod revision_0047;
+mod revision_0048;
+mod revision_0049;
+mod revision_0050;
+mod revision_0051;
 
 pub(crate) use base::base_schema;
 
@@ -16,6 +23,13 @@ pub fn migrations() -> &'static [MigrationFn] {
         revision_0042::migrate,
         revision_0043::migrate,
         revision_0044::migrate,
+        revision_0045::migrate,
+        revision_0046::migrate,
+        revision_0047::migrate,
+        revision_0048::migrate,
+        revision_0049::migrate,
+        revision_0050::migrate,
+        revision_0051::migrate,
     ]
 }
 
@@ -0,0 +1,13 @@
+use anyhow::Context;
+
+/// Adds a per-block verification level. Existing blocks were all verified by recomputing their
+/// state tries, so they default to fully verified (`1`).
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute(
+        "ALTER TABLE block_headers ADD COLUMN verification_level INTEGER NOT NULL DEFAULT 1",
+        [],
+    )
+    .context("Adding verification_level column to block_headers")?;
+
+    Ok(())
+}
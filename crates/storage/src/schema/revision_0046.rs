@@ -0,0 +1,19 @@
+use anyhow::Context;
+
+/// Records the Ethereum block at which each L1 state was observed, so that Starknet state can be
+/// looked up by Ethereum block. Existing rows are left unknown (`NULL`).
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute(
+        "ALTER TABLE l1_state ADD COLUMN ethereum_block_number INTEGER",
+        [],
+    )
+    .context("Adding ethereum_block_number column to l1_state")?;
+
+    tx.execute(
+        "CREATE INDEX l1_state_ethereum_block_number ON l1_state(ethereum_block_number)",
+        [],
+    )
+    .context("Creating ethereum_block_number index")?;
+
+    Ok(())
+}
@@ -0,0 +1,18 @@
+use anyhow::Context;
+
+/// Adds the `checkpoints` table, which records how far each binary version synced and when.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute(
+        r"CREATE TABLE checkpoints (
+    id               INTEGER PRIMARY KEY,
+    block_number     INTEGER NOT NULL,
+    state_commitment BLOB NOT NULL,
+    timestamp        INTEGER NOT NULL,
+    version          TEXT NOT NULL
+)",
+        [],
+    )
+    .context("Creating checkpoints table")?;
+
+    Ok(())
+}
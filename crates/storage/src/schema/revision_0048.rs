@@ -0,0 +1,16 @@
+use anyhow::Context;
+
+/// Adds the `metadata` table, a key-value store for properties of the database as a whole such as
+/// the oldest binary version which may open it.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute(
+        r"CREATE TABLE metadata (
+    key   TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+)",
+        [],
+    )
+    .context("Creating metadata table")?;
+
+    Ok(())
+}
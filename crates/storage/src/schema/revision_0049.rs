@@ -0,0 +1,26 @@
+use anyhow::Context;
+
+/// Adds the `l1_to_l2_messages` table, which holds messages sent from Ethereum to Starknet as
+/// read from the core contract's logs.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute(
+        r"CREATE TABLE l1_to_l2_messages (
+    message_hash              BLOB PRIMARY KEY,
+    from_address              BLOB NOT NULL,
+    to_address                BLOB NOT NULL,
+    selector                  BLOB NOT NULL,
+    -- The payload's felts, concatenated.
+    payload                   BLOB NOT NULL,
+    nonce                     BLOB NOT NULL,
+    fee                       BLOB,
+    ethereum_block_number     INTEGER NOT NULL,
+    ethereum_block_hash       BLOB NOT NULL,
+    ethereum_transaction_hash BLOB NOT NULL,
+    ethereum_log_index        INTEGER NOT NULL
+)",
+        [],
+    )
+    .context("Creating l1_to_l2_messages table")?;
+
+    Ok(())
+}
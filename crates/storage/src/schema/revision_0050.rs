@@ -0,0 +1,89 @@
+use anyhow::Context;
+
+use crate::StoredNode;
+
+/// Clears the padding of edge node paths in the trie tables, which older versions left set when
+/// encoding a path sliced from a longer key. Such nodes can no longer be decoded, since set
+/// padding bits are now treated as corruption.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    for table in ["trie_class", "trie_contracts", "trie_storage"] {
+        let updated =
+            clear_path_padding(tx, table).with_context(|| format!("Migrating {table}"))?;
+        tracing::info!(%table, nodes=%updated, "Cleared edge path padding");
+    }
+
+    Ok(())
+}
+
+fn clear_path_padding(tx: &rusqlite::Transaction<'_>, table: &str) -> anyhow::Result<usize> {
+    let mut select = tx
+        .prepare(&format!(
+            "SELECT idx, data FROM {table} WHERE data IS NOT NULL"
+        ))
+        .context("Preparing select statement")?;
+    let mut update = tx
+        .prepare(&format!("UPDATE {table} SET data = ? WHERE idx = ?"))
+        .context("Preparing update statement")?;
+
+    let mut rows = select.query([]).context("Querying nodes")?;
+    let mut updated = 0;
+    while let Some(row) = rows.next().context("Fetching node")? {
+        let idx = row.get_ref_unwrap(0).as_i64()?;
+        let data = row.get_ref_unwrap(1).as_blob()?;
+
+        let Some(data) =
+            StoredNode::clear_path_padding(data).with_context(|| format!("Decoding node {idx}"))?
+        else {
+            continue;
+        };
+        update
+            .execute(rusqlite::params![data, idx])
+            .with_context(|| format!("Updating node {idx}"))?;
+        updated += 1;
+    }
+
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_edge_nodes_are_fixed() {
+        let mut db = rusqlite::Connection::open_in_memory().unwrap();
+        let tx = db.transaction().unwrap();
+        for table in ["trie_class", "trie_contracts", "trie_storage"] {
+            tx.execute(
+                &format!(
+                    "CREATE TABLE {table} (idx INTEGER PRIMARY KEY, hash BLOB NOT NULL, data BLOB)"
+                ),
+                [],
+            )
+            .unwrap();
+        }
+
+        // An edge node with child 1 and the 5 bit path 10101, followed by the key's leftover bits
+        // 111 as padding; and the same node as written by the current encoder.
+        let legacy = [1u8, 1, 2, 0b1010_1111, 5];
+        let fixed = [1u8, 1, 2, 0b1010_1000, 5];
+        // A binary node, which is left untouched.
+        let binary = [0u8, 1, 2];
+        tx.execute(
+            "INSERT INTO trie_storage (idx, hash, data) VALUES (1, x'01', ?), (2, x'02', ?), (3, x'03', ?)",
+            rusqlite::params![&legacy[..], &fixed[..], &binary[..]],
+        )
+        .unwrap();
+
+        migrate(&tx).unwrap();
+
+        let data = tx
+            .prepare("SELECT data FROM trie_storage ORDER BY idx")
+            .unwrap()
+            .query_map([], |row| row.get::<_, Vec<u8>>(0))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(data, vec![fixed.to_vec(), fixed.to_vec(), binary.to_vec()]);
+    }
+}
@@ -0,0 +1,13 @@
+use anyhow::Context;
+
+/// Indexes block headers by state commitment, so that historical state can be looked up by its
+/// commitment without scanning every header.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute(
+        "CREATE INDEX block_headers_state_commitment ON block_headers(state_commitment, number)",
+        [],
+    )
+    .context("Creating index on block_headers(state_commitment, number)")?;
+
+    Ok(())
+}
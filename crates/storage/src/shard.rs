@@ -0,0 +1,141 @@
+//! Splits block data across several databases, each holding a contiguous range of blocks.
+//!
+//! Only the routing between shards is implemented so far: callers select the [Storage] of the
+//! shard holding a block and use it as usual. State tries are shared across blocks and therefore
+//! cannot be split by block range, so they are expected to remain in a single shared database.
+use anyhow::Context;
+use pathfinder_common::{BlockHeader, BlockNumber};
+
+use crate::Storage;
+
+/// Routes block reads to the shard holding the block, and writes to the active shard.
+#[derive(Clone)]
+pub struct ShardedStorage {
+    /// Ordered by first block, with the first shard starting at genesis.
+    shards: Vec<Shard>,
+}
+
+#[derive(Clone)]
+struct Shard {
+    first_block: BlockNumber,
+    storage: Storage,
+}
+
+impl ShardedStorage {
+    /// Creates a [ShardedStorage] from `(first block, storage)` pairs.
+    ///
+    /// Each shard holds the blocks from its first block up to the next shard's first block. The
+    /// last shard is the active shard, to which new blocks are written.
+    pub fn new(shards: Vec<(BlockNumber, Storage)>) -> anyhow::Result<Self> {
+        let first = shards.first().context("At least one shard is required")?;
+        anyhow::ensure!(
+            first.0 == BlockNumber::GENESIS,
+            "The first shard must start at genesis, but starts at {}",
+            first.0
+        );
+        anyhow::ensure!(
+            shards.windows(2).all(|pair| pair[0].0 < pair[1].0),
+            "Shards must be ordered by their first block, without overlap"
+        );
+
+        let shards = shards
+            .into_iter()
+            .map(|(first_block, storage)| Shard {
+                first_block,
+                storage,
+            })
+            .collect();
+
+        Ok(Self { shards })
+    }
+
+    /// Returns the [Storage] of the shard holding `block`.
+    pub fn shard(&self, block: BlockNumber) -> &Storage {
+        // The first shard starts at genesis, so there is always at least one candidate.
+        let index = self
+            .shards
+            .partition_point(|shard| shard.first_block <= block)
+            - 1;
+
+        &self.shards[index].storage
+    }
+
+    /// Returns the [Storage] of the active shard, to which new blocks are written.
+    pub fn active(&self) -> &Storage {
+        &self
+            .shards
+            .last()
+            .expect("There is always at least one shard")
+            .storage
+    }
+
+    /// Reads the header of `block` from the shard holding it.
+    pub fn block_header(&self, block: BlockNumber) -> anyhow::Result<Option<BlockHeader>> {
+        let mut connection = self
+            .shard(block)
+            .connection()
+            .context("Opening database connection")?;
+        let tx = connection
+            .transaction()
+            .context("Creating database transaction")?;
+
+        tx.block_header(block.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::macro_prelude::*;
+
+    #[test]
+    fn reads_are_routed_to_the_shard_holding_the_block() {
+        let boundary = BlockNumber::new_or_panic(10);
+        let shard0 = Storage::in_memory().unwrap();
+        let shard1 = Storage::in_memory().unwrap();
+        let sharded =
+            ShardedStorage::new(vec![(BlockNumber::GENESIS, shard0), (boundary, shard1)]).unwrap();
+
+        let genesis = BlockHeader::builder().finalize_with_hash(block_hash_bytes!(b"genesis"));
+        let header = BlockHeader::builder()
+            .with_number(boundary)
+            .finalize_with_hash(block_hash_bytes!(b"block 10"));
+
+        for header in [&genesis, &header] {
+            let mut connection = sharded.shard(header.number).connection().unwrap();
+            let tx = connection.transaction().unwrap();
+            tx.insert_block_header(header).unwrap();
+            tx.commit().unwrap();
+        }
+
+        assert_eq!(
+            sharded.block_header(genesis.number).unwrap(),
+            Some(genesis.clone())
+        );
+        assert_eq!(
+            sharded.block_header(header.number).unwrap(),
+            Some(header.clone())
+        );
+
+        // Each block is only stored in its own shard.
+        let mut connection = sharded.active().connection().unwrap();
+        let tx = connection.transaction().unwrap();
+        assert_eq!(tx.block_header(genesis.number.into()).unwrap(), None);
+        assert_eq!(tx.block_header(header.number.into()).unwrap(), Some(header));
+    }
+
+    #[test]
+    fn shards_must_cover_all_blocks_in_order() {
+        let storage = Storage::in_memory().unwrap();
+        let block = BlockNumber::new_or_panic(10);
+
+        ShardedStorage::new(vec![]).unwrap_err();
+        ShardedStorage::new(vec![(block, storage.clone())]).unwrap_err();
+        ShardedStorage::new(vec![
+            (BlockNumber::GENESIS, storage.clone()),
+            (block, storage.clone()),
+            (block, storage),
+        ])
+        .unwrap_err();
+    }
+}